@@ -0,0 +1,170 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::str::FromStr;
+
+use clap::Subcommand;
+
+/// The SQL dialect of the generated DDL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    Databend,
+    ClickHouse,
+    PostgreSQL,
+    DuckDB,
+    BigQuery,
+}
+
+impl ToString for SqlDialect {
+    fn to_string(&self) -> String {
+        match self {
+            SqlDialect::Databend => "databend".to_string(),
+            SqlDialect::ClickHouse => "clickhouse".to_string(),
+            SqlDialect::PostgreSQL => "postgresql".to_string(),
+            SqlDialect::DuckDB => "duckdb".to_string(),
+            SqlDialect::BigQuery => "bigquery".to_string(),
+        }
+    }
+}
+
+impl FromStr for SqlDialect {
+    type Err = common_exceptions::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "databend" => Ok(SqlDialect::Databend),
+            "clickhouse" => Ok(SqlDialect::ClickHouse),
+            "postgresql" | "postgres" => Ok(SqlDialect::PostgreSQL),
+            "duckdb" => Ok(SqlDialect::DuckDB),
+            "bigquery" => Ok(SqlDialect::BigQuery),
+            _ => Err(common_exceptions::Error::msg(format!(
+                "Unknown SQL dialect {:?}, expect databend, clickhouse, postgresql, duckdb or bigquery",
+                s
+            ))),
+        }
+    }
+}
+
+/// Commands of ethetl, exporting the blocks if none.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Estimate the RPC calls, output size and runtime of the export without writing any data.
+    #[clap(alias = "dry-run")]
+    Plan {
+        #[clap(
+            long,
+            value_parser,
+            default_value_t = 20,
+            help = "The number of blocks sampled across the range"
+        )]
+        sample_blocks: usize,
+    },
+
+    /// Verify the exported output: block contiguity, the parent hash chain,
+    /// transaction counts, receipts and the missing range files.
+    Verify {
+        #[clap(
+            long,
+            value_parser,
+            default_value = "mars_verify_report.json",
+            help = "The storage path of the verify report"
+        )]
+        report: String,
+    },
+
+    /// Merge the small range files of each table into large files aligned to the block boundary.
+    Compact {
+        #[clap(
+            long,
+            value_parser,
+            default_value_t = 1000000,
+            help = "A merged file never crosses a multiple of the block boundary"
+        )]
+        block_boundary: u64,
+
+        #[clap(
+            long,
+            value_parser,
+            default_value_t = 256,
+            help = "The target size of the merged files in MB"
+        )]
+        target_file_size_mb: u64,
+    },
+
+    /// Print the DDL of the tables from the schema registry, with the
+    /// configured hash encoding and columns.
+    Schema {
+        #[clap(
+            long,
+            default_value_t = SqlDialect::Databend,
+            help = "The SQL dialect: databend, clickhouse, postgresql, duckdb or bigquery"
+        )]
+        dialect: SqlDialect,
+
+        #[clap(
+            long,
+            value_parser,
+            default_value = "eth",
+            help = "The database, or the schema or dataset, of the tables"
+        )]
+        database: String,
+    },
+
+    /// List the files written with an older schema version which miss the
+    /// added columns, and write their ranges to a block file to export again.
+    Migrate {
+        #[clap(
+            long,
+            value_parser,
+            default_value = "mars_backfill_blocks.txt",
+            help = "The storage path of the block file of the ranges to backfill"
+        )]
+        block_file: String,
+    },
+
+    /// Run SQL with DataFusion over the exported tables of the storage, and
+    /// print the results or write them to a local file.
+    #[cfg(feature = "query")]
+    Query {
+        #[clap(
+            value_parser,
+            default_value_t,
+            help = "The SQL statements, separated by ';'"
+        )]
+        sql: String,
+
+        #[clap(
+            long,
+            value_parser,
+            default_value_t,
+            help = "A local file of SQL statements to run instead, e.g. schemas/databend/3_example.sql"
+        )]
+        file: String,
+
+        #[clap(
+            long,
+            value_parser,
+            default_value_t,
+            help = "Write the results of the last statement to the local .csv, .json or .parquet file"
+        )]
+        output: String,
+
+        #[clap(
+            long,
+            value_parser,
+            default_value = "eth",
+            help = "The schema of the tables"
+        )]
+        database: String,
+    },
+}
@@ -13,7 +13,9 @@
 // limitations under the License.
 // Copy from https://github.com/Sherlock-Holo/ddns/blob/master/src/trace.rs
 
+use std::collections::BTreeMap;
 use std::env;
+use std::str::FromStr;
 
 use clap::Parser;
 use common_exceptions::Result;
@@ -24,8 +26,101 @@ use serfig::collectors::from_file;
 use serfig::collectors::from_self;
 use serfig::parsers::Toml;
 
+use crate::Command;
 use crate::LogConfig;
+use crate::SinksConfig;
 use crate::StorageConfig;
+use crate::TransformConfig;
+
+/// How the hashes, addresses and blooms are written.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashEncoding {
+    // `0x` prefixed hex strings.
+    Hex,
+    // FixedSizeBinary, 32 bytes hashes, 20 bytes addresses and 256 bytes blooms.
+    Binary,
+}
+
+impl ToString for HashEncoding {
+    fn to_string(&self) -> String {
+        match self {
+            HashEncoding::Hex => "hex".to_string(),
+            HashEncoding::Binary => "binary".to_string(),
+        }
+    }
+}
+
+impl FromStr for HashEncoding {
+    type Err = common_exceptions::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(HashEncoding::Hex),
+            "binary" => Ok(HashEncoding::Binary),
+            _ => Err(common_exceptions::Error::msg(format!(
+                "Unknown hash encoding {:?}, expect hex or binary",
+                s
+            ))),
+        }
+    }
+}
+
+/// The table format committed over the parquet files of each table.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TableFormat {
+    // Only the parquet files and the manifests.
+    None,
+    // A Delta Lake table per table at `{output_dir}/{table}`, the log
+    // versions are committed under a lease on the object storages.
+    Delta,
+}
+
+impl ToString for TableFormat {
+    fn to_string(&self) -> String {
+        match self {
+            TableFormat::None => "none".to_string(),
+            TableFormat::Delta => "delta".to_string(),
+        }
+    }
+}
+
+impl FromStr for TableFormat {
+    type Err = common_exceptions::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(TableFormat::None),
+            "delta" => Ok(TableFormat::Delta),
+            _ => Err(common_exceptions::Error::msg(format!(
+                "Unknown table format {:?}, expect none or delta",
+                s
+            ))),
+        }
+    }
+}
+
+/// The columns written of a table, all of them by default.
+///
+/// ```toml
+/// [export.columns.transactions]
+/// exclude = ["input"]
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TableColumns {
+    // Only these columns if not empty.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl TableColumns {
+    pub fn selects(&self, column: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|x| x == column))
+            && !self.exclude.iter().any(|x| x == column)
+    }
+}
 
 #[derive(Parser, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(default, deny_unknown_fields)]
@@ -58,6 +153,30 @@ pub struct ExportConfig {
     )]
     pub end_block: usize,
 
+    #[clap(
+        long,
+        value_parser,
+        default_value_t,
+        help = "Start date, e.g. 2023-03-01, 2023-03-01T08:00:00Z or 6h ago as 6h"
+    )]
+    pub start_date: String,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t,
+        help = "End date(exclusive), e.g. 2023-04-01, 2023-04-01T08:00:00Z or 1h ago as 1h"
+    )]
+    pub end_date: String,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t,
+        help = "Export the block numbers or inclusive ranges(e.g. 100-200) in the storage file, - for stdin"
+    )]
+    pub block_file: String,
+
     #[clap(
         short = 'b',
         long,
@@ -76,9 +195,55 @@ pub struct ExportConfig {
     )]
     pub max_worker: usize,
 
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 2,
+        help = "The maximum number of backfill workers in hybrid mode"
+    )]
+    pub backfill_max_worker: usize,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 0,
+        help = "The maximum number of blocks exported per second, 0 is no limit"
+    )]
+    pub max_blocks_per_sec: usize,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 0,
+        help = "The maximum number of blocks backfilled per second in hybrid mode, 0 is no limit"
+    )]
+    pub backfill_max_blocks_per_sec: usize,
+
     #[clap(long, value_parser, default_value_t = 100)]
     pub web3_batch_size: usize,
 
+    #[clap(
+        long,
+        help = "Verify the header hash, transactions root, receipts root and logs bloom before writing"
+    )]
+    pub verify_roots: bool,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 3,
+        help = "The number of retries of a range failing the root verification"
+    )]
+    pub verify_retries: usize,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 0,
+        help = "The memory of the ranges in flight in MB, the workers wait when it is used up, 0 is no limit"
+    )]
+    pub memory_budget_mb: usize,
+
     #[clap(
         long,
         value_parser,
@@ -95,6 +260,56 @@ pub struct ExportConfig {
         help = "Exporter directory"
     )]
     pub output_dir: String,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value = "{table}/{table}_{start}_{end}",
+        help = "The output path under the exporter directory, placeholders: {table}, {chain_id}, {date}, {block_bucket}, {start}, {end}"
+    )]
+    pub path_template: String,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 1,
+        help = "The chain id, 1 for the Ethereum mainnet"
+    )]
+    pub chain_id: u64,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 1000000,
+        help = "The number of blocks of a {block_bucket}"
+    )]
+    pub block_bucket_size: usize,
+
+    #[clap(
+        long,
+        default_value_t = HashEncoding::Hex,
+        help = "The encoding of the hashes, addresses and blooms: hex or binary"
+    )]
+    pub hash_encoding: HashEncoding,
+
+    #[clap(
+        long,
+        default_value_t = TableFormat::None,
+        help = "The table format committed over the files of each table: none or delta"
+    )]
+    pub table_format: TableFormat,
+
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = 0,
+        help = "The maximum depth of a chain reorg rolled back in stream mode, 0 to not check (default)"
+    )]
+    pub max_reorg_depth: usize,
+
+    // The columns of each table, only from the config file.
+    #[clap(skip)]
+    pub columns: BTreeMap<String, TableColumns>,
 }
 
 impl Default for ExportConfig {
@@ -103,11 +318,27 @@ impl Default for ExportConfig {
             provider_uri: "".to_string(),
             start_block: 0,
             end_block: 0,
+            start_date: "".to_string(),
+            end_date: "".to_string(),
+            block_file: "".to_string(),
             batch_size: 100,
             max_worker: 4,
+            backfill_max_worker: 2,
+            max_blocks_per_sec: 0,
+            backfill_max_blocks_per_sec: 0,
             web3_batch_size: 100,
+            verify_roots: false,
+            verify_retries: 3,
+            memory_budget_mb: 0,
             syncing_interval_secs: 60,
             output_dir: "_datas".to_string(),
+            path_template: "{table}/{table}_{start}_{end}".to_string(),
+            chain_id: 1,
+            block_bucket_size: 1000000,
+            hash_encoding: HashEncoding::Hex,
+            table_format: TableFormat::None,
+            max_reorg_depth: 0,
+            columns: BTreeMap::new(),
         }
     }
 }
@@ -124,8 +355,21 @@ pub struct EthConfig {
     #[clap(flatten)]
     pub storage: StorageConfig,
 
+    // The sinks, only from the config file.
+    #[clap(skip)]
+    pub sinks: SinksConfig,
+
+    // The transforms, only from the config file.
+    #[clap(skip)]
+    pub transforms: Vec<TransformConfig>,
+
     #[clap(long, short = 'c', default_value_t)]
     pub config_file: String,
+
+    // Only from args.
+    #[clap(subcommand)]
+    #[serde(skip)]
+    pub cmd: Option<Command>,
 }
 
 impl Default for EthConfig {
@@ -134,7 +378,10 @@ impl Default for EthConfig {
             log: Default::default(),
             export: Default::default(),
             storage: Default::default(),
+            sinks: Default::default(),
+            transforms: vec![],
             config_file: "".to_string(),
+            cmd: None,
         }
     }
 }
@@ -168,7 +415,11 @@ impl EthConfig {
         builder = builder.collect(from_env());
 
         // Finally, load from args.
+        let cmd = arg_conf.cmd.clone();
         builder = builder.collect(from_self(arg_conf));
-        builder.build()
+
+        let mut conf = builder.build()?;
+        conf.cmd = cmd;
+        Ok(conf)
     }
 }
@@ -12,11 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod command;
 mod eth;
 mod log;
+mod sinks;
 mod storage;
+mod transform;
 
+pub use command::Command;
+pub use command::SqlDialect;
 pub use eth::EthConfig;
 pub use eth::ExportConfig;
+pub use eth::HashEncoding;
+pub use eth::TableColumns;
+pub use eth::TableFormat;
 pub use log::LogConfig;
+pub use sinks::ClickHouseInsertFormat;
+pub use sinks::ClickHouseSinkConfig;
+pub use sinks::DatabendSinkConfig;
+pub use sinks::DuckDBSinkConfig;
+pub use sinks::KafkaSinkConfig;
+pub use sinks::PostgresSinkConfig;
+pub use sinks::PostgresWriteMethod;
+pub use sinks::RecordFormat;
+pub use sinks::SinksConfig;
+pub use sinks::StdoutFormat;
+pub use sinks::StdoutSinkConfig;
+pub use sinks::WebhookFilter;
+pub use sinks::WebhookSinkConfig;
 pub use storage::*;
+pub use transform::TransformConfig;
@@ -0,0 +1,407 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::storage::mask_string;
+
+/// The sinks loading the committed ranges, none by default.
+///
+/// ```toml
+/// [sinks.databend]
+/// endpoint = "http://127.0.0.1:8000"
+///
+/// [sinks.duckdb]
+/// path = "./eth.duckdb"
+///
+/// [sinks.clickhouse]
+/// endpoint = "http://127.0.0.1:8123"
+///
+/// [sinks.kafka]
+/// brokers = ["127.0.0.1:9092"]
+///
+/// [sinks.postgres]
+/// host = "127.0.0.1"
+///
+/// [sinks.stdout]
+/// format = "json"
+///
+/// [sinks.webhook]
+/// url = "http://127.0.0.1:3000/events"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinksConfig {
+    pub clickhouse: Option<ClickHouseSinkConfig>,
+    pub databend: Option<DatabendSinkConfig>,
+    pub duckdb: Option<DuckDBSinkConfig>,
+    pub kafka: Option<KafkaSinkConfig>,
+    pub postgres: Option<PostgresSinkConfig>,
+    pub stdout: Option<StdoutSinkConfig>,
+    pub webhook: Option<WebhookSinkConfig>,
+}
+
+/// Load the files into Databend through its HTTP query API.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabendSinkConfig {
+    // The HTTP handler of the query node.
+    pub endpoint: String,
+    pub user: String,
+    pub password: String,
+    pub database: String,
+    // The stage over the output dir, `COPY INTO` loads the files from it.
+    // The files are uploaded by streaming load if empty.
+    pub stage: String,
+    // Create the database and the missing tables from the schema registry.
+    pub create_tables: bool,
+}
+
+impl Default for DatabendSinkConfig {
+    fn default() -> Self {
+        DatabendSinkConfig {
+            endpoint: "http://127.0.0.1:8000".to_string(),
+            user: "root".to_string(),
+            password: "".to_string(),
+            database: "eth".to_string(),
+            stage: "".to_string(),
+            create_tables: true,
+        }
+    }
+}
+
+impl fmt::Debug for DatabendSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DatabendSinkConfig")
+            .field("endpoint", &self.endpoint)
+            .field("user", &self.user)
+            .field("password", &mask_string(&self.password, 3))
+            .field("database", &self.database)
+            .field("stage", &self.stage)
+            .field("create_tables", &self.create_tables)
+            .finish()
+    }
+}
+
+/// How the rows are encoded in the Kafka records.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    // A JSON object by column name.
+    Json,
+    // Avro single object encoding, the schema from the registry.
+    Avro,
+}
+
+/// Publish the rows of the committed ranges to Kafka, a topic per table.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KafkaSinkConfig {
+    // The bootstrap brokers, `host:port`.
+    pub brokers: Vec<String>,
+    pub client_id: String,
+    // The topic of a table is `{topic_prefix}.{table}`.
+    pub topic_prefix: String,
+    // The tables published, the keys are the block number of the blocks
+    // and the transaction hash of the others.
+    pub tables: Vec<String>,
+    pub format: RecordFormat,
+    // The time the brokers wait for the replicas to acknowledge.
+    pub ack_timeout_ms: u64,
+    // The time a record is retried before the publish fails.
+    pub delivery_timeout_ms: u64,
+    // The max size of a record batch.
+    pub batch_bytes: usize,
+    // none, gzip, snappy, lz4 or zstd.
+    pub compression: String,
+    // plaintext, ssl, sasl_plaintext or sasl_ssl.
+    pub security_protocol: String,
+    // e.g. PLAIN or SCRAM-SHA-512, with the username and password.
+    pub sasl_mechanism: String,
+    pub sasl_username: String,
+    pub sasl_password: String,
+    // The CA certificate of the brokers, the system ones if empty.
+    pub ssl_ca_location: String,
+    // More librdkafka properties, e.g. `linger.ms`.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl Default for KafkaSinkConfig {
+    fn default() -> Self {
+        KafkaSinkConfig {
+            brokers: vec!["127.0.0.1:9092".to_string()],
+            client_id: "ethetl".to_string(),
+            topic_prefix: "eth".to_string(),
+            tables: vec![
+                "blocks".to_string(),
+                "transactions".to_string(),
+                "logs".to_string(),
+                "token_transfers".to_string(),
+            ],
+            format: RecordFormat::Json,
+            ack_timeout_ms: 30000,
+            delivery_timeout_ms: 300000,
+            batch_bytes: 1000000,
+            compression: "lz4".to_string(),
+            security_protocol: "plaintext".to_string(),
+            sasl_mechanism: "".to_string(),
+            sasl_username: "".to_string(),
+            sasl_password: "".to_string(),
+            ssl_ca_location: "".to_string(),
+            properties: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Debug for KafkaSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KafkaSinkConfig")
+            .field("brokers", &self.brokers)
+            .field("client_id", &self.client_id)
+            .field("topic_prefix", &self.topic_prefix)
+            .field("tables", &self.tables)
+            .field("format", &self.format)
+            .field("ack_timeout_ms", &self.ack_timeout_ms)
+            .field("delivery_timeout_ms", &self.delivery_timeout_ms)
+            .field("batch_bytes", &self.batch_bytes)
+            .field("compression", &self.compression)
+            .field("security_protocol", &self.security_protocol)
+            .field("sasl_mechanism", &self.sasl_mechanism)
+            .field("sasl_username", &self.sasl_username)
+            .field("sasl_password", &mask_string(&self.sasl_password, 3))
+            .field("ssl_ca_location", &self.ssl_ca_location)
+            .field("properties", &self.properties.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// How the rows are written to PostgreSQL.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PostgresWriteMethod {
+    // `COPY FROM STDIN` of the binary format.
+    Copy,
+    // Batched multi rows `INSERT`.
+    Insert,
+}
+
+/// Upsert the rows into PostgreSQL, a transaction per range.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostgresSinkConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    // Cleartext, MD5 or SCRAM-SHA-256 authentication.
+    pub password: String,
+    pub database: String,
+    // The schema of the tables.
+    pub schema: String,
+    pub method: PostgresWriteMethod,
+    // The rows of an `INSERT` statement.
+    pub batch_rows: usize,
+    // Create the schema and the missing tables from the schema registry.
+    pub create_tables: bool,
+}
+
+impl Default for PostgresSinkConfig {
+    fn default() -> Self {
+        PostgresSinkConfig {
+            host: "127.0.0.1".to_string(),
+            port: 5432,
+            user: "postgres".to_string(),
+            password: "".to_string(),
+            database: "postgres".to_string(),
+            schema: "eth".to_string(),
+            method: PostgresWriteMethod::Copy,
+            batch_rows: 1000,
+            create_tables: true,
+        }
+    }
+}
+
+impl fmt::Debug for PostgresSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PostgresSinkConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &mask_string(&self.password, 3))
+            .field("database", &self.database)
+            .field("schema", &self.schema)
+            .field("method", &self.method)
+            .field("batch_rows", &self.batch_rows)
+            .field("create_tables", &self.create_tables)
+            .finish()
+    }
+}
+
+/// The format of the rows inserted into ClickHouse.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClickHouseInsertFormat {
+    // The committed parquet files as they are.
+    Parquet,
+    // The chunks read from the files as an Arrow IPC stream.
+    ArrowStream,
+}
+
+/// Insert the rows into ClickHouse through its HTTP interface.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClickHouseSinkConfig {
+    // The HTTP interface of the server.
+    pub endpoint: String,
+    pub user: String,
+    pub password: String,
+    pub database: String,
+    pub format: ClickHouseInsertFormat,
+    // Create the database and the missing tables from the schema registry.
+    pub create_tables: bool,
+}
+
+impl Default for ClickHouseSinkConfig {
+    fn default() -> Self {
+        ClickHouseSinkConfig {
+            endpoint: "http://127.0.0.1:8123".to_string(),
+            user: "default".to_string(),
+            password: "".to_string(),
+            database: "eth".to_string(),
+            format: ClickHouseInsertFormat::Parquet,
+            create_tables: true,
+        }
+    }
+}
+
+impl fmt::Debug for ClickHouseSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClickHouseSinkConfig")
+            .field("endpoint", &self.endpoint)
+            .field("user", &self.user)
+            .field("password", &mask_string(&self.password, 3))
+            .field("database", &self.database)
+            .field("format", &self.format)
+            .field("create_tables", &self.create_tables)
+            .finish()
+    }
+}
+
+/// Deliver only the rows of the table whose column is at least `min`, e.g.
+/// the token transfers of a large value.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookFilter {
+    pub table: String,
+    pub column: String,
+    // A non negative decimal.
+    pub min: String,
+}
+
+/// POST the rows of the committed ranges to a URL in block order.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+    // The HMAC-SHA256 key of the `X-Ethetl-Signature` header, unsigned if empty.
+    pub secret: String,
+    pub tables: Vec<String>,
+    pub filters: Vec<WebhookFilter>,
+    // The max rows of a request, the rows of a block are not split.
+    pub batch_rows: usize,
+    pub timeout_secs: u64,
+    // The first block delivered without a cursor, the first exported block if 0.
+    pub from_block: u64,
+}
+
+impl Default for WebhookSinkConfig {
+    fn default() -> Self {
+        WebhookSinkConfig {
+            url: "http://127.0.0.1:3000/events".to_string(),
+            secret: "".to_string(),
+            tables: vec!["token_transfers".to_string()],
+            filters: vec![],
+            batch_rows: 500,
+            timeout_secs: 30,
+            from_block: 0,
+        }
+    }
+}
+
+impl fmt::Debug for WebhookSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WebhookSinkConfig")
+            .field("url", &self.url)
+            .field("secret", &mask_string(&self.secret, 3))
+            .field("tables", &self.tables)
+            .field("filters", &self.filters)
+            .field("batch_rows", &self.batch_rows)
+            .field("timeout_secs", &self.timeout_secs)
+            .field("from_block", &self.from_block)
+            .finish()
+    }
+}
+
+/// How the rows are written to stdout.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StdoutFormat {
+    // A JSON object per line, `{"table": .., "row": {..}}`.
+    Json,
+    // An Arrow IPC stream per table file, the table in the schema metadata.
+    Arrow,
+}
+
+/// Write the rows of the committed ranges to stdout.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StdoutSinkConfig {
+    pub format: StdoutFormat,
+    // The tables written, all if empty.
+    pub tables: Vec<String>,
+}
+
+impl Default for StdoutSinkConfig {
+    fn default() -> Self {
+        StdoutSinkConfig {
+            format: StdoutFormat::Json,
+            tables: vec![],
+        }
+    }
+}
+
+/// Append the ranges to a local DuckDB database file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DuckDBSinkConfig {
+    // The database file.
+    pub path: String,
+    // The schema of the tables.
+    pub schema: String,
+    // Create the schema and the missing tables from the schema registry.
+    pub create_tables: bool,
+}
+
+impl Default for DuckDBSinkConfig {
+    fn default() -> Self {
+        DuckDBSinkConfig {
+            path: "./eth.duckdb".to_string(),
+            schema: "eth".to_string(),
+            create_tables: true,
+        }
+    }
+}
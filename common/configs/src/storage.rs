@@ -24,6 +24,9 @@ pub enum StorageType {
     Fs,
     S3,
     Azure,
+    // Nothing is persisted, e.g. to only pipe the rows of the stdout sink,
+    // the files of a range are deleted once the sinks have loaded them.
+    Memory,
 }
 
 impl ToString for StorageType {
@@ -32,6 +35,7 @@ impl ToString for StorageType {
             StorageType::Fs => "fs".to_string(),
             StorageType::S3 => "s3".to_string(),
             StorageType::Azure => "azure".to_string(),
+            StorageType::Memory => "memory".to_string(),
         }
     }
 }
@@ -44,6 +48,7 @@ impl FromStr for StorageType {
             "fs" => Ok(StorageType::Fs),
             "s3" => Ok(StorageType::S3),
             "azure" => Ok(StorageType::Azure),
+            "memory" => Ok(StorageType::Memory),
             &_ => Ok(StorageType::Fs),
         }
     }
@@ -210,7 +215,7 @@ impl fmt::Debug for AzblobStorageConfig {
 }
 
 /// Mask a string by "******", but keep `unmask_len` of suffix.
-fn mask_string(s: &str, unmask_len: usize) -> String {
+pub fn mask_string(s: &str, unmask_len: usize) -> String {
     if s.len() <= unmask_len {
         s.to_string()
     } else {
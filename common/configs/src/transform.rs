@@ -0,0 +1,45 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A derived table written with each range, the SQL runs over the tables of
+/// the range:
+///
+/// ```toml
+/// [[transforms]]
+/// name = "block_gas"
+/// sql = "SELECT block_number, sum(gas_used) AS gas_used FROM receipts GROUP BY block_number"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransformConfig {
+    // The table name of the output.
+    pub name: String,
+    pub sql: String,
+    // The block number column of the output, the block range of its files,
+    // empty if it has none.
+    pub block_column: String,
+}
+
+impl Default for TransformConfig {
+    fn default() -> Self {
+        TransformConfig {
+            name: "".to_string(),
+            sql: "".to_string(),
+            block_column: "block_number".to_string(),
+        }
+    }
+}
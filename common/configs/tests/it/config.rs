@@ -15,7 +15,11 @@
 use std::ffi::OsString;
 
 use clap::Parser;
+use common_configs::ClickHouseSinkConfig;
+use common_configs::DatabendSinkConfig;
 use common_configs::EthConfig;
+use common_configs::PostgresSinkConfig;
+use common_configs::WebhookSinkConfig;
 
 #[test]
 fn test_config_default() {
@@ -28,3 +32,32 @@ fn test_config_default() {
         "default setting is different from default config, please check again"
     )
 }
+
+#[test]
+fn test_sinks_config_debug() {
+    let databend = DatabendSinkConfig {
+        password: "databend_secret".to_string(),
+        ..Default::default()
+    };
+    let debug = format!("{:?}", databend);
+    assert!(debug.contains("password: \"******ret\""));
+    assert!(!debug.contains("databend_secret"));
+
+    let postgres = PostgresSinkConfig {
+        password: "postgres_secret".to_string(),
+        ..Default::default()
+    };
+    assert!(!format!("{:?}", postgres).contains("postgres_secret"));
+
+    let clickhouse = ClickHouseSinkConfig {
+        password: "clickhouse_secret".to_string(),
+        ..Default::default()
+    };
+    assert!(!format!("{:?}", clickhouse).contains("clickhouse_secret"));
+
+    let webhook = WebhookSinkConfig {
+        secret: "webhook_secret".to_string(),
+        ..Default::default()
+    };
+    assert!(!format!("{:?}", webhook).contains("webhook_secret"));
+}
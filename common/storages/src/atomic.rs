@@ -0,0 +1,153 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+use opendal::Operator;
+use opendal::Scheme;
+
+use crate::list_files;
+
+/// The prefix of the files being written, under the storage root. The
+/// leftovers of a crashed run are never read and are removed by
+/// `clean_staging`.
+pub static STAGING_DIR: &str = "_staging";
+
+static STAGING_SEQ: AtomicU64 = AtomicU64::new(0);
+
+// A unique staging path with its creation time, without the suffix of the
+// final path so that the readers listing the storage never take it for a
+// data file.
+pub(crate) fn staging_path(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+    format!(
+        "{}/{}_{}_{}_{}.tmp",
+        STAGING_DIR,
+        std::process::id(),
+        secs,
+        STAGING_SEQ.fetch_add(1, Ordering::Relaxed),
+        name
+    )
+}
+
+// The local file of the path under the root of the Fs operator.
+pub(crate) fn fs_path(op: &Operator, path: &str) -> PathBuf {
+    Path::new(op.metadata().root()).join(path.trim_start_matches('/'))
+}
+
+/// Write the object so that the path either doesn't exist or has the whole
+/// data.
+///
+/// Fs writes a staging file and renames it into place. On the object
+/// storages the atomic unit is a single put or a completed multipart upload,
+/// an object is never visible before it is whole, so it is written in place.
+pub async fn write_atomic(op: Arc<Operator>, path: &str, bytes: Vec<u8>) -> Result<()> {
+    if op.metadata().scheme() != Scheme::Fs {
+        op.object(path).write(bytes).await?;
+        return Ok(());
+    }
+
+    let staging = staging_path(path);
+    op.object(&staging).write(bytes).await?;
+    if let Err(e) = commit_object(op.clone(), &staging, path).await {
+        let _ = op.object(&staging).delete().await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Move the staging object to the path: renamed on Fs, copied by a single
+/// streamed put then removed on the object storages, which have no rename.
+pub async fn commit_object(op: Arc<Operator>, staging: &str, path: &str) -> Result<()> {
+    if op.metadata().scheme() != Scheme::Fs {
+        let from = op.object(staging);
+        let size = from.stat().await?.content_length();
+        let reader = from.reader().await?;
+        op.object(path).write_from(size, reader).await?;
+        from.delete().await?;
+        return Ok(());
+    }
+
+    let to = fs_path(&op, path);
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(fs_path(&op, staging), to).await?;
+    Ok(())
+}
+
+/// Write the object only if the path doesn't exist, false if it does.
+///
+/// Fs links the staging file into place, which fails on an existing file.
+/// The object storages of opendal have no conditional put and a check before
+/// the put races with the other writers, so they are refused: the callers
+/// which need it, the Delta log, hold a lock on them instead.
+pub async fn write_if_absent(op: Arc<Operator>, path: &str, bytes: Vec<u8>) -> Result<bool> {
+    if op.metadata().scheme() != Scheme::Fs {
+        return Err(Error::msg(format!(
+            "Write {} if absent needs the fs storage, {:?} has no conditional put",
+            path,
+            op.metadata().scheme()
+        )));
+    }
+
+    let staging = staging_path(path);
+    op.object(&staging).write(bytes).await?;
+    let from = fs_path(&op, &staging);
+    let to = fs_path(&op, path);
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let linked = tokio::fs::hard_link(&from, &to).await;
+    tokio::fs::remove_file(&from).await?;
+    match linked {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove the staging files older than the max age, left by the crashed
+/// runs, the number removed.
+pub async fn clean_staging(op: Arc<Operator>, max_age: Duration) -> Result<usize> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut removed = 0;
+    for path in list_files(op.clone(), STAGING_DIR).await? {
+        // {pid}_{secs}_{seq}_{name}.tmp
+        let name = path.rsplit('/').next().unwrap_or_default();
+        let created = name
+            .split('_')
+            .nth(1)
+            .and_then(|x| x.parse::<u64>().ok())
+            .unwrap_or_default();
+        if created + max_age.as_secs() < now {
+            op.object(&path).delete().await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,533 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use futures::lock::Mutex;
+use opendal::ErrorKind;
+use opendal::Operator;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::delta_checkpoint::read_checkpoint;
+use crate::delta_checkpoint::write_checkpoint;
+use crate::list_files;
+use crate::write_atomic;
+use crate::write_if_absent;
+
+/// A checkpoint of the table is written every CHECKPOINT_VERSIONS versions.
+pub const CHECKPOINT_VERSIONS: u64 = 10;
+
+pub(crate) const MIN_READER_VERSION: i32 = 1;
+pub(crate) const MIN_WRITER_VERSION: i32 = 2;
+
+/// A data file added to a Delta table.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeltaFile {
+    // Relative to the table root.
+    pub path: String,
+    pub size: u64,
+    pub rows: u64,
+    // The column stats, by column name.
+    pub min_values: BTreeMap<String, Value>,
+    pub max_values: BTreeMap<String, Value>,
+}
+
+// The add action of a live file, as the checkpoints keep it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LiveFile {
+    pub size: u64,
+    pub modification_time: u64,
+    pub stats: String,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DeltaState {
+    // The last version, none if the table has no commit.
+    pub version: Option<u64>,
+    pub id: String,
+    pub schema_string: String,
+    pub created_time: u64,
+    // The live files by path.
+    pub files: BTreeMap<String, LiveFile>,
+}
+
+/// DeltaTable commits the parquet files under `table_root` to the Delta log
+/// at `{table_root}/_delta_log`, version by version.
+///
+/// Every CHECKPOINT_VERSIONS versions the live files are written to the
+/// checkpoint `{version}.checkpoint.parquet` and the version to
+/// `_delta_log/_last_checkpoint`. The table is loaded on the first use from
+/// the last checkpoint and the log after it, a version conflicting with
+/// another writer is an error.
+///
+/// A version is linked into place only if absent, which needs the fs
+/// storage. The object storages have no conditional put, there the commits
+/// are serialized by a lock of the caller, see `with_external_lock`.
+#[derive(Debug)]
+pub struct DeltaTable {
+    op: Arc<Operator>,
+    table_root: String,
+    // The commits are serialized by the caller.
+    external_lock: bool,
+    state: Mutex<Option<DeltaState>>,
+}
+
+impl DeltaTable {
+    pub fn create(op: Arc<Operator>, table_root: &str) -> Self {
+        DeltaTable {
+            op,
+            table_root: table_root.trim_end_matches('/').to_string(),
+            external_lock: false,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// The writers hold a lock around each append, remove or replace, e.g.
+    /// a lease, instead of the conditional put the object storages lack. A
+    /// commit first replays the versions the other writers committed since,
+    /// then writes the next one.
+    pub fn with_external_lock(mut self) -> Self {
+        self.external_lock = true;
+        self
+    }
+
+    pub fn table_root(&self) -> &str {
+        &self.table_root
+    }
+
+    pub fn log_path(&self, version: u64) -> String {
+        format!("{}/_delta_log/{:020}.json", self.table_root, version)
+    }
+
+    pub fn checkpoint_path(&self, version: u64) -> String {
+        format!(
+            "{}/_delta_log/{:020}.checkpoint.parquet",
+            self.table_root, version
+        )
+    }
+
+    pub fn last_checkpoint_path(&self) -> String {
+        format!("{}/_delta_log/_last_checkpoint", self.table_root)
+    }
+
+    /// The last version, none if the table has no commit.
+    pub async fn version(&self) -> Result<Option<u64>> {
+        let mut state = self.state.lock().await;
+        Ok(self.load(&mut *state).await?.version)
+    }
+
+    /// The live files of the table, by path.
+    pub async fn files(&self) -> Result<BTreeMap<String, u64>> {
+        let mut state = self.state.lock().await;
+        let state = self.load(&mut *state).await?;
+        Ok(state
+            .files
+            .iter()
+            .map(|(path, file)| (path.clone(), file.size))
+            .collect())
+    }
+
+    /// Add the files in one version, the table is created with the schema by
+    /// the first one and its schema replaced if it changes.
+    ///
+    /// A file already live with the same size is skipped, one written again
+    /// with another size is removed and added back.
+    pub async fn append(&self, schema: &Schema, files: &[DeltaFile]) -> Result<Option<u64>> {
+        let mut guard = self.state.lock().await;
+        let state = self.load(&mut *guard).await?;
+
+        let now = now_millis();
+        let mut actions = self.metadata_actions(state, schema, now)?;
+        let metadata_changed = !actions.is_empty();
+        let mut added = vec![];
+        for file in files {
+            match state.files.get(&file.path) {
+                Some(live) if live.size == file.size => continue,
+                Some(_) => actions.push(remove_action(&file.path, true, now)),
+                None => {}
+            }
+            let action = add_action(file, true, now)?;
+            added.push((file, live_file(&action)));
+            actions.push(action);
+        }
+        if added.is_empty() && !metadata_changed {
+            return Ok(None);
+        }
+        actions.push(commit_info("WRITE", json!({"mode": "Append"}), now));
+
+        let version = self.commit(state, actions).await?;
+        state.schema_string = delta_schema_string(schema)?;
+        for (file, live) in added {
+            state.files.insert(file.path.clone(), live);
+        }
+        self.checkpoint(state).await?;
+        Ok(Some(version))
+    }
+
+    /// Remove the live files in one version, the paths not live are skipped.
+    /// The predicate is recorded in the commit info.
+    pub async fn remove(&self, paths: &[String], predicate: &str) -> Result<Option<u64>> {
+        let mut guard = self.state.lock().await;
+        let state = self.load(&mut *guard).await?;
+
+        let now = now_millis();
+        let removed = paths
+            .iter()
+            .filter(|x| state.files.contains_key(*x))
+            .collect::<Vec<_>>();
+        if removed.is_empty() {
+            return Ok(None);
+        }
+        let mut actions = removed
+            .iter()
+            .map(|x| remove_action(x, true, now))
+            .collect::<Vec<_>>();
+        actions.push(commit_info(
+            "DELETE",
+            json!({ "predicate": predicate }),
+            now,
+        ));
+
+        let version = self.commit(state, actions).await?;
+        for path in removed {
+            state.files.remove(path);
+        }
+        self.checkpoint(state).await?;
+        Ok(Some(version))
+    }
+
+    /// Swap the live files for the file of the same rows in one version,
+    /// without data change, as a compaction does. Nothing is written if the
+    /// file is live and none of the paths is.
+    pub async fn replace(&self, paths: &[String], file: &DeltaFile) -> Result<Option<u64>> {
+        let mut guard = self.state.lock().await;
+        let state = self.load(&mut *guard).await?;
+
+        let now = now_millis();
+        let removed = paths
+            .iter()
+            .filter(|x| state.files.contains_key(*x))
+            .collect::<Vec<_>>();
+        let live = state.files.get(&file.path).map(|x| x.size) == Some(file.size);
+        if removed.is_empty() && live {
+            return Ok(None);
+        }
+        let mut actions = removed
+            .iter()
+            .map(|x| remove_action(x, false, now))
+            .collect::<Vec<_>>();
+        let mut added = None;
+        if !live {
+            let action = add_action(file, false, now)?;
+            added = Some(live_file(&action));
+            actions.push(action);
+        }
+        actions.push(commit_info("OPTIMIZE", json!({}), now));
+
+        let version = self.commit(state, actions).await?;
+        for path in removed {
+            state.files.remove(path);
+        }
+        if let Some(added) = added {
+            state.files.insert(file.path.clone(), added);
+        }
+        self.checkpoint(state).await?;
+        Ok(Some(version))
+    }
+
+    // The protocol of a new table and the metaData of a new schema.
+    fn metadata_actions(
+        &self,
+        state: &mut DeltaState,
+        schema: &Schema,
+        now: u64,
+    ) -> Result<Vec<Value>> {
+        let mut actions = vec![];
+        if state.version.is_none() {
+            actions.push(json!({"protocol": {
+                "minReaderVersion": MIN_READER_VERSION,
+                "minWriterVersion": MIN_WRITER_VERSION,
+            }}));
+        }
+        let schema_string = delta_schema_string(schema)?;
+        if state.schema_string != schema_string {
+            if state.id.is_empty() {
+                state.id = table_id(&self.table_root, now);
+                state.created_time = now;
+            }
+            actions.push(json!({"metaData": {
+                "id": state.id,
+                "format": {"provider": "parquet", "options": {}},
+                "schemaString": schema_string,
+                "partitionColumns": [],
+                "configuration": {},
+                "createdTime": state.created_time,
+            }}));
+        }
+        Ok(actions)
+    }
+
+    // Write the next version, the state is updated by the caller.
+    async fn commit(&self, state: &mut DeltaState, actions: Vec<Value>) -> Result<u64> {
+        let version = state.version.map(|x| x + 1).unwrap_or(0);
+        let mut data = String::new();
+        for action in actions {
+            data.push_str(&serde_json::to_string(&action)?);
+            data.push('\n');
+        }
+
+        let path = self.log_path(version);
+        if self.external_lock {
+            write_atomic(self.op.clone(), &path, data.into_bytes()).await?;
+        } else if !write_if_absent(self.op.clone(), &path, data.into_bytes()).await? {
+            return Err(Error::msg(format!(
+                "Delta table {} version {} is written by another writer",
+                self.table_root, version
+            )));
+        }
+        state.version = Some(version);
+        Ok(version)
+    }
+
+    // Write the checkpoint of the version just committed, if due.
+    async fn checkpoint(&self, state: &DeltaState) -> Result<()> {
+        let version = match state.version {
+            Some(version) if version > 0 && version % CHECKPOINT_VERSIONS == 0 => version,
+            _ => return Ok(()),
+        };
+        let size = write_checkpoint(self.op.clone(), &self.checkpoint_path(version), state).await?;
+        let last = json!({"version": version, "size": size});
+        write_atomic(
+            self.op.clone(),
+            &self.last_checkpoint_path(),
+            serde_json::to_vec(&last)?,
+        )
+        .await
+    }
+
+    // Load the last checkpoint and replay the log after it, once. Under an
+    // external lock the versions of the other writers are replayed each time.
+    async fn load<'a>(&self, state: &'a mut Option<DeltaState>) -> Result<&'a mut DeltaState> {
+        if let (Some(loaded), true) = (state.as_mut(), self.external_lock) {
+            self.replay_after(loaded).await?;
+        }
+        if state.is_none() {
+            let mut loaded = DeltaState::default();
+            if let Ok(data) = self.op.object(&self.last_checkpoint_path()).read().await {
+                let last: Value = serde_json::from_slice(&data)?;
+                if let Some(version) = last["version"].as_u64() {
+                    read_checkpoint(self.op.clone(), &self.checkpoint_path(version), &mut loaded)
+                        .await?;
+                    loaded.version = Some(version);
+                }
+            }
+
+            let log_dir = format!("{}/_delta_log", self.table_root);
+            let mut versions = list_files(self.op.clone(), &log_dir)
+                .await?
+                .iter()
+                .filter_map(|x| x.rsplit('/').next()?.strip_suffix(".json")?.parse().ok())
+                .filter(|x| Some(*x) > loaded.version)
+                .collect::<Vec<u64>>();
+            versions.sort_unstable();
+
+            for version in versions {
+                let data = self.op.object(&self.log_path(version)).read().await?;
+                for line in String::from_utf8_lossy(&data).lines() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let action: Value = serde_json::from_str(line)?;
+                    replay(&mut loaded, &action);
+                }
+                loaded.version = Some(version);
+            }
+            *state = Some(loaded);
+        }
+        Ok(state.as_mut().unwrap())
+    }
+
+    // Replay the versions after the loaded one until the first missing.
+    async fn replay_after(&self, state: &mut DeltaState) -> Result<()> {
+        loop {
+            let version = state.version.map(|x| x + 1).unwrap_or(0);
+            let data = match self.op.object(&self.log_path(version)).read().await {
+                Ok(data) => data,
+                Err(e) if e.kind() == ErrorKind::ObjectNotFound => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            for line in String::from_utf8_lossy(&data).lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let action: Value = serde_json::from_str(line)?;
+                replay(state, &action);
+            }
+            state.version = Some(version);
+        }
+    }
+}
+
+fn replay(state: &mut DeltaState, action: &Value) {
+    if let Some(add) = action.get("add") {
+        if let Some(path) = add["path"].as_str() {
+            state.files.insert(path.to_string(), live_file(action));
+        }
+    } else if let Some(remove) = action.get("remove") {
+        if let Some(path) = remove["path"].as_str() {
+            state.files.remove(path);
+        }
+    } else if let Some(meta) = action.get("metaData") {
+        state.id = meta["id"].as_str().unwrap_or_default().to_string();
+        state.schema_string = meta["schemaString"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        state.created_time = meta["createdTime"].as_u64().unwrap_or_default();
+    }
+}
+
+// The live file of an add action.
+fn live_file(action: &Value) -> LiveFile {
+    let add = &action["add"];
+    LiveFile {
+        size: add["size"].as_u64().unwrap_or_default(),
+        modification_time: add["modificationTime"].as_u64().unwrap_or_default(),
+        stats: add["stats"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn add_action(file: &DeltaFile, data_change: bool, now: u64) -> Result<Value> {
+    let stats = json!({
+        "numRecords": file.rows,
+        "minValues": file.min_values,
+        "maxValues": file.max_values,
+    });
+    Ok(json!({"add": {
+        "path": file.path,
+        "partitionValues": {},
+        "size": file.size,
+        "modificationTime": now,
+        "dataChange": data_change,
+        "stats": serde_json::to_string(&stats)?,
+    }}))
+}
+
+fn remove_action(path: &str, data_change: bool, now: u64) -> Value {
+    json!({"remove": {
+        "path": path,
+        "deletionTimestamp": now,
+        "dataChange": data_change,
+    }})
+}
+
+fn commit_info(operation: &str, parameters: Value, now: u64) -> Value {
+    json!({"commitInfo": {
+        "timestamp": now,
+        "operation": operation,
+        "operationParameters": parameters,
+        "engineInfo": "ethetl",
+    }})
+}
+
+/// The Delta schema of the arrow schema, as the json string of the metaData.
+pub fn delta_schema_string(schema: &Schema) -> Result<String> {
+    let fields = schema
+        .fields
+        .iter()
+        .map(struct_field)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(serde_json::to_string(
+        &json!({"type": "struct", "fields": fields}),
+    )?)
+}
+
+fn struct_field(field: &Field) -> Result<Value> {
+    Ok(json!({
+        "name": field.name,
+        "type": delta_type(&field.data_type)?,
+        "nullable": field.is_nullable,
+        "metadata": {},
+    }))
+}
+
+// Delta has no unsigned types, an unsigned type is the next wider signed
+// one, as Spark reads the unsigned parquet columns: a u64 is decimal(20,0).
+fn delta_type(data_type: &DataType) -> Result<Value> {
+    let ty = match data_type {
+        DataType::Boolean => "boolean",
+        DataType::Int8 => "byte",
+        DataType::Int16 | DataType::UInt8 => "short",
+        DataType::Int32 | DataType::UInt16 => "integer",
+        DataType::Int64 | DataType::UInt32 => "long",
+        DataType::UInt64 => return Ok(json!("decimal(20,0)")),
+        DataType::Float32 => "float",
+        DataType::Float64 => "double",
+        DataType::Utf8 | DataType::LargeUtf8 => "string",
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => "binary",
+        DataType::Date32 => "date",
+        DataType::Timestamp(_, _) => "timestamp",
+        DataType::Decimal(p, s) => return Ok(json!(format!("decimal({},{})", p, s))),
+        DataType::List(inner) | DataType::LargeList(inner) => {
+            return Ok(json!({
+                "type": "array",
+                "elementType": delta_type(&inner.data_type)?,
+                "containsNull": inner.is_nullable,
+            }));
+        }
+        other => {
+            return Err(Error::msg(format!(
+                "Data type {:?} has no Delta type",
+                other
+            )));
+        }
+    };
+    Ok(json!(ty))
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// A uuid formatted id, unique enough for the tables of one storage.
+fn table_id(table_root: &str, now: u64) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hash;
+    use std::hash::Hasher;
+
+    let mut hasher = DefaultHasher::new();
+    table_root.hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    let hash = hasher.finish();
+    format!(
+        "{:08x}-{:04x}-4{:03x}-8{:03x}-{:012x}",
+        (now >> 16) as u32,
+        now as u16,
+        (hash >> 52) as u16 & 0xfff,
+        (hash >> 40) as u16 & 0xfff,
+        hash & 0xffff_ffff_ffff
+    )
+}
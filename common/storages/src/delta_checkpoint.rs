@@ -0,0 +1,296 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow2::array::new_empty_array;
+use arrow2::array::new_null_array;
+use arrow2::array::Array;
+use arrow2::array::BooleanArray;
+use arrow2::array::ListArray;
+use arrow2::array::MapArray;
+use arrow2::array::PrimitiveArray;
+use arrow2::array::StructArray;
+use arrow2::array::Utf8Array;
+use arrow2::bitmap::Bitmap;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use arrow2::offset::Offsets;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use opendal::Operator;
+
+use crate::delta::DeltaState;
+use crate::delta::LiveFile;
+use crate::delta::MIN_READER_VERSION;
+use crate::delta::MIN_WRITER_VERSION;
+use crate::read_parquet;
+use crate::write_parquet;
+
+/// Write the state of the table as a checkpoint parquet file, one action
+/// per row: the protocol, the metaData, then an add by live file. The
+/// removed files are not kept as tombstones. Returns the rows.
+pub(crate) async fn write_checkpoint(
+    op: Arc<Operator>,
+    path: &str,
+    state: &DeltaState,
+) -> Result<u64> {
+    let rows = state.files.len() + 2;
+    let schema = checkpoint_schema();
+    let columns = vec![
+        protocol_column(rows),
+        metadata_column(rows, state),
+        add_column(rows, state),
+        new_null_array(DataType::Struct(remove_fields()), rows),
+    ];
+    write_parquet(op, path, schema, Chunk::try_new(columns)?).await?;
+    Ok(rows as u64)
+}
+
+/// Load the metaData and the live files of a checkpoint into the state.
+pub(crate) async fn read_checkpoint(
+    op: Arc<Operator>,
+    path: &str,
+    state: &mut DeltaState,
+) -> Result<()> {
+    let (schema, chunks) = read_parquet(op, path).await?;
+    for chunk in chunks {
+        let metadata = struct_column(&schema, &chunk, "metaData")?;
+        let id = child::<Utf8Array<i32>>(metadata, "id")?;
+        let schema_string = child::<Utf8Array<i32>>(metadata, "schemaString")?;
+        let created_time = child::<PrimitiveArray<i64>>(metadata, "createdTime")?;
+
+        let add = struct_column(&schema, &chunk, "add")?;
+        let paths = child::<Utf8Array<i32>>(add, "path")?;
+        let sizes = child::<PrimitiveArray<i64>>(add, "size")?;
+        let modification_times = child::<PrimitiveArray<i64>>(add, "modificationTime")?;
+        let stats = child::<Utf8Array<i32>>(add, "stats")?;
+
+        for row in 0..chunk.len() {
+            if metadata.is_valid(row) {
+                state.id = id.value(row).to_string();
+                state.schema_string = schema_string.value(row).to_string();
+                state.created_time = created_time.value(row) as u64;
+            }
+            if add.is_valid(row) {
+                state.files.insert(paths.value(row).to_string(), LiveFile {
+                    size: sizes.value(row) as u64,
+                    modification_time: modification_times.value(row) as u64,
+                    stats: match stats.is_valid(row) {
+                        true => stats.value(row).to_string(),
+                        false => String::new(),
+                    },
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn checkpoint_schema() -> Schema {
+    Schema::from(vec![
+        Field::new("protocol", DataType::Struct(protocol_fields()), true),
+        Field::new("metaData", DataType::Struct(metadata_fields()), true),
+        Field::new("add", DataType::Struct(add_fields()), true),
+        Field::new("remove", DataType::Struct(remove_fields()), true),
+    ])
+}
+
+fn protocol_fields() -> Vec<Field> {
+    vec![
+        Field::new("minReaderVersion", DataType::Int32, true),
+        Field::new("minWriterVersion", DataType::Int32, true),
+    ]
+}
+
+fn format_fields() -> Vec<Field> {
+    vec![
+        Field::new("provider", DataType::Utf8, true),
+        Field::new("options", string_map(), true),
+    ]
+}
+
+fn metadata_fields() -> Vec<Field> {
+    vec![
+        Field::new("id", DataType::Utf8, true),
+        Field::new("format", DataType::Struct(format_fields()), true),
+        Field::new("schemaString", DataType::Utf8, true),
+        Field::new("partitionColumns", string_list(), true),
+        Field::new("configuration", string_map(), true),
+        Field::new("createdTime", DataType::Int64, true),
+    ]
+}
+
+fn add_fields() -> Vec<Field> {
+    vec![
+        Field::new("path", DataType::Utf8, true),
+        Field::new("partitionValues", string_map(), true),
+        Field::new("size", DataType::Int64, true),
+        Field::new("modificationTime", DataType::Int64, true),
+        Field::new("dataChange", DataType::Boolean, true),
+        Field::new("stats", DataType::Utf8, true),
+    ]
+}
+
+fn remove_fields() -> Vec<Field> {
+    vec![
+        Field::new("path", DataType::Utf8, true),
+        Field::new("deletionTimestamp", DataType::Int64, true),
+        Field::new("dataChange", DataType::Boolean, true),
+    ]
+}
+
+fn string_map() -> DataType {
+    let entries = DataType::Struct(vec![
+        Field::new("key", DataType::Utf8, false),
+        Field::new("value", DataType::Utf8, true),
+    ]);
+    DataType::Map(Box::new(Field::new("key_value", entries, false)), false)
+}
+
+fn string_list() -> DataType {
+    DataType::List(Box::new(Field::new("element", DataType::Utf8, true)))
+}
+
+// The table is not partitioned, the maps are empty.
+fn empty_maps(rows: usize) -> Box<dyn Array> {
+    let data_type = string_map();
+    let entries = match &data_type {
+        DataType::Map(field, _) => new_empty_array(field.data_type.clone()),
+        _ => unreachable!(),
+    };
+    MapArray::new(data_type, Offsets::new_zeroed(rows).into(), entries, None).boxed()
+}
+
+fn empty_lists(rows: usize) -> Box<dyn Array> {
+    ListArray::<i32>::new(
+        string_list(),
+        Offsets::new_zeroed(rows).into(),
+        new_empty_array(DataType::Utf8),
+        None,
+    )
+    .boxed()
+}
+
+// The value at the row, null at the others.
+fn at_row<T: Clone>(rows: usize, row: usize, value: T) -> Vec<Option<T>> {
+    (0..rows)
+        .map(|i| if i == row { Some(value.clone()) } else { None })
+        .collect()
+}
+
+fn protocol_column(rows: usize) -> Box<dyn Array> {
+    StructArray::new(
+        DataType::Struct(protocol_fields()),
+        vec![
+            PrimitiveArray::<i32>::from(at_row(rows, 0, MIN_READER_VERSION)).boxed(),
+            PrimitiveArray::<i32>::from(at_row(rows, 0, MIN_WRITER_VERSION)).boxed(),
+        ],
+        Some(Bitmap::from_iter((0..rows).map(|i| i == 0))),
+    )
+    .boxed()
+}
+
+fn metadata_column(rows: usize, state: &DeltaState) -> Box<dyn Array> {
+    let format = StructArray::new(
+        DataType::Struct(format_fields()),
+        vec![
+            Utf8Array::<i32>::from(at_row(rows, 1, "parquet")).boxed(),
+            empty_maps(rows),
+        ],
+        None,
+    );
+    StructArray::new(
+        DataType::Struct(metadata_fields()),
+        vec![
+            Utf8Array::<i32>::from(at_row(rows, 1, state.id.as_str())).boxed(),
+            format.boxed(),
+            Utf8Array::<i32>::from(at_row(rows, 1, state.schema_string.as_str())).boxed(),
+            empty_lists(rows),
+            empty_maps(rows),
+            PrimitiveArray::<i64>::from(at_row(rows, 1, state.created_time as i64)).boxed(),
+        ],
+        Some(Bitmap::from_iter((0..rows).map(|i| i == 1))),
+    )
+    .boxed()
+}
+
+// The live files from the third row.
+fn add_column(rows: usize, state: &DeltaState) -> Box<dyn Array> {
+    let files = [None, None]
+        .into_iter()
+        .chain(state.files.iter().map(Some))
+        .collect::<Vec<_>>();
+    StructArray::new(
+        DataType::Struct(add_fields()),
+        vec![
+            Utf8Array::<i32>::from(
+                files
+                    .iter()
+                    .map(|x| x.map(|(path, _)| path.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+            .boxed(),
+            empty_maps(rows),
+            PrimitiveArray::<i64>::from(
+                files
+                    .iter()
+                    .map(|x| x.map(|(_, file)| file.size as i64))
+                    .collect::<Vec<_>>(),
+            )
+            .boxed(),
+            PrimitiveArray::<i64>::from(
+                files
+                    .iter()
+                    .map(|x| x.map(|(_, file)| file.modification_time as i64))
+                    .collect::<Vec<_>>(),
+            )
+            .boxed(),
+            BooleanArray::from(files.iter().map(|x| x.map(|_| false)).collect::<Vec<_>>()).boxed(),
+            Utf8Array::<i32>::from(
+                files
+                    .iter()
+                    .map(|x| x.map(|(_, file)| file.stats.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+            .boxed(),
+        ],
+        Some(Bitmap::from_iter(files.iter().map(|x| x.is_some()))),
+    )
+    .boxed()
+}
+
+fn struct_column<'a>(
+    schema: &Schema,
+    chunk: &'a Chunk<Box<dyn Array>>,
+    name: &str,
+) -> Result<&'a StructArray> {
+    schema
+        .fields
+        .iter()
+        .position(|x| x.name == name)
+        .and_then(|i| chunk.arrays()[i].as_any().downcast_ref::<StructArray>())
+        .ok_or_else(|| Error::msg(format!("The Delta checkpoint has no {} column", name)))
+}
+
+fn child<'a, T: 'static>(array: &'a StructArray, name: &str) -> Result<&'a T> {
+    array
+        .fields()
+        .iter()
+        .position(|x| x.name == name)
+        .and_then(|i| array.values()[i].as_any().downcast_ref::<T>())
+        .ok_or_else(|| Error::msg(format!("The Delta checkpoint has no {} field", name)))
+}
@@ -14,10 +14,29 @@
 
 #![deny(unused_crate_dependencies)]
 
+mod atomic;
+mod delta;
+mod delta_checkpoint;
 mod parquet;
+mod parquet_writer;
 mod storage;
 mod txt;
 
+pub use atomic::clean_staging;
+pub use atomic::commit_object;
+pub use atomic::write_atomic;
+pub use atomic::write_if_absent;
+pub use atomic::STAGING_DIR;
+pub use delta::delta_schema_string;
+pub use delta::DeltaFile;
+pub use delta::DeltaTable;
+pub use delta::CHECKPOINT_VERSIONS;
+pub use parquet::read_parquet;
+pub use parquet::read_parquet_metadata;
 pub use parquet::write_parquet;
+pub use parquet::write_parquet_chunks;
+pub use parquet_writer::ParquetWriter;
+pub use parquet_writer::WrittenFile;
 pub use storage::*;
+pub use txt::list_files;
 pub use txt::write_txt;
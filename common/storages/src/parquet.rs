@@ -13,54 +13,96 @@
 // limitations under the License.
 // Copy from https://github.com/Sherlock-Holo/ddns/blob/master/src/trace.rs
 
+use std::collections::BTreeMap;
 use std::io::Cursor;
 use std::sync::Arc;
 
 use arrow2::array::Array;
 use arrow2::chunk::Chunk;
 use arrow2::datatypes::Schema;
-use arrow2::io::parquet::write::transverse;
-use arrow2::io::parquet::write::CompressionOptions;
-use arrow2::io::parquet::write::Encoding;
-use arrow2::io::parquet::write::FileWriter;
-use arrow2::io::parquet::write::RowGroupIterator;
-use arrow2::io::parquet::write::Version;
-use arrow2::io::parquet::write::WriteOptions;
+use arrow2::io::parquet::read::infer_schema;
+use arrow2::io::parquet::read::read_metadata;
+use arrow2::io::parquet::read::FileReader;
+use common_exceptions::Error;
 use common_exceptions::Result;
 use opendal::Operator;
 
+use crate::ParquetWriter;
+use crate::WrittenFile;
+
 pub async fn write_parquet(
     op: Arc<Operator>,
     path: &str,
     schema: Schema,
     columns: Chunk<Box<dyn Array>>,
-) -> Result<()> {
-    let options = WriteOptions {
-        write_statistics: false,
-        compression: CompressionOptions::Snappy,
-        version: Version::V2,
-        data_pagesize_limit: None,
-    };
+) -> Result<WrittenFile> {
+    write_parquet_chunks(op, path, schema, vec![columns], BTreeMap::new()).await
+}
 
-    let iter = vec![Ok(columns)];
+/// Write the chunks as the row groups of one parquet file, with the
+/// key-value metadata in the footer.
+pub async fn write_parquet_chunks(
+    op: Arc<Operator>,
+    path: &str,
+    schema: Schema,
+    chunks: Vec<Chunk<Box<dyn Array>>>,
+    metadata: BTreeMap<String, String>,
+) -> Result<WrittenFile> {
+    let mut writer = ParquetWriter::create(op, path, schema).await?;
+    writer.set_metadata(metadata);
+    for chunk in chunks {
+        if let Err(e) = writer.write(chunk).await {
+            writer.abort().await;
+            return Err(e);
+        }
+    }
+    writer.close().await
+}
 
-    let encodings = schema
-        .fields
-        .iter()
-        .map(|f| transverse(&f.data_type, |_| Encoding::Plain))
-        .collect();
-    let row_groups = RowGroupIterator::try_new(iter.into_iter(), &schema, options, encodings)?;
+/// Read all the chunks of a parquet file.
+pub async fn read_parquet(
+    op: Arc<Operator>,
+    path: &str,
+) -> Result<(Schema, Vec<Chunk<Box<dyn Array>>>)> {
+    let data = op.object(path).read().await?;
+    let mut reader = Cursor::new(data);
+    let metadata = read_metadata(&mut reader)?;
+    let schema = infer_schema(&metadata)?;
 
-    let cursor = Cursor::new(Vec::new());
-    let mut writer = FileWriter::try_new(cursor, schema, options)?;
+    let reader = FileReader::new(
+        reader,
+        metadata.row_groups,
+        schema.clone(),
+        None,
+        None,
+        None,
+    );
+    let chunks = reader.collect::<arrow2::error::Result<Vec<_>>>()?;
+    Ok((schema, chunks))
+}
 
-    for group in row_groups {
-        writer.write(group?)?;
+/// Read the key-value metadata of the footer, only the footer is read.
+pub async fn read_parquet_metadata(
+    op: Arc<Operator>,
+    path: &str,
+) -> Result<BTreeMap<String, String>> {
+    let object = op.object(path);
+    let size = object.stat().await?.content_length();
+    if size < 12 {
+        return Err(Error::msg(format!("{} is not a parquet file", path)));
     }
-    let _size = writer.end(None)?;
 
-    op.object(path)
-        .write(writer.into_inner().get_ref().as_slice())
-        .await?;
-    Ok(())
+    // The footer ends with its 4 bytes length and the magic.
+    let tail = object.range_read(size - 8..size).await?;
+    let footer_len = u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64;
+    let start = size.saturating_sub(footer_len + 8);
+    let footer = object.range_read(start..size).await?;
+
+    let metadata = read_metadata(&mut Cursor::new(footer))?;
+    Ok(metadata
+        .key_value_metadata
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|kv| kv.value.map(|value| (kv.key, value)))
+        .collect())
 }
@@ -0,0 +1,350 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arrow2::array::Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Schema;
+use arrow2::io::parquet::write::transverse;
+use arrow2::io::parquet::write::CompressionOptions;
+use arrow2::io::parquet::write::Encoding;
+use arrow2::io::parquet::write::FileWriter;
+use arrow2::io::parquet::write::KeyValue;
+use arrow2::io::parquet::write::RowGroupIterator;
+use arrow2::io::parquet::write::Version;
+use arrow2::io::parquet::write::WriteOptions;
+use common_exceptions::Result;
+use opendal::ObjectMultipart;
+use opendal::ObjectPart;
+use opendal::Operator;
+use opendal::Scheme;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use crate::atomic::fs_path;
+use crate::atomic::staging_path;
+use crate::commit_object;
+use crate::write_atomic;
+
+// The rows of a row group, a chunk is encoded one row group at a time.
+const ROW_GROUP_ROWS: usize = 64 * 1024;
+
+// S3 requires the parts but the last one to be at least 5MiB.
+const MULTIPART_PART_SIZE: usize = 8 * 1024 * 1024;
+
+// The encoded bytes not yet flushed to the storage.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Sink {
+    // Appended to the staging file, renamed into place on close.
+    Fs {
+        staging: String,
+        file: File,
+    },
+    // Uploaded in parts, the object appears on complete.
+    Multipart {
+        multipart: ObjectMultipart,
+        parts: Vec<ObjectPart>,
+    },
+    // The object storages without multipart, appended to a local file and
+    // streamed from it on close.
+    Spill {
+        local: PathBuf,
+        file: File,
+    },
+    // The memory storage, written at once on close.
+    Buffer(Vec<u8>),
+}
+
+/// The file written by a ParquetWriter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrittenFile {
+    pub path: String,
+    pub rows: u64,
+    pub size: u64,
+    // The hex SHA-256 of the file.
+    pub checksum: String,
+}
+
+/// ParquetWriter encodes the chunks row group by row group and flushes the
+/// bytes to the storage as it goes, only about a part is held in memory.
+/// The storages with multipart uploads get the parts, the others but memory
+/// get a local file first.
+///
+/// The file is invisible until `close`, an aborted writer leaves nothing.
+pub struct ParquetWriter {
+    op: Arc<Operator>,
+    path: String,
+    schema: Schema,
+    options: WriteOptions,
+    buffer: SharedBuffer,
+    writer: FileWriter<SharedBuffer>,
+    sink: Sink,
+    rows: u64,
+    size: u64,
+    hasher: Sha256,
+    // The key-value metadata of the footer.
+    metadata: BTreeMap<String, String>,
+}
+
+impl ParquetWriter {
+    pub async fn create(op: Arc<Operator>, path: &str, schema: Schema) -> Result<Self> {
+        let options = WriteOptions {
+            write_statistics: false,
+            compression: CompressionOptions::Snappy,
+            version: Version::V2,
+            data_pagesize_limit: None,
+        };
+        let buffer = SharedBuffer::default();
+        let writer = FileWriter::try_new(buffer.clone(), schema.clone(), options)?;
+
+        let meta = op.metadata();
+        let sink = match meta.scheme() {
+            Scheme::Fs => {
+                let staging = staging_path(path);
+                let file_path = fs_path(&op, &staging);
+                if let Some(parent) = file_path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                Sink::Fs {
+                    staging,
+                    file: File::create(file_path).await?,
+                }
+            }
+            Scheme::Memory => Sink::Buffer(vec![]),
+            _ if meta.can_multipart() => Sink::Multipart {
+                multipart: op.object(path).create_multipart().await?,
+                parts: vec![],
+            },
+            _ => {
+                let local = std::env::temp_dir().join(staging_path(path));
+                if let Some(parent) = local.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                Sink::Spill {
+                    file: File::create(&local).await?,
+                    local,
+                }
+            }
+        };
+
+        Ok(ParquetWriter {
+            op,
+            path: path.to_string(),
+            schema,
+            options,
+            buffer,
+            writer,
+            sink,
+            rows: 0,
+            size: 0,
+            hasher: Sha256::new(),
+            metadata: BTreeMap::new(),
+        })
+    }
+
+    /// Set the key-value metadata written to the footer on close.
+    pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) {
+        self.metadata = metadata;
+    }
+
+    /// Write the chunk as one or more row groups.
+    pub async fn write(&mut self, chunk: Chunk<Box<dyn Array>>) -> Result<()> {
+        let rows = chunk.len();
+        self.rows += rows as u64;
+        if rows <= ROW_GROUP_ROWS {
+            self.write_row_group(chunk)?;
+        } else {
+            for offset in (0..rows).step_by(ROW_GROUP_ROWS) {
+                let len = ROW_GROUP_ROWS.min(rows - offset);
+                let arrays = chunk
+                    .arrays()
+                    .iter()
+                    .map(|array| array.sliced(offset, len))
+                    .collect();
+                self.write_row_group(Chunk::try_new(arrays)?)?;
+                self.flush(false).await?;
+            }
+        }
+        self.flush(false).await
+    }
+
+    /// Finish the file and move it into place.
+    pub async fn close(mut self) -> Result<WrittenFile> {
+        let key_values = (!self.metadata.is_empty()).then(|| {
+            self.metadata
+                .iter()
+                .map(|(key, value)| KeyValue {
+                    key: key.clone(),
+                    value: Some(value.clone()),
+                })
+                .collect::<Vec<_>>()
+        });
+        if let Err(e) = self.writer.end(key_values) {
+            self.abort().await;
+            return Err(e.into());
+        }
+        if let Err(e) = self.flush(true).await {
+            self.abort().await;
+            return Err(e);
+        }
+
+        match self.sink {
+            Sink::Fs { staging, file } => {
+                file.sync_all().await?;
+                drop(file);
+                if let Err(e) = commit_object(self.op.clone(), &staging, &self.path).await {
+                    let _ = self.op.object(&staging).delete().await;
+                    return Err(e);
+                }
+            }
+            Sink::Multipart { multipart, parts } => {
+                if let Err(e) = multipart.complete(parts).await {
+                    let _ = multipart.abort().await;
+                    return Err(e.into());
+                }
+            }
+            Sink::Spill { local, mut file } => {
+                // The last write of a tokio file may still be in flight.
+                let res = match sync_file(&mut file).await {
+                    Ok(()) => {
+                        drop(file);
+                        upload_file(&self.op, &local, &self.path, self.size).await
+                    }
+                    Err(e) => Err(e),
+                };
+                let _ = tokio::fs::remove_file(&local).await;
+                res?;
+            }
+            Sink::Buffer(data) => write_atomic(self.op.clone(), &self.path, data).await?,
+        }
+        Ok(WrittenFile {
+            path: self.path,
+            rows: self.rows,
+            size: self.size,
+            checksum: format!("{:x}", self.hasher.finalize()),
+        })
+    }
+
+    /// Drop the written data.
+    pub async fn abort(self) {
+        match self.sink {
+            Sink::Fs { staging, file } => {
+                drop(file);
+                let _ = self.op.object(&staging).delete().await;
+            }
+            Sink::Multipart { multipart, .. } => {
+                let _ = multipart.abort().await;
+            }
+            Sink::Spill { local, file } => {
+                drop(file);
+                let _ = tokio::fs::remove_file(&local).await;
+            }
+            Sink::Buffer(_) => {}
+        }
+    }
+
+    fn write_row_group(&mut self, chunk: Chunk<Box<dyn Array>>) -> Result<()> {
+        let encodings = self
+            .schema
+            .fields
+            .iter()
+            .map(|f| transverse(&f.data_type, |_| Encoding::Plain))
+            .collect();
+        let row_groups = RowGroupIterator::try_new(
+            std::iter::once(Ok::<_, arrow2::error::Error>(chunk)),
+            &self.schema,
+            self.options,
+            encodings,
+        )?;
+        for group in row_groups {
+            self.writer.write(group?)?;
+        }
+        Ok(())
+    }
+
+    // Move the encoded bytes to the sink, a multipart part waits for the part size.
+    async fn flush(&mut self, last: bool) -> Result<()> {
+        match &mut self.sink {
+            Sink::Fs { file, .. } | Sink::Spill { file, .. } => {
+                let data = self.buffer.take();
+                self.size += data.len() as u64;
+                self.hasher.update(&data);
+                file.write_all(&data).await?;
+            }
+            Sink::Multipart { multipart, parts } => {
+                let pending = self.buffer.len();
+                if pending >= MULTIPART_PART_SIZE || (last && (pending > 0 || parts.is_empty())) {
+                    let data = self.buffer.take();
+                    self.size += data.len() as u64;
+                    self.hasher.update(&data);
+                    let part = multipart.write(parts.len() + 1, data).await?;
+                    parts.push(part);
+                }
+            }
+            Sink::Buffer(buf) => {
+                let data = self.buffer.take();
+                self.size += data.len() as u64;
+                self.hasher.update(&data);
+                buf.extend(data);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Stream the synced local file to the object, a single put of its size.
+async fn upload_file(op: &Operator, local: &Path, path: &str, size: u64) -> Result<()> {
+    let file = File::open(local).await?;
+    op.object(path).write_from(size, file.compat()).await?;
+    Ok(())
+}
+
+// Wait for the writes of the file and sync it to the disk.
+async fn sync_file(file: &mut File) -> Result<()> {
+    file.flush().await?;
+    file.sync_all().await?;
+    Ok(())
+}
@@ -22,6 +22,7 @@ use common_configs::StorageType;
 use common_exceptions::Result;
 use opendal::services::Azblob;
 use opendal::services::Fs;
+use opendal::services::Memory;
 use opendal::services::S3;
 use opendal::Builder;
 use opendal::Operator;
@@ -32,9 +33,16 @@ pub async fn init_object_storage(conf: &EthConfig) -> Result<Operator> {
         StorageType::Fs => init_fs_storage(&conf.storage.fs).await,
         StorageType::S3 => init_s3_operator(&conf.storage.s3).await,
         StorageType::Azure => init_azblob_operator(&conf.storage.azblob).await,
+        StorageType::Memory => init_memory_operator(),
     }
 }
 
+/// init_memory_operator will init an opendal memory operator, nothing is persisted.
+pub fn init_memory_operator() -> Result<Operator> {
+    let mut builder = Memory::default();
+    Ok(Operator::new(builder.build()?).finish())
+}
+
 /// init_fs_operator will init a opendal fs operator.
 async fn init_fs_storage(cfg: &FsStorageConfig) -> Result<Operator> {
     let mut builder = Fs::default();
@@ -15,9 +15,37 @@
 use std::sync::Arc;
 
 use common_exceptions::Result;
+use futures::TryStreamExt;
+use opendal::ErrorKind;
 use opendal::Operator;
 
+use crate::write_atomic;
+
 pub async fn write_txt(op: Arc<Operator>, path: &str, bytes: &[u8]) -> Result<()> {
-    op.object(path).write(bytes).await?;
-    Ok(())
+    write_atomic(op, path, bytes.to_vec()).await
+}
+
+/// List the file paths under the dir, empty if the dir doesn't exist. The
+/// other list errors, e.g. a denied permission, are returned.
+pub async fn list_files(op: Arc<Operator>, dir: &str) -> Result<Vec<String>> {
+    let mut paths = vec![];
+    let mut dirs = vec![format!("{}/", dir.trim_end_matches('/'))];
+    while let Some(dir) = dirs.pop() {
+        let mut lister = match op.object(&dir).list().await {
+            Ok(lister) => lister,
+            Err(e) if e.kind() == ErrorKind::ObjectNotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(object) = lister.try_next().await? {
+            // Dirs end with `/`.
+            if object.path().ends_with('/') {
+                dirs.push(object.path().to_string());
+            } else {
+                paths.push(object.path().to_string());
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
 }
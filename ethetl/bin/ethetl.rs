@@ -12,31 +12,165 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use common_configs::Command;
 use common_configs::EthConfig;
+use common_exceptions::Error;
 use common_exceptions::Result;
 use env_logger::Builder;
 use env_logger::Env;
+use env_logger::Target;
+use ethetl::compaction::Compactor;
 use ethetl::contexts::Context;
+use ethetl::etl::BlockListEtl;
 use ethetl::etl::NormalEtl;
+use ethetl::etl::Planner;
+#[cfg(feature = "query")]
+use ethetl::query::format_batches;
+#[cfg(feature = "query")]
+use ethetl::query::write_batches;
+#[cfg(feature = "query")]
+use ethetl::query::LocalQuery;
+use ethetl::schemas::DdlGenerator;
+use ethetl::schemas::SchemaMigration;
+use ethetl::verify::OutputVerifier;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let env = Env::default().filter_or("RUST_LOG", "info");
-    Builder::from_env(env).format_target(false).init();
+    // The logs and the progress go to stderr, stdout is the data of the stdout sink.
+    Builder::from_env(env)
+        .format_target(false)
+        .target(Target::Stderr)
+        .init();
 
     let conf = EthConfig::load()?;
     log::info!("Config: {:?}", conf);
 
-    // Create data dir.
-    let ctx = Context::create(&conf).await;
+    // Only the export and the compaction write to the sinks.
+    let ctx = match conf.cmd {
+        None | Some(Command::Compact { .. }) => Context::create(&conf).await?,
+        Some(_) => Context::create_without_sinks(&conf).await?,
+    };
+
+    match conf.cmd.clone() {
+        Some(Command::Plan { sample_blocks }) => {
+            let planner = Planner::create(ctx, sample_blocks);
+            let report = planner.start().await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(Command::Verify { report: path }) => {
+            let verifier = OutputVerifier::create(
+                ctx.get_storage(),
+                ctx.get_output_dir(),
+                ctx.get_path_template(),
+                conf.export.chain_id,
+            );
+            let report = verifier.verify().await?;
+            verifier.write_report(&report, &path).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.ok {
+                return Err(Error::msg(format!(
+                    "Verify failed, see the report {}",
+                    path
+                )));
+            }
+            return Ok(());
+        }
+        Some(Command::Compact {
+            block_boundary,
+            target_file_size_mb,
+        }) => {
+            let compactor = Compactor::create(
+                ctx.get_storage(),
+                ctx.get_output_dir(),
+                ctx.get_path_template(),
+                conf.export.chain_id,
+                block_boundary,
+                target_file_size_mb * 1024 * 1024,
+            )
+            .with_sinks(ctx.get_sinks().to_vec());
+            let report = compactor.compact().await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(Command::Schema { dialect, database }) => {
+            let generator = DdlGenerator::create(
+                dialect,
+                ctx.get_hash_encoding(),
+                ctx.get_column_projection().clone(),
+            );
+            print!("{}", generator.render(&database)?);
+            return Ok(());
+        }
+        Some(Command::Migrate { block_file }) => {
+            let migration = SchemaMigration::create(
+                ctx.get_storage(),
+                ctx.get_output_dir(),
+                ctx.get_path_template(),
+                conf.export.chain_id,
+                ctx.get_column_projection().clone(),
+            );
+            let report = migration.check().await?;
+            if !report.backfill_ranges.is_empty() {
+                migration.write_block_file(&report, &block_file).await?;
+            }
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        #[cfg(feature = "query")]
+        Some(Command::Query {
+            sql,
+            file,
+            output,
+            database,
+        }) => {
+            let sql = match (sql.is_empty(), file.is_empty()) {
+                (false, true) => sql,
+                (true, false) => std::fs::read_to_string(&file)?,
+                _ => return Err(Error::msg("Query needs the SQL or --file")),
+            };
+            let query = LocalQuery::create(
+                ctx.get_storage(),
+                ctx.get_output_dir(),
+                ctx.get_path_template(),
+                conf.export.chain_id,
+                &database,
+                ctx.get_tables(),
+            );
+            query.register_tables().await?;
+            let results = query.run(&sql).await?;
+            match results.last() {
+                Some(batches) if !output.is_empty() => {
+                    write_batches(batches, &output)?;
+                    log::info!("Query results written to {}", output);
+                }
+                _ => {
+                    for batches in &results {
+                        println!("{}", format_batches(batches)?);
+                    }
+                }
+            }
+            return Ok(());
+        }
+        None => {}
+    }
 
     // Interval progress.
     let progress = ctx.get_progress();
-    progress.set_name("Normal".to_string());
-    progress.start();
+    if conf.export.block_file.is_empty() {
+        progress.set_name("Normal".to_string());
+        progress.start();
+
+        let normal = NormalEtl::create(ctx);
+        normal.start().await?;
+    } else {
+        progress.set_name("BlockList".to_string());
+        progress.start();
 
-    let normal = NormalEtl::create(ctx);
-    normal.start().await?;
+        let block_list = BlockListEtl::create(ctx);
+        block_list.start().await?;
+    }
     progress.stop();
 
     Ok(())
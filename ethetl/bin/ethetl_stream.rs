@@ -16,19 +16,23 @@ use common_configs::EthConfig;
 use common_exceptions::Result;
 use env_logger::Builder;
 use env_logger::Env;
+use env_logger::Target;
 use ethetl::contexts::Context;
 use ethetl::etl::StreamEtl;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let env = Env::default().filter_or("RUST_LOG", "info");
-    Builder::from_env(env).format_target(false).init();
+    Builder::from_env(env)
+        .format_target(false)
+        .target(Target::Stderr)
+        .init();
 
     let conf = EthConfig::load()?;
     log::info!("Config: {:?}", conf);
 
     // Create data dir.
-    let ctx = Context::create(&conf).await;
+    let ctx = Context::create(&conf).await?;
 
     // Interval progress.
     let progress = ctx.get_progress();
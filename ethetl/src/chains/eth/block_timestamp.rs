@@ -0,0 +1,66 @@
+// Copyright 2022 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_exceptions::Retryable;
+use web3::types::BlockId;
+use web3::types::BlockNumber;
+use web3::types::U64;
+
+use crate::contexts::ContextRef;
+
+pub struct BlockTimestamp {
+    ctx: ContextRef,
+}
+
+impl BlockTimestamp {
+    pub fn create(ctx: &ContextRef) -> BlockTimestamp {
+        Self { ctx: ctx.clone() }
+    }
+
+    pub async fn fetch(&self, number: usize) -> Result<i64> {
+        let notify = |e, duration| {
+            log::warn!(
+                "Fetch block timestamp api error at duration {:?}, error:{:?}",
+                duration,
+                e
+            )
+        };
+        let op = || async {
+            let res = self.fetch_with_no_retry(number).await?;
+            Ok(res)
+        };
+
+        op.retry_with_notify(notify).await
+    }
+
+    // Get the block header without transactions.
+    async fn fetch_with_no_retry(&self, number: usize) -> Result<i64> {
+        let http = web3::transports::Http::new(self.ctx.get_rpc_url())?;
+        let web3 = web3::Web3::new(http);
+
+        let block = web3
+            .eth()
+            .block(BlockId::Number(BlockNumber::Number(U64::from(number))))
+            .await?;
+        match block {
+            None => Err(Error::msg(format!(
+                "Cannot get block {} by eth.block(), please make sure eth node sync is already",
+                number
+            ))),
+            Some(blk) => Ok(blk.timestamp.as_u64() as i64),
+        }
+    }
+}
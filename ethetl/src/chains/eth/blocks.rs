@@ -15,12 +15,13 @@
 use common_exceptions::Error;
 use common_exceptions::Result;
 use common_exceptions::Retryable;
+use serde::Deserialize;
+use serde_json::json;
 use web3::types::Block;
-use web3::types::BlockId;
-use web3::types::BlockNumber;
 use web3::types::Transaction;
-use web3::types::U64;
+use web3::Transport;
 
+use crate::chains::eth::RawBlock;
 use crate::contexts::ContextRef;
 
 pub struct BlockFetcher {
@@ -49,6 +50,20 @@ impl BlockFetcher {
     }
 
     pub async fn fetch(&self) -> Result<Vec<Block<Transaction>>> {
+        let (blocks, _) = self.fetch_blocks(false).await?;
+        Ok(blocks)
+    }
+
+    /// The blocks with their raw fields, both read from the one response of
+    /// each block.
+    pub async fn fetch_with_raw(&self) -> Result<(Vec<Block<Transaction>>, Vec<RawBlock>)> {
+        self.fetch_blocks(true).await
+    }
+
+    async fn fetch_blocks(
+        &self,
+        with_raw: bool,
+    ) -> Result<(Vec<Block<Transaction>>, Vec<RawBlock>)> {
         let notify = |e, duration| {
             log::warn!(
                 "Fetch blocks error at duration {:?}, error:{:?}",
@@ -57,54 +72,56 @@ impl BlockFetcher {
             )
         };
         let op = || async {
-            let res = self.fetch_with_no_retry().await?;
+            let res = self.fetch_with_no_retry(with_raw).await?;
             Ok(res)
         };
 
         op.retry_with_notify(notify).await
     }
 
-    // Get the blocks.
-    async fn fetch_with_no_retry(&self) -> Result<Vec<Block<Transaction>>> {
+    // Get the blocks, eth_getBlockByNumber with the full transactions.
+    async fn fetch_with_no_retry(
+        &self,
+        with_raw: bool,
+    ) -> Result<(Vec<Block<Transaction>>, Vec<RawBlock>)> {
         let http = web3::transports::Http::new(self.ctx.get_rpc_url())?;
         let web3 = web3::Web3::new(web3::transports::Batch::new(http));
 
         let mut blocks = vec![];
+        let mut raw_blocks = vec![];
 
         for chunks in self.numbers.chunks(self.ctx.get_web3_batch_size()) {
             let mut callbacks = vec![];
             for num in chunks {
-                let block = web3
-                    .eth()
-                    .block_with_txs(BlockId::Number(BlockNumber::Number(U64::from(*num))));
-                callbacks.push(block);
+                let params = vec![json!(format!("{:#x}", num)), json!(true)];
+                callbacks.push(web3.transport().execute("eth_getBlockByNumber", params));
             }
             let _ = web3.transport().submit_batch().await?;
 
             // Get the callback.
             for cb in callbacks {
-                let r = cb.await?;
-                match r {
-                    None => {
-                        return Err(Error::msg(
-                            "Cannot export block by eth.block_with_txs(), please make sure eth node sync is already",
-                        ));
-                    }
-                    Some(blk) => {
-                        let num = blk.number;
-                        let len = blk.transactions.len();
-                        blocks.push(blk);
-
-                        self.ctx.get_progress().incr_blocks(1);
-                        self.ctx
-                            .get_progress()
-                            .set_max_blocks(num.unwrap().as_usize());
-                        self.ctx.get_progress().incr_txs(len);
-                    }
+                let value = cb.await?;
+                if value.is_null() {
+                    return Err(Error::msg(
+                        "Cannot export block by eth_getBlockByNumber, please make sure eth node sync is already",
+                    ));
+                }
+                if with_raw {
+                    raw_blocks.push(RawBlock::deserialize(&value)?);
                 }
+                let blk: Block<Transaction> = serde_json::from_value(value)?;
+                let num = blk.number;
+                let len = blk.transactions.len();
+                blocks.push(blk);
+
+                self.ctx.get_progress().incr_blocks(1);
+                self.ctx
+                    .get_progress()
+                    .set_max_blocks(num.unwrap_or_default().as_usize());
+                self.ctx.get_progress().incr_txs(len);
             }
         }
 
-        Ok(blocks)
+        Ok((blocks, raw_blocks))
     }
 }
@@ -13,13 +13,19 @@
 // limitations under the License.
 
 mod block_number;
+mod block_timestamp;
 mod blocks;
 mod contracts;
+mod raw_blocks;
 mod receipts;
 mod syncing;
 
 pub use block_number::BlockNumber;
+pub use block_timestamp::BlockTimestamp;
 pub use blocks::BlockFetcher;
 pub use contracts::ContractFetcher;
+pub use raw_blocks::RawAuthorization;
+pub use raw_blocks::RawBlock;
+pub use raw_blocks::RawTransaction;
 pub use receipts::ReceiptFetcher;
 pub use syncing::Syncing;
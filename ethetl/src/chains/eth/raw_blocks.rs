@@ -0,0 +1,56 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use web3::types::H160;
+use web3::types::H256;
+use web3::types::U256;
+use web3::types::U64;
+
+/// The block fields which are not in web3 `Block` and `Transaction`,
+/// but are needed to encode the header and the typed transactions, read
+/// from the same response as the block by `BlockFetcher::fetch_with_raw`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawBlock {
+    pub hash: H256,
+    pub number: U64,
+    pub withdrawals_root: Option<H256>,
+    pub blob_gas_used: Option<U64>,
+    pub excess_blob_gas: Option<U64>,
+    pub parent_beacon_block_root: Option<H256>,
+    pub requests_hash: Option<H256>,
+    pub transactions: Vec<RawTransaction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawTransaction {
+    pub hash: H256,
+    pub chain_id: Option<U64>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub blob_versioned_hashes: Option<Vec<H256>>,
+    pub authorization_list: Option<Vec<RawAuthorization>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawAuthorization {
+    pub chain_id: U256,
+    pub address: H160,
+    pub nonce: U64,
+    pub y_parity: U64,
+    pub r: U256,
+    pub s: U256,
+}
@@ -0,0 +1,548 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow2::datatypes::Schema;
+use chrono::Utc;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::clean_staging;
+use common_storages::read_parquet;
+use common_storages::ParquetWriter;
+use log::info;
+use log::warn;
+use opendal::Operator;
+use serde::Serialize;
+
+use crate::exporters::eth::TABLES;
+use crate::exporters::list_table_files;
+use crate::exporters::PathTemplate;
+use crate::exporters::PathVars;
+use crate::manifest::Lease;
+use crate::manifest::LeaseCheck;
+use crate::manifest::ManifestFile;
+use crate::manifest::RangeCommit;
+use crate::manifest::TableManifest;
+use crate::schemas::file_schema_version;
+use crate::schemas::table_schema;
+use crate::schemas::FileMetadata;
+use crate::sinks::Compacted;
+use crate::sinks::SinkRef;
+use crate::verify::u64_column;
+
+/// A range file of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeFile {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    pub size: u64,
+    // The path vars other than the range, e.g. the date, files are merged within a partition.
+    pub partition: PathVars,
+    // The files of different schema versions are never merged.
+    pub schema_version: u32,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CompactReport {
+    pub tables: BTreeMap<String, TableCompaction>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TableCompaction {
+    pub files_before: usize,
+    pub files_after: usize,
+    pub merged_files: usize,
+    pub removed_files: usize,
+}
+
+/// Compactor merges the small range files of each table into files of about
+/// the target size, a merged file never crosses a multiple of the block boundary.
+///
+/// The merged file is read back and checked before the manifest and the commit
+/// markers are updated and the originals are removed. An interrupted run is
+/// recovered on the next run: originals covered by a file in the manifest are
+/// removed, a covering file not in the manifest, with the originals in it, was
+/// never verified and is removed instead. Without either in the manifest, the
+/// covering file is kept if it reads back with the rows of the originals.
+///
+/// The compaction holds the compaction lease of the output dir, it doesn't
+/// start while an exporter is writing the dir.
+pub struct Compactor {
+    storage: Arc<Operator>,
+    output_dir: String,
+    path_template: PathTemplate,
+    chain_id: u64,
+    block_boundary: u64,
+    target_file_size: u64,
+    sinks: Vec<SinkRef>,
+}
+
+impl Compactor {
+    pub fn create(
+        storage: Arc<Operator>,
+        output_dir: &str,
+        path_template: &PathTemplate,
+        chain_id: u64,
+        block_boundary: u64,
+        target_file_size: u64,
+    ) -> Self {
+        Compactor {
+            storage,
+            output_dir: output_dir.to_string(),
+            path_template: path_template.clone(),
+            chain_id,
+            block_boundary: block_boundary.max(1),
+            target_file_size,
+            sinks: vec![],
+        }
+    }
+
+    /// Tell the sinks about the merged files, e.g. the Delta tables.
+    pub fn with_sinks(mut self, sinks: Vec<SinkRef>) -> Self {
+        self.sinks = sinks;
+        self
+    }
+
+    pub async fn compact(&self) -> Result<CompactReport> {
+        let lease = Lease::compaction(self.storage.clone(), &self.output_dir).await?;
+        // The staging files of the crashed runs, a day is longer than any write.
+        let removed = clean_staging(self.storage.clone(), Duration::from_secs(24 * 3600)).await?;
+        if removed > 0 {
+            info!("Removed {} staging files of crashed runs", removed);
+        }
+        let res = self.compact_tables(&lease.checker()).await;
+        lease.release().await?;
+        res
+    }
+
+    async fn compact_tables(&self, lease: &LeaseCheck) -> Result<CompactReport> {
+        let mut report = CompactReport::default();
+        for table in TABLES {
+            let compaction = self.compact_table(table, lease).await?;
+            info!("Compacted table {}, {:?}", table, compaction);
+            report.tables.insert(table.to_string(), compaction);
+        }
+        Ok(report)
+    }
+
+    async fn compact_table(&self, table: &str, lease: &LeaseCheck) -> Result<TableCompaction> {
+        let mut compaction = TableCompaction::default();
+        let mut manifest =
+            TableManifest::read(self.storage.clone(), &self.output_dir, table).await?;
+
+        let mut commits = RangeCommit::list(self.storage.clone(), &self.output_dir).await?;
+
+        let files = self.list(table).await?;
+        compaction.files_before = files.len();
+        let files = self
+            .recover(table, files, &mut manifest, &mut commits, &mut compaction)
+            .await?;
+
+        let groups = plan_groups(&files, self.block_boundary, self.target_file_size);
+        let mut files_after = files.len();
+        for group in groups {
+            // The groups are not merged once the lease is lost.
+            lease.check()?;
+            if self
+                .merge(table, &group, &mut manifest, &mut commits)
+                .await?
+            {
+                compaction.merged_files += 1;
+                compaction.removed_files += group.len();
+                files_after = files_after + 1 - group.len();
+            }
+        }
+        compaction.files_after = files_after;
+        Ok(compaction)
+    }
+
+    async fn list(&self, table: &str) -> Result<Vec<RangeFile>> {
+        let table_files = list_table_files(
+            self.storage.clone(),
+            &self.path_template,
+            &self.output_dir,
+            table,
+            self.chain_id,
+        )
+        .await?;
+
+        let mut files = vec![];
+        for file in table_files {
+            let meta = self.storage.object(&file.path).stat().await?;
+            let mut partition = file.vars;
+            partition.remove("start");
+            partition.remove("end");
+            let schema_version = file_schema_version(self.storage.clone(), &file.path).await?;
+            files.push(RangeFile {
+                path: file.path,
+                start: file.start,
+                end: file.end,
+                size: meta.content_length(),
+                partition,
+                schema_version,
+            });
+        }
+        Ok(files)
+    }
+
+    // Remove the leftovers of an interrupted run, returns the live files.
+    async fn recover(
+        &self,
+        table: &str,
+        mut files: Vec<RangeFile>,
+        manifest: &mut TableManifest,
+        commits: &mut [RangeCommit],
+        compaction: &mut TableCompaction,
+    ) -> Result<Vec<RangeFile>> {
+        // The covering file comes first.
+        files.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+        // The files covered by another one, by covering file.
+        let mut covered: BTreeMap<String, (RangeFile, Vec<RangeFile>)> = BTreeMap::new();
+        let mut cover: Option<&RangeFile> = None;
+        for file in &files {
+            match cover {
+                Some(c) if file.end <= c.end => covered
+                    .entry(c.path.clone())
+                    .or_insert_with(|| (c.clone(), vec![]))
+                    .1
+                    .push(file.clone()),
+                _ => cover = Some(file),
+            }
+        }
+
+        let mut removed = BTreeSet::new();
+        for (cover, originals) in covered.into_values() {
+            let paths = originals.iter().map(|x| x.path.clone()).collect::<Vec<_>>();
+            let merged = if manifest.contains(&cover.path) {
+                Some(true)
+            } else if paths.iter().any(|x| manifest.contains(x)) {
+                Some(false)
+            } else {
+                self.covers(table, &cover, &originals).await?
+            };
+
+            match merged {
+                Some(true) => {
+                    if !manifest.remove(&paths).is_empty() {
+                        manifest
+                            .write(self.storage.clone(), &self.output_dir)
+                            .await?;
+                    }
+                    // The sinks may have missed the interrupted merge.
+                    if let Some(added) = manifest.files.iter().find(|x| x.path == cover.path) {
+                        self.notify_sinks(table, paths.clone(), added.clone())
+                            .await?;
+                    }
+                    self.rewrite_commits(table, &paths, &cover.path, commits)
+                        .await?;
+                    removed.extend(paths);
+                }
+                Some(false) => {
+                    removed.insert(cover.path);
+                }
+                None => warn!(
+                    "Keep {} and the files it covers, the block column cannot be checked",
+                    cover.path
+                ),
+            }
+        }
+
+        for path in &removed {
+            warn!("Remove the leftover of an interrupted compaction {}", path);
+            self.storage.object(path).delete().await?;
+            compaction.removed_files += 1;
+        }
+        files.retain(|x| !removed.contains(&x.path));
+        Ok(files)
+    }
+
+    // Whether the covering file has the rows of each covered file in its
+    // range, none if the files have no block column.
+    async fn covers(
+        &self,
+        table: &str,
+        cover: &RangeFile,
+        covered: &[RangeFile],
+    ) -> Result<Option<bool>> {
+        let block_column = table_schema(table)?.block_column();
+        let blocks = match self.read_blocks(&cover.path, block_column).await {
+            Ok(Some(blocks)) => blocks,
+            Ok(None) => return Ok(None),
+            // Unreadable, the merge was never finished.
+            Err(_) => return Ok(Some(false)),
+        };
+        for file in covered {
+            let rows = match self.read_blocks(&file.path, block_column).await? {
+                Some(rows) => rows.len(),
+                None => return Ok(None),
+            };
+            let cover_rows = blocks
+                .iter()
+                .filter(|x| **x >= file.start && **x <= file.end)
+                .count();
+            if cover_rows != rows {
+                return Ok(Some(false));
+            }
+        }
+        Ok(Some(true))
+    }
+
+    // The block numbers of the rows, none if there is no block column.
+    async fn read_blocks(&self, path: &str, block_column: &str) -> Result<Option<Vec<u64>>> {
+        let (schema, chunks) = read_parquet(self.storage.clone(), path).await?;
+        let mut blocks = vec![];
+        for chunk in &chunks {
+            match u64_column(&schema, chunk, block_column) {
+                Ok(numbers) => blocks.extend(numbers),
+                Err(_) => return Ok(None),
+            }
+        }
+        Ok(Some(blocks))
+    }
+
+    // Point the commit markers of the merged files at the merged file.
+    async fn rewrite_commits(
+        &self,
+        table: &str,
+        removed: &[String],
+        path: &str,
+        commits: &mut [RangeCommit],
+    ) -> Result<()> {
+        for commit in commits.iter_mut() {
+            match commit.files.get_mut(table) {
+                Some(file) if removed.contains(file) => *file = path.to_string(),
+                _ => continue,
+            }
+            commit.write(self.storage.clone(), &self.output_dir).await?;
+        }
+        Ok(())
+    }
+
+    // Returns false if the group is skipped.
+    async fn merge(
+        &self,
+        table: &str,
+        group: &[RangeFile],
+        manifest: &mut TableManifest,
+        commits: &mut [RangeCommit],
+    ) -> Result<bool> {
+        let (start, end) = (group[0].start, group[group.len() - 1].end);
+        let mut vars = group[0].partition.clone();
+        vars.insert("start".to_string(), start.to_string());
+        vars.insert("end".to_string(), end.to_string());
+        let path = self.path_template.render(&self.output_dir, &vars)?;
+
+        // One original is in memory at a time, the merged file is streamed.
+        let mut writer: Option<(Schema, ParquetWriter)> = None;
+        let mut rows = 0;
+        for file in group {
+            let (schema, chunks) = read_parquet(self.storage.clone(), &file.path).await?;
+            if matches!(&writer, Some((merged_schema, _)) if merged_schema != &schema) {
+                warn!(
+                    "Skip merging {}, the schema is changed at {}",
+                    path, file.path
+                );
+                if let Some((_, merged)) = writer {
+                    merged.abort().await;
+                }
+                return Ok(false);
+            }
+            if writer.is_none() {
+                let mut merged =
+                    ParquetWriter::create(self.storage.clone(), &path, schema.clone()).await?;
+                let metadata = FileMetadata {
+                    schema_version: file.schema_version,
+                    ..FileMetadata::create(table, self.chain_id, start, end)
+                };
+                merged.set_metadata(metadata.to_map());
+                writer = Some((schema, merged));
+            }
+
+            if let Some((_, merged)) = writer.as_mut() {
+                for chunk in chunks {
+                    rows += chunk.len() as u64;
+                    merged.write(chunk).await?;
+                }
+            }
+        }
+        let (schema, merged) = match writer {
+            Some(writer) => writer,
+            None => return Ok(false),
+        };
+        let written = merged.close().await?;
+
+        // The blocks of the originals, for the files without the block column.
+        let originals = manifest
+            .files
+            .iter()
+            .filter(|x| group.iter().any(|g| g.path == x.path));
+        let blocks = (
+            originals.clone().filter_map(|x| x.min_block).min(),
+            originals.filter_map(|x| x.max_block).max(),
+        );
+
+        // Read back, the originals are kept if the merged file is bad.
+        let checked = self
+            .check(table, &path, &schema, rows, (start, end), blocks)
+            .await;
+        let (min_block, max_block) = match checked {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                self.storage.object(&path).delete().await?;
+                return Err(e);
+            }
+        };
+
+        let removed = group.iter().map(|x| x.path.clone()).collect::<Vec<_>>();
+        manifest.replace(&removed, vec![ManifestFile {
+            path: path.clone(),
+            start,
+            end,
+            rows,
+            size: written.size,
+            min_block,
+            max_block,
+            checksum: written.checksum,
+            committed_at: Utc::now().to_rfc3339(),
+            ..Default::default()
+        }]);
+        manifest
+            .write(self.storage.clone(), &self.output_dir)
+            .await?;
+        if let Some(added) = manifest.files.iter().find(|x| x.path == path) {
+            self.notify_sinks(table, removed.clone(), added.clone())
+                .await?;
+        }
+        self.rewrite_commits(table, &removed, &path, commits)
+            .await?;
+
+        for file in group {
+            self.storage.object(&file.path).delete().await?;
+        }
+        info!("Merged {} files into {}, rows={}", group.len(), path, rows);
+        Ok(true)
+    }
+
+    async fn notify_sinks(
+        &self,
+        table: &str,
+        removed: Vec<String>,
+        added: ManifestFile,
+    ) -> Result<()> {
+        let compacted = Compacted {
+            output_dir: self.output_dir.clone(),
+            table: table.to_string(),
+            removed,
+            added,
+        };
+        for sink in &self.sinks {
+            sink.compacted(&compacted).await?;
+        }
+        Ok(())
+    }
+
+    // Returns the min and max block of the merged file, the `blocks` of the
+    // originals if the column projection leaves out the block column.
+    async fn check(
+        &self,
+        table: &str,
+        path: &str,
+        schema: &Schema,
+        rows: u64,
+        (start, end): (u64, u64),
+        blocks: (Option<u64>, Option<u64>),
+    ) -> Result<(Option<u64>, Option<u64>)> {
+        let (merged_schema, chunks) = read_parquet(self.storage.clone(), path).await?;
+        if &merged_schema != schema {
+            return Err(Error::msg(format!("Merged file {} schema mismatch", path)));
+        }
+
+        let merged_rows = chunks.iter().map(|x| x.len() as u64).sum::<u64>();
+        if merged_rows != rows {
+            return Err(Error::msg(format!(
+                "Merged file {} has {} rows, expect {}",
+                path, merged_rows, rows
+            )));
+        }
+
+        let block_column = table_schema(table)?.block_column();
+        if !merged_schema.fields.iter().any(|x| x.name == block_column) {
+            return Ok(blocks);
+        }
+        let (mut min_block, mut max_block) = (None, None);
+        for chunk in &chunks {
+            for number in u64_column(&merged_schema, chunk, block_column)? {
+                if number < start || number > end {
+                    return Err(Error::msg(format!(
+                        "Merged file {} has block {} out of range",
+                        path, number
+                    )));
+                }
+                min_block = Some(min_block.map_or(number, |x: u64| x.min(number)));
+                max_block = Some(max_block.map_or(number, |x: u64| x.max(number)));
+            }
+        }
+        Ok((min_block, max_block))
+    }
+}
+
+/// Group the contiguous files into merges of about the target size, a group
+/// stays in one block boundary, one partition and one schema version. Single file groups are left as is.
+pub fn plan_groups(
+    files: &[RangeFile],
+    block_boundary: u64,
+    target_file_size: u64,
+) -> Vec<Vec<RangeFile>> {
+    let mut files = files.to_vec();
+    files.sort_by_key(|x| x.start);
+
+    let mut groups = vec![];
+    let mut group: Vec<RangeFile> = vec![];
+    let mut size = 0;
+    for file in files {
+        let bucket = file.start / block_boundary;
+        let flush = match group.last() {
+            Some(last) => {
+                last.start / block_boundary != bucket
+                    || last.partition != file.partition
+                    || last.schema_version != file.schema_version
+                    || last.end + 1 != file.start
+                    || size + file.size > target_file_size
+            }
+            None => false,
+        };
+        if flush {
+            groups.push(std::mem::take(&mut group));
+            size = 0;
+        }
+
+        // A file crossing the boundary is never merged.
+        if file.end / block_boundary != bucket {
+            groups.push(std::mem::take(&mut group));
+            size = 0;
+            continue;
+        }
+        size += file.size;
+        group.push(file);
+    }
+    groups.push(group);
+
+    groups.into_iter().filter(|x| x.len() > 1).collect()
+}
@@ -15,38 +15,107 @@
 use std::sync::Arc;
 
 use common_configs::EthConfig;
+use common_configs::HashEncoding;
+use common_exceptions::Result;
 use common_storages::init_object_storage;
 use opendal::Operator;
 
+use crate::contexts::MemoryBudget;
 use crate::contexts::Progress;
+use crate::contexts::Throttle;
+use crate::exporters::eth::TABLES;
+use crate::exporters::BlockRange;
+use crate::exporters::ColumnEncoder;
+use crate::exporters::ColumnProjection;
+use crate::exporters::PathTemplate;
+use crate::manifest::Catalog;
+use crate::sinks::create_sinks;
+use crate::sinks::SinkRef;
+use crate::transforms::Transformer;
 
 #[derive(Clone, Debug)]
 pub struct Context {
     conf: EthConfig,
     progress: Arc<Progress>,
+    memory_budget: Arc<MemoryBudget>,
+    throttle: Arc<Throttle>,
+    catalog: Arc<Catalog>,
+    transformer: Arc<Transformer>,
     rpc_url: String,
     batch_size: usize,
     max_worker: usize,
     web3_batch_size: usize,
     output_dir: String,
+    path_template: PathTemplate,
+    projection: ColumnProjection,
     storage: Arc<Operator>,
+    sinks: Vec<SinkRef>,
 }
 pub type ContextRef = Arc<Context>;
 
 impl Context {
-    pub async fn create(conf: &EthConfig) -> Arc<Context> {
-        let storage = Arc::new(init_object_storage(conf).await.unwrap());
+    /// The storage, the path template, the sinks and the transforms of the
+    /// config are checked here, a bad config fails before any export.
+    pub async fn create(conf: &EthConfig) -> Result<ContextRef> {
+        Context::build(conf, true).await
+    }
+
+    /// A context without the sinks, for the commands that don't export, e.g.
+    /// `plan` or `verify`, which don't need the sinks to be reachable.
+    pub async fn create_without_sinks(conf: &EthConfig) -> Result<ContextRef> {
+        Context::build(conf, false).await
+    }
+
+    async fn build(conf: &EthConfig, with_sinks: bool) -> Result<ContextRef> {
+        let storage = Arc::new(init_object_storage(conf).await?);
+        let path_template = PathTemplate::create(&conf.export.path_template)?;
+        let sinks = if with_sinks {
+            create_sinks(conf, storage.clone(), &path_template)?
+        } else {
+            vec![]
+        };
+        let transformer = Transformer::create(conf.transforms.clone())?;
 
-        Arc::new(Context {
+        Ok(Arc::new(Context {
             conf: conf.clone(),
             progress: Progress::create(),
+            memory_budget: MemoryBudget::create(conf.export.memory_budget_mb * 1024 * 1024),
+            throttle: Throttle::create(conf.export.max_blocks_per_sec),
+            catalog: Catalog::create(),
+            transformer,
             rpc_url: conf.export.provider_uri.to_string(),
             batch_size: conf.export.batch_size,
             max_worker: conf.export.max_worker,
             web3_batch_size: conf.export.web3_batch_size,
             output_dir: conf.export.output_dir.clone(),
+            path_template,
+            projection: ColumnProjection::create(conf.export.columns.clone()),
             storage,
-        })
+            sinks,
+        }))
+    }
+
+    /// Fork a context which shares the config and storage, but has its own
+    /// progress, worker and throughput budget.
+    pub fn fork(&self, max_worker: usize, max_blocks_per_sec: usize) -> ContextRef {
+        let mut ctx = self.clone();
+        ctx.progress = Progress::create();
+        ctx.throttle = Throttle::create(max_blocks_per_sec);
+        ctx.max_worker = max_worker;
+        Arc::new(ctx)
+    }
+
+    /// Fork a context which writes to another storage, e.g. the memory for
+    /// planning, with its own catalog and transformer, nothing is loaded to
+    /// the sinks.
+    pub fn fork_with_storage(&self, storage: Arc<Operator>) -> ContextRef {
+        let mut ctx = self.clone();
+        ctx.progress = Progress::create();
+        ctx.catalog = Catalog::create();
+        ctx.transformer = self.transformer.fork();
+        ctx.storage = storage;
+        ctx.sinks = vec![];
+        Arc::new(ctx)
     }
 
     pub fn get_config(&self) -> EthConfig {
@@ -73,6 +142,33 @@ impl Context {
         self.progress.clone()
     }
 
+    /// The throughput budget of the context, the forks have their own.
+    pub fn get_throttle(&self) -> Arc<Throttle> {
+        self.throttle.clone()
+    }
+
+    /// The memory budget of the process, shared by the forks.
+    pub fn get_memory_budget(&self) -> Arc<MemoryBudget> {
+        self.memory_budget.clone()
+    }
+
+    /// The files of the ranges being exported, shared by the forks.
+    pub fn get_catalog(&self) -> Arc<Catalog> {
+        self.catalog.clone()
+    }
+
+    /// The tables of the ranges being transformed, shared by the forks.
+    pub fn get_transformer(&self) -> Arc<Transformer> {
+        self.transformer.clone()
+    }
+
+    /// The tables of a range, the registry tables then the transform outputs.
+    pub fn get_tables(&self) -> Vec<String> {
+        let mut tables = TABLES.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        tables.extend(self.transformer.tables());
+        tables
+    }
+
     pub fn get_output_dir(&self) -> &str {
         &self.output_dir
     }
@@ -80,4 +176,35 @@ impl Context {
     pub fn get_storage(&self) -> Arc<Operator> {
         self.storage.clone()
     }
+
+    /// The sinks loading the committed ranges.
+    pub fn get_sinks(&self) -> &[SinkRef] {
+        &self.sinks
+    }
+
+    pub fn get_hash_encoding(&self) -> HashEncoding {
+        self.conf.export.hash_encoding
+    }
+
+    pub fn get_column_encoder(&self) -> ColumnEncoder {
+        ColumnEncoder::create(self.conf.export.hash_encoding)
+    }
+
+    pub fn get_column_projection(&self) -> &ColumnProjection {
+        &self.projection
+    }
+
+    pub fn get_path_template(&self) -> &PathTemplate {
+        &self.path_template
+    }
+
+    /// The parquet path of the table range under the dir.
+    pub fn get_output_path(&self, dir: &str, table: &str, range: &BlockRange) -> Result<String> {
+        let vars = range.vars(
+            table,
+            self.conf.export.chain_id,
+            self.conf.export.block_bucket_size,
+        );
+        self.path_template.render(dir, &vars)
+    }
 }
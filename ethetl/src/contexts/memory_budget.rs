@@ -0,0 +1,131 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use common_exceptions::Result;
+use tokio::sync::Notify;
+
+// The estimate of a block before any block is fetched.
+const DEFAULT_BYTES_PER_BLOCK: usize = 512 * 1024;
+
+/// MemoryBudget bounds the bytes of the ranges in flight across the workers
+/// of the process, a range waits for its reservation before fetching.
+///
+/// A range bigger than the budget takes the whole budget so that it still
+/// runs, alone. A reservation resized to the fetched bytes takes the
+/// difference at once, the bytes are in memory already, and the next ranges
+/// wait until the budget is back under the limit.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    // 0 is no limit.
+    limit: usize,
+    used: Mutex<usize>,
+    released: Notify,
+    // The bytes per block seen so far, to reserve the next range.
+    bytes_per_block: AtomicUsize,
+}
+
+impl MemoryBudget {
+    pub fn create(limit_bytes: usize) -> Arc<MemoryBudget> {
+        Arc::new(MemoryBudget {
+            limit: limit_bytes,
+            used: Mutex::new(0),
+            released: Notify::new(),
+            bytes_per_block: AtomicUsize::new(DEFAULT_BYTES_PER_BLOCK),
+        })
+    }
+
+    /// The free bytes, for the tests and the logs.
+    pub fn available(&self) -> usize {
+        self.limit.saturating_sub(*self.used.lock().unwrap())
+    }
+
+    /// Reserve the estimated bytes of the blocks, waits until they are free.
+    pub async fn reserve(self: &Arc<Self>, blocks: usize) -> Result<MemoryReservation> {
+        let estimate = blocks.saturating_mul(self.bytes_per_block.load(Ordering::Relaxed));
+        let bytes = self.cap(estimate);
+        loop {
+            // Registered before the check, a release in between still wakes it.
+            let released = self.released.notified();
+            {
+                let mut used = self.used.lock().unwrap();
+                if *used + bytes <= self.limit {
+                    *used += bytes;
+                    break;
+                }
+            }
+            released.await;
+        }
+        Ok(MemoryReservation {
+            budget: self.clone(),
+            bytes,
+        })
+    }
+
+    /// Learn the bytes per block from a fetched range.
+    pub fn observe(&self, blocks: usize, bytes: usize) {
+        if blocks > 0 {
+            let seen = bytes / blocks;
+            let prev = self.bytes_per_block.load(Ordering::Relaxed);
+            self.bytes_per_block
+                .store((prev + seen) / 2, Ordering::Relaxed);
+        }
+    }
+
+    fn cap(&self, bytes: usize) -> usize {
+        bytes.min(self.limit)
+    }
+
+    fn release(&self, bytes: usize) {
+        if bytes > 0 {
+            *self.used.lock().unwrap() -= bytes;
+            self.released.notify_waiters();
+        }
+    }
+}
+
+/// The bytes reserved by a range, released on drop.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl MemoryReservation {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Resize to the actual bytes once they are known, only the difference
+    /// is taken or given back.
+    pub fn resize(&mut self, bytes: usize) {
+        let bytes = self.budget.cap(bytes);
+        if bytes < self.bytes {
+            self.budget.release(self.bytes - bytes);
+        } else {
+            *self.budget.used.lock().unwrap() += bytes - self.bytes;
+        }
+        self.bytes = bytes;
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
@@ -13,8 +13,13 @@
 // limitations under the License.
 
 mod context;
+mod memory_budget;
 mod progress;
+mod throttle;
 
 pub use context::Context;
 pub use context::ContextRef;
+pub use memory_budget::MemoryBudget;
+pub use memory_budget::MemoryReservation;
 pub use progress::Progress;
+pub use throttle::Throttle;
@@ -0,0 +1,57 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Throttle spreads the ranges of the workers over time to a number of
+/// blocks per second, the throughput budget of a run.
+#[derive(Debug)]
+pub struct Throttle {
+    // 0 is no limit.
+    blocks_per_sec: usize,
+    // When the next range may start.
+    next: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    pub fn create(blocks_per_sec: usize) -> Arc<Throttle> {
+        Arc::new(Throttle {
+            blocks_per_sec,
+            next: Mutex::new(None),
+        })
+    }
+
+    pub fn blocks_per_sec(&self) -> usize {
+        self.blocks_per_sec
+    }
+
+    /// Wait for the turn of a range of the blocks.
+    pub async fn acquire(&self, blocks: usize) {
+        if self.blocks_per_sec == 0 {
+            return;
+        }
+
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let at = next.map_or(now, |x| x.max(now));
+            *next = Some(at + Duration::from_secs_f64(blocks as f64 / self.blocks_per_sec as f64));
+            at
+        };
+        tokio::time::sleep_until(at.into()).await;
+    }
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use common_exceptions::Result;
+use common_storages::write_atomic;
 use log::info;
 
 use crate::contexts::ContextRef;
@@ -62,7 +63,7 @@ impl Batch {
             // Write syncing file.
             {
                 let syncing_json = serde_json::to_vec(&syncing_status)?;
-                op.object(sync_status_file).write(syncing_json).await?;
+                write_atomic(op.clone(), sync_status_file, syncing_json).await?;
                 info!(
                     "Syncing batch[{}], write file={}, status={:?}",
                     i, sync_status_file, syncing_status
@@ -0,0 +1,183 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+use log::info;
+use tokio::io::AsyncReadExt;
+
+use crate::chains::eth::BlockNumber;
+use crate::contexts::ContextRef;
+use crate::etl::Worker;
+use crate::exporters::list_table_files;
+
+/// Export a scattered list of blocks, e.g. the gaps reported by a data check.
+///
+/// A listed block already exported is exported again with the whole range of
+/// the file holding it, so the file is rewritten instead of another file
+/// holding the block. The other blocks are grouped by `batch_size` from its
+/// multiples, between the exported ranges. The syncing status file is left
+/// untouched.
+pub struct BlockListEtl {
+    ctx: ContextRef,
+}
+
+impl BlockListEtl {
+    pub fn create(ctx: ContextRef) -> Self {
+        BlockListEtl { ctx }
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        let numbers = self.read().await?;
+        let ranges = self.ranges(&numbers).await?;
+        info!(
+            "Syncing block list, file={}, blocks={}, ranges={}",
+            self.ctx.get_config().export.block_file,
+            numbers.len(),
+            ranges.len()
+        );
+
+        // Incr progress.
+        self.ctx
+            .get_progress()
+            .inc_all(ranges.iter().map(|x| x.len()).sum());
+
+        let worker = Worker::create_with_ranges(&self.ctx, ranges);
+        worker.start().await
+    }
+
+    // The ranges of the numbers up to the chain head.
+    pub async fn ranges(&self, numbers: &[usize]) -> Result<Vec<Vec<usize>>> {
+        if numbers.is_empty() {
+            return Ok(vec![]);
+        }
+        // Every range is written to all the tables, the blocks table has them all.
+        let mut exported = list_table_files(
+            self.ctx.get_storage(),
+            self.ctx.get_path_template(),
+            self.ctx.get_output_dir(),
+            "blocks",
+            self.ctx.get_config().export.chain_id,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.start as usize, x.end as usize))
+        .collect::<Vec<_>>();
+        exported.dedup();
+
+        let latest = BlockNumber::create(&self.ctx).fetch().await?.as_usize();
+        Ok(group_block_ranges(
+            numbers,
+            &exported,
+            self.ctx.get_batch_size(),
+            latest,
+        ))
+    }
+
+    // Read the sorted block numbers from the block file.
+    pub async fn read(&self) -> Result<Vec<usize>> {
+        let block_file = self.ctx.get_config().export.block_file;
+        let content = if block_file == "-" {
+            let mut buf = String::new();
+            tokio::io::stdin().read_to_string(&mut buf).await?;
+            buf
+        } else {
+            let data = self.ctx.get_storage().object(&block_file).read().await?;
+            String::from_utf8(data)?
+        };
+        parse_block_list(&content)
+    }
+}
+
+/// Parse block numbers and ranges, `100-200` and `100..=200` include 200,
+/// `100..200` doesn't as in Rust.
+///
+/// Items are separated by lines, commas or spaces, `#` starts a comment.
+/// The result is sorted and deduplicated.
+pub fn parse_block_list(content: &str) -> Result<Vec<usize>> {
+    let mut numbers = vec![];
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        for item in line.split(|c: char| c == ',' || c.is_whitespace()) {
+            if item.is_empty() {
+                continue;
+            }
+
+            let parse = |v: &str| {
+                v.trim()
+                    .parse::<usize>()
+                    .map_err(|_| Error::msg(format!("Invalid block number {:?}", item)))
+            };
+            let range = if let Some((start, end)) = item.split_once("..=") {
+                Some(parse(start)?..parse(end)?.saturating_add(1))
+            } else if let Some((start, end)) = item.split_once("..") {
+                Some(parse(start)?..parse(end)?)
+            } else if let Some((start, end)) = item.split_once('-') {
+                Some(parse(start)?..parse(end)?.saturating_add(1))
+            } else {
+                None
+            };
+            match range {
+                Some(range) => {
+                    if range.is_empty() {
+                        return Err(Error::msg(format!("Invalid block range {:?}", item)));
+                    }
+                    numbers.extend(range);
+                }
+                None => numbers.push(parse(item)?),
+            }
+        }
+    }
+
+    numbers.sort_unstable();
+    numbers.dedup();
+    Ok(numbers)
+}
+
+/// Group sorted block numbers into the ranges to export.
+///
+/// A number in one of the sorted `exported` [start, end] ranges is grouped
+/// into that whole range. The others are grouped into the ranges of
+/// `batch_size` blocks from its multiples, cut at the exported ranges and
+/// the `latest` block, so no range overlaps an exported one.
+pub fn group_block_ranges(
+    numbers: &[usize],
+    exported: &[(usize, usize)],
+    batch_size: usize,
+    latest: usize,
+) -> Vec<Vec<usize>> {
+    let batch_size = batch_size.max(1);
+    let mut ranges: Vec<Vec<usize>> = vec![];
+    for number in numbers {
+        if ranges.last().and_then(|x| x.last()) >= Some(number) {
+            continue;
+        }
+
+        // The exported ranges before and after the number.
+        let i = exported.partition_point(|x| x.0 <= *number);
+        let prev = i.checked_sub(1).map(|i| exported[i]);
+        let (start, end) = match prev {
+            Some((start, end)) if end >= *number => (start, end),
+            _ => {
+                let aligned = number - number % batch_size;
+                let start = prev.map_or(aligned, |x| aligned.max(x.1 + 1));
+                let end = (aligned + batch_size - 1).min(latest.max(*number));
+                let end = exported.get(i).map_or(end, |x| end.min(x.0 - 1));
+                (start, end)
+            }
+        };
+        ranges.push((start..=end).collect());
+    }
+    ranges
+}
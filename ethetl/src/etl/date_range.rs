@@ -0,0 +1,188 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::write_atomic;
+use log::info;
+use opendal::ErrorKind;
+
+use crate::chains::eth::BlockNumber;
+use crate::chains::eth::BlockTimestamp;
+use crate::contexts::ContextRef;
+
+// The block timestamps cache file of the searches.
+pub static BLOCK_TIMESTAMPS_FILE: &str = "mars_block_timestamps.json";
+
+// The blocks this close to the head may be reorged, their timestamps are not cached.
+const UNSETTLED_BLOCKS: usize = 128;
+
+/// Resolve the `start_date`/`end_date` of the config to block numbers.
+///
+/// The start is the first block at or after the start date, the end is the
+/// last block before the end date, so `2023-03-01`..`2023-04-01` is March.
+pub struct DateRange {
+    ctx: ContextRef,
+}
+
+impl DateRange {
+    pub fn create(ctx: &ContextRef) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    /// Returns the [start, end] block range, dates take precedence over blocks.
+    pub async fn resolve(&self) -> Result<(usize, usize)> {
+        let conf = self.ctx.get_config().export;
+        let (mut start, mut end) = (conf.start_block, conf.end_block);
+        if conf.start_date.is_empty() && conf.end_date.is_empty() {
+            return Ok((start, end));
+        }
+
+        let now = Utc::now().timestamp();
+        let latest = BlockNumber::create(&self.ctx).fetch().await?.as_usize();
+        let mut cache = self.read_cache().await?;
+
+        if !conf.start_date.is_empty() {
+            let ts = parse_timestamp(&conf.start_date, now)?;
+            start = self.first_block_since(ts, latest, &mut cache).await?;
+            info!("Resolved start date {} to block {}", conf.start_date, start);
+        }
+
+        if !conf.end_date.is_empty() {
+            let ts = parse_timestamp(&conf.end_date, now)?;
+            end = self
+                .first_block_since(ts, latest, &mut cache)
+                .await?
+                .saturating_sub(1);
+            info!("Resolved end date {} to block {}", conf.end_date, end);
+        } else if end == 0 {
+            // Up to the chain head, e.g. the last 6 hours.
+            end = latest;
+        }
+
+        self.write_cache(&cache).await?;
+        Ok((start, end))
+    }
+
+    // Binary search the first block whose timestamp >= ts, latest + 1 if none.
+    //
+    // The cache keeps the timestamps of the probed blocks, a later search
+    // starts between the closest of them, so a relative date resolved again
+    // only probes the few blocks since the last run.
+    async fn first_block_since(
+        &self,
+        ts: i64,
+        latest: usize,
+        cache: &mut BTreeMap<usize, i64>,
+    ) -> Result<usize> {
+        let mut lo = cache
+            .range(..=latest)
+            .rev()
+            .find(|(_, t)| **t < ts)
+            .map_or(0, |(number, _)| number + 1);
+        let mut hi = cache
+            .range(lo..=latest)
+            .find(|(_, t)| **t >= ts)
+            .map_or(latest + 1, |(number, _)| *number);
+
+        let fetcher = BlockTimestamp::create(&self.ctx);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let mid_ts = match cache.get(&mid) {
+                Some(t) => *t,
+                None => {
+                    let t = fetcher.fetch(mid).await?;
+                    // Only cache the settled blocks, the head may be reorged.
+                    if mid + UNSETTLED_BLOCKS <= latest {
+                        cache.insert(mid, t);
+                    }
+                    t
+                }
+            };
+            if mid_ts < ts {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+
+    async fn read_cache(&self) -> Result<BTreeMap<usize, i64>> {
+        let op = self.ctx.get_storage();
+        match op.object(BLOCK_TIMESTAMPS_FILE).read().await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == ErrorKind::ObjectNotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write_cache(&self, cache: &BTreeMap<usize, i64>) -> Result<()> {
+        let op = self.ctx.get_storage();
+        write_atomic(op, BLOCK_TIMESTAMPS_FILE, serde_json::to_vec(cache)?).await
+    }
+}
+
+/// Parse a date to unix seconds in UTC.
+///
+/// Accepts `2023-03-01`, `2023-03-01 08:00:00`, RFC3339 like
+/// `2023-03-01T08:00:00Z`, `now`, and durations ago like `30m`, `6h`, `7d`, `2w`.
+pub fn parse_timestamp(s: &str, now: i64) -> Result<i64> {
+    let s = s.trim();
+    if s == "now" {
+        return Ok(now);
+    }
+
+    if let Ok(v) = DateTime::parse_from_rfc3339(s) {
+        return Ok(v.timestamp());
+    }
+    if let Ok(v) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Ok(v.timestamp());
+    }
+    if let Some(v) = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|v| v.and_hms_opt(0, 0, 0))
+    {
+        return Ok(v.timestamp());
+    }
+
+    // Relative duration.
+    let s = s.trim_start_matches('-');
+    let (num, unit) =
+        s.split_at(s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len());
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => 0,
+    };
+    match num.parse::<i64>() {
+        Ok(n) if secs > 0 => n
+            .checked_mul(secs)
+            .and_then(|ago| now.checked_sub(ago))
+            .ok_or_else(|| Error::msg(format!("The duration {:?} is out of range", s))),
+        _ => Err(Error::msg(format!(
+            "Invalid date {:?}, expect 2023-03-01, 2023-03-01T08:00:00Z or durations like 6h",
+            s
+        ))),
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use common_exceptions::Result;
+use common_storages::write_atomic;
+use log::error;
+use log::info;
+
+use crate::chains::eth::BlockNumber;
+use crate::contexts::ContextRef;
+use crate::etl::Batch;
+use crate::etl::DateRange;
+use crate::etl::ReorgDetector;
+use crate::etl::SyncingStatus;
+use crate::etl::Worker;
+use crate::etl::BACKFILL_STATUS_FILE;
+use crate::etl::SYNCING_STATUS_FILE;
+
+/// Hybrid mode tails the chain head like the stream mode, and backfills the
+/// history from the tail down to the start block in the background.
+///
+/// The tail shares the checkpoint with the stream mode, so once the backfill
+/// meets the start block the run is a plain stream.
+/// The backfill checkpoint records the exported history as [start, end].
+pub struct HybridEtl {
+    ctx: ContextRef,
+}
+
+impl HybridEtl {
+    pub fn create(ctx: ContextRef) -> Self {
+        HybridEtl { ctx }
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        let conf = self.ctx.get_config();
+        let tail_ctx = self
+            .ctx
+            .fork(conf.export.max_worker, conf.export.max_blocks_per_sec);
+        let backfill_ctx = self.ctx.fork(
+            conf.export.backfill_max_worker,
+            conf.export.backfill_max_blocks_per_sec,
+        );
+
+        let (start_block, _) = DateRange::create(&self.ctx).resolve().await?;
+        let tail_start = self.tail_checkpoint().await?;
+        let backfill_status = self.backfill_checkpoint(tail_start).await?;
+
+        let backfill_progress = backfill_ctx.get_progress();
+        backfill_progress.set_name("Hybrid-Backfill".to_string());
+        backfill_progress.start();
+        let mut backfill = tokio::spawn(async move {
+            let res = Self::backfill(&backfill_ctx, start_block, backfill_status).await;
+            backfill_ctx.get_progress().stop();
+            res
+        });
+
+        let tail_progress = tail_ctx.get_progress();
+        tail_progress.set_name("Hybrid-Tail".to_string());
+        tail_progress.start();
+        let tail = Self::tail(&tail_ctx, tail_start);
+        tokio::pin!(tail);
+
+        // A failed backfill fails the run, a finished one leaves the tail.
+        let res = tokio::select! {
+            res = &mut tail => res,
+            res = &mut backfill => match res? {
+                Ok(_) => {
+                    info!("Backfill met the start block, continue as a plain stream");
+                    tail.await
+                }
+                Err(e) => {
+                    error!("Backfill error: {:?}", e);
+                    Err(e)
+                }
+            },
+        };
+        backfill.abort();
+        tail_progress.stop();
+        res
+    }
+
+    // The tail starts from the stream checkpoint, or the chain head for a new dataset.
+    async fn tail_checkpoint(&self) -> Result<usize> {
+        let op = self.ctx.get_storage();
+        if let Some(prev_syncing_status) = SyncingStatus::read(op, SYNCING_STATUS_FILE).await? {
+            info!(
+                "Found hybrid tail syncing status file={}, status={:?}",
+                SYNCING_STATUS_FILE, prev_syncing_status
+            );
+            return Ok(prev_syncing_status.end + 1);
+        }
+
+        let latest_block = BlockNumber::create(&self.ctx).fetch().await?;
+        info!("Eth node last block number :{}", latest_block);
+        Ok(latest_block.as_usize())
+    }
+
+    // The backfill starts from its own checkpoint, or right below the tail.
+    async fn backfill_checkpoint(&self, tail_start: usize) -> Result<SyncingStatus> {
+        let op = self.ctx.get_storage();
+        if let Some(prev_backfill_status) =
+            SyncingStatus::read(op.clone(), BACKFILL_STATUS_FILE).await?
+        {
+            info!(
+                "Found hybrid backfill status file={}, status={:?}",
+                BACKFILL_STATUS_FILE, prev_backfill_status
+            );
+            return Ok(prev_backfill_status);
+        }
+
+        // Nothing is backfilled yet, persist the upper bound before the tail moves on.
+        let backfill_status = SyncingStatus {
+            start: tail_start,
+            end: tail_start.saturating_sub(1),
+        };
+        let backfill_json = serde_json::to_vec(&backfill_status)?;
+        write_atomic(op.clone(), BACKFILL_STATUS_FILE, backfill_json).await?;
+        Ok(backfill_status)
+    }
+
+    async fn tail(ctx: &ContextRef, mut start: usize) -> Result<()> {
+        let interval = Duration::from_secs(ctx.get_config().export.syncing_interval_secs as u64);
+        let mut detector = ReorgDetector::create(ctx, SYNCING_STATUS_FILE);
+        loop {
+            let end = {
+                let latest_block = BlockNumber::create(ctx).fetch().await?;
+                info!("Eth node last block number :{}", latest_block);
+                latest_block.as_usize()
+            };
+            start = detector.check(start).await?;
+            if start <= end {
+                let batch = Batch::create(ctx.clone());
+                batch.syncing(start, end, SYNCING_STATUS_FILE).await?;
+                start = end + 1;
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn backfill(
+        ctx: &ContextRef,
+        start_block: usize,
+        mut status: SyncingStatus,
+    ) -> Result<()> {
+        let op = ctx.get_storage();
+        if status.start <= start_block {
+            return Ok(());
+        }
+
+        // Incr progress.
+        ctx.get_progress().inc_all(status.start - start_block);
+
+        // Fits each chunk to max worker, walking down to the start block.
+        let chunk_size = (ctx.get_batch_size() * ctx.get_max_worker()).max(1);
+        while status.start > start_block {
+            let end = status.start - 1;
+            let start = end.saturating_sub(chunk_size - 1).max(start_block);
+
+            // Start to backfill.
+            {
+                info!("Backfill chunk, range=[{:?}, {:?}]", start, end);
+                let worker = Worker::create(ctx, (start..=end).collect());
+                worker.start().await?;
+            }
+
+            // Write backfill file.
+            {
+                status.start = start;
+                let backfill_json = serde_json::to_vec(&status)?;
+                write_atomic(op.clone(), BACKFILL_STATUS_FILE, backfill_json).await?;
+                info!(
+                    "Backfill chunk, write file={}, status={:?}",
+                    BACKFILL_STATUS_FILE, status
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
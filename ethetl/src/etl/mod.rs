@@ -13,23 +13,60 @@
 // limitations under the License.
 
 mod batch;
+mod block_list;
+mod date_range;
+mod hybrid;
 mod normal;
 mod pipeline;
+mod plan;
+mod reorg;
 #[allow(clippy::module_inception)]
 mod stream;
 mod worker;
 
+use std::sync::Arc;
+
 pub use batch::Batch;
+pub use block_list::group_block_ranges;
+pub use block_list::parse_block_list;
+pub use block_list::BlockListEtl;
+use common_exceptions::Result;
+pub use date_range::parse_timestamp;
+pub use date_range::DateRange;
+pub use date_range::BLOCK_TIMESTAMPS_FILE;
+pub use hybrid::HybridEtl;
 pub use normal::NormalEtl;
+use opendal::ErrorKind;
+use opendal::Operator;
 pub use pipeline::Pipeline;
+pub use plan::empty_file_bytes;
+pub use plan::project_bytes;
+pub use plan::PlanReport;
+pub use plan::Planner;
+pub use reorg::find_fork;
+pub use reorg::ReorgDetector;
 pub use stream::StreamEtl;
 pub use worker::Worker;
 
 // The syncing status file.
 pub static SYNCING_STATUS_FILE: &str = "mars_syncing_status.json";
 
+// The backfill status file of the hybrid mode.
+pub static BACKFILL_STATUS_FILE: &str = "mars_backfill_status.json";
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct SyncingStatus {
     start: usize,
     end: usize,
 }
+
+impl SyncingStatus {
+    // The status in the file, none if there is no such file yet.
+    async fn read(op: Arc<Operator>, path: &str) -> Result<Option<SyncingStatus>> {
+        match op.object(path).read().await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == ErrorKind::ObjectNotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
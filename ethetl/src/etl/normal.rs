@@ -17,6 +17,7 @@ use log::info;
 
 use crate::contexts::ContextRef;
 use crate::etl::Batch;
+use crate::etl::DateRange;
 use crate::etl::SyncingStatus;
 use crate::etl::SYNCING_STATUS_FILE;
 
@@ -30,21 +31,8 @@ impl NormalEtl {
     }
 
     pub async fn start(&self) -> Result<()> {
-        let mut start = self.ctx.get_config().export.start_block;
-        let end = self.ctx.get_config().export.end_block;
-
-        // Fetch syncing file.
-        {
-            let op = self.ctx.get_storage();
-            if let Ok(data) = op.object(SYNCING_STATUS_FILE).read().await {
-                let prev_syncing_status: SyncingStatus = serde_json::from_slice(&data)?;
-                start = prev_syncing_status.end + 1;
-                info!(
-                    "Found normal syncing status file={}, status={:?}",
-                    SYNCING_STATUS_FILE, prev_syncing_status
-                );
-            }
-        }
+        let (start, end) = DateRange::create(&self.ctx).resolve().await?;
+        let start = self.resume_from(start).await?;
 
         if start <= end {
             let batch = Batch::create(self.ctx.clone());
@@ -53,4 +41,19 @@ impl NormalEtl {
 
         Ok(())
     }
+
+    /// The first block to export, after the syncing status file if any.
+    pub async fn resume_from(&self, start: usize) -> Result<usize> {
+        let op = self.ctx.get_storage();
+        match SyncingStatus::read(op, SYNCING_STATUS_FILE).await? {
+            Some(prev_syncing_status) => {
+                info!(
+                    "Found normal syncing status file={}, status={:?}",
+                    SYNCING_STATUS_FILE, prev_syncing_status
+                );
+                Ok(prev_syncing_status.end + 1)
+            }
+            None => Ok(start),
+        }
+    }
 }
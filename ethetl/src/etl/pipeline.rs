@@ -13,14 +13,18 @@
 // limitations under the License.
 
 use common_exceptions::Result;
+use log::warn;
 
 use crate::contexts::ContextRef;
 use crate::exporters::eth::BlockExporter;
+use crate::exporters::BlockRange;
+use crate::verify::RootMismatch;
 
 pub struct Pipeline {
     ctx: ContextRef,
     block_numbers: Vec<usize>,
     output_dir: String,
+    range: BlockRange,
     range_path: String,
 }
 
@@ -29,19 +33,33 @@ impl Pipeline {
         Self {
             ctx: ctx.clone(),
             output_dir: ctx.get_output_dir().to_string(),
+            range: BlockRange::create(&block_numbers),
             range_path: range_path.to_string(),
             block_numbers,
         }
     }
 
     pub async fn execute(&self) -> Result<()> {
-        let export = BlockExporter::create(
-            &self.ctx,
-            &self.output_dir,
-            &self.range_path,
-            self.block_numbers.to_vec(),
-        );
-        let res = export.export().await;
-        res
+        let retries = self.ctx.get_config().export.verify_retries;
+        let mut retried = 0;
+        loop {
+            let export = BlockExporter::create(
+                &self.ctx,
+                &self.output_dir,
+                &self.range,
+                self.block_numbers.to_vec(),
+            );
+            match export.export().await {
+                // The provider may serve a bad or reorged block, fetch the range again.
+                Err(e) if e.is::<RootMismatch>() && retried < retries => {
+                    retried += 1;
+                    warn!(
+                        "pipeline: {:?} rejected, retry {}/{}, error: {}",
+                        self.range_path, retried, retries, e
+                    );
+                }
+                res => return res,
+            }
+        }
     }
 }
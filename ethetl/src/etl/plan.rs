@@ -0,0 +1,295 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use arrow2::array::new_empty_array;
+use arrow2::chunk::Chunk;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use common_storages::list_files;
+use common_storages::read_parquet;
+use common_storages::read_parquet_metadata;
+use common_storages::write_parquet_chunks;
+use log::info;
+use opendal::Operator;
+use serde::Serialize;
+
+use crate::chains::eth::BlockFetcher;
+use crate::contexts::ContextRef;
+use crate::etl::BlockListEtl;
+use crate::etl::DateRange;
+use crate::etl::NormalEtl;
+use crate::exporters::eth::BlockExporter;
+use crate::exporters::eth::ReceiptExporter;
+use crate::exporters::BlockRange;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PlanReport {
+    pub blocks: usize,
+    pub sampled_blocks: usize,
+    pub txs_per_block: f64,
+    pub logs_per_block: f64,
+    pub token_transfers_per_block: f64,
+    pub ens_per_block: f64,
+    // RPC calls per method.
+    pub rpc_calls: BTreeMap<String, usize>,
+    // The web3 batch requests sent to the provider.
+    pub rpc_requests: usize,
+    // Output bytes per table.
+    pub output_bytes: BTreeMap<String, usize>,
+    pub files: usize,
+    pub estimated_runtime_secs: u64,
+}
+
+/// Planner samples blocks across the range, fetches them and writes their
+/// tables to the memory, then projects the density, RPC calls, output size
+/// and runtime to the range.
+pub struct Planner {
+    ctx: ContextRef,
+    sample_blocks: usize,
+}
+
+impl Planner {
+    pub fn create(ctx: ContextRef, sample_blocks: usize) -> Self {
+        Planner { ctx, sample_blocks }
+    }
+
+    pub async fn start(&self) -> Result<PlanReport> {
+        let batch_size = self.ctx.get_batch_size();
+
+        // The ranges of the run and the sampled block numbers.
+        let (ranges, samples) = if self.ctx.get_config().export.block_file.is_empty() {
+            // The timestamps cache of the dates is not written to the storage.
+            let ctx = self
+                .ctx
+                .fork_with_storage(Arc::new(init_memory_operator()?));
+            let (start, end) = DateRange::create(&ctx).resolve().await?;
+            // The next run resumes from the checkpoint as the export does.
+            let start = NormalEtl::create(self.ctx.clone())
+                .resume_from(start)
+                .await?;
+            let blocks = (end + 1).saturating_sub(start);
+            let mut ranges = vec![batch_size; blocks / batch_size];
+            if blocks % batch_size > 0 {
+                ranges.push(blocks % batch_size);
+            }
+            let samples = sample_indexes(blocks, self.sample_blocks)
+                .into_iter()
+                .map(|i| start + i)
+                .collect::<Vec<_>>();
+            (ranges, samples)
+        } else {
+            let block_list = BlockListEtl::create(self.ctx.clone());
+            let numbers = block_list.read().await?;
+            let ranges = block_list
+                .ranges(&numbers)
+                .await?
+                .iter()
+                .map(|x| x.len())
+                .collect::<Vec<_>>();
+            let samples = sample_indexes(numbers.len(), self.sample_blocks)
+                .into_iter()
+                .map(|i| numbers[i])
+                .collect::<Vec<_>>();
+            (ranges, samples)
+        };
+        info!(
+            "Planning, blocks={}, ranges={}, sampled blocks={:?}",
+            ranges.iter().sum::<usize>(),
+            ranges.len(),
+            samples
+        );
+
+        let report = self.plan(&ranges, &samples).await?;
+        info!("Plan: {:?}", report);
+        Ok(report)
+    }
+
+    async fn plan(&self, ranges: &[usize], samples: &[usize]) -> Result<PlanReport> {
+        let web3_batch_size = self.ctx.get_web3_batch_size().max(1);
+        let blocks = ranges.iter().sum::<usize>();
+        let tables = self.ctx.get_tables();
+        let mut report = PlanReport {
+            blocks,
+            sampled_blocks: samples.len(),
+            // The tables, the tx hash file and the commit marker.
+            files: ranges.len() * (tables.len() + 2),
+            ..Default::default()
+        };
+        if samples.is_empty() {
+            return Ok(report);
+        }
+
+        // Fetch the samples, then write their tables to the memory to measure
+        // the output. The ranges are not committed, nothing is written to the
+        // storage or loaded to the sinks.
+        let storage = Arc::new(init_memory_operator()?);
+        let ctx = self.ctx.fork_with_storage(storage.clone());
+        let progress = ctx.get_progress();
+        progress.set_name("Plan".to_string());
+
+        // The bytes, the files and the bytes of an empty file of each table.
+        let mut sample_bytes: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+        let mut sample_requests = 0;
+        let mut fetch_secs = 0.0;
+        for number in samples {
+            let txs = progress.value().txs;
+            let started = Instant::now();
+            let mut fetcher = BlockFetcher::create(&ctx);
+            fetcher.push(*number)?;
+            let blocks = fetcher.fetch().await?;
+
+            let exporter = BlockExporter::create(
+                &ctx,
+                ctx.get_output_dir(),
+                &BlockRange::create(&[*number]),
+                vec![*number],
+            );
+            let range = exporter.fetched_range(&blocks);
+            let tx_hashes = blocks
+                .iter()
+                .flat_map(|block| block.transactions.iter().map(|tx| tx.hash))
+                .collect();
+            let receipts = ReceiptExporter::create(&ctx, ctx.get_output_dir(), &range, tx_hashes)
+                .fetch()
+                .await?;
+            fetch_secs += started.elapsed().as_secs_f64();
+
+            exporter.write_tables(&blocks, &receipts).await?;
+            exporter.transform(&range).await?;
+            sample_requests += 1 + div_ceil(progress.value().txs - txs, web3_batch_size);
+        }
+
+        // The sampled files of each table, wherever the path template puts them.
+        let chain_id = ctx.get_config().export.chain_id;
+        let template = ctx.get_path_template();
+        for table in &tables {
+            let dir = template.table_dir(ctx.get_output_dir(), table, chain_id);
+            let mut paths = vec![];
+            for path in list_files(storage.clone(), &dir).await? {
+                if template.parse(ctx.get_output_dir(), table, &path).is_some() {
+                    paths.push(path);
+                }
+            }
+            if let Some(first) = paths.first() {
+                let empty = empty_file_bytes(storage.clone(), first).await?;
+                let mut bytes = 0;
+                for path in &paths {
+                    bytes += storage.object(path).stat().await?.content_length() as usize;
+                }
+                sample_bytes.insert(table.to_string(), (bytes, paths.len(), empty));
+            }
+        }
+
+        // Density.
+        let value = progress.value();
+        let n = samples.len() as f64;
+        report.txs_per_block = value.txs as f64 / n;
+        report.logs_per_block = value.logs as f64 / n;
+        report.token_transfers_per_block = value.token_transfers as f64 / n;
+        report.ens_per_block = value.ens as f64 / n;
+
+        // RPC calls.
+        let receipts = (blocks as f64 * value.receipts as f64 / n) as usize;
+        report
+            .rpc_calls
+            .insert("eth_getBlockByNumber".to_string(), blocks);
+        report
+            .rpc_calls
+            .insert("eth_getTransactionReceipt".to_string(), receipts);
+        report.rpc_requests = ranges
+            .iter()
+            .map(|len| {
+                let txs = (*len as f64 * report.txs_per_block).ceil() as usize;
+                div_ceil(*len, web3_batch_size) + div_ceil(txs, web3_batch_size)
+            })
+            .sum();
+
+        // Output size, a range file of the table has one file overhead.
+        for (table, (bytes, files, empty)) in sample_bytes {
+            let projected = project_bytes(bytes, files, empty, blocks, ranges.len());
+            report.output_bytes.insert(table, projected);
+        }
+
+        // Runtime at the sampled latency per request and the max worker, or
+        // at the configured blocks per second if that is slower.
+        let secs_per_request = fetch_secs / sample_requests.max(1) as f64;
+        let mut runtime_secs =
+            report.rpc_requests as f64 * secs_per_request / self.ctx.get_max_worker().max(1) as f64;
+        let blocks_per_sec = self.ctx.get_throttle().blocks_per_sec();
+        if blocks_per_sec > 0 {
+            runtime_secs = runtime_secs.max(blocks as f64 / blocks_per_sec as f64);
+        }
+        report.estimated_runtime_secs = runtime_secs as u64;
+
+        Ok(report)
+    }
+}
+
+/// The bytes of the file of a sampled block projected to `blocks` blocks
+/// written to `files` files.
+///
+/// Each sampled file holds its schema, footer and page headers, the bytes of
+/// an empty file of the table, which are counted once per written file, only
+/// the rest grows with the blocks.
+pub fn project_bytes(
+    sample_bytes: usize,
+    sample_files: usize,
+    empty_file_bytes: usize,
+    blocks: usize,
+    files: usize,
+) -> usize {
+    if sample_files == 0 {
+        return 0;
+    }
+    let data_bytes = sample_bytes.saturating_sub(empty_file_bytes * sample_files);
+    let per_block = data_bytes as f64 / sample_files as f64;
+    (per_block * blocks as f64) as usize + empty_file_bytes * files
+}
+
+/// The bytes of the parquet file of the schema and footer metadata of the
+/// file at the path, without rows.
+pub async fn empty_file_bytes(op: Arc<Operator>, path: &str) -> Result<usize> {
+    let (schema, _) = read_parquet(op.clone(), path).await?;
+    let metadata = read_parquet_metadata(op, path).await?;
+    let arrays = schema
+        .fields
+        .iter()
+        .map(|f| new_empty_array(f.data_type().clone()))
+        .collect();
+    let written = write_parquet_chunks(
+        Arc::new(init_memory_operator()?),
+        "empty.parquet",
+        schema,
+        vec![Chunk::try_new(arrays)?],
+        metadata,
+    )
+    .await?;
+    Ok(written.size as usize)
+}
+
+// The middle indexes of `samples` equal parts of `len`.
+fn sample_indexes(len: usize, samples: usize) -> Vec<usize> {
+    let samples = samples.min(len);
+    (0..samples)
+        .map(|i| (2 * i + 1) * len / (2 * samples))
+        .collect()
+}
+
+fn div_ceil(a: usize, b: usize) -> usize {
+    (a + b - 1) / b
+}
@@ -0,0 +1,316 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use common_eth::h256_to_hex;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::read_parquet;
+use common_storages::write_atomic;
+use log::info;
+use log::warn;
+use opendal::Scheme;
+use web3::types::H256;
+
+use crate::chains::eth::BlockFetcher;
+use crate::contexts::ContextRef;
+use crate::etl::SyncingStatus;
+use crate::exporters::eth::remove_range;
+use crate::exporters::eth::TABLES;
+use crate::manifest::Lease;
+use crate::manifest::ManifestFile;
+use crate::manifest::RangeCommit;
+use crate::manifest::TableManifest;
+use crate::sinks::RolledBack;
+use crate::verify::str_column;
+use crate::verify::u64_column;
+
+/// ReorgDetector checks the exported blocks before the next range of the
+/// stream against the chain. On a reorg the ranges from the fork are rolled
+/// back and exported again:
+///
+/// 1. the sinks retract the rolled back files,
+/// 2. the checkpoint moves back to the block before the first rolled back range,
+/// 3. the files are removed from the manifests, then deleted with their commit markers.
+///
+/// The files committed past the checkpoint, left by an interrupted run, are
+/// rolled back the same way on the first check, so every step is done again
+/// after a crash.
+///
+/// A detector lives as long as the stream, it keeps the blocks manifest and
+/// only reads the newest exported block on each check, the blocks within the
+/// depth are read when its hash diverges from the chain.
+pub struct ReorgDetector {
+    ctx: ContextRef,
+    status_file: String,
+    recovered: bool,
+    blocks: TableManifest,
+}
+
+impl ReorgDetector {
+    pub fn create(ctx: &ContextRef, status_file: &str) -> Self {
+        ReorgDetector {
+            ctx: ctx.clone(),
+            status_file: status_file.to_string(),
+            recovered: false,
+            blocks: TableManifest::create("blocks"),
+        }
+    }
+
+    /// The block to export from, `start` unless some blocks are rolled back.
+    pub async fn check(&mut self, start: usize) -> Result<usize> {
+        let depth = self.ctx.get_config().export.max_reorg_depth;
+        if depth == 0 {
+            return Ok(start);
+        }
+        let mut start = start;
+        if !self.recovered {
+            if let Some(restart) = self.rollback(start as u64).await? {
+                start = restart as usize;
+            }
+            self.recovered = true;
+        }
+        self.prune(start as u64, depth).await?;
+
+        self.refresh_blocks().await?;
+        let latest = match self.latest_hash(start as u64).await? {
+            Some(latest) => latest,
+            None => return Ok(start),
+        };
+        if self.chain_hashes(&[latest.0]).await?.get(&latest.0) == Some(&latest.1) {
+            return Ok(start);
+        }
+
+        // Newest first.
+        let exported = self.exported_hashes(start as u64, depth).await?;
+        let numbers = exported.iter().map(|x| x.0).collect::<Vec<_>>();
+        let chain = self.chain_hashes(&numbers).await?;
+        let fork = find_fork(&exported, &chain).ok_or_else(|| {
+            Error::msg(format!(
+                "Chain reorg deeper than max_reorg_depth {} before block {}",
+                depth, start
+            ))
+        })?;
+        warn!("Chain reorg after block {}, roll back", fork);
+        match self.rollback(fork + 1).await? {
+            Some(restart) => Ok(restart as usize),
+            None => Ok(start),
+        }
+    }
+
+    /// Roll back the committed files with blocks from `from_block`, returns
+    /// the first rolled back block, none if there is no such file.
+    pub async fn rollback(&self, from_block: u64) -> Result<Option<u64>> {
+        // The output dir is not compacted while the files are rolled back.
+        let op = self.ctx.get_storage();
+        let lease = Lease::writer(op, self.ctx.get_output_dir()).await?;
+        let res = self.rollback_from(from_block).await;
+        lease.release().await?;
+        res
+    }
+
+    async fn rollback_from(&self, from_block: u64) -> Result<Option<u64>> {
+        let op = self.ctx.get_storage();
+        let output_dir = self.ctx.get_output_dir();
+
+        let tables = self.ctx.get_tables();
+        let mut files = BTreeMap::new();
+        for table in &tables {
+            let manifest = TableManifest::read(op.clone(), output_dir, table).await?;
+            let removed = manifest
+                .files
+                .into_iter()
+                .filter(|x| x.end >= from_block)
+                .collect::<Vec<_>>();
+            if !removed.is_empty() {
+                files.insert(table.to_string(), removed);
+            }
+        }
+        // The whole ranges are exported again.
+        let restart = match files.values().flatten().map(|x| x.start).min() {
+            Some(start) => start.min(from_block),
+            None => return Ok(None),
+        };
+        info!(
+            "Roll back the files from block {}, export again from block {}",
+            from_block, restart
+        );
+
+        self.rollback_files(restart, &files).await?;
+        Ok(Some(restart))
+    }
+
+    async fn rollback_files(
+        &self,
+        restart: u64,
+        files: &BTreeMap<String, Vec<ManifestFile>>,
+    ) -> Result<()> {
+        let op = self.ctx.get_storage();
+        let output_dir = self.ctx.get_output_dir();
+        let tables = self.ctx.get_tables();
+
+        // The sinks load the registry tables only.
+        let rolled_back = RolledBack {
+            output_dir: output_dir.to_string(),
+            from_block: restart,
+            files: files
+                .iter()
+                .filter(|(table, _)| TABLES.contains(&table.as_str()))
+                .map(|(table, x)| (table.clone(), x.clone()))
+                .collect(),
+        };
+        for sink in self.ctx.get_sinks() {
+            sink.rollback(&rolled_back).await?;
+        }
+
+        self.move_checkpoint(restart).await?;
+
+        self.ctx
+            .get_catalog()
+            .rollback(op.clone(), output_dir, restart, &tables)
+            .await?;
+        let mut ranges = BTreeSet::new();
+        for file in files.values().flatten() {
+            op.object(&file.path).delete().await?;
+            ranges.insert((file.start, file.end));
+        }
+        for (start, end) in ranges {
+            op.object(&RangeCommit::path(output_dir, start, end))
+                .delete()
+                .await?;
+        }
+        Ok(())
+    }
+
+    // The checkpoint never stays past the rolled back blocks.
+    async fn move_checkpoint(&self, restart: u64) -> Result<()> {
+        let op = self.ctx.get_storage();
+        let mut status = match SyncingStatus::read(op.clone(), &self.status_file).await? {
+            Some(status) => status,
+            None => return Ok(()),
+        };
+        let restart = restart as usize;
+        if status.end < restart {
+            return Ok(());
+        }
+        status.start = status.start.min(restart);
+        status.end = restart.saturating_sub(1);
+        write_atomic(op, &self.status_file, serde_json::to_vec(&status)?).await
+    }
+
+    // The memory storage keeps the ranges within the depth only, the older
+    // ones are loaded to the sinks and never rolled back.
+    async fn prune(&self, start: u64, depth: usize) -> Result<()> {
+        let op = self.ctx.get_storage();
+        if op.metadata().scheme() != Scheme::Memory {
+            return Ok(());
+        }
+        let low = start.saturating_sub(depth as u64);
+        let output_dir = self.ctx.get_output_dir();
+        for commit in RangeCommit::list(op.clone(), output_dir).await? {
+            if commit.end >= low {
+                break;
+            }
+            remove_range(op.clone(), output_dir, &commit).await?;
+        }
+        Ok(())
+    }
+
+    // Apply the blocks manifest changes since the last check, the rollbacks
+    // included, read it again if the log has been compacted past it.
+    async fn refresh_blocks(&mut self) -> Result<()> {
+        let op = self.ctx.get_storage();
+        let output_dir = self.ctx.get_output_dir();
+        if !self.blocks.refresh(op.clone(), output_dir).await? {
+            self.blocks = TableManifest::read(op, output_dir, "blocks").await?;
+        }
+        Ok(())
+    }
+
+    // The hash of the newest exported block before `start`, from its file only.
+    async fn latest_hash(&self, start: u64) -> Result<Option<(u64, String)>> {
+        let file = match self.blocks.files.iter().rev().find(|x| x.start < start) {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let hashes = self.file_hashes(file).await?;
+        Ok(hashes
+            .into_iter()
+            .filter(|x| x.0 < start)
+            .max_by_key(|x| x.0))
+    }
+
+    // The exported hashes of the `depth` blocks before `start`, newest first.
+    async fn exported_hashes(&self, start: u64, depth: usize) -> Result<Vec<(u64, String)>> {
+        let low = start.saturating_sub(depth as u64);
+
+        let mut hashes = vec![];
+        for file in self.blocks.files.iter().rev() {
+            if file.start >= start {
+                continue;
+            }
+            if file.end < low {
+                break;
+            }
+            hashes.extend(self.file_hashes(file).await?);
+        }
+        hashes.retain(|x| x.0 >= low && x.0 < start);
+        hashes.sort_by(|a, b| b.0.cmp(&a.0));
+        Ok(hashes)
+    }
+
+    // The block hashes of a blocks file, none if the columns are not exported.
+    async fn file_hashes(&self, file: &ManifestFile) -> Result<Vec<(u64, String)>> {
+        let (schema, chunks) = read_parquet(self.ctx.get_storage(), &file.path).await?;
+        let mut hashes = vec![];
+        for chunk in chunks {
+            match (
+                u64_column(&schema, &chunk, "number"),
+                str_column(&schema, &chunk, "hash"),
+            ) {
+                (Ok(numbers), Ok(blocks)) => hashes.extend(numbers.into_iter().zip(blocks)),
+                _ => {
+                    warn!("The blocks have no number or hash column, skip the reorg check");
+                    return Ok(vec![]);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    async fn chain_hashes(&self, numbers: &[u64]) -> Result<BTreeMap<u64, String>> {
+        let mut fetcher = BlockFetcher::create(&self.ctx);
+        fetcher.push_batch(numbers.iter().map(|x| *x as usize).collect())?;
+        Ok(fetcher
+            .fetch()
+            .await?
+            .iter()
+            .filter_map(|b| {
+                let number = b.number?.as_u64();
+                Some((number, h256_to_hex(&b.hash.unwrap_or_else(H256::zero))))
+            })
+            .collect())
+    }
+}
+
+/// The newest exported block still on the chain, the exported hashes are
+/// newest first. None if all of them are reorged.
+pub fn find_fork(exported: &[(u64, String)], chain: &BTreeMap<u64, String>) -> Option<u64> {
+    exported
+        .iter()
+        .find(|(number, hash)| chain.get(number) == Some(hash))
+        .map(|x| x.0)
+}
@@ -21,6 +21,8 @@ use ticker::Ticker;
 use crate::chains::eth::BlockNumber;
 use crate::contexts::ContextRef;
 use crate::etl::Batch;
+use crate::etl::DateRange;
+use crate::etl::ReorgDetector;
 use crate::etl::SyncingStatus;
 use crate::etl::SYNCING_STATUS_FILE;
 
@@ -34,13 +36,12 @@ impl StreamEtl {
     }
 
     pub async fn start(&self) -> Result<()> {
-        let mut start = self.ctx.get_config().export.start_block;
+        let (mut start, _) = DateRange::create(&self.ctx).resolve().await?;
 
         // Fetch syncing file.
         {
             let op = self.ctx.get_storage();
-            if let Ok(data) = op.object(SYNCING_STATUS_FILE).read().await {
-                let prev_syncing_status: SyncingStatus = serde_json::from_slice(&data)?;
+            if let Some(prev_syncing_status) = SyncingStatus::read(op, SYNCING_STATUS_FILE).await? {
                 start = prev_syncing_status.end + 1;
                 info!(
                     "Found syncing status file={}, status={:?}",
@@ -53,6 +54,7 @@ impl StreamEtl {
             0..,
             Duration::from_secs(self.ctx.get_config().export.syncing_interval_secs as u64),
         );
+        let mut detector = ReorgDetector::create(&self.ctx, SYNCING_STATUS_FILE);
         for _i in ticker {
             // Fetch syncing state.
             let end = {
@@ -60,6 +62,7 @@ impl StreamEtl {
                 info!("Eth node last block number :{}", latest_block);
                 latest_block.as_usize()
             };
+            start = detector.check(start).await?;
             if start <= end {
                 let batch = Batch::create(self.ctx.clone());
                 batch.syncing(start, end, SYNCING_STATUS_FILE).await?;
@@ -20,55 +20,81 @@ use log::error;
 
 use crate::contexts::ContextRef;
 use crate::etl::Pipeline;
+use crate::manifest::Lease;
+use crate::manifest::LeaseCheck;
 
 pub struct Worker {
     ctx: ContextRef,
-    block_numbers: Vec<usize>,
+    ranges: Vec<Vec<usize>>,
 }
 
 impl Worker {
     pub fn create(ctx: &ContextRef, block_numbers: Vec<usize>) -> Self {
+        let ranges = block_numbers
+            .chunks(ctx.get_batch_size())
+            .map(Vec::from)
+            .collect();
+        Self::create_with_ranges(ctx, ranges)
+    }
+
+    // Each range is exported by one pipeline as is.
+    pub fn create_with_ranges(ctx: &ContextRef, ranges: Vec<Vec<usize>>) -> Self {
         Self {
             ctx: ctx.clone(),
-            block_numbers,
+            ranges,
         }
     }
 
     pub async fn start(&self) -> Result<()> {
+        // The output dir is not compacted while the ranges are written.
+        let lease = Lease::writer(self.ctx.get_storage(), self.ctx.get_output_dir()).await?;
+        let res = self.run(lease.checker()).await;
+        lease.release().await?;
+        res
+    }
+
+    // The ranges are not started once the lease is lost.
+    async fn run(&self, lease: LeaseCheck) -> Result<()> {
         let queue: Arc<Queue<Vec<usize>>> = Arc::new(Queue::new());
-        let chunks = self.block_numbers.chunks(self.ctx.get_batch_size());
-        for chunk in chunks {
-            queue.push(Vec::from(chunk));
+        for range in &self.ranges {
+            queue.push(range.clone());
         }
 
         let mut futures = Vec::new();
         for _worker in 0..self.ctx.get_max_worker() {
             let ctx = self.ctx.clone();
             let queue = queue.clone();
+            let lease = lease.clone();
             if !queue.is_empty() {
                 futures.push(tokio::spawn(async move {
+                    let mut res = Ok(());
                     while !queue.is_empty() {
+                        if let Err(e) = lease.check() {
+                            return Err(e);
+                        }
                         let range = queue.pop().await;
                         let (start, end) = (range[0], range[range.len() - 1]);
                         let range_path = format!("{}_{}", start, end);
+                        ctx.get_throttle().acquire(range.len()).await;
 
                         let pipeline = Pipeline::create(&ctx, &range_path, range);
-                        let res = pipeline.execute().await;
-                        match res {
-                            Ok(_) => {}
-                            Err(e) => {
-                                error!("pipeline: {:?} execute error: {:?}", range_path, e);
-                            }
+                        if let Err(e) = pipeline.execute().await {
+                            error!("pipeline: {:?} execute error: {:?}", range_path, e);
+                            res = Err(e);
                         }
                     }
+                    res
                 }));
             }
         }
 
+        // A failed range fails the chunk, so the checkpoint doesn't move past it.
+        let mut res = Ok(());
         for future in futures {
-            future.await?;
+            if let Err(e) = future.await? {
+                res = Err(e);
+            }
         }
-
-        Ok(())
+        res
     }
 }
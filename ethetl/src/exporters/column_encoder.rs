@@ -0,0 +1,100 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow2::array::Array;
+use arrow2::array::BinaryArray;
+use arrow2::array::FixedSizeBinaryArray;
+use arrow2::array::Utf8Array;
+use arrow2::datatypes::DataType;
+use common_configs::HashEncoding;
+use common_eth::h160_to_hex;
+use common_eth::h2048_to_hex;
+use common_eth::h256_to_hex;
+use web3::types::H160;
+use web3::types::H2048;
+use web3::types::H256;
+
+/// ColumnEncoder builds the hash, address and bloom columns in the configured
+/// encoding, the exporters collect the values and leave the encoding to it.
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnEncoder {
+    encoding: HashEncoding,
+}
+
+impl ColumnEncoder {
+    pub fn create(encoding: HashEncoding) -> Self {
+        ColumnEncoder { encoding }
+    }
+
+    pub fn hashes(&self, values: &[H256]) -> Box<dyn Array> {
+        match self.encoding {
+            HashEncoding::Hex => utf8(values.iter().map(h256_to_hex)),
+            HashEncoding::Binary => fixed_size_binary(32, values.iter().map(|x| x.as_bytes())),
+        }
+    }
+
+    pub fn addresses(&self, values: &[H160]) -> Box<dyn Array> {
+        match self.encoding {
+            HashEncoding::Hex => utf8(values.iter().map(h160_to_hex)),
+            HashEncoding::Binary => fixed_size_binary(20, values.iter().map(|x| x.as_bytes())),
+        }
+    }
+
+    // The addresses indexed in the log topics, which are 32 bytes padded.
+    pub fn topic_addresses(&self, values: &[H256]) -> Box<dyn Array> {
+        match self.encoding {
+            HashEncoding::Hex => utf8(values.iter().map(h256_to_hex)),
+            HashEncoding::Binary => {
+                fixed_size_binary(20, values.iter().map(|x| &x.as_bytes()[12..]))
+            }
+        }
+    }
+
+    // Hex trims the leading zeros, binary keeps the 256 bytes.
+    pub fn blooms(&self, values: &[H2048]) -> Box<dyn Array> {
+        match self.encoding {
+            HashEncoding::Hex => utf8(values.iter().map(h2048_to_hex)),
+            HashEncoding::Binary => fixed_size_binary(256, values.iter().map(|x| x.as_bytes())),
+        }
+    }
+
+    // Hex joins the topics with `|`, binary concatenates the 32 bytes topics.
+    pub fn topics(&self, values: &[Vec<H256>]) -> Box<dyn Array> {
+        match self.encoding {
+            HashEncoding::Hex => utf8(values.iter().map(|topics| {
+                topics
+                    .iter()
+                    .map(h256_to_hex)
+                    .collect::<Vec<String>>()
+                    .join("|")
+            })),
+            HashEncoding::Binary => {
+                let values = values
+                    .iter()
+                    .map(|topics| topics.iter().flat_map(|x| x.0).collect::<Vec<u8>>())
+                    .collect::<Vec<_>>();
+                BinaryArray::<i32>::from_slice(values).boxed()
+            }
+        }
+    }
+}
+
+fn utf8(values: impl Iterator<Item = String>) -> Box<dyn Array> {
+    Utf8Array::<i32>::from_slice(values.collect::<Vec<_>>()).boxed()
+}
+
+fn fixed_size_binary<'a>(size: usize, values: impl Iterator<Item = &'a [u8]>) -> Box<dyn Array> {
+    let values = values.flatten().copied().collect::<Vec<u8>>();
+    FixedSizeBinaryArray::new(DataType::FixedSizeBinary(size), values.into(), None).boxed()
+}
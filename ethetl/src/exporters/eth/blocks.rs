@@ -12,27 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Cursor;
 use std::str::FromStr;
+use std::sync::Arc;
 
+use arrow2::array::Array;
 use arrow2::array::Int64Array;
 use arrow2::array::UInt64Array;
 use arrow2::array::Utf8Array;
-use arrow2::chunk::Chunk;
 use arrow2::datatypes::DataType;
-use arrow2::datatypes::Field;
-use arrow2::datatypes::Schema;
 use arrow2::datatypes::TimeUnit::Second;
+use chrono::Utc;
 use common_eth::bytes_to_hex;
-use common_eth::h2048_to_hex;
-use common_eth::h256_to_hex;
 use common_eth::h64_to_hex;
 use common_eth::u256_to_hex;
 use common_exceptions::Result;
+use opendal::Operator;
+use opendal::Scheme;
 use web3::types::Block;
 use web3::types::Transaction;
+use web3::types::TransactionReceipt;
 use web3::types::H2048;
 use web3::types::H256;
 use web3::types::H64;
@@ -40,15 +42,23 @@ use web3::types::U256;
 use web3::types::U64;
 
 use crate::chains::eth::BlockFetcher;
+use crate::chains::eth::RawBlock;
 use crate::contexts::ContextRef;
-use crate::exporters::eth::write_file;
+use crate::exporters::eth::row_batches;
 use crate::exporters::eth::ReceiptExporter;
+use crate::exporters::eth::TableWriter;
 use crate::exporters::eth::TransactionExporter;
+use crate::exporters::eth::TABLES;
+use crate::exporters::BlockRange;
+use crate::manifest::RangeCommit;
+use crate::schemas::BLOCKS;
+use crate::sinks::CommittedRange;
+use crate::verify::RootVerifier;
 
 pub struct BlockExporter {
     ctx: ContextRef,
     output_dir: String,
-    range_path: String,
+    range: BlockRange,
     numbers: Vec<usize>,
 }
 
@@ -56,32 +66,200 @@ impl BlockExporter {
     pub fn create(
         ctx: &ContextRef,
         output_dir: &str,
-        range_path: &str,
+        range: &BlockRange,
         numbers: Vec<usize>,
     ) -> BlockExporter {
         Self {
             ctx: ctx.clone(),
             output_dir: output_dir.to_string(),
-            range_path: range_path.to_string(),
+            range: range.clone(),
             numbers,
         }
     }
 
     pub async fn export(&self) -> Result<()> {
+        let res = self.export_range().await;
+        if res.is_err() {
+            // Nothing of a failed range is committed, a retry stages its files again.
+            let (start, end) = (self.range.start as u64, self.range.end as u64);
+            self.ctx.get_catalog().discard(&self.output_dir, start, end);
+            self.ctx
+                .get_transformer()
+                .discard(&self.output_dir, start, end);
+        }
+        res
+    }
+
+    async fn export_range(&self) -> Result<()> {
+        // Held until the range is written, the next ranges wait if the budget is used up.
+        let budget = self.ctx.get_memory_budget();
+        let mut reservation = budget.reserve(self.numbers.len()).await?;
+
         let mut fetcher = BlockFetcher::create(&self.ctx);
         fetcher.push_batch(self.numbers.to_vec())?;
-        let blocks = fetcher.fetch().await?;
+        // The raw fields of the headers come from the same responses.
+        let verify_roots = self.ctx.get_config().export.verify_roots;
+        let (blocks, raw_blocks) = match verify_roots {
+            true => fetcher.fetch_with_raw().await?,
+            false => (fetcher.fetch().await?, vec![]),
+        };
 
-        {
+        let bytes = fetched_bytes(&blocks);
+        budget.observe(self.numbers.len(), bytes);
+        reservation.resize(bytes);
+
+        if verify_roots {
+            self.export_verified(&blocks, &raw_blocks).await?;
+        } else {
             self.export_blocks(&blocks).await?;
             self.export_txs(&blocks).await?;
-            self.export_tx_receipts().await?;
+            self.export_tx_receipts(&self.fetched_range(&blocks))
+                .await?;
+        }
+
+        // The tables staged for the transforms are held until the range is transformed.
+        let range = self.fetched_range(&blocks);
+        let staged = self.ctx.get_transformer().staged_bytes(
+            &self.output_dir,
+            range.start as u64,
+            range.end as u64,
+        );
+        reservation.resize(bytes + staged);
+        self.transform(&range).await?;
+        self.commit(&range).await
+    }
+
+    // Write the outputs of the transforms over the tables of the range.
+    pub async fn transform(&self, range: &BlockRange) -> Result<()> {
+        let transformer = self.ctx.get_transformer();
+        if transformer.is_empty() {
+            return Ok(());
+        }
+        let tables = transformer.take(&self.output_dir, range.start as u64, range.end as u64);
+        for (table, schema, chunks) in transformer.run(tables).await? {
+            let block_column = transformer.block_column(&table);
+            let mut writer =
+                TableWriter::create(&self.ctx, &self.output_dir, &table, block_column, range)?;
+            for chunk in chunks {
+                writer.write(schema.clone(), chunk).await?;
+            }
+            writer.close().await?;
+        }
+        Ok(())
+    }
+
+    // Add the table files to the catalog and mark the range as complete once
+    // all of them are in place, then load the range to the sinks.
+    async fn commit(&self, range: &BlockRange) -> Result<()> {
+        let committed = self
+            .ctx
+            .get_catalog()
+            .commit(
+                self.ctx.get_storage(),
+                &self.output_dir,
+                range.start as u64,
+                range.end as u64,
+            )
+            .await?;
+
+        let mut files = BTreeMap::new();
+        for table in self.ctx.get_tables() {
+            let path = self.ctx.get_output_path(&self.output_dir, &table, range)?;
+            files.insert(table, path);
         }
+        let commit = RangeCommit {
+            start: range.start as u64,
+            end: range.end as u64,
+            files,
+            committed_at: Utc::now().to_rfc3339(),
+        };
+        commit
+            .write(self.ctx.get_storage(), &self.output_dir)
+            .await?;
 
+        // The sinks load the registry tables only, the transform outputs are
+        // in the storage and the manifests.
+        let range = CommittedRange {
+            output_dir: self.output_dir.clone(),
+            start: commit.start,
+            end: commit.end,
+            files: committed
+                .into_iter()
+                .filter(|(table, _)| TABLES.contains(&table.as_str()))
+                .collect(),
+        };
+        for sink in self.ctx.get_sinks() {
+            sink.commit(&range).await?;
+        }
+
+        // The memory storage keeps nothing the sinks have loaded, unless a
+        // reorg may still roll it back.
+        let op = self.ctx.get_storage();
+        if op.metadata().scheme() == Scheme::Memory
+            && self.ctx.get_config().export.max_reorg_depth == 0
+        {
+            remove_range(op, &self.output_dir, &commit).await?;
+        }
         Ok(())
     }
 
+    // Fetch the receipts and verify the range against the headers before
+    // writing anything, a rejected range leaves no file behind.
+    async fn export_verified(
+        &self,
+        blocks: &[Block<Transaction>],
+        raw_blocks: &[RawBlock],
+    ) -> Result<()> {
+        let tx_hashes = blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| tx.hash))
+            .collect();
+        let receipt_exporter = ReceiptExporter::create(
+            &self.ctx,
+            &self.output_dir,
+            &self.fetched_range(blocks),
+            tx_hashes,
+        );
+        let receipts = receipt_exporter.fetch().await?;
+        RootVerifier::create(blocks, raw_blocks, &receipts).verify()?;
+
+        self.write_tables(blocks, &receipts).await
+    }
+
+    /// Write the tables of the fetched blocks and receipts, the range is not
+    /// committed.
+    pub async fn write_tables(
+        &self,
+        blocks: &[Block<Transaction>],
+        receipts: &[TransactionReceipt],
+    ) -> Result<()> {
+        self.export_blocks(blocks).await?;
+        self.export_txs(blocks).await?;
+        let receipt_exporter = ReceiptExporter::create(
+            &self.ctx,
+            &self.output_dir,
+            &self.fetched_range(blocks),
+            vec![],
+        );
+        receipt_exporter.write(receipts).await
+    }
+
     pub async fn export_blocks(&self, blocks: &[Block<Transaction>]) -> Result<()> {
+        let mut writer = TableWriter::create(
+            &self.ctx,
+            &self.output_dir,
+            BLOCKS.name,
+            BLOCKS.block_column(),
+            &self.fetched_range(blocks),
+        )?;
+        for blocks in row_batches(blocks) {
+            writer.write_arrays(&BLOCKS, self.columns(blocks)).await?;
+        }
+        writer.close().await
+    }
+
+    // The columns of the blocks in the registry order.
+    fn columns(&self, blocks: &[Block<Transaction>]) -> Vec<Box<dyn Array>> {
         let blocks_len = blocks.len();
 
         let mut number_vec = Vec::with_capacity(blocks_len);
@@ -105,14 +283,14 @@ impl BlockExporter {
 
         for block in blocks {
             number_vec.push(block.number.unwrap_or_else(U64::zero).as_u64());
-            hash_vec.push(h256_to_hex(&block.hash.unwrap_or_else(H256::zero)));
-            parent_hash_vec.push(h256_to_hex(&block.parent_hash));
+            hash_vec.push(block.hash.unwrap_or_else(H256::zero));
+            parent_hash_vec.push(block.parent_hash);
             nonce_vec.push(h64_to_hex(&block.nonce.unwrap_or_else(H64::zero)));
-            sha3_uncles_vec.push(h256_to_hex(&block.uncles_hash));
-            logs_bloom_vec.push(h2048_to_hex(&block.logs_bloom.unwrap_or_else(H2048::zero)));
-            transactions_root_vec.push(h256_to_hex(&block.transactions_root));
-            state_root_vec.push(h256_to_hex(&block.state_root));
-            receipts_root_vec.push(h256_to_hex(&block.receipts_root));
+            sha3_uncles_vec.push(block.uncles_hash);
+            logs_bloom_vec.push(block.logs_bloom.unwrap_or_else(H2048::zero));
+            transactions_root_vec.push(block.transactions_root);
+            state_root_vec.push(block.state_root);
+            receipts_root_vec.push(block.receipts_root);
             difficulty_vec.push(u256_to_hex(&block.difficulty));
             total_difficulty_vec.push(u256_to_hex(
                 &block.total_difficulty.unwrap_or_else(U256::zero),
@@ -126,15 +304,16 @@ impl BlockExporter {
             base_fee_per_gas_vec.push(block.base_fee_per_gas.unwrap_or_else(U256::zero).as_u64());
         }
 
+        let encoder = self.ctx.get_column_encoder();
         let number_array = UInt64Array::from_slice(number_vec);
-        let hash_array = Utf8Array::<i32>::from_slice(hash_vec);
-        let parent_hash_array = Utf8Array::<i32>::from_slice(parent_hash_vec);
+        let hash_array = encoder.hashes(&hash_vec);
+        let parent_hash_array = encoder.hashes(&parent_hash_vec);
         let nonce_array = Utf8Array::<i32>::from_slice(nonce_vec);
-        let sha3_uncles_array = Utf8Array::<i32>::from_slice(sha3_uncles_vec);
-        let logs_bloom_array = Utf8Array::<i32>::from_slice(logs_bloom_vec);
-        let transactions_root_array = Utf8Array::<i32>::from_slice(transactions_root_vec);
-        let state_root_array = Utf8Array::<i32>::from_slice(state_root_vec);
-        let receipts_root_array = Utf8Array::<i32>::from_slice(receipts_root_vec);
+        let sha3_uncles_array = encoder.hashes(&sha3_uncles_vec);
+        let logs_bloom_array = encoder.blooms(&logs_bloom_vec);
+        let transactions_root_array = encoder.hashes(&transactions_root_vec);
+        let state_root_array = encoder.hashes(&state_root_vec);
+        let receipts_root_array = encoder.hashes(&receipts_root_vec);
         let difficulty_array = Utf8Array::<i32>::from_slice(difficulty_vec);
         let total_difficulty_array = Utf8Array::<i32>::from_slice(total_difficulty_vec);
         let size_array = UInt64Array::from_slice(size_vec);
@@ -146,75 +325,16 @@ impl BlockExporter {
         let transaction_count_array = UInt64Array::from_slice(transaction_count_vec);
         let base_fee_per_gas_array = UInt64Array::from_slice(base_fee_per_gas_vec);
 
-        let number_field = Field::new("number", number_array.data_type().clone(), true);
-        let hash_field = Field::new("hash", hash_array.data_type().clone(), true);
-        let parent_hash_field =
-            Field::new("parent_hash", parent_hash_array.data_type().clone(), true);
-        let nonce_field = Field::new("nonce", nonce_array.data_type().clone(), true);
-        let sha3_uncles_field =
-            Field::new("sha3_uncles", sha3_uncles_array.data_type().clone(), true);
-        let logs_bloom_field = Field::new("logs_bloom", logs_bloom_array.data_type().clone(), true);
-        let transactions_root_field = Field::new(
-            "transactions_root",
-            transactions_root_array.data_type().clone(),
-            true,
-        );
-        let state_root_field = Field::new("state_root", state_root_array.data_type().clone(), true);
-        let receipts_root_field =
-            Field::new("receipts_root", state_root_array.data_type().clone(), true);
-        let difficulty_field = Field::new("difficulty", difficulty_array.data_type().clone(), true);
-        let total_difficulty_field = Field::new(
-            "total_difficulty",
-            total_difficulty_array.data_type().clone(),
-            true,
-        );
-        let size_field = Field::new("size", size_array.data_type().clone(), true);
-        let extra_data_field = Field::new("extra_data", extra_data_array.data_type().clone(), true);
-        let gas_limit_field = Field::new("gas_limit", gas_limit_array.data_type().clone(), true);
-        let gas_used_field = Field::new("gas_used", gas_used_array.data_type().clone(), true);
-        let timestamp_field = Field::new("timestamp", timestamp_array.data_type().clone(), true);
-        let transaction_count_field = Field::new(
-            "transaction_count",
-            transaction_count_array.data_type().clone(),
-            true,
-        );
-        let base_fee_per_gas_array_field = Field::new(
-            "base_fee_per_gas",
-            base_fee_per_gas_array.data_type().clone(),
-            true,
-        );
-
-        let schema = Schema::from(vec![
-            number_field,
-            hash_field,
-            parent_hash_field,
-            nonce_field,
-            sha3_uncles_field,
-            logs_bloom_field,
-            transactions_root_field,
-            state_root_field,
-            receipts_root_field,
-            difficulty_field,
-            total_difficulty_field,
-            size_field,
-            extra_data_field,
-            gas_limit_field,
-            gas_used_field,
-            timestamp_field,
-            transaction_count_field,
-            base_fee_per_gas_array_field,
-        ]);
-
-        let columns = Chunk::try_new(vec![
+        vec![
             number_array.boxed(),
-            hash_array.boxed(),
-            parent_hash_array.boxed(),
+            hash_array,
+            parent_hash_array,
             nonce_array.boxed(),
-            sha3_uncles_array.boxed(),
-            logs_bloom_array.boxed(),
-            transactions_root_array.boxed(),
-            state_root_array.boxed(),
-            receipts_root_array.boxed(),
+            sha3_uncles_array,
+            logs_bloom_array,
+            transactions_root_array,
+            state_root_array,
+            receipts_root_array,
             difficulty_array.boxed(),
             total_difficulty_array.boxed(),
             size_array.boxed(),
@@ -224,32 +344,37 @@ impl BlockExporter {
             timestamp_array.boxed(),
             transaction_count_array.boxed(),
             base_fee_per_gas_array.boxed(),
-        ])?;
-
-        let block_path = format!("{}/blocks/blocks_{}", self.output_dir, self.range_path);
-        write_file(&self.ctx, &block_path, schema, columns, "blocks").await
+        ]
     }
 
     pub async fn export_txs(&self, blocks: &[Block<Transaction>]) -> Result<()> {
-        let exporter =
-            TransactionExporter::create(&self.ctx, &self.output_dir, &self.range_path, blocks);
+        let exporter = TransactionExporter::create(
+            &self.ctx,
+            &self.output_dir,
+            &self.fetched_range(blocks),
+            blocks,
+        );
         exporter.export().await
     }
 
-    pub async fn export_tx_receipts(&self) -> Result<()> {
+    pub async fn export_tx_receipts(&self, range: &BlockRange) -> Result<()> {
         let tx_hashes = self.read_tx_hash_file().await?;
-        let exporter =
-            ReceiptExporter::create(&self.ctx, &self.output_dir, &self.range_path, tx_hashes);
+        let exporter = ReceiptExporter::create(&self.ctx, &self.output_dir, range, tx_hashes);
         exporter.export().await?;
         Ok(())
     }
 
+    // The range with the timestamp of the first block, for the {date} of the paths.
+    pub fn fetched_range(&self, blocks: &[Block<Transaction>]) -> BlockRange {
+        BlockRange {
+            timestamp: blocks.first().map(|x| x.timestamp.as_u64() as i64),
+            ..self.range.clone()
+        }
+    }
+
     pub async fn read_tx_hash_file(&self) -> Result<Vec<H256>> {
         let mut tx_hashes = vec![];
-        let path = format!(
-            "{}/transactions/_transactions_hash_{}.txt",
-            self.output_dir, self.range_path
-        );
+        let path = TransactionExporter::hash_file(&self.output_dir, &self.range);
 
         let meta = self.ctx.get_storage().object(&path).stat().await?;
         if meta.content_length() > 0 {
@@ -265,3 +390,40 @@ impl BlockExporter {
         Ok(tx_hashes)
     }
 }
+
+/// Delete the table files and the transaction hashes of a committed range,
+/// then its commit marker.
+pub async fn remove_range(op: Arc<Operator>, output_dir: &str, commit: &RangeCommit) -> Result<()> {
+    let range = BlockRange {
+        start: commit.start as usize,
+        end: commit.end as usize,
+        ..Default::default()
+    };
+    for path in commit.files.values() {
+        op.object(path).delete().await?;
+    }
+    op.object(&TransactionExporter::hash_file(output_dir, &range))
+        .delete()
+        .await?;
+    op.object(&RangeCommit::path(output_dir, commit.start, commit.end))
+        .delete()
+        .await?;
+    Ok(())
+}
+
+// The approximate memory of the fetched range, the receipts and logs
+// fetched later take about as much as the blocks.
+fn fetched_bytes(blocks: &[Block<Transaction>]) -> usize {
+    let bytes = blocks
+        .iter()
+        .map(|block| {
+            1024 + block.extra_data.0.len()
+                + block
+                    .transactions
+                    .iter()
+                    .map(|tx| 512 + tx.input.0.len())
+                    .sum::<usize>()
+        })
+        .sum::<usize>();
+    bytes * 2
+}
@@ -22,8 +22,11 @@ mod transactions;
 use arrow2::array::Array;
 use arrow2::chunk::Chunk;
 use arrow2::datatypes::Schema;
+pub use blocks::remove_range;
 pub use blocks::BlockExporter;
+use common_exceptions::Error;
 use common_exceptions::Result;
+use common_storages::ParquetWriter;
 pub use ens::EnsExporter;
 pub use logs::LogsExporter;
 pub use receipts::ReceiptExporter;
@@ -31,15 +34,160 @@ pub use token_transfers::TokenTransferExporter;
 pub use transactions::TransactionExporter;
 
 use crate::contexts::ContextRef;
+use crate::exporters::BlockRange;
+use crate::manifest::ManifestFile;
+use crate::schemas::FileMetadata;
+use crate::schemas::TableSchema;
+use crate::verify::u64_column;
 
-pub async fn write_file(
-    ctx: &ContextRef,
-    path: &str,
-    schema: Schema,
-    columns: Chunk<Box<dyn Array>>,
-    msg: &str,
-) -> Result<()> {
-    let path = format!("{}.parquet", path);
-    log::info!("Write {} to {}", msg, path);
-    common_storages::write_parquet(ctx.get_storage(), &path, schema, columns).await
+/// The parquet tables of one range.
+pub static TABLES: [&str; 6] = [
+    "blocks",
+    "transactions",
+    "receipts",
+    "logs",
+    "token_transfers",
+    "ens",
+];
+
+/// The rows an exporter builds the columns of at a time, a table of a range
+/// is held as arrays a batch at a time.
+pub const WRITE_BATCH_ROWS: usize = 64 * 1024;
+
+/// The rows in batches of WRITE_BATCH_ROWS, one empty batch if there are no
+/// rows so that the file of the range is still written.
+pub fn row_batches<T>(rows: &[T]) -> impl Iterator<Item = &[T]> {
+    let empty: &[T] = &[];
+    rows.chunks(WRITE_BATCH_ROWS)
+        .chain(rows.is_empty().then_some(empty))
+}
+
+/// TableWriter writes the file of a table range to the path rendered by the
+/// path template, a batch of rows at a time. The footer has the file
+/// metadata, the file is added to the catalog when the range is committed.
+pub struct TableWriter {
+    ctx: ContextRef,
+    dir: String,
+    table: String,
+    block_column: String,
+    range: BlockRange,
+    path: String,
+    // Created with the schema of the first batch.
+    writer: Option<ParquetWriter>,
+    min_block: Option<u64>,
+    max_block: Option<u64>,
+    // The batches the transforms of the range see, the registry tables only.
+    staged: Option<(Schema, Vec<Chunk<Box<dyn Array>>>)>,
+}
+
+impl TableWriter {
+    pub fn create(
+        ctx: &ContextRef,
+        dir: &str,
+        table: &str,
+        block_column: &str,
+        range: &BlockRange,
+    ) -> Result<TableWriter> {
+        let path = ctx.get_output_path(dir, table, range)?;
+        log::info!("Write {} to {}", table, path);
+        Ok(TableWriter {
+            ctx: ctx.clone(),
+            dir: dir.to_string(),
+            table: table.to_string(),
+            block_column: block_column.to_string(),
+            range: range.clone(),
+            path,
+            writer: None,
+            min_block: None,
+            max_block: None,
+            staged: None,
+        })
+    }
+
+    /// Write the arrays of a batch in the registry column order, only the
+    /// selected columns, the transforms of the range see them too.
+    pub async fn write_arrays(
+        &mut self,
+        table: &TableSchema,
+        arrays: Vec<Box<dyn Array>>,
+    ) -> Result<()> {
+        let (schema, columns) = table.chunk(self.ctx.get_hash_encoding(), arrays)?;
+        let (schema, columns) = self
+            .ctx
+            .get_column_projection()
+            .project(table.name, schema, columns)?;
+        if !self.ctx.get_transformer().is_empty() {
+            let (_, chunks) = self.staged.get_or_insert_with(|| (schema.clone(), vec![]));
+            chunks.push(columns.clone());
+        }
+        self.write(schema, columns).await
+    }
+
+    /// Write a batch.
+    pub async fn write(&mut self, schema: Schema, columns: Chunk<Box<dyn Array>>) -> Result<()> {
+        // None if the block column is excluded.
+        if let Ok(blocks) = u64_column(&schema, &columns, &self.block_column) {
+            let min = blocks.iter().min().copied();
+            let max = blocks.iter().max().copied();
+            self.min_block = self.min_block.into_iter().chain(min).min();
+            self.max_block = self.max_block.into_iter().chain(max).max();
+        }
+
+        if self.writer.is_none() {
+            let metadata = FileMetadata::create(
+                &self.table,
+                self.ctx.get_config().export.chain_id,
+                self.range.start as u64,
+                self.range.end as u64,
+            );
+            let mut writer =
+                ParquetWriter::create(self.ctx.get_storage(), &self.path, schema).await?;
+            writer.set_metadata(metadata.to_map());
+            self.writer = Some(writer);
+        }
+        let writer = self.writer.as_mut().unwrap();
+        if let Err(e) = writer.write(columns).await {
+            if let Some(writer) = self.writer.take() {
+                writer.abort().await;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Finish the file and stage it for the commit of the range.
+    pub async fn close(mut self) -> Result<()> {
+        let writer = self.writer.take().ok_or_else(|| {
+            Error::msg(format!(
+                "No batch of {} was written to {}",
+                self.table, self.path
+            ))
+        })?;
+        let written = writer.close().await?;
+
+        if let Some((schema, chunks)) = self.staged.take() {
+            self.ctx.get_transformer().stage(
+                &self.dir,
+                self.range.start as u64,
+                self.range.end as u64,
+                &self.table,
+                &schema,
+                chunks,
+            );
+        }
+        self.ctx
+            .get_catalog()
+            .stage(&self.dir, &self.table, ManifestFile {
+                path: written.path,
+                start: self.range.start as u64,
+                end: self.range.end as u64,
+                rows: written.rows,
+                size: written.size,
+                min_block: self.min_block,
+                max_block: self.max_block,
+                checksum: written.checksum,
+                ..Default::default()
+            });
+        Ok(())
+    }
 }
@@ -12,15 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use arrow2::array::Array;
 use arrow2::array::UInt64Array;
 use arrow2::array::Utf8Array;
-use arrow2::chunk::Chunk;
-use arrow2::datatypes::Field;
-use arrow2::datatypes::Schema;
 use common_eth::decode_transfer_batch_data;
 use common_eth::decode_transfer_single_data;
 use common_eth::decode_u256_data;
-use common_eth::h160_to_hex;
 use common_eth::h256_to_hex;
 use common_eth::u256_to_hex;
 use common_eth::ERC1155_TRANSFER_BATCH_SIG;
@@ -34,11 +31,15 @@ use web3::types::U256;
 use web3::types::U64;
 
 use crate::contexts::ContextRef;
-use crate::exporters::eth::write_file;
+use crate::exporters::eth::row_batches;
+use crate::exporters::eth::TableWriter;
+use crate::exporters::BlockRange;
+use crate::schemas::TOKEN_TRANSFERS;
 
 struct Transfer {
-    from: String,
-    to: String,
+    // The 32 bytes padded addresses of the topics.
+    from: H256,
+    to: H256,
     token_id: String,
     value: U256,
     erc: String,
@@ -47,7 +48,7 @@ struct Transfer {
 pub struct TokenTransferExporter {
     ctx: ContextRef,
     output_dir: String,
-    range_path: String,
+    range: BlockRange,
     receipts: Vec<TransactionReceipt>,
 }
 
@@ -55,13 +56,13 @@ impl TokenTransferExporter {
     pub fn create(
         ctx: &ContextRef,
         dir: &str,
-        range_path: &str,
+        range: &BlockRange,
         receipts: &[TransactionReceipt],
     ) -> Self {
         Self {
             ctx: ctx.clone(),
             output_dir: dir.to_string(),
-            range_path: range_path.to_string(),
+            range: range.clone(),
             receipts: receipts.to_vec(),
         }
     }
@@ -78,8 +79,8 @@ impl TokenTransferExporter {
                 // Transfer (index_topic_1 address from, index_topic_2 address to, uint256 value)
                 // Transfer (index_topic_1 address src, index_topic_2 address dst, uint256 wad)
                 let transfer = Transfer {
-                    from: h256_to_hex(&topics[1]),
-                    to: h256_to_hex(&topics[2]),
+                    from: topics[1],
+                    to: topics[2],
                     token_id: "".to_string(),
                     value: decode_u256_data(&log.data).unwrap(),
                     erc: "ERC20".to_string(),
@@ -88,8 +89,8 @@ impl TokenTransferExporter {
             } else if topics.len() == 4 {
                 // Transfer (index_topic_1 address from, index_topic_2 address to, index_topic_3 uint256 tokenId)
                 let transfer = Transfer {
-                    from: h256_to_hex(&topics[1]),
-                    to: h256_to_hex(&topics[2]),
+                    from: topics[1],
+                    to: topics[2],
                     token_id: h256_to_hex(&topics[3]),
                     value: U256::zero(),
                     erc: "ERC721".to_string(),
@@ -107,8 +108,8 @@ impl TokenTransferExporter {
                 u2 = x2;
             }
             let transfer = Transfer {
-                from: h256_to_hex(&topics[1]),
-                to: h256_to_hex(&topics[2]),
+                from: topics[1],
+                to: topics[2],
                 token_id: u256_to_hex(&u1),
                 value: u2,
                 erc: "ERC1155".to_string(),
@@ -125,8 +126,8 @@ impl TokenTransferExporter {
             let mut results = vec![];
             for i in 0..u1.len() {
                 let transfer = Transfer {
-                    from: h256_to_hex(&topics[1]),
-                    to: h256_to_hex(&topics[2]),
+                    from: topics[1],
+                    to: topics[2],
                     token_id: u256_to_hex(&u1[i]),
                     value: u2[i],
                     erc: "ERC1155".to_string(),
@@ -140,6 +141,23 @@ impl TokenTransferExporter {
     }
 
     pub async fn export(&self) -> Result<()> {
+        let mut writer = TableWriter::create(
+            &self.ctx,
+            &self.output_dir,
+            TOKEN_TRANSFERS.name,
+            TOKEN_TRANSFERS.block_column(),
+            &self.range,
+        )?;
+        for receipts in row_batches(&self.receipts) {
+            writer
+                .write_arrays(&TOKEN_TRANSFERS, self.columns(receipts)?)
+                .await?;
+        }
+        writer.close().await
+    }
+
+    // The columns of the transfers of the receipts in the registry order.
+    fn columns(&self, receipts: &[TransactionReceipt]) -> Result<Vec<Box<dyn Array>>> {
         let mut token_address_vec = vec![];
         let mut from_address_vec = vec![];
         let mut to_address_vec = vec![];
@@ -150,7 +168,7 @@ impl TokenTransferExporter {
         let mut log_index_vec = vec![];
         let mut block_number_vec = vec![];
 
-        for receipt in &self.receipts {
+        for receipt in receipts {
             for logs in &receipt.logs {
                 if let Some(transfers) = Self::parse_log(logs)? {
                     for transfer in transfers {
@@ -159,10 +177,8 @@ impl TokenTransferExporter {
                         token_id_vec.push(transfer.token_id);
                         value_vec.push(transfer.value.to_string());
                         erc_standard_vec.push(transfer.erc);
-                        token_address_vec.push(h160_to_hex(&logs.address));
-                        transaction_hash_vec.push(h256_to_hex(
-                            &logs.transaction_hash.unwrap_or_else(H256::zero),
-                        ));
+                        token_address_vec.push(logs.address);
+                        transaction_hash_vec.push(logs.transaction_hash.unwrap_or_else(H256::zero));
                         log_index_vec.push(logs.log_index.unwrap_or_else(U256::zero).as_u64());
                         block_number_vec.push(logs.block_number.unwrap_or_else(U64::zero).as_u64());
 
@@ -172,63 +188,27 @@ impl TokenTransferExporter {
             }
         }
 
-        let token_address_array = Utf8Array::<i32>::from_slice(token_address_vec);
-        let from_address_array = Utf8Array::<i32>::from_slice(from_address_vec);
-        let to_address_array = Utf8Array::<i32>::from_slice(to_address_vec);
+        let encoder = self.ctx.get_column_encoder();
+        let token_address_array = encoder.addresses(&token_address_vec);
+        let from_address_array = encoder.topic_addresses(&from_address_vec);
+        let to_address_array = encoder.topic_addresses(&to_address_vec);
         let token_id_array = Utf8Array::<i32>::from_slice(token_id_vec);
         let value_array = Utf8Array::<i32>::from_slice(value_vec);
         let erc_standard_array = Utf8Array::<i32>::from_slice(erc_standard_vec);
-        let transaction_hash_array = Utf8Array::<i32>::from_slice(transaction_hash_vec);
+        let transaction_hash_array = encoder.hashes(&transaction_hash_vec);
         let log_index_array = UInt64Array::from_slice(log_index_vec);
         let block_number_array = UInt64Array::from_slice(block_number_vec);
 
-        let token_address_field = Field::new(
-            "token_address",
-            token_address_array.data_type().clone(),
-            true,
-        );
-        let from_address_field =
-            Field::new("from_address", from_address_array.data_type().clone(), true);
-        let to_address_field = Field::new("to_address", to_address_array.data_type().clone(), true);
-        let token_id_field = Field::new("token_id", token_id_array.data_type().clone(), true);
-        let value_field = Field::new("value", value_array.data_type().clone(), true);
-        let erc_standard_field =
-            Field::new("erc_standard", erc_standard_array.data_type().clone(), true);
-        let transaction_hash_field = Field::new(
-            "transaction_hash",
-            transaction_hash_array.data_type().clone(),
-            true,
-        );
-        let log_index_field = Field::new("log_index", log_index_array.data_type().clone(), true);
-        let block_number_field =
-            Field::new("block_number", block_number_array.data_type().clone(), true);
-        let schema = Schema::from(vec![
-            token_address_field,
-            from_address_field,
-            to_address_field,
-            token_id_field,
-            value_field,
-            erc_standard_field,
-            transaction_hash_field,
-            log_index_field,
-            block_number_field,
-        ]);
-        let columns = Chunk::try_new(vec![
-            token_address_array.boxed(),
-            from_address_array.boxed(),
-            to_address_array.boxed(),
+        Ok(vec![
+            token_address_array,
+            from_address_array,
+            to_address_array,
             token_id_array.boxed(),
             value_array.boxed(),
             erc_standard_array.boxed(),
-            transaction_hash_array.boxed(),
+            transaction_hash_array,
             log_index_array.boxed(),
             block_number_array.boxed(),
-        ])?;
-
-        let path = format!(
-            "{}/token_transfers/token_transfers_{}",
-            self.output_dir, self.range_path
-        );
-        write_file(&self.ctx, &path, schema, columns, "token_transfer").await
+        ])
     }
 }
@@ -12,4 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod column_encoder;
 pub mod eth;
+mod output_path;
+mod projection;
+
+pub use column_encoder::ColumnEncoder;
+pub use output_path::list_table_files;
+pub use output_path::parse_range;
+pub use output_path::BlockRange;
+pub use output_path::PathTemplate;
+pub use output_path::PathVars;
+pub use output_path::TableFile;
+pub use output_path::DEFAULT_PATH_TEMPLATE;
+pub use projection::ColumnProjection;
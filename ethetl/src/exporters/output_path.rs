@@ -0,0 +1,276 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::list_files;
+use opendal::Operator;
+
+// The default layout, `blocks/blocks_16600001_16600002.parquet`.
+pub static DEFAULT_PATH_TEMPLATE: &str = "{table}/{table}_{start}_{end}";
+
+static PLACEHOLDERS: [&str; 6] = ["table", "chain_id", "date", "block_bucket", "start", "end"];
+
+// The placeholders which are numbers.
+static NUMERIC_PLACEHOLDERS: [&str; 4] = ["chain_id", "block_bucket", "start", "end"];
+
+pub type PathVars = BTreeMap<String, String>;
+
+/// The blocks [start, end] exported together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockRange {
+    pub start: usize,
+    pub end: usize,
+    // The timestamp of the first block, known once the blocks are fetched.
+    pub timestamp: Option<i64>,
+}
+
+impl BlockRange {
+    pub fn create(numbers: &[usize]) -> Self {
+        BlockRange {
+            start: numbers.first().copied().unwrap_or_default(),
+            end: numbers.last().copied().unwrap_or_default(),
+            timestamp: None,
+        }
+    }
+
+    // The range name, like `16600001_16600002`.
+    pub fn range_path(&self) -> String {
+        format!("{}_{}", self.start, self.end)
+    }
+
+    pub fn vars(&self, table: &str, chain_id: u64, block_bucket_size: usize) -> PathVars {
+        let bucket_size = block_bucket_size.max(1);
+        let mut vars = PathVars::new();
+        vars.insert("table".to_string(), table.to_string());
+        vars.insert("chain_id".to_string(), chain_id.to_string());
+        vars.insert(
+            "block_bucket".to_string(),
+            (self.start / bucket_size * bucket_size).to_string(),
+        );
+        vars.insert("start".to_string(), self.start.to_string());
+        vars.insert("end".to_string(), self.end.to_string());
+        if let Some(date) = self
+            .timestamp
+            .and_then(|ts| NaiveDateTime::from_timestamp_opt(ts, 0))
+        {
+            vars.insert("date".to_string(), date.format("%Y-%m-%d").to_string());
+        }
+        vars
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// The output path of a table range under the output dir, e.g.
+/// `{table}/date={date}/{table}_{start}_{end}` for the Hive style partitions.
+///
+/// The rendered path is parsed back by the readers of the output, so the
+/// template must have `{start}` and `{end}`, and `{table}` so that the
+/// tables of a range don't overwrite each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+impl Default for PathTemplate {
+    fn default() -> Self {
+        PathTemplate::create(DEFAULT_PATH_TEMPLATE).unwrap()
+    }
+}
+
+impl PathTemplate {
+    pub fn create(template: &str) -> Result<Self> {
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let close = rest[open..]
+                .find('}')
+                .map(|x| open + x)
+                .ok_or_else(|| Error::msg(format!("Unclosed placeholder in {:?}", template)))?;
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let name = &rest[open + 1..close];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(Error::msg(format!(
+                    "Unknown placeholder {{{}}} in {:?}, expect one of {:?}",
+                    name, template, PLACEHOLDERS
+                )));
+            }
+            segments.push(Segment::Placeholder(name.to_string()));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        let has = |name: &str| segments.contains(&Segment::Placeholder(name.to_string()));
+        if !has("table") || !has("start") || !has("end") {
+            return Err(Error::msg(format!(
+                "Path template {:?} must have {{table}}, {{start}} and {{end}}",
+                template
+            )));
+        }
+        Ok(PathTemplate { segments })
+    }
+
+    /// Render the parquet path, like `{dir}/blocks/blocks_1_2.parquet`.
+    pub fn render(&self, dir: &str, vars: &PathVars) -> Result<String> {
+        let mut path = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(v) => path.push_str(v),
+                Segment::Placeholder(name) => match vars.get(name) {
+                    Some(v) => path.push_str(v),
+                    None => {
+                        return Err(Error::msg(format!("Placeholder {{{}}} has no value", name)));
+                    }
+                },
+            }
+        }
+        Ok(format!("{}/{}.parquet", dir, path))
+    }
+
+    /// Parse the vars back from a parquet path of the table under the dir.
+    pub fn parse(&self, dir: &str, table: &str, path: &str) -> Option<PathVars> {
+        let dir = dir.trim_matches('/');
+        let path = path.trim_start_matches('/');
+        let rel = if dir.is_empty() {
+            path
+        } else {
+            path.strip_prefix(dir)?.strip_prefix('/')?
+        };
+        let rel = rel.strip_suffix(".parquet")?;
+
+        let mut vars = PathVars::new();
+        vars.insert("table".to_string(), table.to_string());
+        if match_segments(&self.segments, rel, &mut vars) {
+            Some(vars)
+        } else {
+            None
+        }
+    }
+
+    /// The dir holding all the files of the table, to list them.
+    pub fn table_dir(&self, dir: &str, table: &str, chain_id: u64) -> String {
+        let mut prefix = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(v) => prefix.push_str(v),
+                Segment::Placeholder(name) if name == "table" => prefix.push_str(table),
+                Segment::Placeholder(name) if name == "chain_id" => {
+                    prefix.push_str(&chain_id.to_string())
+                }
+                Segment::Placeholder(_) => break,
+            }
+        }
+        match prefix.rfind('/') {
+            Some(idx) => format!("{}/{}", dir, &prefix[..idx]),
+            None => dir.to_string(),
+        }
+    }
+}
+
+/// A parquet file of a table matching the path template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableFile {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    pub vars: PathVars,
+}
+
+/// List the parquet files of the table under the dir, in block order.
+pub async fn list_table_files(
+    op: Arc<Operator>,
+    template: &PathTemplate,
+    dir: &str,
+    table: &str,
+    chain_id: u64,
+) -> Result<Vec<TableFile>> {
+    let table_dir = template.table_dir(dir, table, chain_id);
+    let mut files = vec![];
+    for path in list_files(op, &table_dir).await? {
+        let vars = match template.parse(dir, table, &path) {
+            Some(vars) => vars,
+            None => continue,
+        };
+        if let Some((start, end)) = parse_range(&vars) {
+            files.push(TableFile {
+                path,
+                start,
+                end,
+                vars,
+            });
+        }
+    }
+    files.sort_by(|a, b| (a.start, a.end, &a.path).cmp(&(b.start, b.end, &b.path)));
+    Ok(files)
+}
+
+/// The [start, end] of the parsed vars.
+pub fn parse_range(vars: &PathVars) -> Option<(u64, u64)> {
+    Some((
+        vars.get("start")?.parse().ok()?,
+        vars.get("end")?.parse().ok()?,
+    ))
+}
+
+// Match the segments with backtracking, a placeholder never spans dirs.
+fn match_segments(segments: &[Segment], s: &str, vars: &mut PathVars) -> bool {
+    match segments.first() {
+        None => s.is_empty(),
+        Some(Segment::Literal(v)) => match s.strip_prefix(v.as_str()) {
+            Some(rest) => match_segments(&segments[1..], rest, vars),
+            None => false,
+        },
+        Some(Segment::Placeholder(name)) => {
+            // A known value, e.g. the table or a placeholder used twice.
+            if let Some(v) = vars.get(name).cloned() {
+                return match s.strip_prefix(v.as_str()) {
+                    Some(rest) => match_segments(&segments[1..], rest, vars),
+                    None => false,
+                };
+            }
+
+            let numeric = NUMERIC_PLACEHOLDERS.contains(&name.as_str());
+            let ends = s
+                .char_indices()
+                .map(|(i, _)| i)
+                .skip(1)
+                .chain(std::iter::once(s.len()));
+            for end in ends.filter(|x| *x > 0) {
+                let value = &s[..end];
+                if value.contains('/') || (numeric && !value.chars().all(|x| x.is_ascii_digit())) {
+                    break;
+                }
+                vars.insert(name.clone(), value.to_string());
+                if match_segments(&segments[1..], &s[end..], vars) {
+                    return true;
+                }
+                vars.remove(name);
+            }
+            false
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use arrow2::array::Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Schema;
+use common_configs::TableColumns;
+use common_exceptions::Error;
+use common_exceptions::Result;
+
+/// ColumnProjection selects the columns written of each table by the
+/// `[export.columns.<table>]` config, the tables not in it keep all columns.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnProjection {
+    tables: BTreeMap<String, TableColumns>,
+}
+
+impl ColumnProjection {
+    pub fn create(tables: BTreeMap<String, TableColumns>) -> Self {
+        ColumnProjection { tables }
+    }
+
+    /// If the column of the table is written.
+    pub fn selects(&self, table: &str, column: &str) -> bool {
+        self.tables
+            .get(table)
+            .map(|x| x.selects(column))
+            .unwrap_or(true)
+    }
+
+    /// Check the configured columns are columns of the table schema.
+    pub fn check(&self, table: &str, schema: &Schema) -> Result<()> {
+        if let Some(columns) = self.tables.get(table) {
+            for name in columns.include.iter().chain(columns.exclude.iter()) {
+                if !schema.fields.iter().any(|f| &f.name == name) {
+                    return Err(Error::msg(format!(
+                        "Unknown column {} of table {} in export.columns",
+                        name, table
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the selected columns of the chunk, the schema is rebuilt from the
+    /// selected fields in the table order.
+    pub fn project(
+        &self,
+        table: &str,
+        schema: Schema,
+        chunk: Chunk<Box<dyn Array>>,
+    ) -> Result<(Schema, Chunk<Box<dyn Array>>)> {
+        if !self.tables.contains_key(table) {
+            return Ok((schema, chunk));
+        }
+        self.check(table, &schema)?;
+
+        let mut fields = vec![];
+        let mut arrays = vec![];
+        for (field, array) in schema.fields.into_iter().zip(chunk.into_arrays()) {
+            if self.selects(table, &field.name) {
+                fields.push(field);
+                arrays.push(array);
+            }
+        }
+        if fields.is_empty() {
+            return Err(Error::msg(format!(
+                "No column of table {} is selected by export.columns",
+                table
+            )));
+        }
+        Ok((
+            Schema::from(fields).with_metadata(schema.metadata),
+            Chunk::try_new(arrays)?,
+        ))
+    }
+}
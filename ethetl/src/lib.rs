@@ -15,6 +15,14 @@
 extern crate core;
 
 pub mod chains;
+pub mod compaction;
 pub mod contexts;
 pub mod etl;
 pub mod exporters;
+pub mod manifest;
+#[cfg(feature = "query")]
+pub mod query;
+pub mod schemas;
+pub mod sinks;
+pub mod transforms;
+pub mod verify;
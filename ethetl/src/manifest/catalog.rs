@@ -0,0 +1,160 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use common_exceptions::Result;
+use opendal::Operator;
+
+use crate::manifest::Lease;
+use crate::manifest::ManifestFile;
+use crate::manifest::TableManifest;
+
+type RangeKey = (String, u64, u64);
+
+/// Catalog collects the files written for a range and appends them to the
+/// table manifests when the range is committed.
+///
+/// The workers share it, the manifests are cached and brought up to date
+/// with their logs before each change. The writers of an output dir, in
+/// this process or another, change the manifests under the manifest lease.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    // The files of the ranges not committed yet, by output dir and range.
+    pending: Mutex<BTreeMap<RangeKey, Vec<(String, ManifestFile)>>>,
+    // The manifests by output dir and table.
+    manifests: tokio::sync::Mutex<BTreeMap<(String, String), TableManifest>>,
+}
+
+impl Catalog {
+    pub fn create() -> Arc<Catalog> {
+        Arc::new(Catalog::default())
+    }
+
+    /// Hold the file of the table until its range is committed.
+    pub fn stage(&self, output_dir: &str, table: &str, file: ManifestFile) {
+        let key = (output_dir.to_string(), file.start, file.end);
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .entry(key)
+            .or_default()
+            .push((table.to_string(), file));
+    }
+
+    /// Drop the staged files of a failed range.
+    pub fn discard(&self, output_dir: &str, start: u64, end: u64) {
+        let key = (output_dir.to_string(), start, end);
+        self.pending.lock().unwrap().remove(&key);
+    }
+
+    /// Append the staged files of the range to their table manifests, the
+    /// committed files by table.
+    pub async fn commit(
+        &self,
+        op: Arc<Operator>,
+        output_dir: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<BTreeMap<String, ManifestFile>> {
+        let key = (output_dir.to_string(), start, end);
+        let files = self.pending.lock().unwrap().remove(&key);
+        let files = match files {
+            Some(files) => files,
+            None => return Ok(BTreeMap::new()),
+        };
+
+        let mut manifests = self.manifests.lock().await;
+        let lease = Lease::acquire(op.clone(), output_dir, "manifest").await?;
+        let committed_at = Utc::now().to_rfc3339();
+        let mut committed = BTreeMap::new();
+        let mut res = Ok(());
+        for (table, mut file) in files {
+            file.committed_at = committed_at.clone();
+            let change = |manifest: &mut TableManifest| manifest.append(file.clone());
+            match update(&mut manifests, op.clone(), output_dir, &table, change).await {
+                Ok(manifest) => {
+                    if let Some(file) = manifest.files.iter().find(|x| x.path == file.path) {
+                        committed.insert(table, file.clone());
+                    }
+                }
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        lease.release().await?;
+        res.map(|_| committed)
+    }
+
+    /// Remove the files with blocks from `from_block` from the manifests of
+    /// the tables, the removed files by table.
+    pub async fn rollback(
+        &self,
+        op: Arc<Operator>,
+        output_dir: &str,
+        from_block: u64,
+        tables: &[String],
+    ) -> Result<BTreeMap<String, Vec<ManifestFile>>> {
+        let mut manifests = self.manifests.lock().await;
+        let lease = Lease::acquire(op.clone(), output_dir, "manifest").await?;
+        let mut removed = BTreeMap::new();
+        let mut res = Ok(());
+        for table in tables {
+            let mut files = vec![];
+            let change = |manifest: &mut TableManifest| files = manifest.remove_from(from_block);
+            if let Err(e) = update(&mut manifests, op.clone(), output_dir, table, change).await {
+                res = Err(e);
+                break;
+            }
+            if !files.is_empty() {
+                removed.insert(table.to_string(), files);
+            }
+        }
+        lease.release().await?;
+        res.map(|_| removed)
+    }
+}
+
+// Change the cached manifest of the table, brought up to date with the log
+// first, and write the change. A manifest failing to write is read again
+// by the next change.
+async fn update<'a>(
+    manifests: &'a mut BTreeMap<(String, String), TableManifest>,
+    op: Arc<Operator>,
+    output_dir: &str,
+    table: &str,
+    change: impl FnOnce(&mut TableManifest),
+) -> Result<&'a TableManifest> {
+    let key = (output_dir.to_string(), table.to_string());
+    let fresh = match manifests.get_mut(&key) {
+        Some(manifest) => manifest.refresh(op.clone(), output_dir).await?,
+        None => false,
+    };
+    if !fresh {
+        let manifest = TableManifest::read(op.clone(), output_dir, table).await?;
+        manifests.insert(key.clone(), manifest);
+    }
+
+    let manifest = manifests.get_mut(&key).unwrap();
+    change(manifest);
+    if let Err(e) = manifest.write(op, output_dir).await {
+        manifests.remove(&key);
+        return Err(e);
+    }
+    Ok(manifests.get(&key).unwrap())
+}
@@ -0,0 +1,300 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::list_files;
+use common_storages::write_atomic;
+use log::info;
+use log::warn;
+use opendal::ErrorKind;
+use opendal::Operator;
+use rand::Rng;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+/// The seconds a lease lasts without renewal, the leases of a crashed
+/// process block the others that long at most.
+pub const LEASE_SECS: i64 = 30;
+
+// The seconds between the renewals of a held lease.
+const RENEW_SECS: i64 = LEASE_SECS / 3;
+
+static LEASE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaseFile {
+    pub owner: String,
+    // Unix seconds.
+    pub expires_at: i64,
+}
+
+/// A lease of the processes sharing an output dir, stored under
+/// `{output_dir}/_locks/{name}/` as one file per owner.
+///
+/// The exporters hold a writer lease each, a compaction holds the
+/// compaction lease and doesn't start while a writer lease is live, the
+/// exporters wait for a running compaction. A lease is renewed in the
+/// background while held, an expired one is removed by the next reader.
+/// A lease that can't be renewed before it expires is lost, its holder
+/// stops writing once [`Lease::check`] fails.
+///
+/// The object storages have no conditional put, an owner writes its own
+/// file and then lists the others: of two owners racing, at least one sees
+/// the other and gives up.
+#[derive(Debug)]
+pub struct Lease {
+    op: Arc<Operator>,
+    path: String,
+    owner: String,
+    lost: Arc<AtomicBool>,
+    renewal: JoinHandle<()>,
+}
+
+/// Checks whether a lease is still held, shared with the tasks writing
+/// under it.
+#[derive(Debug, Clone)]
+pub struct LeaseCheck {
+    path: String,
+    lost: Arc<AtomicBool>,
+}
+
+impl LeaseCheck {
+    /// An error once the lease is lost, another process may hold it.
+    pub fn check(&self) -> Result<()> {
+        if self.lost.load(Ordering::Acquire) {
+            return Err(Error::msg(format!(
+                "The lease {} is lost, stop writing",
+                self.path
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Lease {
+    pub fn dir(output_dir: &str, name: &str) -> String {
+        format!("{}/_locks/{}", output_dir, name)
+    }
+
+    /// Take the lease of the name, none if another owner holds it.
+    pub async fn try_acquire(
+        op: Arc<Operator>,
+        output_dir: &str,
+        name: &str,
+    ) -> Result<Option<Lease>> {
+        let dir = Lease::dir(output_dir, name);
+        let lease = Lease::create(op.clone(), &dir).await?;
+        let owners = live_owners(&op, &dir).await;
+        if owners.is_err() {
+            lease.release().await?;
+        }
+        let others = owners?.into_iter().filter(|x| x != lease.owner()).count();
+        if others > 0 {
+            lease.release().await?;
+            return Ok(None);
+        }
+        Ok(Some(lease))
+    }
+
+    /// Wait for the lease of the name.
+    pub async fn acquire(op: Arc<Operator>, output_dir: &str, name: &str) -> Result<Lease> {
+        loop {
+            if let Some(lease) = Lease::try_acquire(op.clone(), output_dir, name).await? {
+                return Ok(lease);
+            }
+            // Owners racing back off for different times.
+            let millis = rand::thread_rng().gen_range(100..500);
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+        }
+    }
+
+    /// The lease of an exporter writing the output dir, waits for a
+    /// running compaction.
+    pub async fn writer(op: Arc<Operator>, output_dir: &str) -> Result<Lease> {
+        let compaction = Lease::dir(output_dir, "compaction");
+        loop {
+            // A lease of its own, then the compaction is checked.
+            let lease = Lease::create(op.clone(), &Lease::dir(output_dir, "writers")).await?;
+            let owners = live_owners(&op, &compaction).await;
+            if owners.is_err() {
+                lease.release().await?;
+            }
+            match owners?.first() {
+                Some(owner) => {
+                    lease.release().await?;
+                    info!("Wait for the compaction {} of {}", owner, output_dir);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                None => return Ok(lease),
+            }
+        }
+    }
+
+    /// The lease of the compaction of the output dir, an error if another
+    /// compaction or an exporter is running.
+    pub async fn compaction(op: Arc<Operator>, output_dir: &str) -> Result<Lease> {
+        let lease = Lease::try_acquire(op.clone(), output_dir, "compaction")
+            .await?
+            .ok_or_else(|| {
+                Error::msg(format!("Another compaction of {} is running", output_dir))
+            })?;
+
+        let writers = live_owners(&op, &Lease::dir(output_dir, "writers")).await;
+        if writers.as_ref().map_or(true, |x| !x.is_empty()) {
+            lease.release().await?;
+            return Err(Error::msg(format!(
+                "The exporters {:?} are writing {}, compact once they are done",
+                writers?, output_dir
+            )));
+        }
+        Ok(lease)
+    }
+
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub fn checker(&self) -> LeaseCheck {
+        LeaseCheck {
+            path: self.path.clone(),
+            lost: self.lost.clone(),
+        }
+    }
+
+    /// An error once the lease is lost.
+    pub fn check(&self) -> Result<()> {
+        self.checker().check()
+    }
+
+    /// Give the lease up.
+    pub async fn release(self) -> Result<()> {
+        self.renewal.abort();
+        self.op.object(&self.path).delete().await?;
+        Ok(())
+    }
+
+    // Write a lease file of a new owner under the dir and hold it.
+    async fn create(op: Arc<Operator>, dir: &str) -> Result<Lease> {
+        let owner = lease_owner();
+        let path = format!("{}/{}.json", dir, owner);
+        let mut expires_at = Utc::now().timestamp() + LEASE_SECS;
+        write_atomic(op.clone(), &path, lease_data(&owner, expires_at)?).await?;
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let renewal = {
+            let (op, path, owner, lost) = (op.clone(), path.clone(), owner.clone(), lost.clone());
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(RENEW_SECS as u64)).await;
+                    let next = Utc::now().timestamp() + LEASE_SECS;
+                    match renew(&op, &path, &owner, next).await {
+                        Ok(true) => {
+                            expires_at = next;
+                            continue;
+                        }
+                        Ok(false) => warn!("The lease {} is removed", path),
+                        Err(e) => {
+                            warn!("Renew the lease {} error: {:?}", path, e);
+                            // Retried while the next renewal is in time, otherwise given
+                            // up before it expires and another owner can take it.
+                            if Utc::now().timestamp() + RENEW_SECS < expires_at {
+                                continue;
+                            }
+                        }
+                    }
+                    lost.store(true, Ordering::Release);
+                    return;
+                }
+            })
+        };
+        Ok(Lease {
+            op,
+            path,
+            owner,
+            lost,
+            renewal,
+        })
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.renewal.abort();
+    }
+}
+
+// False if the lease was removed by another process, it's not written again.
+async fn renew(op: &Arc<Operator>, path: &str, owner: &str, expires_at: i64) -> Result<bool> {
+    match read_lease(op, path).await? {
+        Some(file) if file.owner == owner => {
+            write_atomic(op.clone(), path, lease_data(owner, expires_at)?).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+// The owners of the live leases under the dir, the expired ones are removed.
+// A lease that can't be read is an error, its owner may still hold it.
+async fn live_owners(op: &Arc<Operator>, dir: &str) -> Result<Vec<String>> {
+    let now = Utc::now().timestamp();
+    let mut owners = vec![];
+    for path in list_files(op.clone(), dir).await? {
+        match read_lease(op, &path).await? {
+            Some(file) if file.expires_at > now => owners.push(file.owner),
+            Some(_) => op.object(&path).delete().await?,
+            // Released in between.
+            None => {}
+        }
+    }
+    Ok(owners)
+}
+
+// None if there is no such lease.
+async fn read_lease(op: &Arc<Operator>, path: &str) -> Result<Option<LeaseFile>> {
+    match op.object(path).read().await {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| Error::msg(format!("Parse the lease {} error: {:?}", path, e))),
+        Err(e) if e.kind() == ErrorKind::ObjectNotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// The process and a seq, unique among the processes sharing the storage.
+fn lease_owner() -> String {
+    format!(
+        "{}_{}_{}",
+        std::process::id(),
+        Utc::now().timestamp_millis(),
+        LEASE_SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn lease_data(owner: &str, expires_at: i64) -> Result<Vec<u8>> {
+    let file = LeaseFile {
+        owner: owner.to_string(),
+        expires_at,
+    };
+    Ok(serde_json::to_vec(&file)?)
+}
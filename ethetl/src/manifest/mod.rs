@@ -0,0 +1,30 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod catalog;
+mod lease;
+mod range_commit;
+mod table_manifest;
+
+pub use catalog::Catalog;
+pub use lease::Lease;
+pub use lease::LeaseCheck;
+pub use lease::LeaseFile;
+pub use lease::LEASE_SECS;
+pub use range_commit::RangeCommit;
+pub use table_manifest::ManifestChange;
+pub use table_manifest::ManifestFile;
+pub use table_manifest::ManifestLog;
+pub use table_manifest::TableManifest;
+pub use table_manifest::SNAPSHOT_VERSIONS;
@@ -0,0 +1,74 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use common_exceptions::Result;
+use common_storages::list_files;
+use common_storages::write_atomic;
+use opendal::ErrorKind;
+use opendal::Operator;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The commit marker of a range, stored at `{output_dir}/_commits/{start}_{end}.json`.
+///
+/// It is written after all the table files of the range, a loader should
+/// only load the ranges which have the marker.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeCommit {
+    pub start: u64,
+    pub end: u64,
+    // The file path of each table.
+    pub files: BTreeMap<String, String>,
+    // RFC 3339.
+    pub committed_at: String,
+}
+
+impl RangeCommit {
+    pub fn path(output_dir: &str, start: u64, end: u64) -> String {
+        format!("{}/_commits/{}_{}.json", output_dir, start, end)
+    }
+
+    // Read the marker, none if the range is not committed.
+    pub async fn read(
+        op: Arc<Operator>,
+        output_dir: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Option<Self>> {
+        match op.object(&Self::path(output_dir, start, end)).read().await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == ErrorKind::ObjectNotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The markers of the output dir in block order.
+    pub async fn list(op: Arc<Operator>, output_dir: &str) -> Result<Vec<Self>> {
+        let mut commits = vec![];
+        for path in list_files(op.clone(), &format!("{}/_commits", output_dir)).await? {
+            let data = op.object(&path).read().await?;
+            commits.push(serde_json::from_slice::<RangeCommit>(&data)?);
+        }
+        commits.sort_by_key(|x| (x.start, x.end));
+        Ok(commits)
+    }
+
+    pub async fn write(&self, op: Arc<Operator>, output_dir: &str) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        write_atomic(op, &Self::path(output_dir, self.start, self.end), data).await
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::list_files;
+use common_storages::write_atomic;
+use opendal::ErrorKind;
+use opendal::Operator;
+use rand::Rng;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A committed data file of a table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManifestFile {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    pub rows: u64,
+    pub size: u64,
+    // The block numbers in the file, none if the block column is excluded.
+    pub min_block: Option<u64>,
+    pub max_block: Option<u64>,
+    // The hex SHA-256 of the file.
+    pub checksum: String,
+    // Increases with every file added to the table.
+    pub seq: u64,
+    // RFC 3339.
+    pub committed_at: String,
+    // The seqs of the files merged into this one by compaction, the data is
+    // the same as theirs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_seqs: Vec<u64>,
+}
+
+/// The log versions between two snapshots of a manifest.
+pub const SNAPSHOT_VERSIONS: u64 = 100;
+
+// The reads of a manifest whose log is removed by a snapshot meanwhile.
+const READ_ATTEMPTS: usize = 10;
+
+/// A change to the files of a table, replayed from the manifest log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ManifestChange {
+    Append {
+        file: ManifestFile,
+    },
+    Replace {
+        removed: Vec<String>,
+        added: Vec<ManifestFile>,
+    },
+    Remove {
+        paths: Vec<String>,
+    },
+    RemoveFrom {
+        from_block: u64,
+    },
+}
+
+/// An entry of the manifest log, the changes of one write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestLog {
+    pub version: u64,
+    pub changes: Vec<ManifestChange>,
+}
+
+/// The files of one table.
+///
+/// A write appends the changes since the read to the log at
+/// `{output_dir}/_manifest/{table}/{version}.json`, every SNAPSHOT_VERSIONS
+/// versions the whole manifest is written to `{output_dir}/_manifest/{table}.json`
+/// and the log entries of the snapshots before are removed. A read takes the
+/// snapshot and replays the log after it.
+///
+/// The files are appended as the ranges are committed, a consumer keeps the
+/// last seq it loaded and loads `files_after` it, exactly once.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableManifest {
+    pub table: String,
+    pub last_seq: u64,
+    pub files: Vec<ManifestFile>,
+    // The last log version applied.
+    pub version: u64,
+    // The changes not written yet.
+    #[serde(skip)]
+    changes: Vec<ManifestChange>,
+}
+
+impl TableManifest {
+    pub fn create(table: &str) -> Self {
+        TableManifest {
+            table: table.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn path(output_dir: &str, table: &str) -> String {
+        format!("{}/_manifest/{}.json", output_dir, table)
+    }
+
+    pub fn log_dir(output_dir: &str, table: &str) -> String {
+        format!("{}/_manifest/{}", output_dir, table)
+    }
+
+    // Read the manifest, empty if none.
+    pub async fn read(op: Arc<Operator>, output_dir: &str, table: &str) -> Result<Self> {
+        for _ in 0..READ_ATTEMPTS {
+            let mut manifest = match op.object(&Self::path(output_dir, table)).read().await {
+                Ok(data) => serde_json::from_slice(&data)?,
+                Err(e) if e.kind() == ErrorKind::ObjectNotFound => TableManifest::create(table),
+                Err(e) => return Err(e.into()),
+            };
+            // A snapshot written since has removed the log after this one.
+            if manifest.refresh(op.clone(), output_dir).await? {
+                return Ok(manifest);
+            }
+            let millis = rand::thread_rng().gen_range(100..500);
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+        }
+        Err(Error::msg(format!(
+            "Read the manifest of {} in {}: the log is removed by a snapshot {} times",
+            table, output_dir, READ_ATTEMPTS
+        )))
+    }
+
+    /// Apply the log entries written since the manifest was read, false if
+    /// the entries right after it are removed and it has to be read again.
+    pub async fn refresh(&mut self, op: Arc<Operator>, output_dir: &str) -> Result<bool> {
+        let versions = Self::log_versions(op.clone(), output_dir, &self.table).await?;
+        if let Some((first, _)) = versions.first() {
+            if *first > self.version + 1 {
+                return Ok(false);
+            }
+        }
+        for (version, path) in versions {
+            if version <= self.version {
+                continue;
+            }
+            let log: ManifestLog = serde_json::from_slice(&op.object(&path).read().await?)?;
+            for change in &log.changes {
+                self.apply(change);
+            }
+            self.version = version;
+        }
+        Ok(true)
+    }
+
+    /// Write the changes since the read as the next log version, the
+    /// writers of the output dir hold the manifest lease.
+    pub async fn write(&mut self, op: Arc<Operator>, output_dir: &str) -> Result<()> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        let log = ManifestLog {
+            version: self.version + 1,
+            changes: std::mem::take(&mut self.changes),
+        };
+        let path = format!(
+            "{}/{:020}.json",
+            Self::log_dir(output_dir, &self.table),
+            log.version
+        );
+        write_atomic(op.clone(), &path, serde_json::to_vec(&log)?).await?;
+        self.version = log.version;
+
+        if self.version % SNAPSHOT_VERSIONS == 0 {
+            let data = serde_json::to_vec_pretty(self)?;
+            write_atomic(op.clone(), &Self::path(output_dir, &self.table), data).await?;
+            // The readers behind by less than a snapshot still catch up from the log.
+            for (version, path) in Self::log_versions(op.clone(), output_dir, &self.table).await? {
+                if version + SNAPSHOT_VERSIONS <= self.version {
+                    op.object(&path).delete().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The log entries in version order.
+    async fn log_versions(
+        op: Arc<Operator>,
+        output_dir: &str,
+        table: &str,
+    ) -> Result<Vec<(u64, String)>> {
+        let mut versions = vec![];
+        for path in list_files(op, &Self::log_dir(output_dir, table)).await? {
+            let name = path.rsplit('/').next().unwrap_or_default();
+            if let Some(Ok(version)) = name.strip_suffix(".json").map(|x| x.parse::<u64>()) {
+                versions.push((version, path));
+            }
+        }
+        versions.sort();
+        Ok(versions)
+    }
+
+    // Apply a change, the removed files.
+    fn apply(&mut self, change: &ManifestChange) -> Vec<ManifestFile> {
+        match change {
+            ManifestChange::Append { file } => {
+                self.append_file(file.clone());
+                vec![]
+            }
+            ManifestChange::Replace { removed, added } => {
+                self.replace_files(removed, added.clone());
+                vec![]
+            }
+            ManifestChange::Remove { paths } => {
+                let (removed, kept): (Vec<_>, Vec<_>) =
+                    self.files.drain(..).partition(|x| paths.contains(&x.path));
+                self.files = kept;
+                removed
+            }
+            ManifestChange::RemoveFrom { from_block } => {
+                let (removed, kept): (Vec<_>, Vec<_>) =
+                    self.files.drain(..).partition(|x| x.end >= *from_block);
+                self.files = kept;
+                removed
+            }
+        }
+    }
+
+    // Apply and record a change, the removed files.
+    fn change(&mut self, change: ManifestChange) -> Vec<ManifestFile> {
+        let removed = self.apply(&change);
+        self.changes.push(change);
+        removed
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.files.iter().any(|x| x.path == path)
+    }
+
+    /// Add the file with the next seq. A file written again to the same path
+    /// with the same rows keeps its seq, the consumers have loaded the rows.
+    pub fn append(&mut self, file: ManifestFile) {
+        self.change(ManifestChange::Append { file });
+    }
+
+    fn append_file(&mut self, mut file: ManifestFile) {
+        match self.files.iter().position(|x| x.path == file.path) {
+            Some(idx) => {
+                let old = self.files.remove(idx);
+                file.seq = if (old.start, old.end, old.rows) == (file.start, file.end, file.rows) {
+                    old.seq
+                } else {
+                    self.next_seq()
+                };
+            }
+            None => file.seq = self.next_seq(),
+        }
+        self.files.push(file);
+        self.files.sort_by_key(|x| (x.start, x.end));
+    }
+
+    // Swap the removed files for the added ones, keeping the files in block order.
+    pub fn replace(&mut self, removed: &[String], added: Vec<ManifestFile>) {
+        self.change(ManifestChange::Replace {
+            removed: removed.to_vec(),
+            added,
+        });
+    }
+
+    fn replace_files(&mut self, removed: &[String], added: Vec<ManifestFile>) {
+        let source_seqs = self
+            .files
+            .iter()
+            .filter(|x| removed.contains(&x.path))
+            .flat_map(|x| {
+                if x.source_seqs.is_empty() {
+                    vec![x.seq]
+                } else {
+                    x.source_seqs.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.files.retain(|x| !removed.contains(&x.path));
+        for mut file in added {
+            file.seq = self.next_seq();
+            file.source_seqs = source_seqs.clone();
+            self.files.push(file);
+        }
+        self.files.sort_by_key(|x| (x.start, x.end));
+    }
+
+    /// Remove the files of the paths, the removed files.
+    pub fn remove(&mut self, paths: &[String]) -> Vec<ManifestFile> {
+        self.change(ManifestChange::Remove {
+            paths: paths.to_vec(),
+        })
+    }
+
+    /// Remove the files with blocks from `from_block`, rolled back by a
+    /// chain reorg. The seqs are not reused.
+    pub fn remove_from(&mut self, from_block: u64) -> Vec<ManifestFile> {
+        self.change(ManifestChange::RemoveFrom { from_block })
+    }
+
+    /// The files to load by a consumer which has loaded the seqs up to
+    /// `seq`, in seq order. A merged file is skipped if its sources are
+    /// loaded, it is an error if only some of them are, the consumer has to
+    /// reload its range.
+    pub fn files_after(&self, seq: u64) -> Result<Vec<&ManifestFile>> {
+        let mut files = vec![];
+        for file in self.files.iter().filter(|x| x.seq > seq) {
+            let loaded = file
+                .source_seqs
+                .iter()
+                .filter(|x| **x <= seq)
+                .collect::<BTreeSet<_>>();
+            if loaded.is_empty() {
+                files.push(file);
+            } else if loaded.len() != file.source_seqs.len() {
+                return Err(Error::msg(format!(
+                    "{} merges files both loaded and not loaded at seq {}, reload blocks {}-{}",
+                    file.path, seq, file.start, file.end
+                )));
+            }
+        }
+        files.sort_by_key(|x| x.seq);
+        Ok(files)
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.last_seq += 1;
+        self.last_seq
+    }
+}
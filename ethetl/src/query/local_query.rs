@@ -0,0 +1,336 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::File;
+use std::sync::Arc;
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+use datafusion::arrow::array::Array;
+use datafusion::arrow::array::Int64Array;
+use datafusion::arrow::array::TimestampSecondArray;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::datatypes::TimeUnit;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::file_format::FileFormat;
+use datafusion::datasource::listing::ListingOptions;
+use datafusion::datasource::listing::ListingTable;
+use datafusion::datasource::listing::ListingTableConfig;
+use datafusion::datasource::listing::ListingTableUrl;
+use datafusion::error::DataFusionError;
+use datafusion::logical_expr::create_udf;
+use datafusion::logical_expr::ColumnarValue;
+use datafusion::logical_expr::ScalarUDF;
+use datafusion::logical_expr::Volatility;
+use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::prelude::SessionConfig;
+use datafusion::prelude::SessionContext;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use opendal::Operator;
+
+use crate::exporters::list_table_files;
+use crate::exporters::PathTemplate;
+use crate::manifest::TableManifest;
+use crate::query::OpendalStore;
+
+// The URL of the storage in DataFusion, the files are `ethetl://storage/{path}`.
+const STORE_SCHEME: &str = "ethetl";
+const STORE_HOST: &str = "storage";
+
+/// LocalQuery runs SQL with DataFusion over the exported parquet files, e.g.
+/// to check the counts of an export before loading it into a warehouse. The
+/// files are scanned from the storage through opendal, by row group and
+/// column, nothing is loaded into memory before the query.
+///
+/// DataFusion is on arrow-rs while the export is on arrow2, the two only
+/// meet at the parquet files here, the query reads them with the parquet
+/// reader of arrow-rs.
+///
+/// The tables are in the `database` schema, the default one, with the
+/// `subtract_hours` function of Databend so the examples of
+/// `schemas/databend` run as is.
+pub struct LocalQuery {
+    storage: Arc<Operator>,
+    output_dir: String,
+    path_template: PathTemplate,
+    chain_id: u64,
+    database: String,
+    // The registry tables and the transform outputs.
+    tables: Vec<String>,
+    store: Arc<dyn ObjectStore>,
+    ctx: SessionContext,
+}
+
+impl LocalQuery {
+    pub fn create(
+        storage: Arc<Operator>,
+        output_dir: &str,
+        path_template: &PathTemplate,
+        chain_id: u64,
+        database: &str,
+        tables: Vec<String>,
+    ) -> Self {
+        let config = SessionConfig::new()
+            .with_default_catalog_and_schema("datafusion", database)
+            .with_information_schema(true);
+        let ctx = SessionContext::with_config(config);
+        ctx.register_udf(subtract_hours_udf());
+        let store: Arc<dyn ObjectStore> = Arc::new(OpendalStore::create(storage.clone()));
+        ctx.runtime_env()
+            .register_object_store(STORE_SCHEME, STORE_HOST, store.clone());
+        LocalQuery {
+            storage,
+            output_dir: output_dir.to_string(),
+            path_template: path_template.clone(),
+            chain_id,
+            database: database.to_string(),
+            tables,
+            store,
+            ctx,
+        }
+    }
+
+    /// Register the tables with files, returns their row counts.
+    pub async fn register_tables(&self) -> Result<Vec<(String, usize)>> {
+        let mut tables = vec![];
+        for table in &self.tables {
+            let paths = self.table_files(table).await?;
+            if paths.is_empty() {
+                continue;
+            }
+
+            // The schema of all the files, a file written before a migration
+            // reads the new columns as null.
+            let mut urls = vec![];
+            let mut objects = vec![];
+            for path in &paths {
+                let url = format!("{}://{}/{}", STORE_SCHEME, STORE_HOST, path);
+                urls.push(ListingTableUrl::parse(url)?);
+                objects.push(self.store.head(&Path::from(path.as_str())).await?);
+            }
+            let format = Arc::new(ParquetFormat::default());
+            let schema = format
+                .infer_schema(&self.ctx.state(), &self.store, &objects)
+                .await
+                .map_err(|e| {
+                    Error::msg(format!(
+                        "The files of {} have different schemas, run migrate first: {}",
+                        table, e
+                    ))
+                })?;
+            let options = ListingOptions::new(format).with_file_extension(".parquet");
+            let config = ListingTableConfig::new_with_multi_paths(urls)
+                .with_listing_options(options)
+                .with_schema(schema);
+            self.ctx
+                .register_table(table.as_str(), Arc::new(ListingTable::try_new(config)?))?;
+
+            // Counted from the parquet statistics.
+            let sql = format!("SELECT count(*) FROM {}", table);
+            let batches = self.ctx.sql(&sql).await?.collect().await?;
+            let rows = batches
+                .first()
+                .and_then(|b| b.column(0).as_any().downcast_ref::<Int64Array>())
+                .map(|x| x.value(0) as usize)
+                .unwrap_or_default();
+            log::info!(
+                "Query table {} with {} files, {} rows",
+                table,
+                paths.len(),
+                rows
+            );
+            tables.push((table.to_string(), rows));
+        }
+        Ok(tables)
+    }
+
+    // The committed files of the manifest, the files of the path template
+    // for an output without manifests.
+    async fn table_files(&self, table: &str) -> Result<Vec<String>> {
+        let manifest = TableManifest::read(self.storage.clone(), &self.output_dir, table).await?;
+        if !manifest.files.is_empty() {
+            return Ok(manifest.files.into_iter().map(|f| f.path).collect());
+        }
+        let files = list_table_files(
+            self.storage.clone(),
+            &self.path_template,
+            &self.output_dir,
+            table,
+            self.chain_id,
+        )
+        .await?;
+        Ok(files.into_iter().map(|f| f.path).collect())
+    }
+
+    /// Run the statements, returns the results of each.
+    pub async fn run(&self, sql: &str) -> Result<Vec<Vec<RecordBatch>>> {
+        let mut results = vec![];
+        for statement in split_statements(sql) {
+            // The tables are in the default schema already.
+            if let Some(database) = use_database(&statement) {
+                if database != self.database {
+                    return Err(Error::msg(format!(
+                        "Unknown database {}, the tables are in {}",
+                        database, self.database
+                    )));
+                }
+                continue;
+            }
+            let df = self.ctx.sql(&statement).await?;
+            results.push(df.collect().await?);
+        }
+        Ok(results)
+    }
+}
+
+/// Split the SQL into statements at the `;`, not within the quotes or the
+/// comments, the comments are kept and the empty statements dropped.
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                current.push(c);
+                for x in chars.by_ref() {
+                    current.push(x);
+                    if x == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                current.push(c);
+                for x in chars.by_ref() {
+                    current.push(x);
+                    if x == '\n' {
+                        break;
+                    }
+                }
+            }
+            ';' => statements.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    statements.push(current);
+
+    statements
+        .into_iter()
+        .filter(|s| {
+            s.lines()
+                .map(|l| l.trim())
+                .any(|l| !l.is_empty() && !l.starts_with("--"))
+        })
+        .map(|s| s.trim().to_string())
+        .collect()
+}
+
+// The database of a `USE` statement.
+fn use_database(statement: &str) -> Option<&str> {
+    let code = statement
+        .lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty() && !l.starts_with("--"))?;
+    let mut words = code.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(keyword), Some(database), None) if keyword.eq_ignore_ascii_case("use") => {
+            Some(database)
+        }
+        _ => None,
+    }
+}
+
+/// `subtract_hours(timestamp, n)` of Databend.
+pub fn subtract_hours_udf() -> ScalarUDF {
+    let timestamp = DataType::Timestamp(TimeUnit::Second, None);
+    let fun = Arc::new(|args: &[ColumnarValue]| {
+        let len = args
+            .iter()
+            .find_map(|x| match x {
+                ColumnarValue::Array(a) => Some(a.len()),
+                ColumnarValue::Scalar(_) => None,
+            })
+            .unwrap_or(1);
+        let ts = args[0].clone().into_array(len);
+        let hours = args[1].clone().into_array(len);
+        let ts = ts
+            .as_any()
+            .downcast_ref::<TimestampSecondArray>()
+            .ok_or_else(|| {
+                DataFusionError::Execution("subtract_hours on a non timestamp".to_string())
+            })?;
+        let hours = hours.as_any().downcast_ref::<Int64Array>().ok_or_else(|| {
+            DataFusionError::Execution("subtract_hours by a non integer".to_string())
+        })?;
+        let result = ts
+            .iter()
+            .zip(hours.iter())
+            .map(|(t, h)| Some(t? - h? * 3600))
+            .collect::<TimestampSecondArray>();
+        Ok(ColumnarValue::Array(Arc::new(result)))
+    });
+    create_udf(
+        "subtract_hours",
+        vec![timestamp.clone(), DataType::Int64],
+        Arc::new(timestamp),
+        Volatility::Immutable,
+        fun,
+    )
+}
+
+/// The batches as a table to print.
+pub fn format_batches(batches: &[RecordBatch]) -> Result<String> {
+    Ok(pretty_format_batches(batches)?.to_string())
+}
+
+/// Write the batches to the local file, as csv, JSON lines or parquet by the
+/// extension.
+pub fn write_batches(batches: &[RecordBatch], path: &str) -> Result<()> {
+    let extension = path.rsplit('.').next().unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "csv" => {
+            let mut writer = datafusion::arrow::csv::Writer::new(File::create(path)?);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+        }
+        "json" | "jsonl" | "ndjson" => {
+            let mut writer = datafusion::arrow::json::LineDelimitedWriter::new(File::create(path)?);
+            writer.write_batches(batches)?;
+            writer.finish()?;
+        }
+        "parquet" => {
+            let schema = match batches.first() {
+                Some(batch) => batch.schema(),
+                None => return Err(Error::msg("No rows to write to the parquet file")),
+            };
+            let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.close()?;
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "Unknown output format of {}, expect .csv, .json or .parquet",
+                path
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,23 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod local_query;
+mod opendal_store;
+
+pub use local_query::format_batches;
+pub use local_query::split_statements;
+pub use local_query::subtract_hours_udf;
+pub use local_query::write_batches;
+pub use local_query::LocalQuery;
+pub use opendal_store::OpendalStore;
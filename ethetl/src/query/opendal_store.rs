@@ -0,0 +1,176 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use common_storages::list_files;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use object_store::path::Path;
+use object_store::GetResult;
+use object_store::ListResult;
+use object_store::MultipartId;
+use object_store::ObjectMeta;
+use object_store::ObjectStore;
+use opendal::ErrorKind;
+use opendal::Operator;
+use tokio::io::AsyncWrite;
+
+const STORE: &str = "opendal";
+
+/// OpendalStore reads the objects of the storage for DataFusion, so the
+/// parquet files are scanned by range from Fs, S3 or Azure rather than read
+/// into memory. The query never writes, the writes are not implemented.
+#[derive(Debug)]
+pub struct OpendalStore {
+    op: Arc<Operator>,
+}
+
+impl OpendalStore {
+    pub fn create(op: Arc<Operator>) -> Self {
+        OpendalStore { op }
+    }
+}
+
+impl fmt::Display for OpendalStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OpendalStore")
+    }
+}
+
+#[async_trait]
+impl ObjectStore for OpendalStore {
+    async fn put(&self, _location: &Path, _bytes: Bytes) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn put_multipart(
+        &self,
+        _location: &Path,
+    ) -> object_store::Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn abort_multipart(
+        &self,
+        _location: &Path,
+        _multipart_id: &MultipartId,
+    ) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn get(&self, location: &Path) -> object_store::Result<GetResult> {
+        let data = self
+            .op
+            .object(location.as_ref())
+            .read()
+            .await
+            .map_err(|e| store_error(location, e))?;
+        let data = Bytes::from(data);
+        Ok(GetResult::Stream(stream::once(async { Ok(data) }).boxed()))
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> object_store::Result<Bytes> {
+        let data = self
+            .op
+            .object(location.as_ref())
+            .range_read(range.start as u64..range.end as u64)
+            .await
+            .map_err(|e| store_error(location, e))?;
+        Ok(Bytes::from(data))
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        let meta = self
+            .op
+            .object(location.as_ref())
+            .stat()
+            .await
+            .map_err(|e| store_error(location, e))?;
+        let seconds = meta
+            .last_modified()
+            .map(|x| x.unix_timestamp())
+            .unwrap_or_default();
+        Ok(ObjectMeta {
+            location: location.clone(),
+            last_modified: timestamp(seconds),
+            size: meta.content_length() as usize,
+        })
+    }
+
+    async fn delete(&self, _location: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn list(
+        &self,
+        prefix: Option<&Path>,
+    ) -> object_store::Result<BoxStream<'_, object_store::Result<ObjectMeta>>> {
+        let dir = prefix.map(|x| x.as_ref().to_string()).unwrap_or_default();
+        let paths =
+            list_files(self.op.clone(), &dir)
+                .await
+                .map_err(|e| object_store::Error::Generic {
+                    store: STORE,
+                    source: e.into(),
+                })?;
+        let metas = stream::iter(paths)
+            .then(move |path| async move { self.head(&Path::from(path)).await })
+            .boxed();
+        Ok(metas)
+    }
+
+    async fn list_with_delimiter(
+        &self,
+        _prefix: Option<&Path>,
+    ) -> object_store::Result<ListResult> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn copy(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn copy_if_not_exists(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+}
+
+fn store_error(location: &Path, e: opendal::Error) -> object_store::Error {
+    match e.kind() {
+        ErrorKind::ObjectNotFound => object_store::Error::NotFound {
+            path: location.to_string(),
+            source: Box::new(e),
+        },
+        _ => object_store::Error::Generic {
+            store: STORE,
+            source: Box::new(e),
+        },
+    }
+}
+
+// The modification time, the epoch if the storage has none.
+fn timestamp(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds, 0)
+        .single()
+        .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap())
+}
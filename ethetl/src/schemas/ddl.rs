@@ -0,0 +1,213 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_configs::HashEncoding;
+use common_configs::SqlDialect;
+use common_exceptions::Result;
+
+use crate::exporters::ColumnProjection;
+use crate::schemas::ColumnKind;
+use crate::schemas::ColumnSchema;
+use crate::schemas::TableSchema;
+use crate::schemas::REGISTRY;
+
+/// DdlGenerator renders the `CREATE TABLE` statements of the registry
+/// tables, only the columns selected by the projection.
+#[derive(Debug, Clone)]
+pub struct DdlGenerator {
+    dialect: SqlDialect,
+    encoding: HashEncoding,
+    projection: ColumnProjection,
+}
+
+impl DdlGenerator {
+    pub fn create(
+        dialect: SqlDialect,
+        encoding: HashEncoding,
+        projection: ColumnProjection,
+    ) -> Self {
+        DdlGenerator {
+            dialect,
+            encoding,
+            projection,
+        }
+    }
+
+    /// The database and all the tables.
+    pub fn render(&self, database: &str) -> Result<String> {
+        let mut sql = format!(
+            "-- Generated by `ethetl schema --dialect {}`, hash_encoding = \"{}\".\n\n",
+            self.dialect.to_string(),
+            self.encoding.to_string()
+        );
+        sql.push_str(&self.create_database(database));
+        for table in REGISTRY {
+            sql.push_str("\n\n");
+            sql.push_str(&self.create_table(database, table)?);
+            if let Some(index) = self.create_unique_key(database, table) {
+                sql.push('\n');
+                sql.push_str(&index);
+            }
+        }
+        sql.push('\n');
+        Ok(sql)
+    }
+
+    pub fn create_database(&self, database: &str) -> String {
+        match self.dialect {
+            SqlDialect::Databend | SqlDialect::ClickHouse => {
+                format!("CREATE DATABASE IF NOT EXISTS {};", database)
+            }
+            SqlDialect::PostgreSQL | SqlDialect::DuckDB | SqlDialect::BigQuery => {
+                format!("CREATE SCHEMA IF NOT EXISTS {};", database)
+            }
+        }
+    }
+
+    pub fn create_table(&self, database: &str, table: &TableSchema) -> Result<String> {
+        self.projection
+            .check(table.name, &table.arrow_schema(self.encoding))?;
+        let columns = self.columns(table);
+
+        let width = columns.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        let mut sql = format!(
+            "-- {}\nCREATE TABLE IF NOT EXISTS {}.{}\n(\n",
+            table.description, database, table.name
+        );
+        for (i, column) in columns.iter().enumerate() {
+            let comma = if i + 1 < columns.len() { "," } else { "" };
+            sql.push_str(&format!(
+                "    {:width$} {}{} -- {}\n",
+                column.name,
+                self.column_type(column),
+                comma,
+                column.description,
+                width = width
+            ));
+        }
+        sql.push(')');
+
+        if self.dialect == SqlDialect::ClickHouse {
+            sql.push_str(&self.clickhouse_engine(table));
+        }
+        sql.push(';');
+        Ok(sql)
+    }
+
+    /// The unique index of the natural key of PostgreSQL, the upserts
+    /// conflict on it. None if no natural key or a key column is excluded.
+    pub fn create_unique_key(&self, database: &str, table: &TableSchema) -> Option<String> {
+        let selected = table
+            .unique_key
+            .iter()
+            .all(|k| self.projection.selects(table.name, k));
+        if self.dialect != SqlDialect::PostgreSQL || table.unique_key.is_empty() || !selected {
+            return None;
+        }
+        Some(format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS {}_unique_key ON {}.{} ({});",
+            table.name,
+            database,
+            table.name,
+            table.unique_key.join(", ")
+        ))
+    }
+
+    // ReplacingMergeTree ordered by the block and the natural key, the rows
+    // of a range inserted again are replaced when the parts merge. MergeTree
+    // ordered by the sort keys if the natural key is not exported.
+    fn clickhouse_engine(&self, table: &TableSchema) -> String {
+        let selects = |k: &&str| self.projection.selects(table.name, k);
+        let (engine, keys) = if !table.unique_key.is_empty() && table.unique_key.iter().all(selects)
+        {
+            let mut keys = vec![table.block_column()];
+            keys.extend(
+                table
+                    .unique_key
+                    .iter()
+                    .filter(|k| **k != table.block_column()),
+            );
+            ("ReplacingMergeTree", keys)
+        } else {
+            ("MergeTree", table.keys.to_vec())
+        };
+        let keys = keys.into_iter().filter(selects).collect::<Vec<_>>();
+        let order_by = if keys.is_empty() {
+            "tuple()".to_string()
+        } else {
+            format!("({})", keys.join(", "))
+        };
+        format!("\nENGINE = {}\nORDER BY {}", engine, order_by)
+    }
+
+    // The selected columns of the table in the file order.
+    fn columns<'a>(&self, table: &'a TableSchema) -> Vec<&'a ColumnSchema> {
+        table
+            .columns
+            .iter()
+            .filter(|c| self.projection.selects(table.name, c.name))
+            .collect()
+    }
+
+    pub fn column_type(&self, column: &ColumnSchema) -> String {
+        let binary = self.encoding == HashEncoding::Binary;
+        let ty = match (self.dialect, column.kind) {
+            (SqlDialect::Databend, ColumnKind::UInt64) => "BIGINT UNSIGNED",
+            (SqlDialect::Databend, ColumnKind::Timestamp) => "TIMESTAMP",
+            (SqlDialect::Databend, ColumnKind::Decimal) => "DECIMAL(36, 18)",
+            (SqlDialect::Databend, ColumnKind::Utf8) => "VARCHAR",
+            (SqlDialect::Databend, _) if binary => "BINARY",
+            (SqlDialect::Databend, _) => "VARCHAR",
+
+            // The exporters never write nulls, the columns are not Nullable
+            // which the sorting key requires.
+            (SqlDialect::ClickHouse, ColumnKind::UInt64) => "UInt64",
+            (SqlDialect::ClickHouse, ColumnKind::Timestamp) => "DateTime('UTC')",
+            (SqlDialect::ClickHouse, ColumnKind::Decimal) => "Decimal(36, 18)",
+            (SqlDialect::ClickHouse, ColumnKind::Hash) if binary => "FixedString(32)",
+            (SqlDialect::ClickHouse, ColumnKind::Address) if binary => "FixedString(20)",
+            (SqlDialect::ClickHouse, ColumnKind::Bloom) if binary => "FixedString(256)",
+            (SqlDialect::ClickHouse, _) => "String",
+
+            // No unsigned integers, the block numbers and gas fit in BIGINT.
+            (SqlDialect::PostgreSQL, ColumnKind::UInt64) => "NUMERIC(20, 0)",
+            (SqlDialect::PostgreSQL, ColumnKind::Timestamp) => "TIMESTAMP",
+            (SqlDialect::PostgreSQL, ColumnKind::Decimal) => "NUMERIC(36, 18)",
+            (SqlDialect::PostgreSQL, ColumnKind::Utf8) => "TEXT",
+            (SqlDialect::PostgreSQL, _) if binary => "BYTEA",
+            (SqlDialect::PostgreSQL, _) => "TEXT",
+
+            (SqlDialect::DuckDB, ColumnKind::UInt64) => "UBIGINT",
+            (SqlDialect::DuckDB, ColumnKind::Timestamp) => "TIMESTAMP",
+            (SqlDialect::DuckDB, ColumnKind::Decimal) => "DECIMAL(36, 18)",
+            (SqlDialect::DuckDB, ColumnKind::Utf8) => "VARCHAR",
+            (SqlDialect::DuckDB, _) if binary => "BLOB",
+            (SqlDialect::DuckDB, _) => "VARCHAR",
+
+            (SqlDialect::BigQuery, ColumnKind::UInt64) => "INT64",
+            (SqlDialect::BigQuery, ColumnKind::Timestamp) => "TIMESTAMP",
+            // NUMERIC has only 9 digits of scale.
+            (SqlDialect::BigQuery, ColumnKind::Decimal) => "BIGNUMERIC",
+            (SqlDialect::BigQuery, ColumnKind::Utf8) => "STRING",
+            (SqlDialect::BigQuery, _) if binary => "BYTES",
+            (SqlDialect::BigQuery, _) => "STRING",
+        };
+
+        if column.nullable || self.dialect == SqlDialect::ClickHouse {
+            ty.to_string()
+        } else {
+            format!("{} NOT NULL", ty)
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+
+use crate::schemas::SCHEMA_VERSION;
+
+const SCHEMA_VERSION_KEY: &str = "ethetl.schema_version";
+const TABLE_KEY: &str = "ethetl.table";
+const CHAIN_ID_KEY: &str = "ethetl.chain_id";
+const START_BLOCK_KEY: &str = "ethetl.start_block";
+const END_BLOCK_KEY: &str = "ethetl.end_block";
+const VERSION_KEY: &str = "ethetl.version";
+const EXPORTED_AT_KEY: &str = "ethetl.exported_at";
+
+/// The version of ethetl writing the files.
+pub static ETHETL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The key-value metadata in the parquet footer of every file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    // The registry version the file was written with.
+    pub schema_version: u32,
+    pub table: String,
+    pub chain_id: u64,
+    pub start: u64,
+    pub end: u64,
+    // The ethetl version.
+    pub version: String,
+    // RFC 3339.
+    pub exported_at: String,
+}
+
+impl FileMetadata {
+    pub fn create(table: &str, chain_id: u64, start: u64, end: u64) -> Self {
+        FileMetadata {
+            schema_version: SCHEMA_VERSION,
+            table: table.to_string(),
+            chain_id,
+            start,
+            end,
+            version: ETHETL_VERSION.to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([
+            (
+                SCHEMA_VERSION_KEY.to_string(),
+                self.schema_version.to_string(),
+            ),
+            (TABLE_KEY.to_string(), self.table.clone()),
+            (CHAIN_ID_KEY.to_string(), self.chain_id.to_string()),
+            (START_BLOCK_KEY.to_string(), self.start.to_string()),
+            (END_BLOCK_KEY.to_string(), self.end.to_string()),
+            (VERSION_KEY.to_string(), self.version.clone()),
+            (EXPORTED_AT_KEY.to_string(), self.exported_at.clone()),
+        ])
+    }
+
+    /// The metadata of the footer, none for the files written before the
+    /// metadata, which are of schema version 1.
+    pub fn from_map(map: &BTreeMap<String, String>) -> Result<Option<Self>> {
+        let version = match map.get(SCHEMA_VERSION_KEY) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let get = |key: &str| {
+            map.get(key)
+                .cloned()
+                .ok_or_else(|| Error::msg(format!("Parquet metadata {} is missing", key)))
+        };
+        let parse = |key: &str| {
+            get(key)?
+                .parse::<u64>()
+                .map_err(|_| Error::msg(format!("Invalid parquet metadata {}", key)))
+        };
+
+        Ok(Some(FileMetadata {
+            schema_version: version
+                .parse()
+                .map_err(|_| Error::msg(format!("Invalid parquet metadata {}", version)))?,
+            table: get(TABLE_KEY)?,
+            chain_id: parse(CHAIN_ID_KEY)?,
+            start: parse(START_BLOCK_KEY)?,
+            end: parse(END_BLOCK_KEY)?,
+            version: get(VERSION_KEY)?,
+            exported_at: get(EXPORTED_AT_KEY)?,
+        }))
+    }
+}
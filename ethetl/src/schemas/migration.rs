@@ -0,0 +1,153 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use common_exceptions::Result;
+use common_storages::read_parquet_metadata;
+use common_storages::write_atomic;
+use opendal::Operator;
+use serde::Serialize;
+
+use crate::exporters::list_table_files;
+use crate::exporters::ColumnProjection;
+use crate::exporters::PathTemplate;
+use crate::schemas::ColumnSchema;
+use crate::schemas::FileMetadata;
+use crate::schemas::REGISTRY;
+use crate::schemas::SCHEMA_VERSION;
+
+/// A file written with an older schema version, missing the added columns
+/// or with the old values of the changed ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutdatedFile {
+    pub table: String,
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    pub schema_version: u32,
+    pub missing_columns: Vec<String>,
+    pub changed_columns: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MigrationReport {
+    pub schema_version: u32,
+    pub files: usize,
+    pub outdated: Vec<OutdatedFile>,
+    // The block ranges to export again, in the block file format.
+    pub backfill_ranges: Vec<String>,
+}
+
+/// SchemaMigration lists the files which miss the columns added since they
+/// were written or hold the old values of the columns changed since, by the
+/// schema version in their footer. The files written before the footer
+/// metadata are of version 1.
+///
+/// The columns excluded by the config are never missing.
+pub struct SchemaMigration {
+    storage: Arc<Operator>,
+    output_dir: String,
+    path_template: PathTemplate,
+    chain_id: u64,
+    projection: ColumnProjection,
+}
+
+impl SchemaMigration {
+    pub fn create(
+        storage: Arc<Operator>,
+        output_dir: &str,
+        path_template: &PathTemplate,
+        chain_id: u64,
+        projection: ColumnProjection,
+    ) -> Self {
+        SchemaMigration {
+            storage,
+            output_dir: output_dir.to_string(),
+            path_template: path_template.clone(),
+            chain_id,
+            projection,
+        }
+    }
+
+    pub async fn check(&self) -> Result<MigrationReport> {
+        let mut report = MigrationReport {
+            schema_version: SCHEMA_VERSION,
+            ..Default::default()
+        };
+
+        let mut ranges = BTreeSet::new();
+        for table in REGISTRY {
+            let files = list_table_files(
+                self.storage.clone(),
+                &self.path_template,
+                &self.output_dir,
+                table.name,
+                self.chain_id,
+            )
+            .await?;
+            report.files += files.len();
+
+            for file in files {
+                let version = file_schema_version(self.storage.clone(), &file.path).await?;
+                let missing = self.selected(table.name, table.added_since(version));
+                let changed = self.selected(table.name, table.changed_since(version));
+                if missing.is_empty() && changed.is_empty() {
+                    continue;
+                }
+
+                ranges.insert((file.start, file.end));
+                report.outdated.push(OutdatedFile {
+                    table: table.name.to_string(),
+                    path: file.path,
+                    start: file.start,
+                    end: file.end,
+                    schema_version: version,
+                    missing_columns: missing,
+                    changed_columns: changed,
+                });
+            }
+        }
+        report.backfill_ranges = ranges
+            .into_iter()
+            .map(|(start, end)| format!("{}-{}", start, end))
+            .collect();
+        Ok(report)
+    }
+
+    // The names of the columns not excluded by the config.
+    fn selected(&self, table: &str, columns: Vec<&ColumnSchema>) -> Vec<String> {
+        columns
+            .into_iter()
+            .filter(|c| self.projection.selects(table, c.name))
+            .map(|c| c.name.to_string())
+            .collect()
+    }
+
+    /// Write the ranges to backfill as a block file of `--block-file`.
+    pub async fn write_block_file(&self, report: &MigrationReport, path: &str) -> Result<()> {
+        let mut content = report.backfill_ranges.join("\n");
+        content.push('\n');
+        write_atomic(self.storage.clone(), path, content.into_bytes()).await
+    }
+}
+
+/// The schema version of a parquet file, 1 if the footer has no metadata.
+pub async fn file_schema_version(storage: Arc<Operator>, path: &str) -> Result<u32> {
+    let metadata = read_parquet_metadata(storage, path).await?;
+    Ok(FileMetadata::from_map(&metadata)?
+        .map(|x| x.schema_version)
+        .unwrap_or(1))
+}
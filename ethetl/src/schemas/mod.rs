@@ -0,0 +1,38 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod ddl;
+mod file_metadata;
+mod migration;
+mod registry;
+
+pub use ddl::DdlGenerator;
+pub use file_metadata::FileMetadata;
+pub use file_metadata::ETHETL_VERSION;
+pub use migration::file_schema_version;
+pub use migration::MigrationReport;
+pub use migration::OutdatedFile;
+pub use migration::SchemaMigration;
+pub use registry::table_schema;
+pub use registry::ColumnKind;
+pub use registry::ColumnSchema;
+pub use registry::TableSchema;
+pub use registry::BLOCKS;
+pub use registry::ENS;
+pub use registry::LOGS;
+pub use registry::RECEIPTS;
+pub use registry::REGISTRY;
+pub use registry::SCHEMA_VERSION;
+pub use registry::TOKEN_TRANSFERS;
+pub use registry::TRANSACTIONS;
@@ -0,0 +1,370 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow2::array::Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use arrow2::datatypes::TimeUnit;
+use common_configs::HashEncoding;
+use common_exceptions::Error;
+use common_exceptions::Result;
+
+/// The version of the registry, written to the footer of every file.
+///
+/// Adding a column bumps it and declares the column with the new version,
+/// e.g. `ColumnSchema { since: 2, ..column("withdrawals_root", Hash, "...") }`,
+/// the files of an older version miss the column and are listed by
+/// `ethetl migrate` to be backfilled. Changing the values of a column bumps
+/// it the same way and declares the column with `changed`.
+///
+/// Version 2 changed `logs.log_index` from the index of the receipt in the
+/// range to the index of the log in the block.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The logical type of a column, the Arrow type of the hashes, addresses,
+/// blooms and topics depends on the hash encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    UInt64,
+    // Seconds, UTC.
+    Timestamp,
+    // Decimal(36, 18), the wei values in ether.
+    Decimal,
+    // Strings, also the hex of the variable length values.
+    Utf8,
+    // 32 bytes.
+    Hash,
+    // 20 bytes.
+    Address,
+    // 256 bytes.
+    Bloom,
+    // The topics of a log, `|` joined hex or the concatenated 32 bytes.
+    Topics,
+}
+
+impl ColumnKind {
+    pub fn data_type(&self, encoding: HashEncoding) -> DataType {
+        match (self, encoding) {
+            (ColumnKind::UInt64, _) => DataType::UInt64,
+            (ColumnKind::Timestamp, _) => DataType::Timestamp(TimeUnit::Second, None),
+            (ColumnKind::Decimal, _) => DataType::Decimal(36, 18),
+            (ColumnKind::Utf8, _) => DataType::Utf8,
+            (_, HashEncoding::Hex) => DataType::Utf8,
+            (ColumnKind::Hash, HashEncoding::Binary) => DataType::FixedSizeBinary(32),
+            (ColumnKind::Address, HashEncoding::Binary) => DataType::FixedSizeBinary(20),
+            (ColumnKind::Bloom, HashEncoding::Binary) => DataType::FixedSizeBinary(256),
+            (ColumnKind::Topics, HashEncoding::Binary) => DataType::Binary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnSchema {
+    pub name: &'static str,
+    pub kind: ColumnKind,
+    pub nullable: bool,
+    pub description: &'static str,
+    // The schema version which added the column.
+    pub since: u32,
+    // The schema version which last changed the values of the column, the
+    // files of an older version have the old values.
+    pub changed: u32,
+}
+
+/// The columns of a table in the file order.
+#[derive(Debug, Clone, Copy)]
+pub struct TableSchema {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub columns: &'static [ColumnSchema],
+    // The sort key of the sinks, the rows are not unique by it.
+    pub keys: &'static [&'static str],
+    // The natural key the rows are unique by.
+    pub unique_key: &'static [&'static str],
+}
+
+impl TableSchema {
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// The columns added after the schema version of a file.
+    pub fn added_since(&self, schema_version: u32) -> Vec<&ColumnSchema> {
+        self.columns
+            .iter()
+            .filter(|c| c.since > schema_version)
+            .collect()
+    }
+
+    /// The columns of a file of the schema version whose values changed
+    /// after it, the added ones excluded.
+    pub fn changed_since(&self, schema_version: u32) -> Vec<&ColumnSchema> {
+        self.columns
+            .iter()
+            .filter(|c| c.since <= schema_version && c.changed > schema_version)
+            .collect()
+    }
+
+    /// The block number column, `number` of the blocks.
+    pub fn block_column(&self) -> &'static str {
+        if self.name == "blocks" {
+            "number"
+        } else {
+            "block_number"
+        }
+    }
+
+    pub fn arrow_schema(&self, encoding: HashEncoding) -> Schema {
+        Schema::from(
+            self.columns
+                .iter()
+                .map(|c| Field::new(c.name, c.kind.data_type(encoding), c.nullable))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Build the chunk of the arrays in the column order, an array of
+    /// another type than the registry is an error.
+    pub fn chunk(
+        &self,
+        encoding: HashEncoding,
+        arrays: Vec<Box<dyn Array>>,
+    ) -> Result<(Schema, Chunk<Box<dyn Array>>)> {
+        let schema = self.arrow_schema(encoding);
+        if arrays.len() != schema.fields.len() {
+            return Err(Error::msg(format!(
+                "Table {} has {} columns, got {} arrays",
+                self.name,
+                schema.fields.len(),
+                arrays.len()
+            )));
+        }
+        for (field, array) in schema.fields.iter().zip(arrays.iter()) {
+            if &field.data_type != array.data_type() {
+                return Err(Error::msg(format!(
+                    "Column {}.{} is {:?}, the registry has {:?}",
+                    self.name,
+                    field.name,
+                    array.data_type(),
+                    field.data_type
+                )));
+            }
+        }
+        Ok((schema, Chunk::try_new(arrays)?))
+    }
+}
+
+const fn column(name: &'static str, kind: ColumnKind, description: &'static str) -> ColumnSchema {
+    // The exporters never write nulls, the fields stay nullable as the
+    // files written before the registry.
+    ColumnSchema {
+        name,
+        kind,
+        nullable: true,
+        description,
+        since: 1,
+        changed: 1,
+    }
+}
+
+use ColumnKind::*;
+
+pub static BLOCKS: TableSchema = TableSchema {
+    name: "blocks",
+    description: "The blocks.",
+    columns: &[
+        column("number", UInt64, "The block number"),
+        column("hash", Hash, "The block hash"),
+        column("parent_hash", Hash, "The hash of the parent block"),
+        column("nonce", Utf8, "The proof of work nonce, hex"),
+        column("sha3_uncles", Hash, "The hash of the uncles"),
+        column("logs_bloom", Bloom, "The bloom filter of the logs"),
+        column(
+            "transactions_root",
+            Hash,
+            "The root of the transactions trie",
+        ),
+        column("state_root", Hash, "The root of the state trie"),
+        column("receipts_root", Hash, "The root of the receipts trie"),
+        column("difficulty", Utf8, "The difficulty, hex"),
+        column(
+            "total_difficulty",
+            Utf8,
+            "The total difficulty of the chain until this block, hex",
+        ),
+        column("size", UInt64, "The size of the block in bytes"),
+        column("extra_data", Utf8, "The extra data, hex"),
+        column("gas_limit", UInt64, "The gas limit"),
+        column("gas_used", UInt64, "The gas used by the transactions"),
+        column("timestamp", Timestamp, "The block timestamp"),
+        column("transaction_count", UInt64, "The number of transactions"),
+        column(
+            "base_fee_per_gas",
+            UInt64,
+            "The base fee per gas in wei, EIP-1559",
+        ),
+    ],
+    keys: &["number"],
+    unique_key: &["number"],
+};
+
+pub static TRANSACTIONS: TableSchema = TableSchema {
+    name: "transactions",
+    description: "The transactions of the blocks.",
+    columns: &[
+        column("hash", Hash, "The transaction hash"),
+        column("nonce", Utf8, "The nonce of the sender, hex"),
+        column("transaction_index", UInt64, "The index in the block"),
+        column("from_address", Address, "The sender"),
+        column(
+            "to_address",
+            Address,
+            "The receiver, zero for the contract creations",
+        ),
+        column("value", Decimal, "The value transferred in ether"),
+        column("gas", UInt64, "The gas provided by the sender"),
+        column("gas_price", UInt64, "The gas price in wei"),
+        column("method_id", Utf8, "The first 4 bytes of the input, hex"),
+        column("input", Utf8, "The input data, hex"),
+        column(
+            "max_fee_per_gas",
+            UInt64,
+            "The max fee per gas in wei, EIP-1559",
+        ),
+        column(
+            "max_priority_fee_per_gas",
+            UInt64,
+            "The max priority fee per gas in wei, EIP-1559",
+        ),
+        column("transaction_type", UInt64, "The transaction type"),
+        column("block_hash", Hash, "The block hash"),
+        column("block_number", UInt64, "The block number"),
+        column("block_timestamp", Timestamp, "The block timestamp"),
+    ],
+    keys: &["block_number", "transaction_index"],
+    unique_key: &["hash"],
+};
+
+pub static RECEIPTS: TableSchema = TableSchema {
+    name: "receipts",
+    description: "The receipts of the transactions.",
+    columns: &[
+        column("transaction_hash", Hash, "The transaction hash"),
+        column("transaction_index", UInt64, "The index in the block"),
+        column("block_hash", Hash, "The block hash"),
+        column("block_number", UInt64, "The block number"),
+        column(
+            "cumulative_gas_used",
+            UInt64,
+            "The gas used in the block until this transaction",
+        ),
+        column("gas_used", UInt64, "The gas used by the transaction"),
+        column(
+            "contract_address",
+            Address,
+            "The contract created, zero if none",
+        ),
+        column("root", Hash, "The post state root, before Byzantium"),
+        column("status", UInt64, "1 success or 0 failure, since Byzantium"),
+        column("effective_gas_price", UInt64, "The gas price paid in wei"),
+    ],
+    keys: &["block_number", "transaction_index"],
+    unique_key: &["transaction_hash"],
+};
+
+pub static LOGS: TableSchema = TableSchema {
+    name: "logs",
+    description: "The logs emitted by the transactions.",
+    columns: &[
+        ColumnSchema {
+            changed: 2,
+            ..column("log_index", UInt64, "The index of the log in the block")
+        },
+        column("transaction_hash", Hash, "The transaction hash"),
+        column(
+            "transaction_index",
+            UInt64,
+            "The index of the transaction in the block",
+        ),
+        column("block_hash", Hash, "The block hash"),
+        column("block_number", UInt64, "The block number"),
+        column(
+            "contract_address",
+            Address,
+            "The contract created by the transaction, zero if none",
+        ),
+        column(
+            "event_address",
+            Address,
+            "The contract which emitted the log",
+        ),
+        column("data", Utf8, "The non indexed arguments, hex"),
+        column("topics", Topics, "The indexed topics"),
+    ],
+    keys: &["block_number", "log_index"],
+    unique_key: &["transaction_hash", "log_index"],
+};
+
+pub static TOKEN_TRANSFERS: TableSchema = TableSchema {
+    name: "token_transfers",
+    description: "The ERC20, ERC721 and ERC1155 transfers decoded from the logs.",
+    columns: &[
+        column("token_address", Address, "The token contract"),
+        column("from_address", Address, "The sender"),
+        column("to_address", Address, "The receiver"),
+        column("token_id", Utf8, "The token id of ERC721 and ERC1155, hex"),
+        column("value", Utf8, "The amount transferred, decimal"),
+        column("erc_standard", Utf8, "ERC20, ERC721 or ERC1155"),
+        column("transaction_hash", Hash, "The transaction hash"),
+        column("log_index", UInt64, "The index of the log in the block"),
+        column("block_number", UInt64, "The block number"),
+    ],
+    keys: &["block_number", "log_index", "token_id"],
+    unique_key: &["transaction_hash", "log_index", "token_id"],
+};
+
+pub static ENS: TableSchema = TableSchema {
+    name: "ens",
+    description: "The ENS names registered.",
+    columns: &[
+        column("name", Utf8, "The name, without .eth"),
+        column("cost", Decimal, "The cost in ether"),
+        column("expires", Timestamp, "The expiry time"),
+        column("owner", Address, "The owner"),
+        column("transaction_hash", Hash, "The transaction hash"),
+        column("block_number", UInt64, "The block number"),
+    ],
+    keys: &["block_number", "transaction_hash", "name"],
+    unique_key: &["transaction_hash", "name"],
+};
+
+/// The tables in the export order.
+pub static REGISTRY: [&TableSchema; 6] = [
+    &BLOCKS,
+    &TRANSACTIONS,
+    &RECEIPTS,
+    &LOGS,
+    &TOKEN_TRANSFERS,
+    &ENS,
+];
+
+pub fn table_schema(table: &str) -> Result<&'static TableSchema> {
+    REGISTRY
+        .iter()
+        .find(|t| t.name == table)
+        .copied()
+        .ok_or_else(|| Error::msg(format!("Unknown table {}", table)))
+}
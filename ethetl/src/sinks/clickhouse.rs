@@ -0,0 +1,246 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common_configs::mask_string;
+use common_configs::ClickHouseInsertFormat;
+use common_configs::ClickHouseSinkConfig;
+use common_configs::HashEncoding;
+use common_configs::SqlDialect;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_exceptions::Retryable;
+use common_storages::read_parquet;
+use opendal::Operator;
+
+use crate::exporters::ColumnProjection;
+use crate::manifest::ManifestFile;
+use crate::schemas::table_schema;
+use crate::schemas::DdlGenerator;
+use crate::schemas::REGISTRY;
+use crate::sinks::arrow_stream;
+use crate::sinks::CommittedRange;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+/// ClickHouseSink inserts the committed ranges into ClickHouse through the
+/// HTTP interface, the parquet files as they are or the chunks as an Arrow
+/// stream.
+///
+/// The tables are ReplacingMergeTree on the block and the unique key, the
+/// logs on the transaction hash and the log index, a range inserted again
+/// replaces its rows when the parts merge, query them with `FINAL` to see the
+/// replaced rows once. A reorg deletes the rows from the first rolled back
+/// block.
+#[derive(Debug)]
+pub struct ClickHouseSink {
+    storage: Arc<Operator>,
+    conf: ClickHouseSinkConfig,
+    ddl: DdlGenerator,
+    client: ClickHouseClient,
+    // Held while inserting, the tables are created once.
+    tables_created: tokio::sync::Mutex<bool>,
+}
+
+impl ClickHouseSink {
+    pub fn create(
+        storage: Arc<Operator>,
+        conf: ClickHouseSinkConfig,
+        encoding: HashEncoding,
+        projection: ColumnProjection,
+    ) -> Result<Self> {
+        for table in REGISTRY {
+            if !projection.selects(table.name, table.block_column()) {
+                return Err(Error::msg(format!(
+                    "The clickhouse sink deletes {} by {}, the column can't be excluded",
+                    table.name,
+                    table.block_column()
+                )));
+            }
+        }
+        Ok(ClickHouseSink {
+            storage,
+            client: ClickHouseClient::create(&conf)?,
+            ddl: DdlGenerator::create(SqlDialect::ClickHouse, encoding, projection),
+            conf,
+            tables_created: tokio::sync::Mutex::new(false),
+        })
+    }
+
+    async fn create_tables(&self) -> Result<()> {
+        self.client
+            .query(&self.ddl.create_database(&self.conf.database))
+            .await?;
+        for table in REGISTRY {
+            let sql = self.ddl.create_table(&self.conf.database, table)?;
+            self.client.query(&sql).await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_with_no_retry(&self, table: &str, file: &ManifestFile) -> Result<()> {
+        let (format, data) = match self.conf.format {
+            ClickHouseInsertFormat::Parquet => {
+                ("Parquet", self.storage.object(&file.path).read().await?)
+            }
+            ClickHouseInsertFormat::ArrowStream => {
+                let (schema, chunks) = read_parquet(self.storage.clone(), &file.path).await?;
+                ("ArrowStream", arrow_stream(&schema, &chunks)?)
+            }
+        };
+        let sql = format!(
+            "INSERT INTO {}.{} FORMAT {}",
+            self.conf.database, table, format
+        );
+        self.client.insert(&sql, data).await
+    }
+}
+
+#[async_trait]
+impl Sink for ClickHouseSink {
+    fn name(&self) -> &str {
+        "clickhouse"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let mut created = self.tables_created.lock().await;
+        if self.conf.create_tables && !*created {
+            self.create_tables().await?;
+            *created = true;
+        }
+
+        for (table, file) in &range.files {
+            log::info!(
+                "Insert {} into clickhouse {}.{}",
+                file.path,
+                self.conf.database,
+                table
+            );
+            let notify = |e, duration| {
+                log::warn!(
+                    "ClickHouse insert error at duration {:?}, error:{:?}",
+                    duration,
+                    e
+                )
+            };
+            let op = || async {
+                self.insert_with_no_retry(table, file).await?;
+                Ok(())
+            };
+            op.retry_with_notify(notify).await?;
+        }
+        Ok(())
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let _guard = self.tables_created.lock().await;
+        for table in rolled_back.files.keys() {
+            let sql = format!(
+                "ALTER TABLE {}.{} DELETE WHERE {} >= {}",
+                self.conf.database,
+                table,
+                table_schema(table)?.block_column(),
+                rolled_back.from_block
+            );
+            let notify = |e, duration| {
+                log::warn!(
+                    "ClickHouse rollback error at duration {:?}, error:{:?}",
+                    duration,
+                    e
+                )
+            };
+            let op = || async {
+                self.client.mutation(&sql).await?;
+                Ok(())
+            };
+            op.retry_with_notify(notify).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The client of the ClickHouse HTTP interface, the statements are not
+/// retried.
+#[derive(Clone)]
+pub struct ClickHouseClient {
+    http: reqwest::Client,
+    endpoint: String,
+    user: String,
+    password: String,
+}
+
+impl fmt::Debug for ClickHouseClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClickHouseClient")
+            .field("endpoint", &self.endpoint)
+            .field("user", &self.user)
+            .field("password", &mask_string(&self.password, 3))
+            .finish()
+    }
+}
+
+impl ClickHouseClient {
+    pub fn create(conf: &ClickHouseSinkConfig) -> Result<Self> {
+        Ok(ClickHouseClient {
+            http: reqwest::Client::builder().build()?,
+            endpoint: format!("{}/", conf.endpoint.trim_end_matches('/')),
+            user: conf.user.clone(),
+            password: conf.password.clone(),
+        })
+    }
+
+    /// Run the statement of the body, returns the response.
+    pub async fn query(&self, sql: &str) -> Result<String> {
+        self.send(&[], sql.as_bytes().to_vec(), sql).await
+    }
+
+    /// Run the `ALTER TABLE ... DELETE`, returns after the parts of all the
+    /// replicas are rewritten.
+    pub async fn mutation(&self, sql: &str) -> Result<String> {
+        self.send(&[("mutations_sync", "2")], sql.as_bytes().to_vec(), sql)
+            .await
+    }
+
+    /// Run the `INSERT ... FORMAT` of the query parameter with the data.
+    pub async fn insert(&self, sql: &str, data: Vec<u8>) -> Result<()> {
+        self.send(&[("query", sql)], data, sql).await?;
+        Ok(())
+    }
+
+    async fn send(&self, params: &[(&str, &str)], body: Vec<u8>, sql: &str) -> Result<String> {
+        let resp = self
+            .http
+            .post(&self.endpoint)
+            .header("X-ClickHouse-User", &self.user)
+            .header("X-ClickHouse-Key", &self.password)
+            .query(params)
+            .body(body)
+            .send()
+            .await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(Error::msg(format!(
+                "ClickHouse error of {}: {} {}",
+                sql,
+                status,
+                text.trim()
+            )));
+        }
+        Ok(text)
+    }
+}
@@ -0,0 +1,323 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common_configs::mask_string;
+use common_configs::DatabendSinkConfig;
+use common_configs::HashEncoding;
+use common_configs::SqlDialect;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_exceptions::Retryable;
+use opendal::Operator;
+use reqwest::multipart::Form;
+use reqwest::multipart::Part;
+use reqwest::Body;
+use serde_json::json;
+use serde_json::Value;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::io::ReaderStream;
+
+use crate::exporters::ColumnProjection;
+use crate::manifest::ManifestFile;
+use crate::manifest::TableManifest;
+use crate::schemas::table_schema;
+use crate::schemas::DdlGenerator;
+use crate::schemas::REGISTRY;
+use crate::sinks::CommittedRange;
+use crate::sinks::LoadState;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+/// DatabendSink loads the committed files into Databend through the HTTP
+/// query API, by `COPY INTO` from the stage over the output dir, or by
+/// streaming load of the files read from the storage.
+///
+/// The loaded files are marked in the load state, every commit also loads
+/// the files of the manifests not loaded yet, e.g. after a failure or for
+/// the ranges exported before the sink.
+#[derive(Debug)]
+pub struct DatabendSink {
+    storage: Arc<Operator>,
+    conf: DatabendSinkConfig,
+    ddl: DdlGenerator,
+    client: DatabendClient,
+    // Held while loading, the tables are created once.
+    tables_created: tokio::sync::Mutex<bool>,
+}
+
+impl DatabendSink {
+    pub fn create(
+        storage: Arc<Operator>,
+        conf: DatabendSinkConfig,
+        encoding: HashEncoding,
+        projection: ColumnProjection,
+    ) -> Result<Self> {
+        Ok(DatabendSink {
+            storage,
+            client: DatabendClient::create(&conf)?,
+            ddl: DdlGenerator::create(SqlDialect::Databend, encoding, projection),
+            conf,
+            tables_created: tokio::sync::Mutex::new(false),
+        })
+    }
+
+    async fn create_tables(&self) -> Result<()> {
+        self.client
+            .query(&self.ddl.create_database(&self.conf.database))
+            .await?;
+        for table in REGISTRY {
+            let sql = self.ddl.create_table(&self.conf.database, table)?;
+            self.client.query(&sql).await?;
+        }
+        Ok(())
+    }
+
+    async fn load(&self, output_dir: &str, table: &str, file: &ManifestFile) -> Result<()> {
+        log::info!(
+            "Load {} into databend {}.{}",
+            file.path,
+            self.conf.database,
+            table
+        );
+        if !self.conf.stage.is_empty() {
+            let sql = format!(
+                "COPY INTO {}.{} FROM @{} FILES = ('{}') FILE_FORMAT = (type = 'PARQUET')",
+                self.conf.database,
+                table,
+                self.conf.stage,
+                stage_path(output_dir, &file.path)
+            );
+            self.client.query(&sql).await?;
+        } else {
+            let sql = format!(
+                "INSERT INTO {}.{} FILE_FORMAT = (type = 'PARQUET')",
+                self.conf.database, table
+            );
+            self.client
+                .streaming_load(&sql, self.storage.clone(), &file.path)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for DatabendSink {
+    fn name(&self) -> &str {
+        "databend"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let mut created = self.tables_created.lock().await;
+        if self.conf.create_tables && !*created {
+            self.create_tables().await?;
+            *created = true;
+        }
+
+        let op = self.storage.clone();
+        let mut state = LoadState::read(op.clone(), &range.output_dir, self.name()).await?;
+        for table in range.files.keys() {
+            let manifest = TableManifest::read(op.clone(), &range.output_dir, table).await?;
+            for file in state.pending(&manifest)? {
+                self.load(&range.output_dir, table, file).await?;
+                state.insert(table, file.seq);
+                state
+                    .write(op.clone(), &range.output_dir, self.name())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let _guard = self.tables_created.lock().await;
+        for table in rolled_back.files.keys() {
+            let sql = format!(
+                "DELETE FROM {}.{} WHERE {} >= {}",
+                self.conf.database,
+                table,
+                table_schema(table)?.block_column(),
+                rolled_back.from_block
+            );
+            self.client.query(&sql).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The client of the Databend HTTP handler, the requests are retried.
+#[derive(Clone)]
+pub struct DatabendClient {
+    http: reqwest::Client,
+    endpoint: String,
+    user: String,
+    password: String,
+}
+
+impl fmt::Debug for DatabendClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DatabendClient")
+            .field("endpoint", &self.endpoint)
+            .field("user", &self.user)
+            .field("password", &mask_string(&self.password, 3))
+            .finish()
+    }
+}
+
+impl DatabendClient {
+    pub fn create(conf: &DatabendSinkConfig) -> Result<Self> {
+        Ok(DatabendClient {
+            http: reqwest::Client::builder().build()?,
+            endpoint: conf.endpoint.trim_end_matches('/').to_string(),
+            user: conf.user.clone(),
+            password: conf.password.clone(),
+        })
+    }
+
+    /// Run the statement and return the rows.
+    pub async fn query(&self, sql: &str) -> Result<Vec<Value>> {
+        let notify = |e, duration| {
+            log::warn!(
+                "Databend query error at duration {:?}, error:{:?}",
+                duration,
+                e
+            )
+        };
+        let op = || async {
+            let res = self.query_with_no_retry(sql).await?;
+            Ok(res)
+        };
+
+        op.retry_with_notify(notify).await
+    }
+
+    async fn query_with_no_retry(&self, sql: &str) -> Result<Vec<Value>> {
+        let mut resp: Value = self
+            .http
+            .post(format!("{}/v1/query", self.endpoint))
+            .basic_auth(&self.user, Some(&self.password))
+            .json(&json!({"sql": sql, "pagination": {"wait_time_secs": 10}}))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // The next pages until the query is done.
+        let mut rows = vec![];
+        loop {
+            check_error(sql, &resp)?;
+            if let Some(data) = resp["data"].as_array() {
+                rows.extend(data.iter().cloned());
+            }
+            let next_uri = match resp["next_uri"].as_str() {
+                Some(uri) if !uri.is_empty() => uri.to_string(),
+                _ => break,
+            };
+            resp = self
+                .http
+                .get(format!("{}{}", self.endpoint, next_uri))
+                .basic_auth(&self.user, Some(&self.password))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+        }
+        Ok(rows)
+    }
+
+    /// Upload the file of the storage to the insert statement, streamed from
+    /// the storage by each try.
+    pub async fn streaming_load(
+        &self,
+        sql: &str,
+        storage: Arc<Operator>,
+        path: &str,
+    ) -> Result<()> {
+        let notify = |e, duration| {
+            log::warn!(
+                "Databend streaming load error at duration {:?}, error:{:?}",
+                duration,
+                e
+            )
+        };
+        let op = || async {
+            self.streaming_load_with_no_retry(sql, storage.clone(), path)
+                .await?;
+            Ok(())
+        };
+
+        op.retry_with_notify(notify).await
+    }
+
+    async fn streaming_load_with_no_retry(
+        &self,
+        sql: &str,
+        storage: Arc<Operator>,
+        path: &str,
+    ) -> Result<()> {
+        let object = storage.object(path);
+        let size = object.stat().await?.content_length();
+        let reader = object.reader().await?;
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        // The length is known, the form is sent with its content length.
+        let part =
+            Part::stream_with_length(Body::wrap_stream(ReaderStream::new(reader.compat())), size)
+                .file_name(file_name.to_string())
+                .mime_str("application/octet-stream")?;
+        let form = Form::new().part("upload", part);
+
+        let resp: Value = self
+            .http
+            .put(format!("{}/v1/streaming_load", self.endpoint))
+            .basic_auth(&self.user, Some(&self.password))
+            .header("insert_sql", sql)
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        check_error(sql, &resp)
+    }
+}
+
+// The file path in the stage over the output dir.
+fn stage_path<'a>(output_dir: &str, path: &'a str) -> &'a str {
+    let dir = output_dir.trim_matches('/');
+    let path = path.trim_start_matches('/');
+    path.strip_prefix(dir)
+        .and_then(|x| x.strip_prefix('/'))
+        .unwrap_or(path)
+}
+
+fn check_error(sql: &str, resp: &Value) -> Result<()> {
+    let error = &resp["error"];
+    if !error.is_null() {
+        let message = error["message"].as_str().unwrap_or_default();
+        return Err(Error::msg(format!(
+            "Databend error of {}: {}",
+            sql, message
+        )));
+    }
+    if resp["state"].as_str() == Some("Failed") {
+        return Err(Error::msg(format!("Databend query failed: {}", sql)));
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arrow2::datatypes::Schema;
+use async_trait::async_trait;
+use common_configs::HashEncoding;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::DeltaFile;
+use common_storages::DeltaTable;
+use opendal::Operator;
+use opendal::Scheme;
+use serde_json::json;
+
+use crate::exporters::ColumnProjection;
+use crate::exporters::PathTemplate;
+use crate::manifest::Lease;
+use crate::manifest::ManifestFile;
+use crate::schemas::table_schema;
+use crate::schemas::TableSchema;
+use crate::sinks::CommittedRange;
+use crate::sinks::Compacted;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+/// DeltaSink commits the files of each range as an append to the Delta
+/// table of each table, rooted at the table dir of the path template, e.g.
+/// `{output_dir}/blocks/_delta_log`.
+///
+/// The rolled back files are removed in one version per table, the engines
+/// reading a version never see the blocks of both forks.
+///
+/// On the object storages the commits of the processes sharing the output
+/// dir are serialized by the lease `_locks/delta/`, on Fs a version is
+/// linked into place only if absent.
+#[derive(Debug)]
+pub struct DeltaSink {
+    storage: Arc<Operator>,
+    // The storage has no conditional put, the commits hold the lease.
+    external_lock: bool,
+    path_template: PathTemplate,
+    chain_id: u64,
+    encoding: HashEncoding,
+    projection: ColumnProjection,
+    // By table root.
+    tables: Mutex<BTreeMap<String, Arc<DeltaTable>>>,
+}
+
+impl DeltaSink {
+    pub fn create(
+        storage: Arc<Operator>,
+        path_template: &PathTemplate,
+        chain_id: u64,
+        encoding: HashEncoding,
+        projection: ColumnProjection,
+    ) -> Result<Self> {
+        // The table roots must not hold the files of other tables.
+        if path_template.table_dir("", "blocks", chain_id)
+            == path_template.table_dir("", "transactions", chain_id)
+        {
+            return Err(Error::msg(
+                "table_format = delta needs a path template starting with the {table} dir, e.g. {table}/{table}_{start}_{end}",
+            ));
+        }
+
+        Ok(DeltaSink {
+            external_lock: storage.metadata().scheme() != Scheme::Fs,
+            storage,
+            path_template: path_template.clone(),
+            chain_id,
+            encoding,
+            projection,
+            tables: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// The Delta table of the table under the output dir.
+    pub fn table(&self, output_dir: &str, table: &str) -> Arc<DeltaTable> {
+        let root = self
+            .path_template
+            .table_dir(output_dir, table, self.chain_id);
+        let mut tables = self.tables.lock().unwrap();
+        tables
+            .entry(root.clone())
+            .or_insert_with(|| {
+                let table = DeltaTable::create(self.storage.clone(), &root);
+                if self.external_lock {
+                    Arc::new(table.with_external_lock())
+                } else {
+                    Arc::new(table)
+                }
+            })
+            .clone()
+    }
+
+    // The lease of the commits of the output dir, none on Fs.
+    async fn lock(&self, output_dir: &str) -> Result<Option<Lease>> {
+        if !self.external_lock {
+            return Ok(None);
+        }
+        let lease = Lease::acquire(self.storage.clone(), output_dir, "delta").await?;
+        Ok(Some(lease))
+    }
+
+    // The registry schema of the selected columns.
+    fn schema(&self, table: &TableSchema) -> Schema {
+        let fields = table
+            .arrow_schema(self.encoding)
+            .fields
+            .into_iter()
+            .filter(|f| self.projection.selects(table.name, &f.name))
+            .collect::<Vec<_>>();
+        Schema::from(fields)
+    }
+
+    fn delta_file(
+        &self,
+        delta: &DeltaTable,
+        table: &TableSchema,
+        file: &ManifestFile,
+    ) -> Result<DeltaFile> {
+        let mut delta_file = DeltaFile {
+            path: relative_path(delta.table_root(), &file.path)?,
+            size: file.size,
+            rows: file.rows,
+            ..Default::default()
+        };
+        if let (Some(min), Some(max)) = (file.min_block, file.max_block) {
+            let column = table.block_column().to_string();
+            delta_file.min_values.insert(column.clone(), json!(min));
+            delta_file.max_values.insert(column, json!(max));
+        }
+        Ok(delta_file)
+    }
+
+    async fn append(&self, range: &CommittedRange) -> Result<()> {
+        for (name, file) in &range.files {
+            let table = table_schema(name)?;
+            let delta = self.table(&range.output_dir, name);
+            let delta_file = self.delta_file(&delta, table, file)?;
+            if let Some(version) = delta.append(&self.schema(table), &[delta_file]).await? {
+                log::info!(
+                    "Delta table {} version {} appends {}",
+                    name,
+                    version,
+                    file.path
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, rolled_back: &RolledBack) -> Result<()> {
+        for (name, files) in &rolled_back.files {
+            let table = table_schema(name)?;
+            let delta = self.table(&rolled_back.output_dir, name);
+            let paths = files
+                .iter()
+                .map(|x| relative_path(delta.table_root(), &x.path))
+                .collect::<Result<Vec<_>>>()?;
+            let predicate = format!("{} >= {}", table.block_column(), rolled_back.from_block);
+            if let Some(version) = delta.remove(&paths, &predicate).await? {
+                log::info!(
+                    "Delta table {} version {} removes {}",
+                    name,
+                    version,
+                    predicate
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn replace(&self, compacted: &Compacted) -> Result<()> {
+        let table = table_schema(&compacted.table)?;
+        let delta = self.table(&compacted.output_dir, &compacted.table);
+        // Never loaded, the next commit creates the table.
+        if delta.version().await?.is_none() {
+            return Ok(());
+        }
+        let paths = compacted
+            .removed
+            .iter()
+            .map(|x| relative_path(delta.table_root(), x))
+            .collect::<Result<Vec<_>>>()?;
+        let file = self.delta_file(&delta, table, &compacted.added)?;
+        delta.replace(&paths, &file).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for DeltaSink {
+    fn name(&self) -> &str {
+        "delta"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let lease = self.lock(&range.output_dir).await?;
+        let res = self.append(range).await;
+        release(lease).await?;
+        res
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let lease = self.lock(&rolled_back.output_dir).await?;
+        let res = self.remove(rolled_back).await;
+        release(lease).await?;
+        res
+    }
+
+    async fn compacted(&self, compacted: &Compacted) -> Result<()> {
+        let lease = self.lock(&compacted.output_dir).await?;
+        let res = self.replace(compacted).await;
+        release(lease).await?;
+        res
+    }
+}
+
+async fn release(lease: Option<Lease>) -> Result<()> {
+    match lease {
+        Some(lease) => lease.release().await,
+        None => Ok(()),
+    }
+}
+
+// The path in the Delta log, relative to the table root.
+fn relative_path(table_root: &str, path: &str) -> Result<String> {
+    let root = table_root.trim_matches('/');
+    path.trim_start_matches('/')
+        .strip_prefix(root)
+        .and_then(|x| x.strip_prefix('/'))
+        .map(|x| x.to_string())
+        .ok_or_else(|| Error::msg(format!("{} is not under the Delta table {}", path, root)))
+}
@@ -0,0 +1,204 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common_configs::DuckDBSinkConfig;
+use common_configs::HashEncoding;
+use common_configs::SqlDialect;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_exceptions::Retryable;
+use duckdb::Connection;
+use opendal::Operator;
+
+use crate::exporters::ColumnProjection;
+use crate::schemas::table_schema;
+use crate::schemas::DdlGenerator;
+use crate::schemas::REGISTRY;
+use crate::sinks::CommittedRange;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+/// DuckDBSink appends the committed ranges to a local DuckDB database file
+/// with the embedded DuckDB, the files of a range are inserted by
+/// `read_parquet` in one transaction.
+///
+/// The rows of the range are deleted first, a range exported again replaces
+/// its rows. A reorg deletes the rows from the first rolled back block.
+#[derive(Debug)]
+pub struct DuckDBSink {
+    storage: Arc<Operator>,
+    conf: DuckDBSinkConfig,
+    ddl: DdlGenerator,
+    projection: ColumnProjection,
+    // Held while writing, the database has one writer.
+    tables_created: tokio::sync::Mutex<bool>,
+}
+
+impl DuckDBSink {
+    pub fn create(
+        storage: Arc<Operator>,
+        conf: DuckDBSinkConfig,
+        encoding: HashEncoding,
+        projection: ColumnProjection,
+    ) -> Result<Self> {
+        for table in REGISTRY {
+            if !projection.selects(table.name, table.block_column()) {
+                return Err(Error::msg(format!(
+                    "The duckdb sink deletes {} by {}, the column can't be excluded",
+                    table.name,
+                    table.block_column()
+                )));
+            }
+        }
+        Ok(DuckDBSink {
+            storage,
+            conf,
+            ddl: DdlGenerator::create(SqlDialect::DuckDB, encoding, projection.clone()),
+            projection,
+            tables_created: tokio::sync::Mutex::new(false),
+        })
+    }
+
+    /// The schema, the tables, and the macros of the Databend functions the
+    /// examples of `schemas/databend` use.
+    pub fn create_script(&self) -> Result<String> {
+        let schema = &self.conf.schema;
+        let mut sql = self.ddl.create_database(schema);
+        for table in REGISTRY {
+            sql.push('\n');
+            sql.push_str(&self.ddl.create_table(schema, table)?);
+        }
+        sql.push_str(&format!(
+            "\nCREATE OR REPLACE MACRO {}.subtract_hours(ts, n) AS ts - INTERVAL (n) HOUR;\n",
+            schema
+        ));
+        Ok(sql)
+    }
+
+    /// The transaction appending the local parquet files of the range, by
+    /// table.
+    pub fn append_script(
+        &self,
+        range: &CommittedRange,
+        files: &[(String, String)],
+    ) -> Result<String> {
+        let mut sql = "BEGIN TRANSACTION;\n".to_string();
+        for (table, path) in files {
+            let table = table_schema(table)?;
+            let columns = table
+                .columns
+                .iter()
+                .filter(|c| self.projection.selects(table.name, c.name))
+                .map(|c| c.name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            sql.push_str(&format!(
+                "DELETE FROM {}.{} WHERE {} BETWEEN {} AND {};\n",
+                self.conf.schema,
+                table.name,
+                table.block_column(),
+                range.start,
+                range.end
+            ));
+            sql.push_str(&format!(
+                "INSERT INTO {}.{} ({}) SELECT {} FROM read_parquet('{}');\n",
+                self.conf.schema,
+                table.name,
+                columns,
+                columns,
+                path.replace('\'', "''")
+            ));
+        }
+        sql.push_str("COMMIT;\n");
+        Ok(sql)
+    }
+
+    // Run the statements on a connection of their own, the transaction of a
+    // failed statement is rolled back when the connection is dropped.
+    async fn run(&self, sql: &str) -> Result<()> {
+        let path = self.conf.path.clone();
+        let sql = sql.to_string();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(&path)?;
+            conn.execute_batch(&sql)
+                .map_err(|e| Error::msg(format!("DuckDB {} failed: {}", path, e)))
+        })
+        .await?
+    }
+
+    async fn run_with_retry(&self, sql: &str) -> Result<()> {
+        let notify =
+            |e, duration| log::warn!("DuckDB error at duration {:?}, error:{:?}", duration, e);
+        let op = || async {
+            self.run(sql).await?;
+            Ok(())
+        };
+
+        op.retry_with_notify(notify).await
+    }
+}
+
+#[async_trait]
+impl Sink for DuckDBSink {
+    fn name(&self) -> &str {
+        "duckdb"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let mut created = self.tables_created.lock().await;
+
+        // The files may be remote, read_parquet reads local copies.
+        let dir = std::env::temp_dir().join(format!("ethetl-duckdb-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut files = vec![];
+        for (table, file) in &range.files {
+            let data = self.storage.object(&file.path).read().await?;
+            let path = dir.join(format!("{}_{}_{}.parquet", table, range.start, range.end));
+            tokio::fs::write(&path, data).await?;
+            files.push((table.clone(), path.display().to_string()));
+        }
+
+        let mut sql = String::new();
+        if self.conf.create_tables && !*created {
+            sql.push_str(&self.create_script()?);
+        }
+        sql.push_str(&self.append_script(range, &files)?);
+        let res = self.run_with_retry(&sql).await;
+        for (_, path) in &files {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        res?;
+        *created = true;
+        Ok(())
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let _guard = self.tables_created.lock().await;
+        let mut sql = "BEGIN TRANSACTION;\n".to_string();
+        for table in rolled_back.files.keys() {
+            sql.push_str(&format!(
+                "DELETE FROM {}.{} WHERE {} >= {};\n",
+                self.conf.schema,
+                table,
+                table_schema(table)?.block_column(),
+                rolled_back.from_block
+            ));
+        }
+        sql.push_str("COMMIT;\n");
+        self.run_with_retry(&sql).await
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common_configs::KafkaSinkConfig;
+use common_configs::RecordFormat;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::read_parquet;
+use opendal::Operator;
+
+use crate::schemas::table_schema;
+use crate::sinks::json_rows;
+use crate::sinks::text_column;
+use crate::sinks::AvroSchema;
+use crate::sinks::CommittedRange;
+use crate::sinks::KafkaProducer;
+use crate::sinks::KafkaRecord;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+/// The header of the tombstones, the first block rolled back.
+pub const RETRACTED_FROM_HEADER: &str = "ethetl.retracted_from";
+
+/// KafkaSink publishes a record per row of the committed ranges to the topic
+/// `{topic_prefix}.{table}`, keyed by the block number of the blocks and the
+/// transaction hash of the others. The commit returns once the brokers
+/// acknowledged all the records, the syncing checkpoint moves after it.
+///
+/// A rollback publishes a tombstone per key of the rolled back files, with
+/// the first block rolled back in the `ethetl.retracted_from` header, the
+/// rows of the blocks exported again are published again after it.
+#[derive(Debug)]
+pub struct KafkaSink {
+    storage: Arc<Operator>,
+    conf: KafkaSinkConfig,
+    producer: KafkaProducer,
+}
+
+impl KafkaSink {
+    pub fn create(storage: Arc<Operator>, conf: KafkaSinkConfig) -> Result<Self> {
+        for table in &conf.tables {
+            table_schema(table)?
+                .column(key_column(table))
+                .ok_or_else(|| Error::msg(format!("Table {} has no key column", table)))?;
+        }
+        Ok(KafkaSink {
+            storage,
+            producer: KafkaProducer::create(&conf)?,
+            conf,
+        })
+    }
+
+    pub fn topic(&self, table: &str) -> String {
+        format!("{}.{}", self.conf.topic_prefix, table)
+    }
+
+    /// The records of the rows of a file, the tombstones of the keys if retracted.
+    pub async fn records(
+        &self,
+        table: &str,
+        path: &str,
+        retracted_from: Option<u64>,
+    ) -> Result<Vec<KafkaRecord>> {
+        let topic = self.topic(table);
+        let (schema, chunks) = read_parquet(self.storage.clone(), path).await?;
+        let mut records = vec![];
+        let mut retracted = HashSet::new();
+        for chunk in &chunks {
+            let keys = text_column(&schema, chunk, key_column(table))?;
+            if let Some(from_block) = retracted_from {
+                for key in keys {
+                    if retracted.insert(key.clone()) {
+                        records.push(KafkaRecord {
+                            topic: topic.clone(),
+                            key: Some(key.into_bytes()),
+                            value: None,
+                            headers: vec![(
+                                RETRACTED_FROM_HEADER.to_string(),
+                                from_block.to_string().into_bytes(),
+                            )],
+                        });
+                    }
+                }
+                continue;
+            }
+
+            let values = match self.conf.format {
+                RecordFormat::Json => json_rows(&schema, chunk)?
+                    .iter()
+                    .map(serde_json::to_vec)
+                    .collect::<serde_json::Result<Vec<_>>>()?,
+                RecordFormat::Avro => {
+                    AvroSchema::create(table, &schema, chunk)?.encode(&schema, chunk)?
+                }
+            };
+            for (key, value) in keys.into_iter().zip(values) {
+                records.push(KafkaRecord {
+                    topic: topic.clone(),
+                    key: Some(key.into_bytes()),
+                    value: Some(value),
+                    headers: vec![],
+                });
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[async_trait]
+impl Sink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let mut records = vec![];
+        for table in &self.conf.tables {
+            if let Some(file) = range.files.get(table) {
+                records.extend(self.records(table, &file.path, None).await?);
+            }
+        }
+        log::info!(
+            "Publish {} records of blocks {}-{} to kafka",
+            records.len(),
+            range.start,
+            range.end
+        );
+        self.producer.send(&records).await
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let mut records = vec![];
+        for table in &self.conf.tables {
+            for file in rolled_back.files.get(table).into_iter().flatten() {
+                let from_block = Some(rolled_back.from_block);
+                records.extend(self.records(table, &file.path, from_block).await?);
+            }
+        }
+        log::info!(
+            "Publish {} tombstones from block {} to kafka",
+            records.len(),
+            rolled_back.from_block
+        );
+        self.producer.send(&records).await
+    }
+}
+
+/// The key column of the records of a table.
+pub fn key_column(table: &str) -> &'static str {
+    match table {
+        "blocks" => "number",
+        "transactions" => "hash",
+        _ => "transaction_hash",
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::time::Duration;
+
+use common_configs::KafkaSinkConfig;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::error::RDKafkaErrorCode;
+use rdkafka::message::OwnedHeaders;
+use rdkafka::producer::FutureProducer;
+use rdkafka::producer::FutureRecord;
+
+/// A record to publish, a null value is a tombstone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KafkaRecord {
+    pub topic: String,
+    pub key: Option<Vec<u8>>,
+    pub value: Option<Vec<u8>>,
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+/// KafkaProducer publishes the records with librdkafka, idempotent and
+/// acknowledged by all the in-sync replicas.
+///
+/// The records are partitioned by the murmur2 hash of the key as the Java
+/// client. The idempotent producer retries a failed request, e.g. after a
+/// leader change, without duplicates or reordering, so the records of a key
+/// keep their order. A record not delivered within the delivery timeout
+/// fails the send, the records may be published again by the next one.
+pub struct KafkaProducer {
+    producer: FutureProducer,
+    brokers: String,
+}
+
+impl KafkaProducer {
+    pub fn create(conf: &KafkaSinkConfig) -> Result<Self> {
+        if conf.brokers.is_empty() {
+            return Err(Error::msg("The kafka sink has no brokers"));
+        }
+        Ok(KafkaProducer {
+            producer: Self::client_config(conf).create()?,
+            brokers: conf.brokers.join(","),
+        })
+    }
+
+    /// The librdkafka properties of the config, the `properties` of the
+    /// config set last.
+    pub fn client_config(conf: &KafkaSinkConfig) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", conf.brokers.join(","))
+            .set("client.id", &conf.client_id)
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            .set("partitioner", "murmur2_random")
+            .set("compression.type", &conf.compression)
+            .set("batch.size", conf.batch_bytes.to_string())
+            .set("request.timeout.ms", conf.ack_timeout_ms.to_string())
+            .set("message.timeout.ms", conf.delivery_timeout_ms.to_string())
+            .set("security.protocol", &conf.security_protocol);
+        for (key, value) in [
+            ("sasl.mechanism", &conf.sasl_mechanism),
+            ("sasl.username", &conf.sasl_username),
+            ("sasl.password", &conf.sasl_password),
+            ("ssl.ca.location", &conf.ssl_ca_location),
+        ] {
+            if !value.is_empty() {
+                config.set(key, value);
+            }
+        }
+        for (key, value) in &conf.properties {
+            config.set(key, value);
+        }
+        config
+    }
+
+    /// Publish the records in order, returns once the brokers acknowledged
+    /// all of them.
+    pub async fn send(&self, records: &[KafkaRecord]) -> Result<()> {
+        let mut deliveries = Vec::with_capacity(records.len());
+        for record in records {
+            let mut headers = OwnedHeaders::new();
+            for (name, value) in &record.headers {
+                headers = headers.add(name.as_str(), value.as_slice());
+            }
+            let mut future = FutureRecord::<[u8], [u8]>::to(&record.topic).headers(headers);
+            if let Some(key) = &record.key {
+                future = future.key(key.as_slice());
+            }
+            if let Some(value) = &record.value {
+                future = future.payload(value.as_slice());
+            }
+
+            // Enqueued in order, waits for the queue to drain when it is full.
+            loop {
+                match self.producer.send_result(future) {
+                    Ok(delivery) => {
+                        deliveries.push(delivery);
+                        break;
+                    }
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), back)) => {
+                        future = back;
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                    Err((e, _)) => return Err(e.into()),
+                }
+            }
+        }
+
+        for delivery in deliveries {
+            match delivery.await {
+                Ok(Ok(_)) => {}
+                Ok(Err((e, _))) => {
+                    return Err(Error::msg(format!(
+                        "Kafka delivery to {} failed: {}",
+                        self.brokers, e
+                    )));
+                }
+                Err(_) => {
+                    return Err(Error::msg(format!(
+                        "Kafka delivery to {} is canceled",
+                        self.brokers
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for KafkaProducer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KafkaProducer")
+            .field("brokers", &self.brokers)
+            .finish()
+    }
+}
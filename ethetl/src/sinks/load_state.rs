@@ -0,0 +1,138 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::write_atomic;
+use opendal::ErrorKind;
+use opendal::Operator;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::manifest::ManifestFile;
+use crate::manifest::TableManifest;
+
+/// The manifest seqs of a table loaded by a sink.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadedSeqs {
+    // All the seqs up to it are loaded or no longer listed by the manifest.
+    pub through: u64,
+    // The seqs loaded above `through`, the ranges are committed out of order.
+    pub above: BTreeSet<u64>,
+}
+
+/// LoadState is the bookkeeping of a sink loading the table manifests,
+/// stored at `{output_dir}/_sinks/{sink}.json`.
+///
+/// A file is marked after it is loaded, a file loaded but not marked before
+/// a crash is loaded again, at least once.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadState {
+    pub tables: BTreeMap<String, LoadedSeqs>,
+}
+
+impl LoadState {
+    pub fn path(output_dir: &str, sink: &str) -> String {
+        format!("{}/_sinks/{}.json", output_dir, sink)
+    }
+
+    // Read the state, empty if none.
+    pub async fn read(op: Arc<Operator>, output_dir: &str, sink: &str) -> Result<Self> {
+        match op.object(&Self::path(output_dir, sink)).read().await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == ErrorKind::ObjectNotFound => Ok(LoadState::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn write(&self, op: Arc<Operator>, output_dir: &str, sink: &str) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        write_atomic(op, &Self::path(output_dir, sink), data).await
+    }
+
+    pub fn contains(&self, table: &str, seq: u64) -> bool {
+        self.tables
+            .get(table)
+            .map(|x| seq <= x.through || x.above.contains(&seq))
+            .unwrap_or(false)
+    }
+
+    pub fn insert(&mut self, table: &str, seq: u64) {
+        let seqs = self.tables.entry(table.to_string()).or_default();
+        if seq > seqs.through {
+            seqs.above.insert(seq);
+        }
+        while seqs.above.remove(&(seqs.through + 1)) {
+            seqs.through += 1;
+        }
+    }
+
+    /// The files of the manifest not loaded yet, in seq order. A merged file
+    /// of loaded files is marked loaded, it is an error if only some of them
+    /// are, the sink has to reload the range.
+    pub fn pending<'a>(&mut self, manifest: &'a TableManifest) -> Result<Vec<&'a ManifestFile>> {
+        let table = manifest.table.as_str();
+        self.skip_unlisted(manifest);
+
+        let mut files = vec![];
+        for file in &manifest.files {
+            if self.contains(table, file.seq) {
+                continue;
+            }
+            let loaded = file
+                .source_seqs
+                .iter()
+                .filter(|x| self.contains(table, **x))
+                .count();
+            if loaded == 0 {
+                files.push(file);
+            } else if loaded == file.source_seqs.len() {
+                self.insert(table, file.seq);
+            } else {
+                return Err(Error::msg(format!(
+                    "{} merges files both loaded and not loaded, reload blocks {}-{}",
+                    file.path, file.start, file.end
+                )));
+            }
+        }
+        files.sort_by_key(|x| x.seq);
+        Ok(files)
+    }
+
+    // Move `through` past the seqs the manifest no longer lists, the files
+    // rolled back by a reorg and the merged ones which are not the source of
+    // a listed file. The seqs are not reused, so they never come back and
+    // `above` doesn't grow over the holes.
+    fn skip_unlisted(&mut self, manifest: &TableManifest) {
+        let listed = manifest
+            .files
+            .iter()
+            .flat_map(|x| std::iter::once(x.seq).chain(x.source_seqs.iter().copied()))
+            .collect::<BTreeSet<_>>();
+        let seqs = self.tables.entry(manifest.table.clone()).or_default();
+        while seqs.through < manifest.last_seq {
+            let next = seqs.through + 1;
+            if !seqs.above.remove(&next) && listed.contains(&next) {
+                break;
+            }
+            seqs.through = next;
+        }
+    }
+}
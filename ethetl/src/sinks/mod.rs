@@ -0,0 +1,214 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod clickhouse;
+mod databend;
+mod delta;
+#[cfg(feature = "duckdb")]
+mod duckdb;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "kafka")]
+mod kafka_producer;
+mod load_state;
+mod postgres;
+mod records;
+mod stdout;
+mod webhook;
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+pub use clickhouse::ClickHouseClient;
+pub use clickhouse::ClickHouseSink;
+use common_configs::EthConfig;
+use common_configs::TableFormat;
+use common_exceptions::Result;
+pub use databend::DatabendClient;
+pub use databend::DatabendSink;
+pub use delta::DeltaSink;
+#[cfg(feature = "duckdb")]
+pub use duckdb::DuckDBSink;
+#[cfg(feature = "kafka")]
+pub use kafka::key_column;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+#[cfg(feature = "kafka")]
+pub use kafka::RETRACTED_FROM_HEADER;
+#[cfg(feature = "kafka")]
+pub use kafka_producer::KafkaProducer;
+#[cfg(feature = "kafka")]
+pub use kafka_producer::KafkaRecord;
+pub use load_state::LoadState;
+pub use load_state::LoadedSeqs;
+use opendal::Operator;
+pub use postgres::connect_postgres;
+pub use postgres::copy_binary;
+pub use postgres::insert_values;
+pub use postgres::numeric;
+pub use postgres::upsert_sql;
+pub use postgres::PostgresSink;
+pub use records::arrow_stream;
+pub use records::avro_fingerprint;
+pub use records::json_rows;
+pub use records::text_column;
+pub use records::AvroSchema;
+pub use stdout::StdoutSink;
+pub use stdout::TABLE_METADATA_KEY;
+pub use webhook::compare_decimal;
+pub use webhook::sign;
+pub use webhook::WebhookCursor;
+pub use webhook::WebhookSink;
+pub use webhook::SIGNATURE_HEADER;
+
+use crate::exporters::ColumnProjection;
+use crate::exporters::PathTemplate;
+use crate::manifest::ManifestFile;
+
+/// The files of a committed range, by table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommittedRange {
+    pub output_dir: String,
+    pub start: u64,
+    pub end: u64,
+    pub files: BTreeMap<String, ManifestFile>,
+}
+
+/// The files rolled back by a chain reorg, by table. The blocks from
+/// `from_block` are exported again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RolledBack {
+    pub output_dir: String,
+    pub from_block: u64,
+    pub files: BTreeMap<String, Vec<ManifestFile>>,
+}
+
+/// The files merged by compaction into the added one of the same rows. The
+/// removed files are deleted after the sinks are called.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compacted {
+    pub output_dir: String,
+    pub table: String,
+    pub removed: Vec<String>,
+    pub added: ManifestFile,
+}
+
+/// A Sink loads the committed ranges somewhere else.
+///
+/// A range is passed again if its export is retried, a sink loads it at
+/// least once.
+#[async_trait]
+pub trait Sink: Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Called after the range commit marker is written.
+    async fn commit(&self, range: &CommittedRange) -> Result<()>;
+
+    /// Called before the rolled back files are deleted, the files are
+    /// still readable.
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()>;
+
+    /// Called before the merged files are deleted, the sinks loading the
+    /// rows have nothing to do.
+    async fn compacted(&self, _compacted: &Compacted) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub type SinkRef = Arc<dyn Sink>;
+
+// The error of a config section whose sink is not built in.
+#[cfg(not(all(feature = "kafka", feature = "duckdb")))]
+fn feature_error(feature: &str, section: &str) -> common_exceptions::Error {
+    common_exceptions::Error::msg(format!(
+        "{} needs ethetl built with `--features {}`",
+        section, feature
+    ))
+}
+
+/// The sinks of the config, the Kafka and DuckDB sinks are built with the
+/// cargo features of the same name.
+pub fn create_sinks(
+    conf: &EthConfig,
+    storage: Arc<Operator>,
+    path_template: &PathTemplate,
+) -> Result<Vec<SinkRef>> {
+    let mut sinks: Vec<SinkRef> = vec![];
+    if conf.export.table_format == TableFormat::Delta {
+        sinks.push(Arc::new(DeltaSink::create(
+            storage.clone(),
+            path_template,
+            conf.export.chain_id,
+            conf.export.hash_encoding,
+            ColumnProjection::create(conf.export.columns.clone()),
+        )?));
+    }
+    if let Some(clickhouse) = &conf.sinks.clickhouse {
+        sinks.push(Arc::new(ClickHouseSink::create(
+            storage.clone(),
+            clickhouse.clone(),
+            conf.export.hash_encoding,
+            ColumnProjection::create(conf.export.columns.clone()),
+        )?));
+    }
+    if let Some(databend) = &conf.sinks.databend {
+        sinks.push(Arc::new(DatabendSink::create(
+            storage.clone(),
+            databend.clone(),
+            conf.export.hash_encoding,
+            ColumnProjection::create(conf.export.columns.clone()),
+        )?));
+    }
+    #[cfg(feature = "duckdb")]
+    if let Some(duckdb) = &conf.sinks.duckdb {
+        sinks.push(Arc::new(DuckDBSink::create(
+            storage.clone(),
+            duckdb.clone(),
+            conf.export.hash_encoding,
+            ColumnProjection::create(conf.export.columns.clone()),
+        )?));
+    }
+    #[cfg(not(feature = "duckdb"))]
+    if conf.sinks.duckdb.is_some() {
+        return Err(feature_error("duckdb", "[sinks.duckdb]"));
+    }
+    #[cfg(feature = "kafka")]
+    if let Some(kafka) = &conf.sinks.kafka {
+        sinks.push(Arc::new(KafkaSink::create(storage.clone(), kafka.clone())?));
+    }
+    #[cfg(not(feature = "kafka"))]
+    if conf.sinks.kafka.is_some() {
+        return Err(feature_error("kafka", "[sinks.kafka]"));
+    }
+    if let Some(postgres) = &conf.sinks.postgres {
+        sinks.push(Arc::new(PostgresSink::create(
+            storage.clone(),
+            postgres.clone(),
+            conf.export.hash_encoding,
+            ColumnProjection::create(conf.export.columns.clone()),
+        )?));
+    }
+    if let Some(stdout) = &conf.sinks.stdout {
+        sinks.push(Arc::new(StdoutSink::create(
+            storage.clone(),
+            stdout.clone(),
+        )?));
+    }
+    if let Some(webhook) = &conf.sinks.webhook {
+        sinks.push(Arc::new(WebhookSink::create(storage, webhook.clone())?));
+    }
+    Ok(sinks)
+}
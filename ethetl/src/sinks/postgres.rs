@@ -0,0 +1,439 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+
+use arrow2::array::Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Schema;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+use common_configs::HashEncoding;
+use common_configs::PostgresSinkConfig;
+use common_configs::PostgresWriteMethod;
+use common_configs::SqlDialect;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_exceptions::Retryable;
+use common_storages::read_parquet;
+use futures::SinkExt;
+use opendal::Operator;
+use tokio_postgres::Client;
+use tokio_postgres::NoTls;
+use tokio_postgres::Transaction;
+
+use crate::exporters::ColumnProjection;
+use crate::schemas::table_schema;
+use crate::schemas::DdlGenerator;
+use crate::schemas::TableSchema;
+use crate::schemas::REGISTRY;
+use crate::sinks::records::columns;
+use crate::sinks::records::decimal_to_string;
+use crate::sinks::records::Column;
+use crate::sinks::CommittedRange;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+// The seconds from the unix epoch to the PostgreSQL epoch, 2000-01-01.
+const POSTGRES_EPOCH_SECS: i64 = 946_684_800;
+
+#[derive(Default)]
+struct PostgresState {
+    conn: Option<Client>,
+    tables_created: bool,
+}
+
+impl fmt::Debug for PostgresState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PostgresState")
+            .field("connected", &self.conn.is_some())
+            .field("tables_created", &self.tables_created)
+            .finish()
+    }
+}
+
+/// Connect to PostgreSQL with tokio-postgres, the timestamps are UTC.
+pub async fn connect_postgres(conf: &PostgresSinkConfig) -> Result<Client> {
+    let mut config = tokio_postgres::Config::new();
+    config
+        .host(&conf.host)
+        .port(conf.port)
+        .user(&conf.user)
+        .password(&conf.password)
+        .dbname(&conf.database)
+        .application_name("ethetl")
+        .options("-c TimeZone=UTC");
+    let (client, connection) = config.connect(NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::warn!("Postgres connection error: {:?}", e);
+        }
+    });
+    Ok(client)
+}
+
+/// PostgresSink upserts the rows of a committed range in one transaction.
+///
+/// The chunks of each table are written to a temporary table by `COPY` of
+/// the binary format or by batched `INSERT`s, then inserted into the table
+/// with `ON CONFLICT` on the unique key of the registry, a range exported
+/// again updates its rows. A reorg deletes the rows from the first rolled
+/// back block.
+#[derive(Debug)]
+pub struct PostgresSink {
+    storage: Arc<Operator>,
+    conf: PostgresSinkConfig,
+    ddl: DdlGenerator,
+    // Held while writing.
+    state: tokio::sync::Mutex<PostgresState>,
+}
+
+impl PostgresSink {
+    pub fn create(
+        storage: Arc<Operator>,
+        conf: PostgresSinkConfig,
+        encoding: HashEncoding,
+        projection: ColumnProjection,
+    ) -> Result<Self> {
+        for table in REGISTRY {
+            for key in table.unique_key {
+                if !projection.selects(table.name, key) {
+                    return Err(Error::msg(format!(
+                        "The postgres sink upserts {} on {}, the column can't be excluded",
+                        table.name, key
+                    )));
+                }
+            }
+        }
+        Ok(PostgresSink {
+            storage,
+            conf,
+            ddl: DdlGenerator::create(SqlDialect::PostgreSQL, encoding, projection),
+            state: tokio::sync::Mutex::new(PostgresState::default()),
+        })
+    }
+
+    async fn connection<'a>(&self, state: &'a mut PostgresState) -> Result<&'a mut Client> {
+        if state.conn.as_ref().map_or(true, |x| x.is_closed()) {
+            state.conn = Some(connect_postgres(&self.conf).await?);
+        }
+        let conn = state.conn.as_mut().unwrap();
+        if self.conf.create_tables && !state.tables_created {
+            conn.batch_execute(&self.ddl.create_database(&self.conf.schema))
+                .await?;
+            for table in REGISTRY {
+                let mut sql = self.ddl.create_table(&self.conf.schema, table)?;
+                if let Some(index) = self.ddl.create_unique_key(&self.conf.schema, table) {
+                    sql.push('\n');
+                    sql.push_str(&index);
+                }
+                conn.batch_execute(&sql).await?;
+            }
+            state.tables_created = true;
+        }
+        Ok(conn)
+    }
+
+    async fn commit_with_no_retry(&self, range: &CommittedRange) -> Result<()> {
+        // Read before the transaction is open.
+        let mut tables = vec![];
+        for (table, file) in &range.files {
+            let (schema, chunks) = read_parquet(self.storage.clone(), &file.path).await?;
+            tables.push((table_schema(table)?, schema, chunks));
+        }
+
+        // A failed connection is dropped, the server rolls back the transaction.
+        let mut state = self.state.lock().await;
+        let res = async {
+            let conn = self.connection(&mut state).await?;
+            let tx = conn.transaction().await?;
+            for (table, schema, chunks) in &tables {
+                self.write_table(&tx, table, schema, chunks).await?;
+            }
+            tx.commit().await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+        if res.is_err() {
+            state.conn = None;
+        }
+        res
+    }
+
+    async fn write_table(
+        &self,
+        tx: &Transaction<'_>,
+        table: &TableSchema,
+        schema: &Schema,
+        chunks: &[Chunk<Box<dyn Array>>],
+    ) -> Result<()> {
+        let target = format!("{}.{}", self.conf.schema, table.name);
+        let stage = format!("_ethetl_{}", table.name);
+        let names = schema
+            .fields
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        let column_list = names.join(", ");
+
+        tx.batch_execute(&format!(
+            "CREATE TEMP TABLE {} (LIKE {}) ON COMMIT DROP",
+            stage, target
+        ))
+        .await?;
+        for chunk in chunks {
+            match self.conf.method {
+                PostgresWriteMethod::Copy => {
+                    let sql = format!(
+                        "COPY {} ({}) FROM STDIN (FORMAT binary)",
+                        stage, column_list
+                    );
+                    let sink = tx.copy_in::<_, Bytes>(sql.as_str()).await?;
+                    futures::pin_mut!(sink);
+                    sink.send(Bytes::from(copy_binary(schema, chunk)?)).await?;
+                    sink.finish().await?;
+                }
+                PostgresWriteMethod::Insert => {
+                    let rows = insert_values(schema, chunk)?;
+                    for batch in rows.chunks(self.conf.batch_rows.max(1)) {
+                        let sql = format!(
+                            "INSERT INTO {} ({}) VALUES {}",
+                            stage,
+                            column_list,
+                            batch.join(", ")
+                        );
+                        tx.batch_execute(&sql).await?;
+                    }
+                }
+            }
+        }
+
+        tx.batch_execute(&upsert_sql(&target, &stage, &names, table.unique_key))
+            .await?;
+        Ok(())
+    }
+
+    async fn rollback_with_no_retry(&self, rolled_back: &RolledBack) -> Result<()> {
+        let mut sql = String::new();
+        for table in rolled_back.files.keys() {
+            sql.push_str(&format!(
+                "DELETE FROM {}.{} WHERE {} >= {};\n",
+                self.conf.schema,
+                table,
+                table_schema(table)?.block_column(),
+                rolled_back.from_block
+            ));
+        }
+
+        // A failed connection is dropped, the server rolls back the transaction.
+        let mut state = self.state.lock().await;
+        let res = async {
+            let conn = self.connection(&mut state).await?;
+            let tx = conn.transaction().await?;
+            tx.batch_execute(&sql).await?;
+            tx.commit().await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+        if res.is_err() {
+            state.conn = None;
+        }
+        res
+    }
+}
+
+#[async_trait]
+impl Sink for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let notify = |e, duration| {
+            log::warn!(
+                "Postgres commit error at duration {:?}, error:{:?}",
+                duration,
+                e
+            )
+        };
+        let op = || async {
+            self.commit_with_no_retry(range).await?;
+            Ok(())
+        };
+
+        op.retry_with_notify(notify).await
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let notify = |e, duration| {
+            log::warn!(
+                "Postgres rollback error at duration {:?}, error:{:?}",
+                duration,
+                e
+            )
+        };
+        let op = || async {
+            self.rollback_with_no_retry(rolled_back).await?;
+            Ok(())
+        };
+
+        op.retry_with_notify(notify).await
+    }
+}
+
+/// Insert the rows of the stage into the target, the other columns of a
+/// conflicting row are updated. The rows of the same key in the stage are
+/// inserted once, `ON CONFLICT` can't update a row twice.
+pub fn upsert_sql(target: &str, stage: &str, columns: &[&str], keys: &[&str]) -> String {
+    let column_list = columns.join(", ");
+    let keys = keys.join(", ");
+    let updates = columns
+        .iter()
+        .filter(|c| !keys.split(", ").any(|k| k == **c))
+        .map(|c| format!("{} = EXCLUDED.{}", c, c))
+        .collect::<Vec<_>>();
+    let action = if updates.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        format!("DO UPDATE SET {}", updates.join(", "))
+    };
+    format!(
+        "INSERT INTO {} ({}) SELECT DISTINCT ON ({}) {} FROM {} ON CONFLICT ({}) {};",
+        target, column_list, keys, column_list, stage, keys, action
+    )
+}
+
+/// The rows of the chunk in the binary `COPY` format, the UInt64 as
+/// NUMERIC(20, 0) since they may overflow BIGINT, the Int64 as BIGINT, the
+/// timestamps as TIMESTAMP, the decimals as NUMERIC, the strings as TEXT and
+/// the binaries as BYTEA.
+pub fn copy_binary(schema: &Schema, chunk: &Chunk<Box<dyn Array>>) -> Result<Vec<u8>> {
+    let columns = columns(schema, chunk)?;
+    let mut buf = b"PGCOPY\n\xff\r\n\0".to_vec();
+    // The flags and the header extension.
+    buf.extend(0i32.to_be_bytes());
+    buf.extend(0i32.to_be_bytes());
+    for i in 0..chunk.len() {
+        buf.extend((columns.len() as i16).to_be_bytes());
+        for (array, column) in chunk.arrays().iter().zip(&columns) {
+            if array.is_null(i) {
+                buf.extend((-1i32).to_be_bytes());
+                continue;
+            }
+            let value = match column {
+                Column::UInt64(x) => numeric(x.value(i) as i128, 0),
+                Column::Int64(x) => x.value(i).to_be_bytes().to_vec(),
+                Column::Timestamp(x) => ((x.value(i) - POSTGRES_EPOCH_SECS) * 1_000_000)
+                    .to_be_bytes()
+                    .to_vec(),
+                Column::Decimal(x, scale) => numeric(x.value(i), *scale),
+                Column::Utf8(x) => x.value(i).as_bytes().to_vec(),
+                Column::FixedSizeBinary(x) => x.value(i).to_vec(),
+                Column::Binary(x) => x.value(i).to_vec(),
+            };
+            buf.extend((value.len() as i32).to_be_bytes());
+            buf.extend(value);
+        }
+    }
+    buf.extend((-1i16).to_be_bytes());
+    Ok(buf)
+}
+
+/// The rows of the chunk as the `(...)` tuples of an `INSERT`.
+pub fn insert_values(schema: &Schema, chunk: &Chunk<Box<dyn Array>>) -> Result<Vec<String>> {
+    let columns = columns(schema, chunk)?;
+    let mut rows = Vec::with_capacity(chunk.len());
+    for i in 0..chunk.len() {
+        let mut values = Vec::with_capacity(columns.len());
+        for (array, column) in chunk.arrays().iter().zip(&columns) {
+            if array.is_null(i) {
+                values.push("NULL".to_string());
+                continue;
+            }
+            values.push(match column {
+                Column::UInt64(x) => x.value(i).to_string(),
+                Column::Int64(x) => x.value(i).to_string(),
+                Column::Timestamp(x) => {
+                    let datetime = NaiveDateTime::from_timestamp_opt(x.value(i), 0)
+                        .ok_or_else(|| Error::msg(format!("Invalid timestamp {}", x.value(i))))?;
+                    format!("'{}'", datetime.format("%Y-%m-%d %H:%M:%S"))
+                }
+                Column::Decimal(x, scale) => decimal_to_string(x.value(i), *scale),
+                Column::Utf8(x) => format!("'{}'", x.value(i).replace('\'', "''")),
+                Column::FixedSizeBinary(x) => bytea(x.value(i)),
+                Column::Binary(x) => bytea(x.value(i)),
+            });
+        }
+        rows.push(format!("({})", values.join(", ")));
+    }
+    Ok(rows)
+}
+
+fn bytea(value: &[u8]) -> String {
+    let hex = value
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("'\\x{}'", hex)
+}
+
+/// The binary NUMERIC: the number of digits, the weight of the first digit,
+/// the sign and the display scale, then the base 10000 digits.
+pub fn numeric(value: i128, scale: usize) -> Vec<u8> {
+    let abs = value.unsigned_abs().to_string();
+    let abs = format!("{:0>width$}", abs, width = scale + 1);
+    let (int, frac) = abs.split_at(abs.len() - scale);
+
+    let int = int.trim_start_matches('0');
+    let int = format!("{:0>width$}", int, width = (int.len() + 3) / 4 * 4);
+    let frac = format!("{:0<width$}", frac, width = (frac.len() + 3) / 4 * 4);
+    let group = |s: &str| {
+        s.as_bytes()
+            .chunks(4)
+            .map(|x| std::str::from_utf8(x).unwrap().parse::<i16>().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let mut weight = int.len() as i16 / 4 - 1;
+    let mut digits = group(&int);
+    digits.extend(group(&frac));
+
+    // No leading and trailing zero digits.
+    while digits.first() == Some(&0) {
+        digits.remove(0);
+        weight -= 1;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    if digits.is_empty() {
+        weight = 0;
+    }
+    let sign: u16 = if value < 0 && !digits.is_empty() {
+        0x4000
+    } else {
+        0
+    };
+
+    let mut buf = vec![];
+    buf.extend((digits.len() as i16).to_be_bytes());
+    buf.extend(weight.to_be_bytes());
+    buf.extend(sign.to_be_bytes());
+    buf.extend((scale as i16).to_be_bytes());
+    for digit in digits {
+        buf.extend(digit.to_be_bytes());
+    }
+    buf
+}
@@ -0,0 +1,280 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow2::array::Array;
+use arrow2::array::BinaryArray;
+use arrow2::array::FixedSizeBinaryArray;
+use arrow2::array::PrimitiveArray;
+use arrow2::array::Utf8Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Schema;
+use arrow2::io::ipc::write::StreamWriter;
+use arrow2::io::ipc::write::WriteOptions;
+use common_eth::bytes_to_hex;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use serde_json::Map;
+use serde_json::Value;
+use web3::types::Bytes;
+
+// The columns of the exported types.
+pub(crate) enum Column<'a> {
+    UInt64(&'a PrimitiveArray<u64>),
+    Int64(&'a PrimitiveArray<i64>),
+    // The timestamps in seconds.
+    Timestamp(&'a PrimitiveArray<i64>),
+    Decimal(&'a PrimitiveArray<i128>, usize),
+    Utf8(&'a Utf8Array<i32>),
+    FixedSizeBinary(&'a FixedSizeBinaryArray),
+    Binary(&'a BinaryArray<i32>),
+}
+
+impl<'a> Column<'a> {
+    pub(crate) fn create(name: &str, array: &'a dyn Array) -> Result<Self> {
+        let any = array.as_any();
+        let column = match array.data_type() {
+            DataType::UInt64 => any.downcast_ref().map(Column::UInt64),
+            DataType::Int64 => any.downcast_ref().map(Column::Int64),
+            DataType::Timestamp(_, _) => any.downcast_ref().map(Column::Timestamp),
+            DataType::Decimal(_, scale) => any.downcast_ref().map(|x| Column::Decimal(x, *scale)),
+            DataType::Utf8 => any.downcast_ref().map(Column::Utf8),
+            DataType::FixedSizeBinary(_) => any.downcast_ref().map(Column::FixedSizeBinary),
+            DataType::Binary => any.downcast_ref().map(Column::Binary),
+            _ => None,
+        };
+        column.ok_or_else(|| {
+            Error::msg(format!(
+                "Column {} of {:?} can't be encoded",
+                name,
+                array.data_type()
+            ))
+        })
+    }
+
+    fn avro_type(&self) -> &'static str {
+        match self {
+            Column::UInt64(_) | Column::Int64(_) | Column::Timestamp(_) => "long",
+            Column::Decimal(_, _) | Column::Utf8(_) => "string",
+            Column::FixedSizeBinary(_) | Column::Binary(_) => "bytes",
+        }
+    }
+
+    // The hashes and binaries as `0x` hex, the decimals as strings.
+    fn json(&self, i: usize) -> Value {
+        match self {
+            Column::UInt64(x) => Value::from(x.value(i)),
+            Column::Int64(x) | Column::Timestamp(x) => Value::from(x.value(i)),
+            Column::Decimal(x, scale) => Value::from(decimal_to_string(x.value(i), *scale)),
+            Column::Utf8(x) => Value::from(x.value(i)),
+            Column::FixedSizeBinary(x) => Value::from(bytes_to_hex(&Bytes(x.value(i).to_vec()))),
+            Column::Binary(x) => Value::from(bytes_to_hex(&Bytes(x.value(i).to_vec()))),
+        }
+    }
+
+    fn write_avro(&self, i: usize, buf: &mut Vec<u8>) {
+        match self {
+            Column::UInt64(x) => write_avro_long(x.value(i) as i64, buf),
+            Column::Int64(x) | Column::Timestamp(x) => write_avro_long(x.value(i), buf),
+            Column::Decimal(x, scale) => {
+                write_avro_bytes(decimal_to_string(x.value(i), *scale).as_bytes(), buf)
+            }
+            Column::Utf8(x) => write_avro_bytes(x.value(i).as_bytes(), buf),
+            Column::FixedSizeBinary(x) => write_avro_bytes(x.value(i), buf),
+            Column::Binary(x) => write_avro_bytes(x.value(i), buf),
+        }
+    }
+}
+
+pub(crate) fn columns<'a>(
+    schema: &Schema,
+    chunk: &'a Chunk<Box<dyn Array>>,
+) -> Result<Vec<Column<'a>>> {
+    schema
+        .fields
+        .iter()
+        .zip(chunk.arrays())
+        .map(|(field, array)| Column::create(&field.name, array.as_ref()))
+        .collect()
+}
+
+/// The rows of the chunk as JSON objects by column name, null for the nulls.
+pub fn json_rows(
+    schema: &Schema,
+    chunk: &Chunk<Box<dyn Array>>,
+) -> Result<Vec<Map<String, Value>>> {
+    let columns = columns(schema, chunk)?;
+    let mut rows = Vec::with_capacity(chunk.len());
+    for i in 0..chunk.len() {
+        let mut row = Map::new();
+        for ((field, array), column) in schema.fields.iter().zip(chunk.arrays()).zip(&columns) {
+            let value = if array.is_null(i) {
+                Value::Null
+            } else {
+                column.json(i)
+            };
+            row.insert(field.name.clone(), value);
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// The values of a column as strings, the numbers in decimal.
+pub fn text_column(
+    schema: &Schema,
+    chunk: &Chunk<Box<dyn Array>>,
+    name: &str,
+) -> Result<Vec<String>> {
+    let index = schema
+        .fields
+        .iter()
+        .position(|f| f.name == name)
+        .ok_or_else(|| Error::msg(format!("Column {} not found", name)))?;
+    let array = chunk.arrays()[index].as_ref();
+    let column = Column::create(name, array)?;
+    Ok((0..chunk.len())
+        .map(|i| match column.json(i) {
+            Value::String(x) => x,
+            x => x.to_string(),
+        })
+        .collect())
+}
+
+/// The chunks as an Arrow IPC stream, uncompressed.
+pub fn arrow_stream(schema: &Schema, chunks: &[Chunk<Box<dyn Array>>]) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut writer = StreamWriter::new(&mut buf, WriteOptions { compression: None });
+    writer.start(schema, None)?;
+    for chunk in chunks {
+        writer.write(chunk, None)?;
+    }
+    writer.finish()?;
+    Ok(buf)
+}
+
+/// The Avro record schema of a table, the rows are written with the single
+/// object encoding: `C3 01`, the little endian CRC-64-AVRO fingerprint of
+/// the canonical schema, then the record.
+///
+/// The nullable columns are unions of null and the type, the decimals are
+/// strings and the hashes are bytes in the binary hash encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvroSchema {
+    // The parsing canonical form.
+    pub canonical: String,
+    pub fingerprint: u64,
+}
+
+impl AvroSchema {
+    pub fn create(table: &str, schema: &Schema, chunk: &Chunk<Box<dyn Array>>) -> Result<Self> {
+        let columns = columns(schema, chunk)?;
+        let fields = schema
+            .fields
+            .iter()
+            .zip(&columns)
+            .map(|(field, column)| {
+                let data_type = if field.is_nullable {
+                    format!("[\"null\",\"{}\"]", column.avro_type())
+                } else {
+                    format!("\"{}\"", column.avro_type())
+                };
+                format!("{{\"name\":\"{}\",\"type\":{}}}", field.name, data_type)
+            })
+            .collect::<Vec<_>>();
+        let canonical = format!(
+            "{{\"name\":\"ethetl.{}\",\"type\":\"record\",\"fields\":[{}]}}",
+            table,
+            fields.join(",")
+        );
+        Ok(AvroSchema {
+            fingerprint: avro_fingerprint(canonical.as_bytes()),
+            canonical,
+        })
+    }
+
+    /// Encode the rows of the chunk, one single object encoded record per row.
+    pub fn encode(&self, schema: &Schema, chunk: &Chunk<Box<dyn Array>>) -> Result<Vec<Vec<u8>>> {
+        let columns = columns(schema, chunk)?;
+        let mut rows = Vec::with_capacity(chunk.len());
+        for i in 0..chunk.len() {
+            let mut buf = vec![0xC3, 0x01];
+            buf.extend(self.fingerprint.to_le_bytes());
+            for ((field, array), column) in schema.fields.iter().zip(chunk.arrays()).zip(&columns) {
+                match (field.is_nullable, array.is_null(i)) {
+                    (true, true) => write_avro_long(0, &mut buf),
+                    (true, false) => {
+                        write_avro_long(1, &mut buf);
+                        column.write_avro(i, &mut buf);
+                    }
+                    (false, true) => {
+                        return Err(Error::msg(format!(
+                            "Column {} is not nullable, got null",
+                            field.name
+                        )));
+                    }
+                    (false, false) => column.write_avro(i, &mut buf),
+                }
+            }
+            rows.push(buf);
+        }
+        Ok(rows)
+    }
+}
+
+// The decimal string, without the trailing zeros of the fraction.
+pub(crate) fn decimal_to_string(value: i128, scale: usize) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (int, frac) = digits.split_at(digits.len() - scale);
+    let frac = frac.trim_end_matches('0');
+    let sign = if value < 0 { "-" } else { "" };
+    if frac.is_empty() {
+        format!("{}{}", sign, int)
+    } else {
+        format!("{}{}.{}", sign, int, frac)
+    }
+}
+
+// Zigzag varint.
+fn write_avro_long(value: i64, buf: &mut Vec<u8>) {
+    let mut n = ((value << 1) ^ (value >> 63)) as u64;
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn write_avro_bytes(value: &[u8], buf: &mut Vec<u8>) {
+    write_avro_long(value.len() as i64, buf);
+    buf.extend_from_slice(value);
+}
+
+const AVRO_EMPTY_FINGERPRINT: u64 = 0xc15d213aa4d7a795;
+
+/// The CRC-64-AVRO (Rabin) fingerprint.
+pub fn avro_fingerprint(data: &[u8]) -> u64 {
+    let mut table = [0u64; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut fp = i as u64;
+        for _ in 0..8 {
+            fp = (fp >> 1) ^ (AVRO_EMPTY_FINGERPRINT & (fp & 1).wrapping_neg());
+        }
+        *entry = fp;
+    }
+    data.iter().fold(AVRO_EMPTY_FINGERPRINT, |fp, b| {
+        (fp >> 8) ^ table[((fp ^ *b as u64) & 0xff) as usize]
+    })
+}
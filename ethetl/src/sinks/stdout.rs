@@ -0,0 +1,136 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arrow2::datatypes::Schema;
+use async_trait::async_trait;
+use common_configs::StdoutFormat;
+use common_configs::StdoutSinkConfig;
+use common_exceptions::Result;
+use common_storages::read_parquet;
+use opendal::Operator;
+use serde_json::json;
+
+use crate::schemas::table_schema;
+use crate::sinks::arrow_stream;
+use crate::sinks::json_rows;
+use crate::sinks::CommittedRange;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+pub const TABLE_METADATA_KEY: &str = "ethetl.table";
+
+/// StdoutSink writes the rows of the committed ranges to stdout, as JSON
+/// lines or as Arrow IPC streams, e.g. `ethetl | jq`. The logs go to
+/// stderr.
+///
+/// A range is written at once, the ranges are in commit order which is not
+/// the block order with more than one worker.
+pub struct StdoutSink {
+    storage: Arc<Operator>,
+    conf: StdoutSinkConfig,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl StdoutSink {
+    pub fn create(storage: Arc<Operator>, conf: StdoutSinkConfig) -> Result<Self> {
+        Self::create_with_writer(storage, conf, Box::new(std::io::stdout()))
+    }
+
+    pub fn create_with_writer(
+        storage: Arc<Operator>,
+        conf: StdoutSinkConfig,
+        out: Box<dyn Write + Send>,
+    ) -> Result<Self> {
+        for table in &conf.tables {
+            table_schema(table)?;
+        }
+        Ok(StdoutSink {
+            storage,
+            conf,
+            out: Mutex::new(out),
+        })
+    }
+
+    fn write(&self, data: &[u8]) -> Result<()> {
+        let mut out = self.out.lock().unwrap();
+        out.write_all(data)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for StdoutSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StdoutSink")
+            .field("conf", &self.conf)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Sink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let mut buf = vec![];
+        for (table, file) in &range.files {
+            if !self.conf.tables.is_empty() && !self.conf.tables.contains(table) {
+                continue;
+            }
+            let (schema, chunks) = read_parquet(self.storage.clone(), &file.path).await?;
+            match self.conf.format {
+                StdoutFormat::Json => {
+                    for chunk in &chunks {
+                        for row in json_rows(&schema, chunk)? {
+                            serde_json::to_writer(&mut buf, &json!({"table": table, "row": row}))?;
+                            buf.push(b'\n');
+                        }
+                    }
+                }
+                StdoutFormat::Arrow => {
+                    let mut metadata = schema.metadata.clone();
+                    metadata.insert(TABLE_METADATA_KEY.to_string(), table.to_string());
+                    let schema = Schema::from(schema.fields).with_metadata(metadata);
+                    buf.extend(arrow_stream(&schema, &chunks)?);
+                }
+            }
+        }
+        self.write(&buf)
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        match self.conf.format {
+            StdoutFormat::Json => {
+                let mut buf = serde_json::to_vec(&json!({
+                    "rolled_back_from": rolled_back.from_block
+                }))?;
+                buf.push(b'\n');
+                self.write(&buf)
+            }
+            StdoutFormat::Arrow => {
+                log::warn!(
+                    "The blocks from {} were written to stdout and rolled back",
+                    rolled_back.from_block
+                );
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common_configs::WebhookSinkConfig;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_exceptions::Retryable;
+use common_storages::read_parquet;
+use common_storages::write_atomic;
+use hmac::Hmac;
+use hmac::Mac;
+use opendal::Operator;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::manifest::ManifestFile;
+use crate::manifest::TableManifest;
+use crate::schemas::table_schema;
+use crate::schemas::TableSchema;
+use crate::sinks::json_rows;
+use crate::sinks::text_column;
+use crate::sinks::CommittedRange;
+use crate::sinks::LoadState;
+use crate::sinks::RolledBack;
+use crate::sinks::Sink;
+
+pub const SIGNATURE_HEADER: &str = "X-Ethetl-Signature";
+
+/// The next block to deliver, stored at `{output_dir}/_sinks/webhook.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookCursor {
+    pub next_block: u64,
+}
+
+impl WebhookCursor {
+    // Read the cursor, none before the first delivery.
+    pub async fn read(op: Arc<Operator>, output_dir: &str) -> Result<Option<Self>> {
+        match op
+            .object(&LoadState::path(output_dir, "webhook"))
+            .read()
+            .await
+        {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub async fn write(&self, op: Arc<Operator>, output_dir: &str) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        write_atomic(op, &LoadState::path(output_dir, "webhook"), data).await
+    }
+}
+
+/// WebhookSink POSTs the rows of the tables to the URL as JSON, in block
+/// order and the rows of a block in one request:
+///
+/// ```json
+/// {"from_block": 100, "to_block": 120, "rows": [{"table": "token_transfers", "row": {...}}]}
+/// ```
+///
+/// The ranges are committed out of order, the rows are read from the table
+/// manifests from the cursor on, a range waits for the ranges before it.
+/// The cursor moves after each request, a request sent again after a crash
+/// has the same `X-Ethetl-Delivery` id. On a reorg `{"rolled_back_from": n}`
+/// is sent and the cursor moves back.
+#[derive(Debug)]
+pub struct WebhookSink {
+    storage: Arc<Operator>,
+    conf: WebhookSinkConfig,
+    http: reqwest::Client,
+    // Held while delivering.
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl WebhookSink {
+    pub fn create(storage: Arc<Operator>, conf: WebhookSinkConfig) -> Result<Self> {
+        for table in &conf.tables {
+            table_schema(table)?;
+        }
+        for filter in &conf.filters {
+            let schema = table_schema(&filter.table)?;
+            if !conf.tables.contains(&filter.table)
+                || !schema.columns.iter().any(|c| c.name == filter.column)
+                || compare_decimal(&filter.min, "0").is_none()
+            {
+                return Err(Error::msg(format!(
+                    "Webhook filter {}.{} >= {:?} needs a delivered table, its column and a decimal",
+                    filter.table, filter.column, filter.min
+                )));
+            }
+        }
+        Ok(WebhookSink {
+            storage,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(conf.timeout_secs))
+                .build()?,
+            conf,
+            lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    // The rows of the blocks `start..=end` passing the filters, by block.
+    async fn read_rows(
+        &self,
+        files: &[(&TableSchema, &ManifestFile)],
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(u64, Value)>> {
+        let mut rows = vec![];
+        for (table, file) in files {
+            let filters = self
+                .conf
+                .filters
+                .iter()
+                .filter(|f| f.table == table.name)
+                .collect::<Vec<_>>();
+            let (schema, chunks) = read_parquet(self.storage.clone(), &file.path).await?;
+            for chunk in &chunks {
+                let blocks = text_column(&schema, chunk, table.block_column())?;
+                let values = filters
+                    .iter()
+                    .map(|f| text_column(&schema, chunk, &f.column))
+                    .collect::<Result<Vec<_>>>()?;
+                for (i, row) in json_rows(&schema, chunk)?.into_iter().enumerate() {
+                    let block: u64 = blocks[i].parse()?;
+                    let passed = filters.iter().zip(&values).all(|(f, v)| {
+                        compare_decimal(&v[i], &f.min).map_or(false, |x| x != Ordering::Less)
+                    });
+                    if start <= block && block <= end && passed {
+                        rows.push((block, json!({"table": table.name, "row": row})));
+                    }
+                }
+            }
+        }
+        // Stable, the tables in order within a block.
+        rows.sort_by_key(|(block, _)| *block);
+        Ok(rows)
+    }
+
+    async fn deliver_rows(&self, from: u64, to: u64, batch: &mut Vec<(u64, Value)>) -> Result<()> {
+        let rows = batch.drain(..).map(|(_, row)| row).collect::<Vec<_>>();
+        log::info!(
+            "Webhook deliver {} rows of blocks {}-{}",
+            rows.len(),
+            from,
+            to
+        );
+        let body = json!({"from_block": from, "to_block": to, "rows": rows});
+        self.deliver("rows", &format!("{}-{}", from, to), &body)
+            .await
+    }
+
+    async fn deliver(&self, event: &str, id: &str, body: &Value) -> Result<()> {
+        let body = serde_json::to_vec(body)?;
+        let notify = |e, duration| {
+            log::warn!(
+                "Webhook delivery {} error at duration {:?}, error:{:?}",
+                id,
+                duration,
+                e
+            )
+        };
+        let op = || async {
+            self.post_with_no_retry(event, id, body.clone()).await?;
+            Ok(())
+        };
+
+        op.retry_with_notify(notify).await
+    }
+
+    async fn post_with_no_retry(&self, event: &str, id: &str, body: Vec<u8>) -> Result<()> {
+        let mut request = self
+            .http
+            .post(&self.conf.url)
+            .header("Content-Type", "application/json")
+            .header("X-Ethetl-Event", event)
+            .header("X-Ethetl-Delivery", id);
+        if !self.conf.secret.is_empty() {
+            request = request.header(SIGNATURE_HEADER, sign(&self.conf.secret, &body)?);
+        }
+        let resp = request.body(body).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(Error::msg(format!(
+                "Webhook {} responded {}: {}",
+                self.conf.url,
+                status,
+                text.trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn commit(&self, range: &CommittedRange) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let op = self.storage.clone();
+        let mut manifests = vec![];
+        for table in &self.conf.tables {
+            let manifest = TableManifest::read(op.clone(), &range.output_dir, table).await?;
+            manifests.push((table_schema(table)?, manifest));
+        }
+
+        let mut next = match WebhookCursor::read(op.clone(), &range.output_dir).await? {
+            Some(cursor) => cursor.next_block,
+            None if self.conf.from_block > 0 => self.conf.from_block,
+            None => manifests
+                .iter()
+                .flat_map(|(_, m)| m.files.iter().map(|f| f.start))
+                .min()
+                .unwrap_or(range.start),
+        };
+
+        // The files of all the tables with the next block, until a range
+        // before is not committed yet.
+        loop {
+            let files = manifests
+                .iter()
+                .filter_map(|(table, manifest)| {
+                    manifest
+                        .files
+                        .iter()
+                        .find(|f| f.start <= next && next <= f.end)
+                        .map(|f| (*table, f))
+                })
+                .collect::<Vec<_>>();
+            if files.is_empty() || files.len() < manifests.len() {
+                return Ok(());
+            }
+            let end = files.iter().map(|(_, f)| f.end).min().unwrap();
+
+            let rows = self.read_rows(&files, next, end).await?;
+            let mut batch: Vec<(u64, Value)> = vec![];
+            for (block, row) in rows {
+                let full = batch.len() >= self.conf.batch_rows.max(1);
+                if full && batch.last().map(|x| x.0) != Some(block) {
+                    let to = batch.last().unwrap().0;
+                    self.deliver_rows(next, to, &mut batch).await?;
+                    next = to + 1;
+                    WebhookCursor { next_block: next }
+                        .write(op.clone(), &range.output_dir)
+                        .await?;
+                }
+                batch.push((block, row));
+            }
+            if !batch.is_empty() {
+                self.deliver_rows(next, end, &mut batch).await?;
+            }
+            next = end + 1;
+            WebhookCursor { next_block: next }
+                .write(op.clone(), &range.output_dir)
+                .await?;
+        }
+    }
+
+    async fn rollback(&self, rolled_back: &RolledBack) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let op = self.storage.clone();
+        let output_dir = &rolled_back.output_dir;
+        match WebhookCursor::read(op.clone(), output_dir).await? {
+            Some(cursor) if cursor.next_block > rolled_back.from_block => {
+                let id = format!("rollback-{}", rolled_back.from_block);
+                let body = json!({ "rolled_back_from": rolled_back.from_block });
+                self.deliver("rollback", &id, &body).await?;
+                WebhookCursor {
+                    next_block: rolled_back.from_block,
+                }
+                .write(op, output_dir)
+                .await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The signature header value of the body, `sha256=` and the hex HMAC.
+pub fn sign(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| Error::msg(format!("HMAC key: {}", e)))?;
+    mac.update(body);
+    let hex = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect::<String>();
+    Ok(format!("sha256={}", hex))
+}
+
+/// Compare the non negative decimals, none if one is not a decimal.
+pub fn compare_decimal(a: &str, b: &str) -> Option<Ordering> {
+    let parse = |x: &str| {
+        let (int, frac) = x.split_once('.').unwrap_or((x, ""));
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+        if !digits(int) || !(frac.is_empty() || digits(frac)) {
+            return None;
+        }
+        let int = int.trim_start_matches('0');
+        Some((int, frac.trim_end_matches('0')))
+    };
+    let (a_int, a_frac) = parse(a)?;
+    let (b_int, b_frac) = parse(b)?;
+    Some(
+        a_int
+            .len()
+            .cmp(&b_int.len())
+            .then_with(|| a_int.cmp(b_int))
+            .then_with(|| a_frac.cmp(b_frac)),
+    )
+}
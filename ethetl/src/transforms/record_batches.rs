@@ -0,0 +1,127 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem::transmute;
+use std::sync::Arc;
+
+use arrow2::array::new_empty_array;
+use arrow2::array::Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use arrow2::ffi;
+use common_exceptions::Result;
+use datafusion::arrow::array::make_array;
+use datafusion::arrow::array::ArrayData;
+use datafusion::arrow::array::ArrayRef;
+use datafusion::arrow::datatypes::Field as BatchField;
+use datafusion::arrow::datatypes::Schema as BatchSchema;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::ffi::ArrowArray;
+use datafusion::arrow::ffi::FFI_ArrowArray;
+use datafusion::arrow::ffi::FFI_ArrowSchema;
+use datafusion::arrow::record_batch::RecordBatch;
+
+/// The arrow2 chunks as the record batches of DataFusion.
+pub fn to_record_batches(
+    schema: &Schema,
+    chunks: Vec<Chunk<Box<dyn Array>>>,
+) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let fields = schema
+        .fields
+        .iter()
+        .map(to_batch_field)
+        .collect::<Result<Vec<_>>>()?;
+    let schema = Arc::new(BatchSchema::new(fields));
+    let mut batches = vec![];
+    for chunk in chunks {
+        let columns = chunk
+            .into_arrays()
+            .into_iter()
+            .map(to_batch_array)
+            .collect::<Result<Vec<_>>>()?;
+        batches.push(RecordBatch::try_new(schema.clone(), columns)?);
+    }
+    Ok((schema, batches))
+}
+
+/// The record batches as arrow2 chunks. An output without rows is one empty
+/// chunk, its file is still written.
+pub fn from_record_batches(
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<(Schema, Vec<Chunk<Box<dyn Array>>>)> {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(from_batch_field)
+        .collect::<Result<Vec<_>>>()?;
+    let schema = Schema::from(fields);
+    let mut chunks = vec![];
+    for batch in batches {
+        let arrays = batch
+            .columns()
+            .iter()
+            .map(from_batch_array)
+            .collect::<Result<Vec<_>>>()?;
+        chunks.push(Chunk::try_new(arrays)?);
+    }
+    if chunks.is_empty() {
+        let arrays = schema
+            .fields
+            .iter()
+            .map(|f| new_empty_array(f.data_type.clone()))
+            .collect();
+        chunks.push(Chunk::try_new(arrays)?);
+    }
+    Ok((schema, chunks))
+}
+
+// The structs of the C data interface are the same in both crates, each
+// side releases what it imported through the callback of the other.
+fn to_batch_field(field: &Field) -> Result<BatchField> {
+    let schema = ffi::export_field_to_c(field);
+    let schema = unsafe { transmute::<ffi::ArrowSchema, FFI_ArrowSchema>(schema) };
+    Ok(BatchField::try_from(&schema)?)
+}
+
+fn from_batch_field(field: &BatchField) -> Result<Field> {
+    let schema = FFI_ArrowSchema::try_from(field)?;
+    let schema = unsafe { transmute::<FFI_ArrowSchema, ffi::ArrowSchema>(schema) };
+    Ok(unsafe { ffi::import_field_from_c(&schema)? })
+}
+
+fn to_batch_array(array: Box<dyn Array>) -> Result<ArrayRef> {
+    let field = Field::new("", array.data_type().clone(), true);
+    let schema = ffi::export_field_to_c(&field);
+    let array = ffi::export_array_to_c(array);
+    let data = unsafe {
+        let schema = transmute::<ffi::ArrowSchema, FFI_ArrowSchema>(schema);
+        let array = transmute::<ffi::ArrowArray, FFI_ArrowArray>(array);
+        ArrayData::try_from(ArrowArray::try_new(array, schema)?)?
+    };
+    Ok(make_array(data))
+}
+
+fn from_batch_array(array: &ArrayRef) -> Result<Box<dyn Array>> {
+    let data = array.data();
+    let schema = FFI_ArrowSchema::try_from(data.data_type())?;
+    let array = FFI_ArrowArray::new(data);
+    unsafe {
+        let schema = transmute::<FFI_ArrowSchema, ffi::ArrowSchema>(schema);
+        let array = transmute::<FFI_ArrowArray, ffi::ArrowArray>(array);
+        let field = ffi::import_field_from_c(&schema)?;
+        Ok(ffi::import_array_from_c(array, field.data_type)?)
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arrow2::array::Array;
+use arrow2::chunk::Chunk;
+use arrow2::compute::aggregate::estimated_bytes_size;
+use arrow2::datatypes::Schema;
+use common_configs::TransformConfig;
+use common_exceptions::Error;
+use common_exceptions::Result;
+#[cfg(feature = "transform")]
+use datafusion::arrow::datatypes::SchemaRef;
+#[cfg(feature = "transform")]
+use datafusion::datasource::MemTable;
+#[cfg(feature = "transform")]
+use datafusion::prelude::SessionContext;
+
+use crate::schemas::table_schema;
+#[cfg(feature = "transform")]
+use crate::transforms::record_batches::from_record_batches;
+#[cfg(feature = "transform")]
+use crate::transforms::record_batches::to_record_batches;
+
+type RangeKey = (String, u64, u64);
+
+/// A table of a range in memory, in batches.
+pub type RangeTable = (String, Schema, Vec<Chunk<Box<dyn Array>>>);
+
+/// Transformer runs the SQL transforms of the config with DataFusion over
+/// the tables of a range, while they are still in memory, before the range
+/// is committed. The outputs are written as more tables of the range.
+///
+/// The tables have the configured columns, a transform sees the outputs of
+/// the transforms before it. The arrays go between arrow2 and the arrow-rs
+/// of DataFusion through the C data interface, the buffers are shared, not
+/// copied. The transforms are built with the `transform` cargo feature.
+#[derive(Debug, Default)]
+pub struct Transformer {
+    transforms: Vec<TransformConfig>,
+    // The tables of the ranges being exported, by output dir and range.
+    pending: Mutex<BTreeMap<RangeKey, BTreeMap<String, (Schema, Vec<Chunk<Box<dyn Array>>>)>>>,
+}
+
+impl Transformer {
+    pub fn create(transforms: Vec<TransformConfig>) -> Result<Arc<Transformer>> {
+        if cfg!(not(feature = "transform")) && !transforms.is_empty() {
+            return Err(Error::msg(
+                "[[transforms]] needs ethetl built with `--features transform`",
+            ));
+        }
+        for (i, transform) in transforms.iter().enumerate() {
+            let name = &transform.name;
+            let valid = !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_');
+            if !valid || table_schema(name).is_ok() {
+                return Err(Error::msg(format!(
+                    "Transform name {:?} must be lowercase letters, digits and '_', and not a table of the registry",
+                    name
+                )));
+            }
+            if transforms[..i].iter().any(|x| &x.name == name) {
+                return Err(Error::msg(format!("Duplicate transform {}", name)));
+            }
+            if transform.sql.trim().is_empty() {
+                return Err(Error::msg(format!("Transform {} has no SQL", name)));
+            }
+        }
+        Ok(Arc::new(Transformer {
+            transforms,
+            pending: Default::default(),
+        }))
+    }
+
+    /// A transformer of the same transforms which holds its own tables.
+    pub fn fork(&self) -> Arc<Transformer> {
+        Arc::new(Transformer {
+            transforms: self.transforms.clone(),
+            pending: Default::default(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// The output tables.
+    pub fn tables(&self) -> Vec<String> {
+        self.transforms.iter().map(|x| x.name.clone()).collect()
+    }
+
+    /// The block column of the output, empty if it has none.
+    pub fn block_column(&self, table: &str) -> &str {
+        self.transforms
+            .iter()
+            .find(|x| x.name == table)
+            .map(|x| x.block_column.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Hold the table of the range until the range is transformed, nothing
+    /// is held without transforms. A range exported again replaces it.
+    pub fn stage(
+        &self,
+        output_dir: &str,
+        start: u64,
+        end: u64,
+        table: &str,
+        schema: &Schema,
+        chunks: Vec<Chunk<Box<dyn Array>>>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let key = (output_dir.to_string(), start, end);
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .entry(key)
+            .or_default()
+            .insert(table.to_string(), (schema.clone(), chunks));
+    }
+
+    /// The estimated bytes of the staged tables of the range, they are held
+    /// on top of the fetched blocks until the range is transformed.
+    pub fn staged_bytes(&self, output_dir: &str, start: u64, end: u64) -> usize {
+        let key = (output_dir.to_string(), start, end);
+        let pending = self.pending.lock().unwrap();
+        pending
+            .get(&key)
+            .into_iter()
+            .flat_map(|tables| tables.values())
+            .flat_map(|(_, chunks)| chunks.iter())
+            .flat_map(|chunk| chunk.arrays().iter())
+            .map(|array| estimated_bytes_size(array.as_ref()))
+            .sum()
+    }
+
+    /// Drop the staged tables of a failed range.
+    pub fn discard(&self, output_dir: &str, start: u64, end: u64) {
+        let key = (output_dir.to_string(), start, end);
+        self.pending.lock().unwrap().remove(&key);
+    }
+
+    /// Take the staged tables of the range.
+    pub fn take(&self, output_dir: &str, start: u64, end: u64) -> Vec<RangeTable> {
+        let key = (output_dir.to_string(), start, end);
+        let tables = self.pending.lock().unwrap().remove(&key);
+        tables
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(table, (schema, chunks))| (table, schema, chunks))
+            .collect()
+    }
+
+    /// Run the transforms over the tables, the outputs in the config order.
+    #[cfg(feature = "transform")]
+    pub async fn run(&self, tables: Vec<RangeTable>) -> Result<Vec<RangeTable>> {
+        let ctx = SessionContext::new();
+        for (table, schema, chunks) in tables {
+            let (schema, batches) = to_record_batches(&schema, chunks)?;
+            ctx.register_table(
+                table.as_str(),
+                Arc::new(MemTable::try_new(schema, vec![batches])?),
+            )?;
+        }
+
+        let mut outputs = vec![];
+        for transform in &self.transforms {
+            let df = ctx
+                .sql(&transform.sql)
+                .await
+                .map_err(|e| Error::msg(format!("Transform {}: {}", transform.name, e)))?;
+            let schema: SchemaRef = Arc::new(df.schema().into());
+            let batches = df.collect().await?;
+            ctx.register_table(
+                transform.name.as_str(),
+                Arc::new(MemTable::try_new(schema.clone(), vec![batches.clone()])?),
+            )?;
+
+            let (schema, chunks) = from_record_batches(&schema, &batches)?;
+            let block_column = &transform.block_column;
+            if !block_column.is_empty() && !schema.fields.iter().any(|f| &f.name == block_column) {
+                return Err(Error::msg(format!(
+                    "Transform {} has no block column {}, set its block_column",
+                    transform.name, block_column
+                )));
+            }
+            outputs.push((transform.name.clone(), schema, chunks));
+        }
+        Ok(outputs)
+    }
+
+    // Without the feature there are no transforms, see `create`.
+    #[cfg(not(feature = "transform"))]
+    pub async fn run(&self, _tables: Vec<RangeTable>) -> Result<Vec<RangeTable>> {
+        Ok(vec![])
+    }
+}
@@ -0,0 +1,30 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod output;
+mod roots;
+
+pub(crate) use output::str_column;
+pub(crate) use output::u64_column;
+pub use output::BlockGap;
+pub use output::Issue;
+pub use output::IssueKind;
+pub use output::MissingFile;
+pub use output::OutputVerifier;
+pub use output::VerifyReport;
+pub use roots::logs_bloom;
+pub use roots::ordered_trie_root;
+pub use roots::receipts_root;
+pub use roots::RootMismatch;
+pub use roots::RootVerifier;
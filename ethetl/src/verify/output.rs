@@ -0,0 +1,401 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use arrow2::array::Array;
+use arrow2::array::FixedSizeBinaryArray;
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Schema;
+use common_eth::bytes_to_hex;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use common_storages::read_parquet;
+use common_storages::write_atomic;
+use log::info;
+use opendal::Operator;
+use serde::Serialize;
+use web3::types::Bytes;
+
+use crate::exporters::eth::TABLES;
+use crate::exporters::list_table_files;
+use crate::exporters::PathTemplate;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct VerifyReport {
+    pub ok: bool,
+    pub first_block: Option<u64>,
+    pub last_block: Option<u64>,
+    pub blocks: usize,
+    pub transactions: usize,
+    pub receipts: usize,
+    // Range files per table.
+    pub files: BTreeMap<String, usize>,
+    pub gaps: Vec<BlockGap>,
+    pub missing_files: Vec<MissingFile>,
+    pub issues: Vec<Issue>,
+}
+
+/// The missing blocks [start, end].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockGap {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// A range exported to some tables but not to this one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingFile {
+    pub table: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    DuplicateBlock,
+    ParentHashMismatch,
+    TransactionCountMismatch,
+    MissingReceipt,
+    OrphanReceipt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    // The range file, like `16600001_16600002`.
+    pub range: String,
+    pub block: Option<u64>,
+    pub detail: String,
+}
+
+struct BlockRow {
+    number: u64,
+    hash: String,
+    parent_hash: String,
+    transaction_count: u64,
+}
+
+/// OutputVerifier reads the exported parquet files back and checks them
+/// range by range, so only one range is in memory at a time.
+pub struct OutputVerifier {
+    storage: Arc<Operator>,
+    output_dir: String,
+    path_template: PathTemplate,
+    chain_id: u64,
+}
+
+impl OutputVerifier {
+    pub fn create(
+        storage: Arc<Operator>,
+        output_dir: &str,
+        path_template: &PathTemplate,
+        chain_id: u64,
+    ) -> Self {
+        OutputVerifier {
+            storage,
+            output_dir: output_dir.to_string(),
+            path_template: path_template.clone(),
+            chain_id,
+        }
+    }
+
+    pub async fn verify(&self) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+
+        // The range files of each table.
+        let mut files: BTreeMap<&str, BTreeMap<(u64, u64), String>> = BTreeMap::new();
+        for table in TABLES {
+            let ranges = list_table_files(
+                self.storage.clone(),
+                &self.path_template,
+                &self.output_dir,
+                table,
+                self.chain_id,
+            )
+            .await?
+            .into_iter()
+            .map(|file| ((file.start, file.end), file.path))
+            .collect::<BTreeMap<_, _>>();
+            report.files.insert(table.to_string(), ranges.len());
+            files.insert(table, ranges);
+        }
+        info!(
+            "Verifying output, dir={}, files={:?}",
+            self.output_dir, report.files
+        );
+
+        // Every range must be exported to all the tables.
+        let ranges = files
+            .values()
+            .flat_map(|x| x.keys().copied())
+            .collect::<BTreeSet<_>>();
+        for (start, end) in &ranges {
+            for table in TABLES {
+                if !files[table].contains_key(&(*start, *end)) {
+                    report.missing_files.push(MissingFile {
+                        table: table.to_string(),
+                        start: *start,
+                        end: *end,
+                    });
+                }
+            }
+        }
+
+        // Walk the blocks in order, the next expected block crosses the files.
+        let mut prev: Option<(u64, String)> = None;
+        let mut next: Option<u64> = None;
+        for ((start, end), path) in &files["blocks"] {
+            let range = format!("{}_{}", start, end);
+            let mut expected = next.unwrap_or(*start);
+            let mut tx_counts = BTreeMap::new();
+
+            for block in self.read_blocks(path).await? {
+                if block.number < expected {
+                    report.issues.push(Issue {
+                        kind: IssueKind::DuplicateBlock,
+                        range: range.clone(),
+                        block: Some(block.number),
+                        detail: format!("block {} is exported more than once", block.number),
+                    });
+                    continue;
+                }
+                if block.number > expected {
+                    report.gaps.push(BlockGap {
+                        start: expected,
+                        end: block.number - 1,
+                    });
+                }
+                if let Some((number, hash)) = &prev {
+                    if number + 1 == block.number && &block.parent_hash != hash {
+                        report.issues.push(Issue {
+                            kind: IssueKind::ParentHashMismatch,
+                            range: range.clone(),
+                            block: Some(block.number),
+                            detail: format!(
+                                "parent_hash {} != hash {} of block {}",
+                                block.parent_hash, hash, number
+                            ),
+                        });
+                    }
+                }
+
+                report.first_block.get_or_insert(block.number);
+                report.last_block = Some(block.number);
+                report.blocks += 1;
+                tx_counts.insert(block.number, block.transaction_count);
+                expected = block.number + 1;
+                prev = Some((block.number, block.hash));
+            }
+
+            if expected <= *end {
+                report.gaps.push(BlockGap {
+                    start: expected,
+                    end: *end,
+                });
+                expected = end + 1;
+            }
+            next = Some(expected);
+
+            let tx_hashes = match files["transactions"].get(&(*start, *end)) {
+                Some(path) => {
+                    self.verify_transactions(path, &range, &tx_counts, &mut report)
+                        .await?
+                }
+                None => continue,
+            };
+            if let Some(path) = files["receipts"].get(&(*start, *end)) {
+                self.verify_receipts(path, &range, &tx_hashes, &mut report)
+                    .await?;
+            }
+        }
+
+        report.ok =
+            report.gaps.is_empty() && report.missing_files.is_empty() && report.issues.is_empty();
+        info!(
+            "Verified output, ok={}, blocks={}, gaps={}, missing files={}, issues={}",
+            report.ok,
+            report.blocks,
+            report.gaps.len(),
+            report.missing_files.len(),
+            report.issues.len()
+        );
+        Ok(report)
+    }
+
+    pub async fn write_report(&self, report: &VerifyReport, path: &str) -> Result<()> {
+        let data = serde_json::to_vec_pretty(report)?;
+        write_atomic(self.storage.clone(), path, data).await?;
+        info!("Write verify report to {}", path);
+        Ok(())
+    }
+
+    async fn read_blocks(&self, path: &str) -> Result<Vec<BlockRow>> {
+        let (schema, chunks) = read_parquet(self.storage.clone(), path).await?;
+
+        let mut rows = vec![];
+        for chunk in &chunks {
+            let numbers = u64_column(&schema, chunk, "number")?;
+            let hashes = str_column(&schema, chunk, "hash")?;
+            let parent_hashes = str_column(&schema, chunk, "parent_hash")?;
+            let transaction_counts = u64_column(&schema, chunk, "transaction_count")?;
+            for i in 0..numbers.len() {
+                rows.push(BlockRow {
+                    number: numbers[i],
+                    hash: hashes[i].clone(),
+                    parent_hash: parent_hashes[i].clone(),
+                    transaction_count: transaction_counts[i],
+                });
+            }
+        }
+        rows.sort_by_key(|x| x.number);
+        Ok(rows)
+    }
+
+    // Check the transaction rows per block, returns the tx hash to block number.
+    async fn verify_transactions(
+        &self,
+        path: &str,
+        range: &str,
+        tx_counts: &BTreeMap<u64, u64>,
+        report: &mut VerifyReport,
+    ) -> Result<BTreeMap<String, u64>> {
+        let (schema, chunks) = read_parquet(self.storage.clone(), path).await?;
+
+        let mut tx_hashes = BTreeMap::new();
+        let mut rows: BTreeMap<u64, u64> = BTreeMap::new();
+        for chunk in &chunks {
+            let hashes = str_column(&schema, chunk, "hash")?;
+            let block_numbers = u64_column(&schema, chunk, "block_number")?;
+            for (hash, number) in hashes.into_iter().zip(block_numbers) {
+                *rows.entry(number).or_default() += 1;
+                tx_hashes.insert(hash, number);
+                report.transactions += 1;
+            }
+        }
+
+        let numbers = tx_counts.keys().chain(rows.keys()).collect::<BTreeSet<_>>();
+        for number in numbers {
+            let expected = tx_counts.get(number).copied().unwrap_or_default();
+            let actual = rows.get(number).copied().unwrap_or_default();
+            if expected != actual {
+                report.issues.push(Issue {
+                    kind: IssueKind::TransactionCountMismatch,
+                    range: range.to_string(),
+                    block: Some(*number),
+                    detail: format!(
+                        "transaction_count is {}, but {} transaction rows",
+                        expected, actual
+                    ),
+                });
+            }
+        }
+        Ok(tx_hashes)
+    }
+
+    // Receipts must be 1:1 with the transactions.
+    async fn verify_receipts(
+        &self,
+        path: &str,
+        range: &str,
+        tx_hashes: &BTreeMap<String, u64>,
+        report: &mut VerifyReport,
+    ) -> Result<()> {
+        let (schema, chunks) = read_parquet(self.storage.clone(), path).await?;
+
+        let mut receipt_hashes = BTreeMap::new();
+        for chunk in &chunks {
+            let hashes = str_column(&schema, chunk, "transaction_hash")?;
+            let block_numbers = u64_column(&schema, chunk, "block_number")?;
+            for (hash, number) in hashes.into_iter().zip(block_numbers) {
+                report.receipts += 1;
+                receipt_hashes.insert(hash, number);
+            }
+        }
+
+        for (hash, number) in tx_hashes {
+            if !receipt_hashes.contains_key(hash) {
+                report.issues.push(Issue {
+                    kind: IssueKind::MissingReceipt,
+                    range: range.to_string(),
+                    block: Some(*number),
+                    detail: format!("transaction {} has no receipt", hash),
+                });
+            }
+        }
+        for (hash, number) in &receipt_hashes {
+            if !tx_hashes.contains_key(hash) {
+                report.issues.push(Issue {
+                    kind: IssueKind::OrphanReceipt,
+                    range: range.to_string(),
+                    block: Some(*number),
+                    detail: format!("receipt {} has no transaction", hash),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn column<'a>(
+    schema: &Schema,
+    chunk: &'a Chunk<Box<dyn Array>>,
+    name: &str,
+) -> Result<&'a dyn Array> {
+    let idx = schema
+        .fields
+        .iter()
+        .position(|f| f.name == name)
+        .ok_or_else(|| Error::msg(format!("Column {} not found", name)))?;
+    Ok(chunk.arrays()[idx].as_ref())
+}
+
+pub(crate) fn u64_column(
+    schema: &Schema,
+    chunk: &Chunk<Box<dyn Array>>,
+    name: &str,
+) -> Result<Vec<u64>> {
+    let array = column(schema, chunk, name)?
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .ok_or_else(|| Error::msg(format!("Column {} is not UInt64", name)))?;
+    Ok(array.values().iter().copied().collect())
+}
+
+// The hex strings of a hash column, written as Utf8 or FixedSizeBinary.
+pub(crate) fn str_column(
+    schema: &Schema,
+    chunk: &Chunk<Box<dyn Array>>,
+    name: &str,
+) -> Result<Vec<String>> {
+    let array = column(schema, chunk, name)?;
+    if let Some(array) = array.as_any().downcast_ref::<Utf8Array<i32>>() {
+        return Ok(array.values_iter().map(|x| x.to_string()).collect());
+    }
+    if let Some(array) = array.as_any().downcast_ref::<FixedSizeBinaryArray>() {
+        return Ok(array
+            .values_iter()
+            .map(|x| bytes_to_hex(&Bytes(x.to_vec())))
+            .collect());
+    }
+    Err(Error::msg(format!(
+        "Column {} is not Utf8 or FixedSizeBinary",
+        name
+    )))
+}
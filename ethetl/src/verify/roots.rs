@@ -0,0 +1,483 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+
+use common_eth::bytes_to_hex;
+use common_exceptions::Error;
+use common_exceptions::Result;
+use rlp::RlpStream;
+use web3::signing::keccak256;
+use web3::types::Block;
+use web3::types::Bytes;
+use web3::types::Log;
+use web3::types::Transaction;
+use web3::types::TransactionReceipt;
+use web3::types::H256;
+
+use crate::chains::eth::RawBlock;
+use crate::chains::eth::RawTransaction;
+
+/// A block doesn't match its header, the range is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootMismatch {
+    pub number: u64,
+    pub field: &'static str,
+    pub expected: String,
+    pub computed: String,
+}
+
+impl fmt::Display for RootMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Block {} {} mismatch, expected {}, computed {}",
+            self.number, self.field, self.expected, self.computed
+        )
+    }
+}
+
+impl std::error::Error for RootMismatch {}
+
+/// RootVerifier proves the fetched data is the chain's data:
+///
+/// - keccak(rlp(header)) == block.hash
+/// - the transactions trie root == transactions_root
+/// - the receipts trie root == receipts_root
+/// - the bloom of the receipt logs == logs_bloom
+///
+/// The raw blocks carry the fields web3 doesn't decode, e.g. the withdrawals
+/// root of the header and the chain id of the typed transactions.
+pub struct RootVerifier<'a> {
+    blocks: &'a [Block<Transaction>],
+    raw_blocks: &'a [RawBlock],
+    receipts: &'a [TransactionReceipt],
+}
+
+impl<'a> RootVerifier<'a> {
+    pub fn create(
+        blocks: &'a [Block<Transaction>],
+        raw_blocks: &'a [RawBlock],
+        receipts: &'a [TransactionReceipt],
+    ) -> Self {
+        RootVerifier {
+            blocks,
+            raw_blocks,
+            receipts,
+        }
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        let raw_blocks = self
+            .raw_blocks
+            .iter()
+            .map(|x| (x.number.as_u64(), x))
+            .collect::<HashMap<_, _>>();
+
+        // Receipts of each block in the transaction order.
+        let mut receipts: BTreeMap<u64, Vec<&TransactionReceipt>> = BTreeMap::new();
+        for receipt in self.receipts {
+            let number = receipt.block_number.unwrap_or_default().as_u64();
+            receipts.entry(number).or_default().push(receipt);
+        }
+        for x in receipts.values_mut() {
+            x.sort_by_key(|r| r.transaction_index);
+        }
+
+        for block in self.blocks {
+            let number = block.number.unwrap_or_default().as_u64();
+            let raw = raw_blocks
+                .get(&number)
+                .ok_or_else(|| Error::msg(format!("Raw block {} not fetched", number)))?;
+            let block_receipts = receipts.get(&number).cloned().unwrap_or_default();
+            verify_block(block, raw, &block_receipts)?;
+        }
+        Ok(())
+    }
+}
+
+fn verify_block(
+    block: &Block<Transaction>,
+    raw: &RawBlock,
+    receipts: &[&TransactionReceipt],
+) -> Result<()> {
+    let number = block.number.unwrap_or_default().as_u64();
+    let check = |field: &'static str, expected: &[u8], computed: &[u8]| -> Result<()> {
+        if expected == computed {
+            return Ok(());
+        }
+        Err(RootMismatch {
+            number,
+            field,
+            expected: bytes_to_hex(&Bytes(expected.to_vec())),
+            computed: bytes_to_hex(&Bytes(computed.to_vec())),
+        }
+        .into())
+    };
+
+    // Header.
+    let hash = block.hash.unwrap_or_default();
+    check("raw block hash", hash.as_bytes(), raw.hash.as_bytes())?;
+    check("hash", hash.as_bytes(), &keccak256(&header_rlp(block, raw)))?;
+
+    // Transactions.
+    let raw_txs = raw
+        .transactions
+        .iter()
+        .map(|x| (x.hash, x))
+        .collect::<HashMap<_, _>>();
+    let txs = block
+        .transactions
+        .iter()
+        .map(|tx| transaction_rlp(tx, raw_txs.get(&tx.hash).copied()))
+        .collect::<Result<Vec<_>>>()?;
+    check(
+        "transactions_root",
+        block.transactions_root.as_bytes(),
+        ordered_trie_root(&txs).as_bytes(),
+    )?;
+
+    // Receipts.
+    if receipts.len() != block.transactions.len() {
+        return Err(RootMismatch {
+            number,
+            field: "receipts",
+            expected: block.transactions.len().to_string(),
+            computed: receipts.len().to_string(),
+        }
+        .into());
+    }
+    let (receipts_root, bloom) = receipts_root(receipts);
+    check(
+        "receipts_root",
+        block.receipts_root.as_bytes(),
+        receipts_root.as_bytes(),
+    )?;
+    check(
+        "logs_bloom",
+        block.logs_bloom.unwrap_or_default().as_bytes(),
+        &bloom,
+    )
+}
+
+fn header_rlp(block: &Block<Transaction>, raw: &RawBlock) -> Vec<u8> {
+    let mut s = RlpStream::new();
+    s.begin_unbounded_list();
+    append_bytes(&mut s, block.parent_hash.as_bytes());
+    append_bytes(&mut s, block.uncles_hash.as_bytes());
+    append_bytes(&mut s, block.author.as_bytes());
+    append_bytes(&mut s, block.state_root.as_bytes());
+    append_bytes(&mut s, block.transactions_root.as_bytes());
+    append_bytes(&mut s, block.receipts_root.as_bytes());
+    append_bytes(&mut s, block.logs_bloom.unwrap_or_default().as_bytes());
+    s.append(&block.difficulty);
+    s.append(&block.number.unwrap_or_default());
+    s.append(&block.gas_limit);
+    s.append(&block.gas_used);
+    s.append(&block.timestamp);
+    append_bytes(&mut s, &block.extra_data.0);
+    append_bytes(&mut s, block.mix_hash.unwrap_or_default().as_bytes());
+    append_bytes(&mut s, block.nonce.unwrap_or_default().as_bytes());
+
+    // Fork fields, each fork appends to the previous ones.
+    if let Some(v) = block.base_fee_per_gas {
+        s.append(&v);
+    }
+    if let Some(v) = raw.withdrawals_root {
+        append_bytes(&mut s, v.as_bytes());
+    }
+    if let Some(v) = raw.blob_gas_used {
+        s.append(&v);
+    }
+    if let Some(v) = raw.excess_blob_gas {
+        s.append(&v);
+    }
+    if let Some(v) = raw.parent_beacon_block_root {
+        append_bytes(&mut s, v.as_bytes());
+    }
+    if let Some(v) = raw.requests_hash {
+        append_bytes(&mut s, v.as_bytes());
+    }
+    s.finalize_unbounded_list();
+    s.out().to_vec()
+}
+
+// The transaction envelope: legacy rlp, or type || rlp for the typed ones.
+fn transaction_rlp(tx: &Transaction, raw: Option<&RawTransaction>) -> Result<Vec<u8>> {
+    let tx_type = tx.transaction_type.unwrap_or_default().as_u64();
+    let unsupported = || Error::msg(format!("Cannot encode transaction {:#x}", tx.hash));
+
+    let mut s = RlpStream::new();
+    s.begin_unbounded_list();
+    if tx_type == 0 {
+        s.append(&tx.nonce);
+        s.append(&tx.gas_price.unwrap_or_default());
+        s.append(&tx.gas);
+        append_to(&mut s, tx);
+        s.append(&tx.value);
+        append_bytes(&mut s, &tx.input.0);
+    } else {
+        let raw = raw.ok_or_else(unsupported)?;
+        s.append(&raw.chain_id.ok_or_else(unsupported)?);
+        s.append(&tx.nonce);
+        if tx_type == 1 {
+            s.append(&tx.gas_price.unwrap_or_default());
+        } else {
+            s.append(&tx.max_priority_fee_per_gas.unwrap_or_default());
+            s.append(&tx.max_fee_per_gas.unwrap_or_default());
+        }
+        s.append(&tx.gas);
+        append_to(&mut s, tx);
+        s.append(&tx.value);
+        append_bytes(&mut s, &tx.input.0);
+
+        // Access list.
+        let access_list = tx.access_list.clone().unwrap_or_default();
+        s.begin_list(access_list.len());
+        for item in &access_list {
+            s.begin_list(2);
+            append_bytes(&mut s, item.address.as_bytes());
+            s.begin_list(item.storage_keys.len());
+            for key in &item.storage_keys {
+                append_bytes(&mut s, key.as_bytes());
+            }
+        }
+
+        match tx_type {
+            1 | 2 => {}
+            // EIP-4844 blob transaction.
+            3 => {
+                s.append(&raw.max_fee_per_blob_gas.ok_or_else(unsupported)?);
+                let hashes = raw.blob_versioned_hashes.clone().unwrap_or_default();
+                s.begin_list(hashes.len());
+                for hash in &hashes {
+                    append_bytes(&mut s, hash.as_bytes());
+                }
+            }
+            // EIP-7702 set code transaction.
+            4 => {
+                let authorizations = raw.authorization_list.clone().unwrap_or_default();
+                s.begin_list(authorizations.len());
+                for auth in &authorizations {
+                    s.begin_list(6);
+                    s.append(&auth.chain_id);
+                    append_bytes(&mut s, auth.address.as_bytes());
+                    s.append(&auth.nonce);
+                    s.append(&auth.y_parity);
+                    s.append(&auth.r);
+                    s.append(&auth.s);
+                }
+            }
+            _ => return Err(unsupported()),
+        }
+    }
+    s.append(&tx.v.unwrap_or_default());
+    s.append(&tx.r.unwrap_or_default());
+    s.append(&tx.s.unwrap_or_default());
+    s.finalize_unbounded_list();
+
+    let mut out = vec![];
+    if tx_type > 0 {
+        out.push(tx_type as u8);
+    }
+    out.extend_from_slice(&s.out());
+    Ok(out)
+}
+
+fn append_to(s: &mut RlpStream, tx: &Transaction) {
+    match tx.to {
+        Some(to) => append_bytes(s, to.as_bytes()),
+        // Contract creation.
+        None => {
+            s.append_empty_data();
+        }
+    }
+}
+
+// The receipt envelope: legacy rlp, or type || rlp for the typed ones.
+fn receipt_rlp(receipt: &TransactionReceipt, bloom: &[u8; 256]) -> Vec<u8> {
+    let mut s = RlpStream::new();
+    s.begin_list(4);
+    match (receipt.status, receipt.root) {
+        (Some(status), _) => {
+            s.append(&status);
+        }
+        // Pre-Byzantium state root.
+        (None, Some(root)) => append_bytes(&mut s, root.as_bytes()),
+        (None, None) => {
+            s.append_empty_data();
+        }
+    }
+    s.append(&receipt.cumulative_gas_used);
+    append_bytes(&mut s, bloom);
+    s.begin_list(receipt.logs.len());
+    for log in &receipt.logs {
+        s.begin_list(3);
+        append_bytes(&mut s, log.address.as_bytes());
+        s.begin_list(log.topics.len());
+        for topic in &log.topics {
+            append_bytes(&mut s, topic.as_bytes());
+        }
+        append_bytes(&mut s, &log.data.0);
+    }
+
+    let tx_type = receipt.transaction_type.unwrap_or_default().as_u64();
+    let mut out = vec![];
+    if tx_type > 0 {
+        out.push(tx_type as u8);
+    }
+    out.extend_from_slice(&s.out());
+    out
+}
+
+/// The receipts trie root and the logs bloom of the receipts of a block, in
+/// the transaction order.
+pub fn receipts_root(receipts: &[&TransactionReceipt]) -> (H256, [u8; 256]) {
+    let mut bloom = [0u8; 256];
+    let mut items = Vec::with_capacity(receipts.len());
+    for receipt in receipts {
+        let receipt_bloom = logs_bloom(&receipt.logs);
+        for (i, v) in receipt_bloom.iter().enumerate() {
+            bloom[i] |= v;
+        }
+        items.push(receipt_rlp(receipt, &receipt_bloom));
+    }
+    (ordered_trie_root(&items), bloom)
+}
+
+/// The 2048 bits bloom of the log addresses and topics.
+pub fn logs_bloom(logs: &[Log]) -> [u8; 256] {
+    let mut bloom = [0u8; 256];
+    let mut accrue = |input: &[u8]| {
+        let hash = keccak256(input);
+        for i in [0, 2, 4] {
+            let bit = (((hash[i] as usize) << 8) | hash[i + 1] as usize) & 2047;
+            bloom[255 - bit / 8] |= 1 << (bit % 8);
+        }
+    };
+    for log in logs {
+        accrue(log.address.as_bytes());
+        for topic in &log.topics {
+            accrue(topic.as_bytes());
+        }
+    }
+    bloom
+}
+
+/// The root of the trie keyed by rlp(index), as the transactions and receipts tries.
+pub fn ordered_trie_root(items: &[Vec<u8>]) -> H256 {
+    let mut input = items
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (nibbles(&rlp::encode(&i)), v.as_slice()))
+        .collect::<Vec<_>>();
+    input.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut s = RlpStream::new();
+    trie_node(&input, 0, &mut s);
+    H256::from(keccak256(&s.out()))
+}
+
+fn trie_node(input: &[(Vec<u8>, &[u8])], pre_len: usize, s: &mut RlpStream) {
+    if input.is_empty() {
+        s.append_empty_data();
+        return;
+    }
+
+    let (key, value) = &input[0];
+    // Leaf.
+    if input.len() == 1 {
+        s.begin_list(2);
+        append_bytes(s, &hex_prefix(&key[pre_len..], true));
+        append_bytes(s, value);
+        return;
+    }
+
+    // Extension of the shared prefix.
+    let shared = input[1..].iter().fold(key.len(), |acc, (k, _)| {
+        acc.min(key.iter().zip(k).take_while(|(a, b)| a == b).count())
+    });
+    if shared > pre_len {
+        s.begin_list(2);
+        append_bytes(s, &hex_prefix(&key[pre_len..shared], false));
+        trie_child(input, shared, s);
+        return;
+    }
+
+    // Branch, the sorted keys are grouped by the next nibble.
+    s.begin_list(17);
+    let value = if key.len() == pre_len {
+        Some(value)
+    } else {
+        None
+    };
+    let mut begin = if value.is_some() { 1 } else { 0 };
+    for nibble in 0..16 {
+        let len = input[begin..]
+            .iter()
+            .take_while(|(k, _)| k[pre_len] == nibble)
+            .count();
+        if len == 0 {
+            s.append_empty_data();
+        } else {
+            trie_child(&input[begin..begin + len], pre_len + 1, s);
+        }
+        begin += len;
+    }
+    match value {
+        Some(v) => append_bytes(s, v),
+        None => {
+            s.append_empty_data();
+        }
+    }
+}
+
+// Nodes shorter than 32 bytes are inlined, others are referenced by hash.
+fn trie_child(input: &[(Vec<u8>, &[u8])], pre_len: usize, s: &mut RlpStream) {
+    let mut child = RlpStream::new();
+    trie_node(input, pre_len, &mut child);
+    let out = child.out();
+    if out.len() < 32 {
+        s.append_raw(&out, 1);
+    } else {
+        append_bytes(s, &keccak256(&out));
+    }
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+// Compact encoding of a nibble path with the leaf flag.
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 0x20 } else { 0x00 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        out.push(flag | 0x10 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag);
+        nibbles
+    };
+    for pair in rest.chunks(2) {
+        out.push((pair[0] << 4) | pair[1]);
+    }
+    out
+}
+
+fn append_bytes(s: &mut RlpStream, bytes: &[u8]) {
+    s.append(&bytes.to_vec());
+}
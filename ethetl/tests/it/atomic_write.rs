@@ -0,0 +1,220 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow2::array::UInt64Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_exceptions::Result;
+use common_storages::clean_staging;
+use common_storages::commit_object;
+use common_storages::init_memory_operator;
+use common_storages::list_files;
+use common_storages::read_parquet;
+use common_storages::write_if_absent;
+use common_storages::write_parquet;
+use common_storages::write_txt;
+use common_storages::ParquetWriter;
+use common_storages::STAGING_DIR;
+use ethetl::exporters::eth::remove_range;
+use ethetl::manifest::RangeCommit;
+use opendal::services::Fs;
+use opendal::Builder;
+use opendal::Operator;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_atomic_write_fs() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("mars_atomic_write_{}", std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&root.display().to_string());
+    let op = Arc::new(Operator::new(builder.build()?).finish());
+
+    let array = UInt64Array::from_vec(vec![1, 2, 3]);
+    let schema = Schema::from(vec![Field::new("number", array.data_type().clone(), true)]);
+    write_parquet(
+        op.clone(),
+        "pub/blocks/blocks_1_3.parquet",
+        schema.clone(),
+        Chunk::try_new(vec![array.boxed()])?,
+    )
+    .await?;
+    write_txt(
+        op.clone(),
+        "pub/transactions/_transactions_hash_1_3.txt",
+        b"0x1",
+    )
+    .await?;
+
+    let (read_schema, chunks) = read_parquet(op.clone(), "pub/blocks/blocks_1_3.parquet").await?;
+    assert_eq!(read_schema, schema);
+    assert_eq!(chunks[0].len(), 3);
+    assert_eq!(list_files(op.clone(), "pub").await?, vec![
+        "pub/blocks/blocks_1_3.parquet".to_string(),
+        "pub/transactions/_transactions_hash_1_3.txt".to_string(),
+    ]);
+
+    // Nothing is left in the staging.
+    assert!(list_files(op.clone(), STAGING_DIR).await?.is_empty());
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_parquet_writer_row_groups() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("mars_parquet_writer_{}", std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&root.display().to_string());
+    let fs = Arc::new(Operator::new(builder.build()?).finish());
+    let memory = Arc::new(init_memory_operator()?);
+
+    // Bigger than a row group.
+    let rows = 200000u64;
+    for op in [fs, memory] {
+        let array = UInt64Array::from_vec((0..rows).collect());
+        let schema = Schema::from(vec![Field::new("number", array.data_type().clone(), true)]);
+        let mut writer = ParquetWriter::create(op.clone(), "pub/big.parquet", schema).await?;
+        writer.write(Chunk::try_new(vec![array.boxed()])?).await?;
+        let size = writer.close().await?.size;
+
+        assert_eq!(
+            op.object("pub/big.parquet").stat().await?.content_length(),
+            size
+        );
+        let (_, chunks) = read_parquet(op.clone(), "pub/big.parquet").await?;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.iter().map(|x| x.len() as u64).sum::<u64>(), rows);
+        assert!(list_files(op.clone(), STAGING_DIR).await?.is_empty());
+    }
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_atomic_write_memory() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    write_txt(op.clone(), "pub/a.txt", b"mars").await?;
+    assert_eq!(op.object("pub/a.txt").read().await?, b"mars".to_vec());
+    assert!(list_files(op.clone(), STAGING_DIR).await?.is_empty());
+
+    // Copied then removed without rename.
+    let staging = format!("{}/1_1676160000_0_b.txt.tmp", STAGING_DIR);
+    op.object(&staging).write(b"ethetl".to_vec()).await?;
+    commit_object(op.clone(), &staging, "pub/b.txt").await?;
+    assert_eq!(op.object("pub/b.txt").read().await?, b"ethetl".to_vec());
+    assert!(list_files(op.clone(), STAGING_DIR).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_write_if_absent() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("mars_write_if_absent_{}", std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&root.display().to_string());
+    let op = Arc::new(Operator::new(builder.build()?).finish());
+
+    assert!(write_if_absent(op.clone(), "pub/_log/0.json", b"a".to_vec()).await?);
+    assert!(!write_if_absent(op.clone(), "pub/_log/0.json", b"b".to_vec()).await?);
+    assert_eq!(op.object("pub/_log/0.json").read().await?, b"a".to_vec());
+    assert!(list_files(op.clone(), STAGING_DIR).await?.is_empty());
+
+    // No conditional put without fs.
+    let memory = Arc::new(init_memory_operator()?);
+    assert!(
+        write_if_absent(memory, "pub/_log/0.json", b"a".to_vec())
+            .await
+            .is_err()
+    );
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_clean_staging() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    // Left by a crashed run a day ago, and by a running one.
+    let old = format!("{}/1_{}_0_blocks_1_3.parquet.tmp", STAGING_DIR, 1676160000);
+    let now = chrono::Utc::now().timestamp();
+    let fresh = format!("{}/2_{}_0_blocks_4_6.parquet.tmp", STAGING_DIR, now);
+    op.object(&old).write(b"mars".to_vec()).await?;
+    op.object(&fresh).write(b"mars".to_vec()).await?;
+
+    let removed = clean_staging(op.clone(), Duration::from_secs(24 * 3600)).await?;
+    assert_eq!(removed, 1);
+    assert_eq!(list_files(op.clone(), STAGING_DIR).await?, vec![fresh]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_range_commit() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    assert_eq!(RangeCommit::read(op.clone(), "pub", 1, 3).await?, None);
+
+    let mut files = BTreeMap::new();
+    files.insert(
+        "blocks".to_string(),
+        "pub/blocks/blocks_1_3.parquet".to_string(),
+    );
+    let commit = RangeCommit {
+        start: 1,
+        end: 3,
+        files,
+        committed_at: "2023-02-12T00:00:11+00:00".to_string(),
+    };
+    commit.write(op.clone(), "pub").await?;
+    assert_eq!(
+        RangeCommit::path("pub", 1, 3),
+        "pub/_commits/1_3.json".to_string()
+    );
+    assert_eq!(
+        RangeCommit::read(op.clone(), "pub", 1, 3).await?,
+        Some(commit)
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_remove_range() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    let mut files = BTreeMap::new();
+    for table in ["blocks", "transactions"] {
+        let path = format!("pub/{}/{}_1_3.parquet", table, table);
+        write_txt(op.clone(), &path, b"mars").await?;
+        files.insert(table.to_string(), path);
+    }
+    write_txt(
+        op.clone(),
+        "pub/transactions/_transactions_hash_1_3.txt",
+        b"0x00",
+    )
+    .await?;
+    let commit = RangeCommit {
+        start: 1,
+        end: 3,
+        files,
+        committed_at: "2023-02-12T00:00:11+00:00".to_string(),
+    };
+    commit.write(op.clone(), "pub").await?;
+
+    // Nothing of the range is left in the memory.
+    remove_range(op.clone(), "pub", &commit).await?;
+    assert!(list_files(op.clone(), "pub").await?.is_empty());
+    Ok(())
+}
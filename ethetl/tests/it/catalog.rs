@@ -0,0 +1,241 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow2::array::UInt64Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use common_storages::list_files;
+use common_storages::write_parquet;
+use ethetl::manifest::Catalog;
+use ethetl::manifest::ManifestFile;
+use ethetl::manifest::TableManifest;
+use ethetl::manifest::SNAPSHOT_VERSIONS;
+use opendal::services::Fs;
+use opendal::Builder;
+use opendal::Operator;
+
+fn manifest_file(start: u64, end: u64, rows: u64) -> ManifestFile {
+    ManifestFile {
+        path: format!("pub/logs/logs_{}_{}.parquet", start, end),
+        start,
+        end,
+        rows,
+        ..Default::default()
+    }
+}
+
+fn seqs(files: &[&ManifestFile]) -> Vec<u64> {
+    files.iter().map(|x| x.seq).collect()
+}
+
+#[test]
+fn test_manifest_append() -> Result<()> {
+    let mut manifest = TableManifest::default();
+    manifest.append(manifest_file(10, 19, 5));
+    manifest.append(manifest_file(0, 9, 5));
+    assert_eq!(manifest.last_seq, 2);
+    // Block order.
+    assert_eq!(manifest.files[0].start, 0);
+    assert_eq!(manifest.files[0].seq, 2);
+
+    // Written again with the same rows, loaded already.
+    manifest.append(manifest_file(0, 9, 5));
+    assert_eq!(manifest.files.len(), 2);
+    assert_eq!(manifest.files[0].seq, 2);
+
+    // Written again with other rows.
+    manifest.append(manifest_file(0, 9, 6));
+    assert_eq!(manifest.files.len(), 2);
+    assert_eq!(manifest.files[0].seq, 3);
+
+    assert_eq!(seqs(&manifest.files_after(0)?), vec![1, 3]);
+    assert_eq!(seqs(&manifest.files_after(1)?), vec![3]);
+    assert!(manifest.files_after(3)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_manifest_compaction() -> Result<()> {
+    let mut manifest = TableManifest::default();
+    manifest.append(manifest_file(0, 9, 5));
+    manifest.append(manifest_file(10, 19, 5));
+    manifest.append(manifest_file(20, 29, 5));
+
+    manifest.replace(
+        &[
+            "pub/logs/logs_0_9.parquet".to_string(),
+            "pub/logs/logs_10_19.parquet".to_string(),
+        ],
+        vec![manifest_file(0, 19, 10)],
+    );
+    assert_eq!(manifest.files.len(), 2);
+    assert_eq!(manifest.files[0].seq, 4);
+    assert_eq!(manifest.files[0].source_seqs, vec![1, 2]);
+
+    // A new consumer loads the merged file.
+    assert_eq!(seqs(&manifest.files_after(0)?), vec![3, 4]);
+    // The merged rows are loaded.
+    assert!(manifest.files_after(3)?.is_empty());
+    // Half of them are loaded.
+    assert!(manifest.files_after(1).is_err());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_catalog_commit() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+
+    let array = UInt64Array::from_vec(vec![100, 101]);
+    let schema = Schema::from(vec![Field::new(
+        "block_number",
+        array.data_type().clone(),
+        true,
+    )]);
+    let written = write_parquet(
+        op.clone(),
+        "pub/logs/logs_100_101.parquet",
+        schema,
+        Chunk::try_new(vec![array.boxed()])?,
+    )
+    .await?;
+    assert_eq!(written.rows, 2);
+    assert_eq!(written.checksum.len(), 64);
+
+    let catalog = Catalog::create();
+    catalog.stage("pub", "logs", ManifestFile {
+        path: written.path.clone(),
+        start: 100,
+        end: 101,
+        rows: written.rows,
+        size: written.size,
+        min_block: Some(100),
+        max_block: Some(101),
+        checksum: written.checksum.clone(),
+        ..Default::default()
+    });
+
+    // Not committed yet.
+    let manifest = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert!(manifest.files.is_empty());
+
+    // Another range.
+    catalog.commit(op.clone(), "pub", 0, 99).await?;
+    let manifest = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert!(manifest.files.is_empty());
+
+    catalog.commit(op.clone(), "pub", 100, 101).await?;
+    let manifest = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert_eq!(manifest.table, "logs");
+    assert_eq!(manifest.last_seq, 1);
+    assert_eq!(manifest.files.len(), 1);
+    let file = &manifest.files[0];
+    assert_eq!(file.path, "pub/logs/logs_100_101.parquet");
+    assert_eq!(file.size, written.size);
+    assert_eq!(file.checksum, written.checksum);
+    assert!(!file.committed_at.is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manifest_log() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    let mut manifest = TableManifest::create("logs");
+    // A reader of the first versions.
+    manifest.append(manifest_file(0, 9, 5));
+    manifest.write(op.clone(), "pub").await?;
+    let mut behind = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert_eq!(behind.version, 1);
+
+    let versions = 2 * SNAPSHOT_VERSIONS + 1;
+    for i in 1..versions {
+        manifest.append(manifest_file(i * 10, i * 10 + 9, 5));
+        manifest.write(op.clone(), "pub").await?;
+    }
+    assert_eq!(manifest.version, versions);
+    // Nothing to write.
+    manifest.write(op.clone(), "pub").await?;
+    assert_eq!(manifest.version, versions);
+
+    // The snapshot and the log of the last snapshot interval.
+    let snapshot: TableManifest = serde_json::from_slice(
+        &op.object(&TableManifest::path("pub", "logs"))
+            .read()
+            .await?,
+    )?;
+    assert_eq!(snapshot.version, 2 * SNAPSHOT_VERSIONS);
+    let log = list_files(op.clone(), &TableManifest::log_dir("pub", "logs")).await?;
+    assert_eq!(log.len() as u64, SNAPSHOT_VERSIONS + 1);
+
+    let read = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert_eq!(read.version, versions);
+    assert_eq!(read.last_seq, versions);
+    assert_eq!(read.files, manifest.files);
+
+    // Behind the removed log, read again.
+    assert!(!behind.refresh(op.clone(), "pub").await?);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manifest_read_error() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("mars_manifest_read_{}", std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&root.display().to_string());
+    let op = Arc::new(Operator::new(builder.build()?).finish());
+
+    // None is empty, a manifest which can't be read is an error.
+    assert_eq!(
+        TableManifest::read(op.clone(), "pub", "logs").await?,
+        TableManifest::create("logs")
+    );
+    std::fs::create_dir_all(root.join(TableManifest::path("pub", "logs")))?;
+    assert!(
+        TableManifest::read(op.clone(), "pub", "logs")
+            .await
+            .is_err()
+    );
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_catalog_processes() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    // Two exporters of the output dir, each with its catalog.
+    let (first, second) = (Catalog::create(), Catalog::create());
+    for (i, catalog) in [&first, &second, &first].into_iter().enumerate() {
+        let start = i as u64 * 10;
+        catalog.stage("pub", "logs", manifest_file(start, start + 9, 5));
+        catalog.commit(op.clone(), "pub", start, start + 9).await?;
+    }
+    let manifest = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert_eq!(seqs(&manifest.files_after(0)?), vec![1, 2, 3]);
+
+    // A failed range is dropped, a retry commits its files only.
+    first.stage("pub", "logs", manifest_file(30, 39, 5));
+    first.discard("pub", 30, 39);
+    first.stage("pub", "logs", manifest_file(30, 39, 6));
+    let committed = first.commit(op.clone(), "pub", 30, 39).await?;
+    assert_eq!(committed["logs"].rows, 6);
+    let manifest = TableManifest::read(op.clone(), "pub", "logs").await?;
+    assert_eq!(manifest.files.len(), 4);
+    assert!(list_files(op.clone(), "pub/_locks").await?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,183 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use arrow2::io::ipc::read::read_stream_metadata;
+use arrow2::io::ipc::read::StreamReader;
+use arrow2::io::ipc::read::StreamState;
+use common_configs::ClickHouseInsertFormat;
+use common_configs::ClickHouseSinkConfig;
+use common_configs::HashEncoding;
+use common_configs::TableColumns;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use common_storages::read_parquet;
+use ethetl::exporters::ColumnProjection;
+use ethetl::sinks::ClickHouseClient;
+use ethetl::sinks::ClickHouseSink;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::common::testdata_operator;
+use crate::common::testdata_range;
+use crate::common::MockHttpServer;
+use crate::common::MockRequest;
+
+fn clickhouse_responder(_: &MockRequest) -> Value {
+    json!("")
+}
+
+// The statement of the query parameter or of the body, after the settings.
+fn statements(requests: &[MockRequest]) -> Vec<String> {
+    requests
+        .iter()
+        .map(|x| {
+            let url = reqwest::Url::parse(&format!("http://localhost{}", x.path)).unwrap();
+            let params = url.query_pairs().into_owned().collect::<BTreeMap<_, _>>();
+            let mut statement = params
+                .iter()
+                .filter(|(k, _)| *k != "query")
+                .map(|(k, v)| format!("{}={} ", k, v))
+                .collect::<String>();
+            match params.get("query") {
+                Some(query) => statement.push_str(query),
+                None => statement.push_str(&String::from_utf8_lossy(&x.body)),
+            }
+            statement
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_clickhouse_sink() -> Result<()> {
+    let server = MockHttpServer::start(clickhouse_responder).await;
+    let op = testdata_operator()?;
+    let sink = ClickHouseSink::create(
+        op.clone(),
+        ClickHouseSinkConfig {
+            endpoint: server.endpoint.clone(),
+            password: "secret".to_string(),
+            ..Default::default()
+        },
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+
+    sink.commit(&testdata_range(&["blocks", "logs"])).await?;
+    let requests = server.requests();
+    let statements = statements(&requests);
+    assert_eq!(statements.len(), 9);
+    assert_eq!(statements[0], "CREATE DATABASE IF NOT EXISTS eth;");
+    assert!(statements[1].starts_with("-- The blocks.\nCREATE TABLE IF NOT EXISTS eth.blocks"));
+    assert!(statements[1].ends_with("ENGINE = ReplacingMergeTree\nORDER BY (number);"));
+    assert_eq!(&statements[7..], [
+        "INSERT INTO eth.blocks FORMAT Parquet",
+        "INSERT INTO eth.logs FORMAT Parquet",
+    ]);
+    assert_eq!(requests[7].headers["x-clickhouse-user"], "default");
+    assert_eq!(requests[7].headers["x-clickhouse-key"], "secret");
+    assert_eq!(
+        requests[7].body,
+        op.object("blocks/blocks_16600001_16600002.parquet")
+            .read()
+            .await?
+    );
+
+    sink.rollback(&RolledBack {
+        output_dir: "".to_string(),
+        from_block: 16600002,
+        files: testdata_range(&["blocks", "logs"])
+            .files
+            .into_iter()
+            .map(|(k, v)| (k, vec![v]))
+            .collect(),
+    })
+    .await?;
+    assert_eq!(statements(&server.requests())[9..], [
+        "mutations_sync=2 ALTER TABLE eth.blocks DELETE WHERE number >= 16600002",
+        "mutations_sync=2 ALTER TABLE eth.logs DELETE WHERE block_number >= 16600002",
+    ]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_clickhouse_sink_arrow_stream() -> Result<()> {
+    let server = MockHttpServer::start(clickhouse_responder).await;
+    let op = testdata_operator()?;
+    let sink = ClickHouseSink::create(
+        op.clone(),
+        ClickHouseSinkConfig {
+            endpoint: server.endpoint.clone(),
+            format: ClickHouseInsertFormat::ArrowStream,
+            create_tables: false,
+            ..Default::default()
+        },
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+
+    sink.commit(&testdata_range(&["transactions"])).await?;
+    let requests = server.requests();
+    assert_eq!(statements(&requests), [
+        "INSERT INTO eth.transactions FORMAT ArrowStream"
+    ]);
+
+    // The stream has the rows of the file.
+    let (schema, chunks) =
+        read_parquet(op, "transactions/transactions_16600001_16600002.parquet").await?;
+    let mut reader = Cursor::new(&requests[0].body);
+    let metadata = read_stream_metadata(&mut reader)?;
+    assert_eq!(metadata.schema.fields, schema.fields);
+    let mut rows = 0;
+    for state in StreamReader::new(reader, metadata, None) {
+        if let StreamState::Some(chunk) = state? {
+            rows += chunk.len();
+        }
+    }
+    assert_eq!(rows, chunks.iter().map(|c| c.len()).sum::<usize>());
+    Ok(())
+}
+
+#[test]
+fn test_clickhouse_client_debug() -> Result<()> {
+    let client = ClickHouseClient::create(&ClickHouseSinkConfig {
+        password: "clickhouse_secret".to_string(),
+        ..Default::default()
+    })?;
+    let debug = format!("{:?}", client);
+    assert!(debug.contains("password: \"******ret\""));
+    assert!(!debug.contains("clickhouse_secret"));
+    Ok(())
+}
+
+#[test]
+fn test_clickhouse_sink_projection() {
+    let mut tables = BTreeMap::new();
+    tables.insert("logs".to_string(), TableColumns {
+        include: vec![],
+        exclude: vec!["block_number".to_string()],
+    });
+    let res = ClickHouseSink::create(
+        Arc::new(init_memory_operator().unwrap()),
+        ClickHouseSinkConfig::default(),
+        HashEncoding::Hex,
+        ColumnProjection::create(tables),
+    );
+    assert!(res.is_err());
+}
@@ -0,0 +1,79 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow2::array::BinaryArray;
+use arrow2::array::FixedSizeBinaryArray;
+use arrow2::array::Utf8Array;
+use arrow2::datatypes::DataType;
+use common_configs::HashEncoding;
+use ethetl::exporters::ColumnEncoder;
+use web3::types::H160;
+use web3::types::H2048;
+use web3::types::H256;
+
+#[test]
+fn test_hex_encoding() {
+    let encoder = ColumnEncoder::create(HashEncoding::Hex);
+
+    let array = encoder.hashes(&[H256::repeat_byte(0xab)]);
+    let array = array.as_any().downcast_ref::<Utf8Array<i32>>().unwrap();
+    assert_eq!(array.value(0), format!("0x{}", "ab".repeat(32)));
+
+    let mut bloom = H2048::zero();
+    bloom.0[255] = 1;
+    let array = encoder.blooms(&[bloom]);
+    let array = array.as_any().downcast_ref::<Utf8Array<i32>>().unwrap();
+    assert_eq!(array.value(0), "0x1");
+
+    let array = encoder.topics(&[vec![H256::repeat_byte(1), H256::repeat_byte(2)], vec![]]);
+    let array = array.as_any().downcast_ref::<Utf8Array<i32>>().unwrap();
+    assert_eq!(
+        array.value(0),
+        format!("0x{}|0x{}", "01".repeat(32), "02".repeat(32))
+    );
+    assert_eq!(array.value(1), "");
+}
+
+#[test]
+fn test_binary_encoding() {
+    let encoder = ColumnEncoder::create(HashEncoding::Binary);
+
+    let array = encoder.hashes(&[H256::repeat_byte(0xab), H256::zero()]);
+    assert_eq!(array.data_type(), &DataType::FixedSizeBinary(32));
+    let array = array
+        .as_any()
+        .downcast_ref::<FixedSizeBinaryArray>()
+        .unwrap();
+    assert_eq!(array.len(), 2);
+    assert_eq!(array.value(0), &[0xab; 32]);
+
+    let array = encoder.addresses(&[H160::repeat_byte(7)]);
+    assert_eq!(array.data_type(), &DataType::FixedSizeBinary(20));
+
+    // The topic address is the low 20 bytes.
+    let topic = H256::from(H160::repeat_byte(9));
+    let array = encoder.topic_addresses(&[topic]);
+    let array = array
+        .as_any()
+        .downcast_ref::<FixedSizeBinaryArray>()
+        .unwrap();
+    assert_eq!(array.value(0), &[9; 20]);
+
+    let array = encoder.blooms(&[H2048::zero()]);
+    assert_eq!(array.data_type(), &DataType::FixedSizeBinary(256));
+
+    let array = encoder.topics(&[vec![H256::repeat_byte(1), H256::repeat_byte(2)]]);
+    let array = array.as_any().downcast_ref::<BinaryArray<i32>>().unwrap();
+    assert_eq!(array.value(0).len(), 64);
+}
@@ -12,10 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arrow2::array::Array;
+use arrow2::io::parquet::read::infer_schema;
+use arrow2::io::parquet::read::read_metadata;
+use arrow2::io::parquet::read::FileReader;
 use common_configs::EthConfig;
 use common_configs::ExportConfig;
+use common_exceptions::Result;
+use common_storages::read_parquet;
 use ethetl::contexts::Context;
 use ethetl::contexts::ContextRef;
+use ethetl::manifest::ManifestFile;
+use ethetl::sinks::CommittedRange;
+use opendal::services::Fs;
+use opendal::Builder;
+use opendal::Operator;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
 
 pub fn create_config() -> EthConfig {
     // export PROVIDER_URI='<your-provider-uri>'
@@ -31,11 +54,169 @@ pub fn create_config() -> EthConfig {
             web3_batch_size: 50,
             syncing_interval_secs: 1,
             output_dir: "_test_output_dir".to_string(),
+            ..Default::default()
         },
         ..Default::default()
     }
 }
 
-pub async fn create_ctx(conf: &EthConfig) -> ContextRef {
+pub async fn create_ctx(conf: &EthConfig) -> Result<ContextRef> {
     Context::create(conf).await
 }
+
+// The exported files of the blocks 16600001-16600002 in tests/it/testdata.
+pub fn testdata_operator() -> Result<Arc<Operator>> {
+    let mut builder = Fs::default();
+    builder.root(&format!("{}/tests/it/testdata", env!("CARGO_MANIFEST_DIR")));
+    Ok(Arc::new(Operator::new(builder.build()?).finish()))
+}
+
+pub fn testdata_file(table: &str) -> ManifestFile {
+    ManifestFile {
+        path: format!("{}/{}_16600001_16600002.parquet", table, table),
+        start: 16600001,
+        end: 16600002,
+        ..Default::default()
+    }
+}
+
+pub fn testdata_range(tables: &[&str]) -> CommittedRange {
+    let files = tables
+        .iter()
+        .map(|table| (table.to_string(), testdata_file(table)))
+        .collect();
+    CommittedRange {
+        output_dir: "".to_string(),
+        start: 16600001,
+        end: 16600002,
+        files,
+    }
+}
+
+// The columns of a testdata table by name, the files have one row group.
+pub async fn testdata_table(
+    op: &Arc<Operator>,
+    table: &str,
+) -> Result<BTreeMap<String, Box<dyn Array>>> {
+    let (schema, mut chunks) = read_parquet(op.clone(), &testdata_file(table).path).await?;
+    let arrays = chunks.remove(0).into_arrays();
+    Ok(schema
+        .fields
+        .iter()
+        .map(|f| f.name.clone())
+        .zip(arrays)
+        .collect())
+}
+
+// Compare the schema and the rows of two parquet files, the footer metadata
+// differs by the export time.
+pub fn parquet_diff(old: &Path, new: &Path) {
+    let read = |path: &Path| {
+        let mut reader = File::open(path).unwrap();
+        let metadata = read_metadata(&mut reader).unwrap();
+        let schema = infer_schema(&metadata).unwrap();
+        let reader = FileReader::new(
+            reader,
+            metadata.row_groups,
+            schema.clone(),
+            None,
+            None,
+            None,
+        );
+        let chunks = reader.collect::<arrow2::error::Result<Vec<_>>>().unwrap();
+        (schema, chunks)
+    };
+    assert_eq!(read(old), read(new), "{} differs", new.display());
+}
+
+/// A request received by the mock HTTP server.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// A local HTTP/1.1 server answering every request with the JSON of the
+/// responder, the requests are recorded in order.
+pub struct MockHttpServer {
+    pub endpoint: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockHttpServer {
+    pub async fn start(responder: fn(&MockRequest) -> serde_json::Value) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    while let Some(request) = read_request(&mut stream).await {
+                        let body = responder(&request).to_string();
+                        recorded.lock().unwrap().push(request);
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        if stream.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        MockHttpServer { endpoint, requests }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> Option<MockRequest> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await.ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = BTreeMap::new();
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    let len = headers
+        .get("content-length")
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).await.ok()?;
+    Some(MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
@@ -0,0 +1,360 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use common_storages::list_files;
+use common_storages::read_parquet_metadata;
+use common_storages::write_parquet;
+use ethetl::compaction::plan_groups;
+use ethetl::compaction::Compactor;
+use ethetl::compaction::RangeFile;
+use ethetl::exporters::PathTemplate;
+use ethetl::exporters::PathVars;
+use ethetl::manifest::Lease;
+use ethetl::manifest::LeaseFile;
+use ethetl::manifest::ManifestFile;
+use ethetl::manifest::RangeCommit;
+use ethetl::manifest::TableManifest;
+use ethetl::schemas::FileMetadata;
+use opendal::services::Fs;
+use opendal::Builder;
+use opendal::Operator;
+
+fn range_file(start: u64, end: u64, size: u64) -> RangeFile {
+    RangeFile {
+        path: format!("blocks/blocks_{}_{}.parquet", start, end),
+        start,
+        end,
+        size,
+        partition: PathVars::new(),
+        schema_version: 1,
+    }
+}
+
+async fn write_blocks(op: &Arc<Operator>, path: &str, start: u64, end: u64) -> Result<()> {
+    let array = UInt64Array::from_vec((start..=end).collect());
+    let schema = Schema::from(vec![Field::new("number", array.data_type().clone(), true)]);
+    write_parquet(
+        op.clone(),
+        path,
+        schema,
+        Chunk::try_new(vec![array.boxed()])?,
+    )
+    .await?;
+    Ok(())
+}
+
+fn fs_operator(name: &str) -> Result<(std::path::PathBuf, Arc<Operator>)> {
+    let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&root.display().to_string());
+    Ok((root, Arc::new(Operator::new(builder.build()?).finish())))
+}
+
+fn group_ranges(groups: Vec<Vec<RangeFile>>) -> Vec<Vec<(u64, u64)>> {
+    groups
+        .iter()
+        .map(|x| x.iter().map(|f| (f.start, f.end)).collect())
+        .collect()
+}
+
+#[test]
+fn test_plan_groups() {
+    let files = vec![
+        range_file(0, 99, 10),
+        range_file(100, 199, 10),
+        range_file(200, 299, 10),
+        // Crosses the boundary 1000.
+        range_file(950, 1049, 10),
+        range_file(1050, 1149, 10),
+        range_file(1150, 1249, 10),
+        // Gap.
+        range_file(1300, 1399, 10),
+        range_file(1400, 1499, 10),
+    ];
+
+    assert_eq!(group_ranges(plan_groups(&files, 1000, 100)), vec![
+        vec![(0, 99), (100, 199), (200, 299)],
+        vec![(1050, 1149), (1150, 1249)],
+        vec![(1300, 1399), (1400, 1499)],
+    ]);
+
+    // Target size.
+    assert_eq!(group_ranges(plan_groups(&files, 1000, 20)), vec![
+        vec![(0, 99), (100, 199)],
+        vec![(1050, 1149), (1150, 1249)],
+        vec![(1300, 1399), (1400, 1499)],
+    ]);
+
+    // Files of different dates are never merged.
+    let mut files = vec![range_file(0, 99, 10), range_file(100, 199, 10)];
+    files[1]
+        .partition
+        .insert("date".to_string(), "2023-02-12".to_string());
+    assert!(plan_groups(&files, 1000, 100).is_empty());
+
+    // Nor files of different schema versions.
+    let mut files = vec![range_file(0, 99, 10), range_file(100, 199, 10)];
+    files[1].schema_version = 2;
+    assert!(plan_groups(&files, 1000, 100).is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_compact() -> Result<()> {
+    let (root, op) = fs_operator("mars_compaction")?;
+
+    for (start, end) in [(0, 9), (10, 19), (20, 29)] {
+        let path = format!("pub/blocks/blocks_{}_{}.parquet", start, end);
+        write_blocks(&op, &path, start, end).await?;
+        let commit = RangeCommit {
+            start,
+            end,
+            files: [("blocks".to_string(), path)].into_iter().collect(),
+            committed_at: String::new(),
+        };
+        commit.write(op.clone(), "pub").await?;
+    }
+
+    let compactor = Compactor::create(
+        op.clone(),
+        "pub",
+        &PathTemplate::default(),
+        1,
+        100,
+        1024 * 1024,
+    );
+    let report = compactor.compact().await?;
+    assert_eq!(report.tables["blocks"].merged_files, 1);
+    assert_eq!(report.tables["blocks"].files_after, 1);
+
+    let files = list_files(op.clone(), "pub/blocks").await?;
+    assert_eq!(files, vec!["pub/blocks/blocks_0_29.parquet".to_string()]);
+
+    let manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    assert_eq!(manifest.files.len(), 1);
+    assert_eq!(manifest.files[0].rows, 30);
+
+    // The originals had no footer metadata, the merged file is of version 1.
+    let metadata = read_parquet_metadata(op.clone(), "pub/blocks/blocks_0_29.parquet").await?;
+    let metadata = FileMetadata::from_map(&metadata)?.unwrap();
+    assert_eq!(metadata.schema_version, 1);
+    assert_eq!((metadata.start, metadata.end), (0, 29));
+    assert_eq!(metadata.chain_id, 1);
+
+    // The commit markers point at the merged file.
+    for commit in RangeCommit::list(op.clone(), "pub").await? {
+        assert_eq!(commit.files["blocks"], "pub/blocks/blocks_0_29.parquet");
+    }
+
+    // Nothing to merge.
+    let report = compactor.compact().await?;
+    assert_eq!(report.tables["blocks"].merged_files, 0);
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_compact_projection() -> Result<()> {
+    let (root, op) = fs_operator("mars_compaction_projection")?;
+
+    // The column projection leaves out `number`.
+    let mut manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    for (start, end) in [(0, 9), (10, 19), (20, 29)] {
+        let path = format!("pub/blocks/blocks_{}_{}.parquet", start, end);
+        let array = Utf8Array::<i32>::from_iter_values((start..=end).map(|x| format!("0x{:x}", x)));
+        let schema = Schema::from(vec![Field::new("hash", array.data_type().clone(), true)]);
+        write_parquet(
+            op.clone(),
+            &path,
+            schema,
+            Chunk::try_new(vec![array.boxed()])?,
+        )
+        .await?;
+        manifest.append(ManifestFile {
+            path,
+            start,
+            end,
+            rows: end - start + 1,
+            min_block: Some(start),
+            max_block: Some(end),
+            ..Default::default()
+        });
+    }
+    manifest.write(op.clone(), "pub").await?;
+
+    let compactor = Compactor::create(
+        op.clone(),
+        "pub",
+        &PathTemplate::default(),
+        1,
+        100,
+        1024 * 1024,
+    );
+    let report = compactor.compact().await?;
+    assert_eq!(report.tables["blocks"].merged_files, 1);
+
+    let files = list_files(op.clone(), "pub/blocks").await?;
+    assert_eq!(files, vec!["pub/blocks/blocks_0_29.parquet".to_string()]);
+
+    // The blocks are the ones of the originals.
+    let manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    assert_eq!(manifest.files.len(), 1);
+    assert_eq!(manifest.files[0].rows, 30);
+    assert_eq!(manifest.files[0].min_block, Some(0));
+    assert_eq!(manifest.files[0].max_block, Some(29));
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_compact_recover() -> Result<()> {
+    let (root, op) = fs_operator("mars_compaction_recover")?;
+    let compactor = Compactor::create(
+        op.clone(),
+        "pub",
+        &PathTemplate::default(),
+        1,
+        100,
+        1024 * 1024,
+    );
+
+    // Without the manifest the covering file with the rows of the others is kept.
+    write_blocks(&op, "pub/blocks/blocks_0_9.parquet", 0, 9).await?;
+    write_blocks(&op, "pub/blocks/blocks_10_19.parquet", 10, 19).await?;
+    write_blocks(&op, "pub/blocks/blocks_0_19.parquet", 0, 19).await?;
+    let report = compactor.compact().await?;
+    assert_eq!(report.tables["blocks"].removed_files, 2);
+    let files = list_files(op.clone(), "pub/blocks").await?;
+    assert_eq!(files, vec!["pub/blocks/blocks_0_19.parquet".to_string()]);
+
+    // A covering file missing rows is removed.
+    write_blocks(&op, "pub/blocks/blocks_20_29.parquet", 20, 29).await?;
+    write_blocks(&op, "pub/blocks/blocks_20_39.parquet", 20, 25).await?;
+    compactor.compact().await?;
+    let files = list_files(op.clone(), "pub/blocks").await?;
+    assert_eq!(files, vec!["pub/blocks/blocks_0_29.parquet".to_string()]);
+
+    // The originals of a merged file in the manifest are removed from it.
+    std::fs::remove_dir_all(&root)?;
+    write_blocks(&op, "pub/blocks/blocks_0_9.parquet", 0, 9).await?;
+    write_blocks(&op, "pub/blocks/blocks_0_19.parquet", 0, 19).await?;
+    let mut manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    for (start, end) in [(0, 9), (0, 19)] {
+        manifest.append(ManifestFile {
+            path: format!("pub/blocks/blocks_{}_{}.parquet", start, end),
+            start,
+            end,
+            ..Default::default()
+        });
+    }
+    manifest.write(op.clone(), "pub").await?;
+    compactor.compact().await?;
+    let manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    assert_eq!(manifest.files.len(), 1);
+    assert_eq!(manifest.files[0].path, "pub/blocks/blocks_0_19.parquet");
+    assert!(
+        !op.object("pub/blocks/blocks_0_9.parquet")
+            .is_exist()
+            .await?
+    );
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_compact_lease() -> Result<()> {
+    let (root, op) = fs_operator("mars_compaction_lease")?;
+    let compactor = Compactor::create(
+        op.clone(),
+        "pub",
+        &PathTemplate::default(),
+        1,
+        100,
+        1024 * 1024,
+    );
+
+    // Not while an exporter writes the output dir.
+    let writer = Lease::writer(op.clone(), "pub").await?;
+    assert!(compactor.compact().await.is_err());
+    writer.release().await?;
+    compactor.compact().await?;
+
+    // Nor while another compaction runs, the exporters wait for it.
+    let compaction = Lease::compaction(op.clone(), "pub").await?;
+    assert!(compactor.compact().await.is_err());
+    let writer = tokio::spawn({
+        let op = op.clone();
+        async move { Lease::writer(op, "pub").await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!writer.is_finished());
+    compaction.release().await?;
+    writer.await??.release().await?;
+
+    // The object storages have no conditional put, the leases are listed.
+    let memory = Arc::new(init_memory_operator()?);
+    let lease = Lease::try_acquire(memory.clone(), "pub", "compaction")
+        .await?
+        .unwrap();
+    assert!(
+        Lease::try_acquire(memory.clone(), "pub", "compaction")
+            .await?
+            .is_none()
+    );
+    lease.release().await?;
+    Lease::acquire(memory.clone(), "pub", "compaction")
+        .await?
+        .release()
+        .await?;
+
+    // An expired lease is removed, one that can't be read is kept.
+    let expired = LeaseFile {
+        owner: "crashed".to_string(),
+        expires_at: 0,
+    };
+    let path = format!("{}/crashed.json", Lease::dir("pub", "compaction"));
+    memory
+        .object(&path)
+        .write(serde_json::to_vec(&expired)?)
+        .await?;
+    let lease = Lease::compaction(memory.clone(), "pub").await?;
+    lease.check()?;
+    lease.release().await?;
+    assert!(
+        list_files(memory.clone(), &Lease::dir("pub", "compaction"))
+            .await?
+            .is_empty()
+    );
+
+    memory.object(&path).write(b"{".to_vec()).await?;
+    assert!(Lease::compaction(memory.clone(), "pub").await.is_err());
+    assert_eq!(
+        list_files(memory.clone(), &Lease::dir("pub", "compaction")).await?,
+        vec![path]
+    );
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
@@ -0,0 +1,224 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use common_configs::DatabendSinkConfig;
+use common_configs::HashEncoding;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use ethetl::exporters::ColumnProjection;
+use ethetl::manifest::ManifestFile;
+use ethetl::manifest::TableManifest;
+use ethetl::sinks::CommittedRange;
+use ethetl::sinks::DatabendClient;
+use ethetl::sinks::DatabendSink;
+use ethetl::sinks::LoadState;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::common::MockHttpServer;
+use crate::common::MockRequest;
+
+fn databend_responder(request: &MockRequest) -> Value {
+    if request.path == "/v1/streaming_load" {
+        return json!({"id": "load", "state": "SUCCESS", "stats": {"rows": 10}, "error": null});
+    }
+    if request.path == "/v1/query/1/page/1" {
+        return json!({"id": "1", "state": "Succeeded", "error": null, "data": [["2"]], "next_uri": null});
+    }
+    if request.json()["sql"] == "SELECT number FROM numbers(2)" {
+        return json!({"id": "1", "state": "Running", "error": null, "data": [["1"]], "next_uri": "/v1/query/1/page/1"});
+    }
+    json!({"id": "0", "state": "Succeeded", "error": null, "data": [], "next_uri": null})
+}
+
+fn statements(requests: &[MockRequest]) -> Vec<String> {
+    requests
+        .iter()
+        .map(|x| match x.path.as_str() {
+            "/v1/query" => x.json()["sql"].as_str().unwrap().to_string(),
+            "/v1/streaming_load" => format!("LOAD {}", x.headers["insert_sql"]),
+            path => format!("{} {}", x.method, path),
+        })
+        .collect()
+}
+
+fn blocks_file(start: u64, end: u64) -> ManifestFile {
+    ManifestFile {
+        path: format!("pub/blocks/blocks_{}_{}.parquet", start, end),
+        start,
+        end,
+        rows: end - start + 1,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_databend_client_debug() -> Result<()> {
+    let client = DatabendClient::create(&DatabendSinkConfig {
+        password: "databend_secret".to_string(),
+        ..Default::default()
+    })?;
+    let debug = format!("{:?}", client);
+    assert!(debug.contains("password: \"******ret\""));
+    assert!(!debug.contains("databend_secret"));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_databend_client_pages() -> Result<()> {
+    let server = MockHttpServer::start(databend_responder).await;
+    let client = DatabendClient::create(&DatabendSinkConfig {
+        endpoint: server.endpoint.clone(),
+        ..Default::default()
+    })?;
+
+    let rows = client.query("SELECT number FROM numbers(2)").await?;
+    assert_eq!(rows, vec![json!(["1"]), json!(["2"])]);
+    assert_eq!(server.requests()[1].method, "GET");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_databend_sink() -> Result<()> {
+    let server = MockHttpServer::start(databend_responder).await;
+    let op = Arc::new(init_memory_operator()?);
+    let range = |start: u64, end: u64| CommittedRange {
+        output_dir: "pub".to_string(),
+        start,
+        end,
+        files: BTreeMap::from([("blocks".to_string(), blocks_file(start, end))]),
+    };
+
+    let mut manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    manifest.append(blocks_file(0, 9));
+    manifest.write(op.clone(), "pub").await?;
+    op.object("pub/blocks/blocks_0_9.parquet")
+        .write(b"PAR1".to_vec())
+        .await?;
+
+    // Streaming load.
+    let sink = DatabendSink::create(
+        op.clone(),
+        DatabendSinkConfig {
+            endpoint: server.endpoint.clone(),
+            ..Default::default()
+        },
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+    sink.commit(&range(0, 9)).await?;
+    let requests = server.requests();
+    let sqls = statements(&requests);
+    assert_eq!(sqls[0], "CREATE DATABASE IF NOT EXISTS eth;");
+    assert!(sqls[1].contains("CREATE TABLE IF NOT EXISTS eth.blocks"));
+    assert_eq!(sqls.len(), 8);
+    assert_eq!(
+        sqls[7],
+        "LOAD INSERT INTO eth.blocks FILE_FORMAT = (type = 'PARQUET')"
+    );
+    let body = String::from_utf8_lossy(&requests[7].body);
+    assert!(body.contains("filename=\"blocks_0_9.parquet\""));
+    assert!(body.contains("PAR1"));
+
+    let state = LoadState::read(op.clone(), "pub", "databend").await?;
+    assert!(state.contains("blocks", 1));
+
+    // Loaded already.
+    sink.commit(&range(0, 9)).await?;
+    assert_eq!(server.requests().len(), 8);
+
+    // COPY INTO from the stage, only the new file.
+    let mut manifest = TableManifest::read(op.clone(), "pub", "blocks").await?;
+    manifest.append(blocks_file(10, 19));
+    manifest.write(op.clone(), "pub").await?;
+    let sink = DatabendSink::create(
+        op.clone(),
+        DatabendSinkConfig {
+            endpoint: server.endpoint.clone(),
+            stage: "eth_stage".to_string(),
+            create_tables: false,
+            ..Default::default()
+        },
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+    sink.commit(&range(10, 19)).await?;
+    let sqls = statements(&server.requests());
+    assert_eq!(sqls[8..].to_vec(), vec![
+        "COPY INTO eth.blocks FROM @eth_stage FILES = ('blocks/blocks_10_19.parquet') FILE_FORMAT = (type = 'PARQUET')"
+    ]);
+
+    sink.rollback(&RolledBack {
+        output_dir: "pub".to_string(),
+        from_block: 10,
+        files: BTreeMap::from([("blocks".to_string(), vec![blocks_file(10, 19)])]),
+    })
+    .await?;
+    let sqls = statements(&server.requests());
+    assert_eq!(sqls[9..].to_vec(), vec![
+        "DELETE FROM eth.blocks WHERE number >= 10"
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_load_state() -> Result<()> {
+    let mut state = LoadState::default();
+    state.insert("logs", 2);
+    assert!(!state.contains("logs", 1));
+    assert!(state.contains("logs", 2));
+    state.insert("logs", 1);
+    assert_eq!(state.tables["logs"].through, 2);
+    assert!(state.tables["logs"].above.is_empty());
+
+    let mut manifest = TableManifest::create("logs");
+    for (start, end) in [(0, 9), (10, 19), (20, 29)] {
+        manifest.append(ManifestFile {
+            path: format!("pub/logs/logs_{}_{}.parquet", start, end),
+            start,
+            end,
+            ..Default::default()
+        });
+    }
+    let pending = state.pending(&manifest)?;
+    assert_eq!(pending.iter().map(|x| x.seq).collect::<Vec<_>>(), vec![3]);
+
+    // The merged files of loaded files are loaded.
+    manifest.replace(
+        &[
+            "pub/logs/logs_0_9.parquet".to_string(),
+            "pub/logs/logs_10_19.parquet".to_string(),
+        ],
+        vec![ManifestFile {
+            path: "pub/logs/logs_0_19.parquet".to_string(),
+            start: 0,
+            end: 19,
+            ..Default::default()
+        }],
+    );
+    assert_eq!(state.pending(&manifest)?.len(), 1);
+    assert!(state.contains("logs", 4));
+
+    // The seqs of the rolled back and merged files are skipped.
+    manifest.remove_from(20);
+    assert!(state.pending(&manifest)?.is_empty());
+    assert_eq!(state.tables["logs"].through, 4);
+    assert!(state.tables["logs"].above.is_empty());
+    Ok(())
+}
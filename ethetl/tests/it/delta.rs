@@ -0,0 +1,369 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_configs::HashEncoding;
+use common_exceptions::Result;
+use common_storages::delta_schema_string;
+use common_storages::init_memory_operator;
+use common_storages::list_files;
+use common_storages::DeltaFile;
+use common_storages::DeltaTable;
+use common_storages::CHECKPOINT_VERSIONS;
+use ethetl::etl::find_fork;
+use ethetl::exporters::ColumnProjection;
+use ethetl::exporters::PathTemplate;
+use ethetl::manifest::ManifestFile;
+use ethetl::sinks::CommittedRange;
+use ethetl::sinks::DeltaSink;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+use opendal::services::Fs;
+use opendal::Builder;
+use opendal::Operator;
+use serde_json::Value;
+
+fn fs_operator(name: &str) -> Result<(std::path::PathBuf, Arc<Operator>)> {
+    let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&root.display().to_string());
+    Ok((root, Arc::new(Operator::new(builder.build()?).finish())))
+}
+
+// The actions of a log version, by action name.
+async fn read_log(op: &Operator, path: &str) -> Result<Vec<(String, Value)>> {
+    let data = op.object(path).read().await?;
+    let mut actions = vec![];
+    for line in String::from_utf8(data)?.lines() {
+        let action: BTreeMap<String, Value> = serde_json::from_str(line)?;
+        actions.extend(action);
+    }
+    Ok(actions)
+}
+
+fn names(actions: &[(String, Value)]) -> Vec<&str> {
+    actions.iter().map(|x| x.0.as_str()).collect()
+}
+
+fn delta_file(path: &str, size: u64) -> DeltaFile {
+    DeltaFile {
+        path: path.to_string(),
+        size,
+        rows: 10,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_delta_schema_string() -> Result<()> {
+    let schema = Schema::from(vec![
+        Field::new("number", DataType::UInt64, false),
+        Field::new("transaction_count", DataType::UInt32, false),
+        Field::new("value", DataType::Int64, false),
+    ]);
+    let fields: Value = serde_json::from_str(&delta_schema_string(&schema)?)?;
+    let types = fields["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["type"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(types, vec!["decimal(20,0)", "long", "long"]);
+
+    // No Delta type.
+    let schema = Schema::from(vec![Field::new("x", DataType::Float16, false)]);
+    assert!(delta_schema_string(&schema).is_err());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_delta_table() -> Result<()> {
+    let (root, op) = fs_operator("mars_delta_table")?;
+    let schema = Schema::from(vec![Field::new("number", DataType::UInt64, true)]);
+
+    let table = DeltaTable::create(op.clone(), "pub/blocks");
+    assert_eq!(table.version().await?, None);
+    assert_eq!(
+        table
+            .append(&schema, &[delta_file("a.parquet", 100)])
+            .await?,
+        Some(0)
+    );
+    let actions = read_log(&op, &table.log_path(0)).await?;
+    assert_eq!(names(&actions), vec![
+        "protocol",
+        "metaData",
+        "add",
+        "commitInfo"
+    ]);
+    assert_eq!(
+        actions[1].1["schemaString"],
+        r#"{"fields":[{"metadata":{},"name":"number","nullable":true,"type":"decimal(20,0)"}],"type":"struct"}"#
+    );
+    assert_eq!(actions[2].1["path"], "a.parquet");
+    assert_eq!(actions[2].1["dataChange"], true);
+
+    // Committed again after a retry.
+    assert_eq!(
+        table
+            .append(&schema, &[delta_file("a.parquet", 100)])
+            .await?,
+        None
+    );
+    assert_eq!(
+        table
+            .append(&schema, &[delta_file("b.parquet", 100)])
+            .await?,
+        Some(1)
+    );
+    assert_eq!(names(&read_log(&op, &table.log_path(1)).await?), vec![
+        "add",
+        "commitInfo"
+    ]);
+
+    // Another writer replays the log.
+    let table = DeltaTable::create(op.clone(), "pub/blocks");
+    assert_eq!(table.version().await?, Some(1));
+    let files = table.files().await?;
+    assert_eq!(files.keys().collect::<Vec<_>>(), vec![
+        "a.parquet",
+        "b.parquet"
+    ]);
+
+    // A version written by another writer.
+    let other = DeltaTable::create(op.clone(), "pub/blocks");
+    other
+        .remove(&["a.parquet".to_string()], "number >= 0")
+        .await?;
+    assert!(
+        table
+            .remove(&["b.parquet".to_string()], "number >= 10")
+            .await
+            .is_err()
+    );
+
+    let table = DeltaTable::create(op.clone(), "pub/blocks");
+    assert_eq!(
+        table
+            .replace(&["b.parquet".to_string()], &delta_file("ab.parquet", 150))
+            .await?,
+        Some(3)
+    );
+    let actions = read_log(&op, &table.log_path(3)).await?;
+    assert_eq!(names(&actions), vec!["remove", "add", "commitInfo"]);
+    assert_eq!(actions[0].1["dataChange"], false);
+    assert_eq!(table.files().await?.keys().collect::<Vec<_>>(), vec![
+        "ab.parquet"
+    ]);
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_delta_sink() -> Result<()> {
+    let (root, op) = fs_operator("mars_delta_sink")?;
+    let sink = DeltaSink::create(
+        op.clone(),
+        &PathTemplate::default(),
+        1,
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+
+    let file = |start: u64, end: u64| ManifestFile {
+        path: format!("pub/blocks/blocks_{}_{}.parquet", start, end),
+        start,
+        end,
+        rows: end - start + 1,
+        size: 100,
+        min_block: Some(start),
+        max_block: Some(end),
+        ..Default::default()
+    };
+    for (start, end) in [(0, 9), (10, 19)] {
+        sink.commit(&CommittedRange {
+            output_dir: "pub".to_string(),
+            start,
+            end,
+            files: BTreeMap::from([("blocks".to_string(), file(start, end))]),
+        })
+        .await?;
+    }
+
+    let table = sink.table("pub", "blocks");
+    assert_eq!(table.table_root(), "pub/blocks");
+    let actions = read_log(&op, &table.log_path(0)).await?;
+    assert_eq!(actions[2].1["path"], "blocks_0_9.parquet");
+    let stats: Value = serde_json::from_str(actions[2].1["stats"].as_str().unwrap())?;
+    assert_eq!(stats["numRecords"], 10);
+    assert_eq!(stats["minValues"]["number"], 0);
+    assert_eq!(stats["maxValues"]["number"], 9);
+
+    // The reorged range.
+    sink.rollback(&RolledBack {
+        output_dir: "pub".to_string(),
+        from_block: 10,
+        files: BTreeMap::from([("blocks".to_string(), vec![file(10, 19)])]),
+    })
+    .await?;
+    let actions = read_log(&op, &table.log_path(2)).await?;
+    assert_eq!(names(&actions), vec!["remove", "commitInfo"]);
+    assert_eq!(actions[0].1["path"], "blocks_10_19.parquet");
+    assert_eq!(
+        actions[1].1["operationParameters"]["predicate"],
+        "number >= 10"
+    );
+    assert_eq!(table.files().await?.len(), 1);
+
+    // The table dirs are shared by the tables.
+    let template = PathTemplate::create("date={date}/{table}_{start}_{end}")?;
+    assert!(
+        DeltaSink::create(
+            op.clone(),
+            &template,
+            1,
+            HashEncoding::Hex,
+            ColumnProjection::default()
+        )
+        .is_err()
+    );
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_delta_sink_object_storage() -> Result<()> {
+    // The object storages have no conditional put, the commits hold the lease.
+    let op = Arc::new(init_memory_operator()?);
+    let create = || {
+        DeltaSink::create(
+            op.clone(),
+            &PathTemplate::default(),
+            1,
+            HashEncoding::Hex,
+            ColumnProjection::default(),
+        )
+    };
+    let (first, second) = (create()?, create()?);
+
+    let file = |start: u64, end: u64| ManifestFile {
+        path: format!("pub/blocks/blocks_{}_{}.parquet", start, end),
+        start,
+        end,
+        rows: end - start + 1,
+        size: 100,
+        ..Default::default()
+    };
+    let range = |start: u64, end: u64| CommittedRange {
+        output_dir: "pub".to_string(),
+        start,
+        end,
+        files: BTreeMap::from([("blocks".to_string(), file(start, end))]),
+    };
+    first.commit(&range(0, 9)).await?;
+    // The other writer replays the version of the first before its own.
+    second.commit(&range(10, 19)).await?;
+    first.commit(&range(20, 29)).await?;
+
+    let table = first.table("pub", "blocks");
+    assert_eq!(table.version().await?, Some(2));
+    assert_eq!(table.files().await?.len(), 3);
+    let actions = read_log(&op, &table.log_path(1)).await?;
+    assert_eq!(names(&actions), vec!["add", "commitInfo"]);
+    assert_eq!(actions[0].1["path"], "blocks_10_19.parquet");
+    // The lease is released.
+    assert!(list_files(op.clone(), "pub/_locks/delta").await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_delta_checkpoint() -> Result<()> {
+    let (root, op) = fs_operator("mars_delta_checkpoint")?;
+    let schema = Schema::from(vec![Field::new("number", DataType::UInt64, true)]);
+
+    let table = DeltaTable::create(op.clone(), "pub/blocks");
+    for i in 0..CHECKPOINT_VERSIONS {
+        let path = format!("{}.parquet", i);
+        table.append(&schema, &[delta_file(&path, 100)]).await?;
+    }
+    assert!(
+        op.object(&table.last_checkpoint_path())
+            .read()
+            .await
+            .is_err()
+    );
+
+    table
+        .remove(&["0.parquet".to_string()], "number >= 0")
+        .await?;
+    let last: Value =
+        serde_json::from_slice(&op.object(&table.last_checkpoint_path()).read().await?)?;
+    assert_eq!(last["version"], CHECKPOINT_VERSIONS);
+    // The protocol, the metaData and the live files.
+    assert_eq!(last["size"], CHECKPOINT_VERSIONS + 1);
+    assert!(
+        op.object(&table.checkpoint_path(CHECKPOINT_VERSIONS))
+            .stat()
+            .await
+            .is_ok()
+    );
+
+    // The log before the checkpoint is not needed to load the table.
+    table
+        .append(&schema, &[delta_file("a.parquet", 100)])
+        .await?;
+    for version in 0..CHECKPOINT_VERSIONS {
+        op.object(&table.log_path(version)).delete().await?;
+    }
+    let loaded = DeltaTable::create(op.clone(), "pub/blocks");
+    assert_eq!(loaded.version().await?, Some(CHECKPOINT_VERSIONS + 1));
+    assert_eq!(loaded.files().await?, table.files().await?);
+    assert!(!loaded.files().await?.contains_key("0.parquet"));
+
+    // The schema is kept, an append of the same schema adds no metaData.
+    let version = loaded
+        .append(&schema, &[delta_file("b.parquet", 100)])
+        .await?
+        .unwrap();
+    assert_eq!(
+        names(&read_log(&op, &loaded.log_path(version)).await?),
+        vec!["add", "commitInfo"]
+    );
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[test]
+fn test_find_fork() {
+    let exported = vec![
+        (12, "0xc2".to_string()),
+        (11, "0xb2".to_string()),
+        (10, "0xa".to_string()),
+    ];
+    let chain = BTreeMap::from([
+        (10, "0xa".to_string()),
+        (11, "0xb".to_string()),
+        (12, "0xc".to_string()),
+    ]);
+    assert_eq!(find_fork(&exported, &chain), Some(10));
+    assert_eq!(find_fork(&exported[..2], &chain), None);
+}
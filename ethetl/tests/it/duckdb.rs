@@ -0,0 +1,151 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use common_configs::DuckDBSinkConfig;
+use common_configs::HashEncoding;
+use common_configs::TableColumns;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use duckdb::Connection;
+use ethetl::exporters::ColumnProjection;
+use ethetl::sinks::DuckDBSink;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+
+use crate::common::testdata_operator;
+use crate::common::testdata_range;
+
+const TABLES: [&str; 6] = [
+    "blocks",
+    "ens",
+    "logs",
+    "receipts",
+    "token_transfers",
+    "transactions",
+];
+
+#[test]
+fn test_duckdb_sink_script() -> Result<()> {
+    let mut tables = BTreeMap::new();
+    tables.insert("blocks".to_string(), TableColumns {
+        include: vec!["number".to_string(), "hash".to_string()],
+        exclude: vec![],
+    });
+    let sink = DuckDBSink::create(
+        Arc::new(init_memory_operator()?),
+        DuckDBSinkConfig::default(),
+        HashEncoding::Hex,
+        ColumnProjection::create(tables),
+    )?;
+
+    let sql = sink.create_script()?;
+    assert!(sql.starts_with("CREATE SCHEMA IF NOT EXISTS eth;\n"));
+    assert!(sql.contains("CREATE TABLE IF NOT EXISTS eth.token_transfers"));
+    assert!(sql.ends_with(
+        "CREATE OR REPLACE MACRO eth.subtract_hours(ts, n) AS ts - INTERVAL (n) HOUR;\n"
+    ));
+
+    let files = vec![
+        ("blocks".to_string(), "/tmp/blocks.parquet".to_string()),
+        ("logs".to_string(), "/tmp/o'logs.parquet".to_string()),
+    ];
+    let sql = sink.append_script(&testdata_range(&["blocks", "logs"]), &files)?;
+    let lines = sql.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "BEGIN TRANSACTION;");
+    assert_eq!(
+        lines[1],
+        "DELETE FROM eth.blocks WHERE number BETWEEN 16600001 AND 16600002;"
+    );
+    assert_eq!(
+        lines[2],
+        "INSERT INTO eth.blocks (number, hash) SELECT number, hash FROM read_parquet('/tmp/blocks.parquet');"
+    );
+    assert_eq!(
+        lines[3],
+        "DELETE FROM eth.logs WHERE block_number BETWEEN 16600001 AND 16600002;"
+    );
+    assert!(lines[4].starts_with("INSERT INTO eth.logs (log_index, "));
+    assert!(lines[4].ends_with("FROM read_parquet('/tmp/o''logs.parquet');"));
+    assert_eq!(lines[5], "COMMIT;");
+
+    // The block column can't be excluded.
+    let mut tables = BTreeMap::new();
+    tables.insert("receipts".to_string(), TableColumns {
+        include: vec![],
+        exclude: vec!["block_number".to_string()],
+    });
+    let res = DuckDBSink::create(
+        Arc::new(init_memory_operator()?),
+        DuckDBSinkConfig::default(),
+        HashEncoding::Hex,
+        ColumnProjection::create(tables),
+    );
+    assert!(res.is_err());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_duckdb_sink() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("ethetl-duckdb-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("eth.duckdb").display().to_string();
+    let sink = DuckDBSink::create(
+        testdata_operator()?,
+        DuckDBSinkConfig {
+            path: path.clone(),
+            ..Default::default()
+        },
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+    let count = |table: &str| -> Result<i64> {
+        let conn = Connection::open(&path)?;
+        let sql = format!("SELECT count(*) FROM eth.{}", table);
+        Ok(conn.query_row(&sql, [], |row| row.get(0))?)
+    };
+
+    // Committed twice, the range replaces its rows.
+    sink.commit(&testdata_range(&TABLES)).await?;
+    let blocks = count("blocks")?;
+    let logs = count("logs")?;
+    assert_eq!(blocks, 2);
+    sink.commit(&testdata_range(&TABLES)).await?;
+    assert_eq!(count("blocks")?, blocks);
+    assert_eq!(count("logs")?, logs);
+
+    // The Databend examples run on the database.
+    let example = std::fs::read_to_string(format!(
+        "{}/../schemas/databend/3_example.sql",
+        env!("CARGO_MANIFEST_DIR")
+    ))?;
+    Connection::open(&path)?.execute_batch(&example)?;
+
+    sink.rollback(&RolledBack {
+        output_dir: "".to_string(),
+        from_block: 16600002,
+        files: testdata_range(&TABLES)
+            .files
+            .into_iter()
+            .map(|(k, v)| (k, vec![v]))
+            .collect(),
+    })
+    .await?;
+    assert_eq!(count("blocks")?, 1);
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
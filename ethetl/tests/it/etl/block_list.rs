@@ -0,0 +1,65 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use common_exceptions::Result;
+use ethetl::etl::group_block_ranges;
+use ethetl::etl::parse_block_list;
+
+#[test]
+fn test_parse_block_list() -> Result<()> {
+    let content = "
+# reorged
+16600005
+16600001-16600003, 16600003
+16600010..16600012 16600020..=16600020
+";
+    let numbers = parse_block_list(content)?;
+    assert_eq!(
+        vec![
+            16600001, 16600002, 16600003, 16600005, 16600010, 16600011, 16600020
+        ],
+        numbers
+    );
+
+    assert!(parse_block_list("16600003-16600001").is_err());
+    assert!(parse_block_list("16600001..16600001").is_err());
+    assert!(parse_block_list("abc").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_group_block_ranges() -> Result<()> {
+    // Never exported, by the batches from the multiples of the batch size.
+    let numbers = vec![1, 2, 3, 4, 5, 7, 10];
+    let ranges = group_block_ranges(&numbers, &[], 3, 100);
+    assert_eq!(
+        vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8], vec![9, 10, 11]],
+        ranges
+    );
+
+    // The exported files are rewritten whole, the others don't overlap them.
+    let exported = vec![(1, 3), (4, 6), (11, 12)];
+    let ranges = group_block_ranges(&[2, 5, 6, 8, 10, 13], &exported, 3, 100);
+    assert_eq!(
+        vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8], vec![9, 10], vec![
+            13, 14
+        ]],
+        ranges
+    );
+
+    // Not past the chain head.
+    assert_eq!(vec![vec![9, 10]], group_block_ranges(&[10], &[], 3, 10));
+
+    assert!(group_block_ranges(&[], &exported, 3, 100).is_empty());
+    Ok(())
+}
@@ -0,0 +1,135 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use common_configs::EthConfig;
+use common_configs::ExportConfig;
+use common_configs::StorageConfig;
+use common_configs::StorageType;
+use common_exceptions::Result;
+use ethetl::contexts::Context;
+use ethetl::etl::parse_timestamp;
+use ethetl::etl::DateRange;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::common::MockHttpServer;
+use crate::common::MockRequest;
+
+#[test]
+fn test_parse_timestamp() -> Result<()> {
+    let now = 1677628800;
+
+    assert_eq!(1677628800, parse_timestamp("2023-03-01", now)?);
+    assert_eq!(1677657600, parse_timestamp("2023-03-01 08:00:00", now)?);
+    assert_eq!(1677657600, parse_timestamp("2023-03-01T08:00:00Z", now)?);
+    assert_eq!(
+        1677628800,
+        parse_timestamp("2023-03-01T08:00:00+08:00", now)?
+    );
+    assert_eq!(now, parse_timestamp("now", now)?);
+    assert_eq!(now - 6 * 3600, parse_timestamp("6h", now)?);
+    assert_eq!(now - 6 * 3600, parse_timestamp("-6h", now)?);
+    assert_eq!(now - 30 * 60, parse_timestamp("30m", now)?);
+    assert_eq!(now - 7 * 86400, parse_timestamp("7d", now)?);
+    assert_eq!(now - 14 * 86400, parse_timestamp("2w", now)?);
+
+    assert!(parse_timestamp("6x", now).is_err());
+    assert!(parse_timestamp("h", now).is_err());
+    assert!(parse_timestamp("", now).is_err());
+    assert!(parse_timestamp("9223372036854775807w", now).is_err());
+    assert!(parse_timestamp("9223372036854775807s", -now).is_err());
+    Ok(())
+}
+
+// The head is block 1000 at the next full hour, a block every 12 seconds.
+fn chain_responder(request: &MockRequest) -> Value {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let head = now / 3600 * 3600 + 3600;
+    let call = request.json();
+    let result = match call["method"].as_str() {
+        Some("eth_blockNumber") => json!("0x3e8"),
+        _ => {
+            let number = u64::from_str_radix(
+                call["params"][0].as_str().unwrap().trim_start_matches("0x"),
+                16,
+            )
+            .unwrap();
+            let zero = |bytes: usize| json!(format!("0x{}", "00".repeat(bytes)));
+            json!({
+                "hash": zero(32),
+                "parentHash": zero(32),
+                "sha3Uncles": zero(32),
+                "miner": zero(20),
+                "stateRoot": zero(32),
+                "transactionsRoot": zero(32),
+                "receiptsRoot": zero(32),
+                "number": format!("{:#x}", number),
+                "gasUsed": "0x0",
+                "gasLimit": "0x0",
+                "extraData": "0x",
+                "logsBloom": zero(256),
+                "timestamp": format!("{:#x}", head - (1000 - number) * 12),
+                "difficulty": "0x0",
+                "totalDifficulty": "0x0",
+                "sealFields": [],
+                "uncles": [],
+                "transactions": [],
+                "size": "0x0",
+                "mixHash": zero(32),
+                "nonce": zero(8),
+            })
+        }
+    };
+    json!({"jsonrpc": "2.0", "id": call["id"], "result": result})
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_date_range_cache() -> Result<()> {
+    let server = MockHttpServer::start(chain_responder).await;
+    let conf = EthConfig {
+        export: ExportConfig {
+            provider_uri: server.endpoint.clone(),
+            start_date: "1h".to_string(),
+            ..Default::default()
+        },
+        storage: StorageConfig {
+            storage_type: StorageType::Memory,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let ctx = Context::create(&conf).await?;
+    let probes = || {
+        server
+            .requests()
+            .iter()
+            .filter(|x| x.json()["method"] == "eth_getBlockByNumber")
+            .count()
+    };
+
+    let (start, end) = DateRange::create(&ctx).resolve().await?;
+    assert!(start > 0 && start <= 1000);
+    assert_eq!(end, 1000);
+    let searched = probes();
+    assert!(searched >= 8);
+
+    // The relative date moved on a little, the search starts from the
+    // cached timestamps around it.
+    let (again, _) = DateRange::create(&ctx).resolve().await?;
+    assert!(again == start || again == start + 1);
+    assert!(probes() - searched <= 4);
+    Ok(())
+}
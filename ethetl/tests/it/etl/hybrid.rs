@@ -0,0 +1,117 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use common_configs::EthConfig;
+use common_configs::ExportConfig;
+use common_configs::StorageConfig;
+use common_configs::StorageType;
+use common_exceptions::Result;
+use ethetl::contexts::Context;
+use ethetl::etl::HybridEtl;
+use ethetl::etl::BACKFILL_STATUS_FILE;
+use ethetl::etl::SYNCING_STATUS_FILE;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::common::MockHttpServer;
+use crate::common::MockRequest;
+
+// The chain head is block 100, no block can be fetched.
+fn rpc_responder(request: &MockRequest) -> Value {
+    let reply = |call: &Value| {
+        let result = match call["method"].as_str() {
+            Some("eth_blockNumber") => json!("0x64"),
+            _ => Value::Null,
+        };
+        json!({"jsonrpc": "2.0", "id": call["id"], "result": result})
+    };
+    match request.json() {
+        Value::Array(calls) => Value::Array(calls.iter().map(reply).collect()),
+        call => reply(&call),
+    }
+}
+
+fn hybrid_config(provider_uri: &str) -> EthConfig {
+    EthConfig {
+        export: ExportConfig {
+            provider_uri: provider_uri.to_string(),
+            start_block: 49,
+            batch_size: 10,
+            syncing_interval_secs: 1,
+            max_reorg_depth: 0,
+            ..Default::default()
+        },
+        storage: StorageConfig {
+            storage_type: StorageType::Memory,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_hybrid_backfill_error() -> Result<()> {
+    let server = MockHttpServer::start(rpc_responder).await;
+    let conf = hybrid_config(&server.endpoint);
+    let ctx = Context::create(&conf).await?;
+
+    // The tail is ahead of the chain head and waits, the backfill of block 49
+    // fails once its retries are used up.
+    let op = ctx.get_storage();
+    op.object(SYNCING_STATUS_FILE)
+        .write(json!({"start": 150, "end": 200}).to_string())
+        .await?;
+    op.object(BACKFILL_STATUS_FILE)
+        .write(json!({"start": 50, "end": 200}).to_string())
+        .await?;
+
+    let hybrid = HybridEtl::create(ctx);
+    let res = tokio::time::timeout(Duration::from_secs(120), hybrid.start()).await;
+    assert!(
+        matches!(res, Ok(Err(_))),
+        "the backfill error fails the run"
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_hybrid_backfill_done() -> Result<()> {
+    let server = MockHttpServer::start(rpc_responder).await;
+    let conf = hybrid_config(&server.endpoint);
+    let ctx = Context::create(&conf).await?;
+
+    // Nothing is left to backfill, the run goes on as a plain stream.
+    let op = ctx.get_storage();
+    op.object(SYNCING_STATUS_FILE)
+        .write(json!({"start": 150, "end": 200}).to_string())
+        .await?;
+    op.object(BACKFILL_STATUS_FILE)
+        .write(json!({"start": 49, "end": 200}).to_string())
+        .await?;
+
+    let hybrid = HybridEtl::create(ctx);
+    let res = tokio::time::timeout(Duration::from_secs(3), hybrid.start()).await;
+    assert!(res.is_err(), "the tail keeps running");
+
+    let calls = server
+        .requests()
+        .iter()
+        .map(|x| x.json().to_string())
+        .filter(|x| x.contains("eth_getBlockByNumber"))
+        .count();
+    assert_eq!(calls, 0);
+    Ok(())
+}
@@ -0,0 +1,91 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use common_storages::write_parquet_chunks;
+use ethetl::etl::empty_file_bytes;
+use ethetl::etl::project_bytes;
+use opendal::Operator;
+
+const ROWS_PER_BLOCK: u64 = 20;
+
+// The file of the blocks, rows of a number and a hash each.
+async fn write_blocks(op: &Arc<Operator>, path: &str, start: u64, end: u64) -> Result<usize> {
+    let rows = (start * ROWS_PER_BLOCK..(end + 1) * ROWS_PER_BLOCK).collect::<Vec<_>>();
+    let numbers = UInt64Array::from_vec(rows.iter().map(|x| x / ROWS_PER_BLOCK).collect());
+    let hashes = Utf8Array::<i32>::from_iter_values(
+        rows.iter()
+            .map(|x| format!("0x{:064x}", x.wrapping_mul(0x9e37_79b9_7f4a_7c15))),
+    );
+    let schema = Schema::from(vec![
+        Field::new("block_number", numbers.data_type().clone(), true),
+        Field::new("hash", hashes.data_type().clone(), true),
+    ]);
+    let chunk = Chunk::try_new(vec![numbers.boxed(), hashes.boxed()])?;
+    let metadata = BTreeMap::from([("ethetl.table".to_string(), "logs".to_string())]);
+    let written = write_parquet_chunks(op.clone(), path, schema, vec![chunk], metadata).await?;
+    Ok(written.size as usize)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_project_bytes() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+
+    // A file per sampled block, as the plan writes them.
+    let mut sample_bytes = 0;
+    for number in 0..10 {
+        let path = format!("samples/logs_{}_{}.parquet", number * 100, number * 100);
+        sample_bytes += write_blocks(&op, &path, number * 100, number * 100).await?;
+    }
+    let empty = empty_file_bytes(op.clone(), "samples/logs_0_0.parquet").await?;
+    assert!(empty > 0 && empty < sample_bytes / 10);
+
+    // The range files of an export.
+    let (blocks, files) = (1000, 4);
+    let mut bytes = 0;
+    for i in 0..files {
+        let (start, end) = (i * 250, i * 250 + 249);
+        bytes += write_blocks(
+            &op,
+            &format!("logs/logs_{}_{}.parquet", start, end),
+            start,
+            end,
+        )
+        .await?;
+    }
+
+    let projected = project_bytes(sample_bytes, 10, empty, blocks as usize, files as usize);
+    let error = (projected as f64 - bytes as f64).abs() / bytes as f64;
+    assert!(
+        error < 0.1,
+        "projected {} bytes, written {} bytes",
+        projected,
+        bytes
+    );
+    // Without the file overhead counted once per file, it is off by far.
+    let naive = sample_bytes / 10 * blocks as usize;
+    assert!(naive as f64 > bytes as f64 * 1.2);
+
+    assert_eq!(0, project_bytes(0, 0, 0, blocks as usize, files as usize));
+    Ok(())
+}
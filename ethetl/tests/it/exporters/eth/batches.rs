@@ -0,0 +1,27 @@
+// Copyright 2022 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ethetl::exporters::eth::row_batches;
+use ethetl::exporters::eth::WRITE_BATCH_ROWS;
+
+#[test]
+fn test_row_batches() {
+    let rows = (0..WRITE_BATCH_ROWS * 2 + 1).collect::<Vec<_>>();
+    let lens = row_batches(&rows).map(|x| x.len()).collect::<Vec<_>>();
+    assert_eq!(lens, [WRITE_BATCH_ROWS, WRITE_BATCH_ROWS, 1]);
+
+    // The file of a range without rows is still written.
+    let lens = row_batches::<u64>(&[]).map(|x| x.len()).collect::<Vec<_>>();
+    assert_eq!(lens, [0]);
+}
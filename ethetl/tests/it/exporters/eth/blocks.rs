@@ -16,33 +16,37 @@ use std::path::Path;
 
 use common_exceptions::Result;
 use ethetl::exporters::eth::BlockExporter;
+use ethetl::exporters::BlockRange;
 
 use crate::common::create_config;
 use crate::common::create_ctx;
+use crate::common::parquet_diff;
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 async fn test_blocks_exporters() -> Result<()> {
     let conf = create_config();
-    let ctx = create_ctx(&conf).await;
+    let ctx = create_ctx(&conf).await?;
 
     let range_name = format!("{}_{}", conf.export.start_block, conf.export.end_block);
-    let range_path = format!("{}_{}", conf.export.start_block, conf.export.end_block);
-
     let range: Vec<usize> = (conf.export.start_block..conf.export.end_block + 1).collect();
 
     {
-        let exporter =
-            BlockExporter::create(&ctx, ctx.get_output_dir(), &range_path, range.to_vec());
+        let exporter = BlockExporter::create(
+            &ctx,
+            ctx.get_output_dir(),
+            &BlockRange::create(&range),
+            range.to_vec(),
+        );
         exporter.export().await?;
 
-        goldenfile::differs::binary_diff(
+        parquet_diff(
             Path::new(format!("tests/it/testdata/blocks/blocks_{range_name}.parquet").as_str()),
             Path::new(
                 format!("_datas/_test_output_dir/blocks/blocks_{range_name}.parquet").as_str(),
             ),
         );
 
-        goldenfile::differs::binary_diff(
+        parquet_diff(
             Path::new(
                 format!("tests/it/testdata/transactions/transactions_{range_name}.parquet")
                     .as_str(),
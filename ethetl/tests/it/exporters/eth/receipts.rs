@@ -20,16 +20,18 @@ use std::str::FromStr;
 
 use common_exceptions::Result;
 use ethetl::exporters::eth::ReceiptExporter;
+use ethetl::exporters::BlockRange;
 use web3::types::H256;
 
 use crate::common::create_config;
 use crate::common::create_ctx;
+use crate::common::parquet_diff;
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 async fn test_receipts_exporters() -> Result<()> {
     let conf = create_config();
     let range_name = format!("{}_{}", conf.export.start_block, conf.export.end_block);
-    let ctx = create_ctx(&conf).await;
+    let ctx = create_ctx(&conf).await?;
 
     let path = format!("tests/it/testdata/transactions/_transactions_hash_{range_name}.txt");
     let file = File::open(path)?;
@@ -41,26 +43,30 @@ async fn test_receipts_exporters() -> Result<()> {
         tx_hashes.push(H256::from_str(line_str).unwrap());
     }
 
-    let range_path = format!("{}_{}", conf.export.start_block, conf.export.end_block);
+    let range = BlockRange {
+        start: conf.export.start_block,
+        end: conf.export.end_block,
+        timestamp: None,
+    };
 
     {
         let exporter =
-            ReceiptExporter::create(&ctx, ctx.get_output_dir(), &range_path, tx_hashes.to_vec());
+            ReceiptExporter::create(&ctx, ctx.get_output_dir(), &range, tx_hashes.to_vec());
         exporter.export().await?;
 
-        goldenfile::differs::binary_diff(
+        parquet_diff(
             Path::new(format!("tests/it/testdata/receipts/receipts_{range_name}.parquet").as_str()),
             Path::new(
                 format!("_datas/_test_output_dir/receipts/receipts_{range_name}.parquet").as_str(),
             ),
         );
 
-        goldenfile::differs::binary_diff(
+        parquet_diff(
             Path::new(format!("tests/it/testdata/logs/logs_{range_name}.parquet").as_str()),
             Path::new(format!("_datas/_test_output_dir/logs/logs_{range_name}.parquet").as_str()),
         );
 
-        goldenfile::differs::binary_diff(
+        parquet_diff(
             Path::new(
                 format!("tests/it/testdata/token_transfers/token_transfers_{range_name}.parquet")
                     .as_str(),
@@ -73,7 +79,7 @@ async fn test_receipts_exporters() -> Result<()> {
             ),
         );
 
-        goldenfile::differs::binary_diff(
+        parquet_diff(
             Path::new(format!("tests/it/testdata/ens/ens_{range_name}.parquet").as_str()),
             Path::new(format!("_datas/_test_output_dir/ens/ens_{range_name}.parquet").as_str()),
         );
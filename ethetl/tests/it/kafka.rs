@@ -0,0 +1,155 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use common_configs::KafkaSinkConfig;
+use common_configs::RecordFormat;
+use common_exceptions::Result;
+use ethetl::sinks::KafkaProducer;
+use ethetl::sinks::KafkaRecord;
+use ethetl::sinks::KafkaSink;
+use ethetl::sinks::RETRACTED_FROM_HEADER;
+use serde_json::Value;
+
+use crate::common::testdata_file;
+use crate::common::testdata_operator;
+use crate::common::testdata_range;
+
+#[test]
+fn test_kafka_client_config() {
+    let mut conf = KafkaSinkConfig {
+        brokers: vec!["b1:9092".to_string(), "b2:9092".to_string()],
+        security_protocol: "sasl_ssl".to_string(),
+        sasl_mechanism: "SCRAM-SHA-512".to_string(),
+        sasl_username: "ethetl".to_string(),
+        sasl_password: "kafka_secret".to_string(),
+        ..Default::default()
+    };
+    conf.properties
+        .insert("linger.ms".to_string(), "50".to_string());
+    conf.properties
+        .insert("compression.type".to_string(), "zstd".to_string());
+
+    let config = KafkaProducer::client_config(&conf);
+    assert_eq!(config.get("bootstrap.servers"), Some("b1:9092,b2:9092"));
+    assert_eq!(config.get("enable.idempotence"), Some("true"));
+    assert_eq!(config.get("acks"), Some("all"));
+    assert_eq!(config.get("partitioner"), Some("murmur2_random"));
+    assert_eq!(config.get("sasl.mechanism"), Some("SCRAM-SHA-512"));
+    assert_eq!(config.get("ssl.ca.location"), None);
+    // The properties are set last.
+    assert_eq!(config.get("linger.ms"), Some("50"));
+    assert_eq!(config.get("compression.type"), Some("zstd"));
+
+    let debug = format!("{:?}", conf);
+    assert!(!debug.contains("kafka_secret"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_kafka_records() -> Result<()> {
+    let sink = KafkaSink::create(testdata_operator()?, KafkaSinkConfig::default())?;
+
+    let path = |table: &str| testdata_file(table).path;
+    let blocks = sink.records("blocks", &path("blocks"), None).await?;
+    assert!(blocks.iter().all(|x| x.topic == "eth.blocks"));
+    let mut keys = blocks
+        .iter()
+        .map(|x| String::from_utf8(x.key.clone().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, vec!["16600001", "16600002"]);
+    let block: Value = serde_json::from_slice(blocks[0].value.as_ref().unwrap())?;
+    assert_eq!(
+        block["number"].to_string().as_bytes(),
+        blocks[0].key.as_ref().unwrap().as_slice()
+    );
+
+    let transactions = sink
+        .records("transactions", &path("transactions"), None)
+        .await?;
+    assert!(!transactions.is_empty());
+    let tx: Value = serde_json::from_slice(transactions[0].value.as_ref().unwrap())?;
+    assert_eq!(
+        tx["hash"].as_str().unwrap().as_bytes(),
+        transactions[0].key.as_ref().unwrap().as_slice()
+    );
+
+    // The tombstones of the rolled back keys, once by key.
+    let tombstones = sink
+        .records("transactions", &path("transactions"), Some(16600002))
+        .await?;
+    assert!(tombstones.iter().all(|x| x.value.is_none()));
+    assert!(
+        tombstones
+            .iter()
+            .all(|x| x.headers == vec![(RETRACTED_FROM_HEADER.to_string(), b"16600002".to_vec())])
+    );
+    let tx_hashes = transactions
+        .iter()
+        .map(|x| x.key.clone())
+        .collect::<BTreeSet<_>>();
+    assert_eq!(tombstones.len(), tx_hashes.len());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_kafka_records_avro() -> Result<()> {
+    let sink = KafkaSink::create(testdata_operator()?, KafkaSinkConfig {
+        tables: vec!["blocks".to_string()],
+        format: RecordFormat::Avro,
+        ..Default::default()
+    })?;
+    let records = sink
+        .records("blocks", &testdata_file("blocks").path, None)
+        .await?;
+    assert_eq!(records.len(), 2);
+    let value = records[0].value.clone().unwrap();
+    assert_eq!(&value[..2], &[0xc3, 0x01]);
+    // The union branch of the block number, not null.
+    assert_eq!(value[10], 2);
+    Ok(())
+}
+
+// A local broker with the topics created automatically, e.g.
+// `KAFKA_BROKERS=127.0.0.1:9092 cargo test -- --ignored test_kafka_local_broker`.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[ignore]
+async fn test_kafka_local_broker() -> Result<()> {
+    let brokers = std::env::var("KAFKA_BROKERS").unwrap_or_else(|_| "127.0.0.1:9092".to_string());
+    let conf = KafkaSinkConfig {
+        brokers: brokers.split(',').map(|x| x.to_string()).collect(),
+        ..Default::default()
+    };
+    let producer = KafkaProducer::create(&conf)?;
+    producer
+        .send(&[KafkaRecord {
+            topic: "ethetl.test".to_string(),
+            key: Some(b"16600001".to_vec()),
+            value: Some(b"{}".to_vec()),
+            headers: vec![],
+        }])
+        .await?;
+
+    // The sink publishes the committed files.
+    let sink = KafkaSink::create(testdata_operator()?, conf)?;
+    sink.commit(&testdata_range(&[
+        "blocks",
+        "transactions",
+        "logs",
+        "token_transfers",
+    ]))
+    .await?;
+    Ok(())
+}
@@ -12,5 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod atomic_write;
+mod catalog;
+mod clickhouse;
+mod column_encoder;
 mod common;
+mod compaction;
+mod databend;
+mod delta;
+#[cfg(feature = "duckdb")]
+mod duckdb;
+mod etl;
 mod exporters;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod memory_budget;
+mod output_path;
+mod postgres;
+mod projection;
+#[cfg(feature = "query")]
+mod query;
+mod schemas;
+mod stdout;
+mod throttle;
+#[cfg(feature = "transform")]
+mod transforms;
+mod verify;
+mod webhook;
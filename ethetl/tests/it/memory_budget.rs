@@ -0,0 +1,68 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use common_exceptions::Result;
+use ethetl::contexts::MemoryBudget;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_memory_budget() -> Result<()> {
+    let budget = MemoryBudget::create(1024 * 1024);
+    assert_eq!(budget.available(), 1024 * 1024);
+
+    // The default estimate of a block fits.
+    let mut first = budget.reserve(1).await?;
+    first.resize(600 * 1024);
+    assert_eq!(budget.available(), 424 * 1024);
+
+    // The next range waits until the first one is written.
+    let mut waiting = {
+        let budget = budget.clone();
+        tokio::spawn(async move {
+            let second = budget.reserve(1).await?;
+            Ok::<_, common_exceptions::Error>(second.bytes())
+        })
+    };
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), &mut waiting)
+            .await
+            .is_err()
+    );
+
+    drop(first);
+    assert_eq!(waiting.await??, 512 * 1024);
+    assert_eq!(budget.available(), 1024 * 1024);
+
+    // Only the difference is taken, a range never waits once it has fetched.
+    let mut first = budget.reserve(1).await?;
+    let mut second = budget.reserve(1).await?;
+    first.resize(700 * 1024);
+    assert_eq!(budget.available(), 0);
+    second.resize(100 * 1024);
+    assert_eq!(budget.available(), 224 * 1024);
+    drop((first, second));
+    assert_eq!(budget.available(), 1024 * 1024);
+
+    // A range bigger than the budget takes all of it.
+    let all = budget.reserve(100).await?;
+    assert_eq!(all.bytes(), 1024 * 1024);
+    drop(all);
+
+    // No limit.
+    let unlimited = MemoryBudget::create(0);
+    let reservation = unlimited.reserve(1000).await?;
+    assert_eq!(reservation.bytes(), 0);
+    Ok(())
+}
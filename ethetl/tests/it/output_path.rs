@@ -0,0 +1,160 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_configs::EthConfig;
+use common_configs::ExportConfig;
+use common_configs::StorageConfig;
+use common_configs::StorageType;
+use common_configs::TableFormat;
+use common_exceptions::Result;
+use ethetl::contexts::Context;
+use ethetl::exporters::parse_range;
+use ethetl::exporters::BlockRange;
+use ethetl::exporters::PathTemplate;
+
+#[test]
+fn test_default_path_template() -> Result<()> {
+    let template = PathTemplate::default();
+    let range = BlockRange::create(&[100, 150, 199]);
+    let vars = range.vars("blocks", 1, 1000000);
+
+    let path = template.render("pub", &vars)?;
+    assert_eq!(path, "pub/blocks/blocks_100_199.parquet");
+    assert_eq!(template.table_dir("pub", "blocks", 1), "pub/blocks");
+
+    let parsed = template.parse("pub", "blocks", &path).unwrap();
+    assert_eq!(parse_range(&parsed), Some((100, 199)));
+
+    // The table name has the separator.
+    let parsed = template
+        .parse(
+            "",
+            "token_transfers",
+            "token_transfers/token_transfers_1_2.parquet",
+        )
+        .unwrap();
+    assert_eq!(parse_range(&parsed), Some((1, 2)));
+
+    assert!(
+        template
+            .parse(
+                "",
+                "transactions",
+                "transactions/_transactions_hash_1_2.txt"
+            )
+            .is_none()
+    );
+    assert!(
+        template
+            .parse("", "blocks", "blocks/blocks_1.parquet")
+            .is_none()
+    );
+    assert!(
+        template
+            .parse("", "blocks", "logs/logs_1_2.parquet")
+            .is_none()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_hive_path_template() -> Result<()> {
+    let template =
+        PathTemplate::create("chain={chain_id}/{table}/date={date}/{table}_{start}_{end}")?;
+    let range = BlockRange {
+        start: 16600001,
+        end: 16600002,
+        timestamp: Some(1676160011),
+    };
+    let vars = range.vars("logs", 1, 1000000);
+
+    let path = template.render("pub", &vars)?;
+    assert_eq!(
+        path,
+        "pub/chain=1/logs/date=2023-02-12/logs_16600001_16600002.parquet"
+    );
+    assert_eq!(template.table_dir("pub", "logs", 1), "pub/chain=1/logs");
+
+    let parsed = template.parse("pub", "logs", &path).unwrap();
+    assert_eq!(parsed["date"], "2023-02-12");
+    assert_eq!(parsed["chain_id"], "1");
+    assert_eq!(parse_range(&parsed), Some((16600001, 16600002)));
+
+    // The date is unknown before the blocks are fetched.
+    assert!(
+        template
+            .render("pub", &BlockRange::create(&[1, 2]).vars("logs", 1, 1000000))
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_block_bucket_path_template() -> Result<()> {
+    let template = PathTemplate::create("{table}/bucket={block_bucket}/{start}_{end}")?;
+    let vars = BlockRange::create(&[16600001, 16600100]).vars("blocks", 1, 1000000);
+
+    let path = template.render("", &vars)?;
+    assert_eq!(path, "/blocks/bucket=16000000/16600001_16600100.parquet");
+    assert_eq!(
+        parse_range(&template.parse("", "blocks", &path).unwrap()),
+        Some((16600001, 16600100))
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_invalid_path_template() {
+    assert!(PathTemplate::create("{table}/{table}_{begin}_{end}").is_err());
+    assert!(PathTemplate::create("{table}/{table}_{start}").is_err());
+    assert!(PathTemplate::create("{table}/{table}_{start}_{end").is_err());
+    // The tables of a range would share the path.
+    assert!(PathTemplate::create("date={date}/{start}_{end}").is_err());
+
+    // The context of the config is an error, not a panic.
+    let conf = EthConfig {
+        export: ExportConfig {
+            path_template: "{table}/{table}_{start}".to_string(),
+            ..Default::default()
+        },
+        storage: StorageConfig {
+            storage_type: StorageType::Memory,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(Context::create(&conf).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_context_without_sinks() -> Result<()> {
+    // The Delta tables need the {table} dir, only the export fails.
+    let conf = EthConfig {
+        export: ExportConfig {
+            path_template: "date={date}/{table}_{start}_{end}".to_string(),
+            table_format: TableFormat::Delta,
+            ..Default::default()
+        },
+        storage: StorageConfig {
+            storage_type: StorageType::Memory,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(Context::create(&conf).await.is_err());
+
+    let ctx = Context::create_without_sinks(&conf).await?;
+    assert!(ctx.get_sinks().is_empty());
+    Ok(())
+}
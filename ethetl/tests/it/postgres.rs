@@ -0,0 +1,214 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow2::array::Int64Array;
+use arrow2::array::PrimitiveArray;
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use arrow2::datatypes::TimeUnit;
+use common_configs::HashEncoding;
+use common_configs::PostgresSinkConfig;
+use common_configs::PostgresWriteMethod;
+use common_exceptions::Result;
+use ethetl::exporters::ColumnProjection;
+use ethetl::sinks::connect_postgres;
+use ethetl::sinks::copy_binary;
+use ethetl::sinks::insert_values;
+use ethetl::sinks::numeric;
+use ethetl::sinks::upsert_sql;
+use ethetl::sinks::PostgresSink;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+use tokio_postgres::Client;
+
+use crate::common::testdata_operator;
+use crate::common::testdata_range;
+
+#[test]
+fn test_postgres_rows() -> Result<()> {
+    let schema = Schema::from(vec![
+        Field::new("number", DataType::UInt64, true),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Second, None),
+            true,
+        ),
+        Field::new("value", DataType::Decimal(36, 18), true),
+        Field::new("nonce", DataType::Utf8, true),
+        Field::new("count", DataType::Int64, true),
+        Field::new("gas", DataType::UInt64, true),
+    ]);
+    let chunk = Chunk::try_new(vec![
+        UInt64Array::from_slice([1]).boxed(),
+        Int64Array::from_slice([946684801])
+            .to(DataType::Timestamp(TimeUnit::Second, None))
+            .boxed(),
+        PrimitiveArray::<i128>::from_slice([1_500_000_000_000_000_000])
+            .to(DataType::Decimal(36, 18))
+            .boxed(),
+        Utf8Array::<i32>::from_slice(["it's"]).boxed(),
+        Int64Array::from_slice([2]).boxed(),
+        // Above i64::MAX.
+        UInt64Array::from_slice([u64::MAX]).boxed(),
+    ])?;
+
+    assert_eq!(insert_values(&schema, &chunk)?, vec![
+        "(1, '2000-01-01 00:00:01', 1.5, 'it''s', 2, 18446744073709551615)"
+    ]);
+
+    let mut expected = b"PGCOPY\n\xff\r\n\0".to_vec();
+    expected.extend([0; 8]);
+    expected.extend(6i16.to_be_bytes());
+    expected.extend(10i32.to_be_bytes());
+    expected.extend(numeric(1, 0));
+    expected.extend(8i32.to_be_bytes());
+    expected.extend(1_000_000i64.to_be_bytes());
+    expected.extend(12i32.to_be_bytes());
+    expected.extend(numeric(1_500_000_000_000_000_000, 18));
+    expected.extend(4i32.to_be_bytes());
+    expected.extend(b"it's");
+    expected.extend(8i32.to_be_bytes());
+    expected.extend(2i64.to_be_bytes());
+    expected.extend(18i32.to_be_bytes());
+    expected.extend(numeric(u64::MAX as i128, 0));
+    expected.extend((-1i16).to_be_bytes());
+    assert_eq!(copy_binary(&schema, &chunk)?, expected);
+
+    // 1.5 is 1 and 5000 of base 10000.
+    let digits = |x: &[i16]| x.iter().flat_map(|d| d.to_be_bytes()).collect::<Vec<_>>();
+    assert_eq!(
+        numeric(1_500_000_000_000_000_000, 18),
+        digits(&[2, 0, 0, 18, 1, 5000])
+    );
+    assert_eq!(numeric(-1_000_000, 18), digits(&[1, -3, 0x4000, 18, 1]));
+    assert_eq!(numeric(0, 18), digits(&[0, 0, 0, 18]));
+    assert_eq!(numeric(1, 0), digits(&[1, 0, 0, 0, 1]));
+    // 18446744073709551615 is 1844 6744 0737 0955 1615 of base 10000.
+    assert_eq!(
+        numeric(u64::MAX as i128, 0),
+        digits(&[5, 4, 0, 0, 1844, 6744, 737, 955, 1615])
+    );
+    assert_eq!(
+        numeric(123_456_789_000_000_000_000_000, 18),
+        digits(&[3, 1, 0, 18, 12, 3456, 7890])
+    );
+    Ok(())
+}
+
+#[test]
+fn test_postgres_upsert_sql() {
+    assert_eq!(
+        upsert_sql(
+            "eth.receipts",
+            "_ethetl_receipts",
+            &["transaction_hash", "status"],
+            &["transaction_hash"]
+        ),
+        "INSERT INTO eth.receipts (transaction_hash, status) SELECT DISTINCT ON (transaction_hash) transaction_hash, status FROM _ethetl_receipts ON CONFLICT (transaction_hash) DO UPDATE SET status = EXCLUDED.status;"
+    );
+    assert!(
+        upsert_sql("eth.blocks", "_ethetl_blocks", &["number"], &["number"])
+            .ends_with("DO NOTHING;")
+    );
+}
+
+async fn count(conn: &Client, table: &str) -> Result<i64> {
+    let sql = format!("SELECT count(*) FROM ethetl_test.{}", table);
+    Ok(conn.query_one(sql.as_str(), &[]).await?.get(0))
+}
+
+// A local PostgreSQL from the libpq variables, e.g. `PGPASSWORD=postgres
+// cargo test -- --ignored test_postgres_sink`.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[ignore]
+async fn test_postgres_sink() -> Result<()> {
+    let env =
+        |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+    let conf = PostgresSinkConfig {
+        host: env("PGHOST", "127.0.0.1"),
+        port: env("PGPORT", "5432").parse()?,
+        user: env("PGUSER", "postgres"),
+        password: env("PGPASSWORD", ""),
+        database: env("PGDATABASE", "postgres"),
+        schema: "ethetl_test".to_string(),
+        ..Default::default()
+    };
+    let conn = connect_postgres(&conf).await?;
+    conn.batch_execute("DROP SCHEMA IF EXISTS ethetl_test CASCADE")
+        .await?;
+
+    let op = testdata_operator()?;
+    let range = testdata_range(&[
+        "blocks",
+        "transactions",
+        "receipts",
+        "logs",
+        "token_transfers",
+        "ens",
+    ]);
+
+    let sink = PostgresSink::create(
+        op.clone(),
+        conf.clone(),
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+    sink.commit(&range).await?;
+    assert_eq!(count(&conn, "blocks").await?, 2);
+    let logs = count(&conn, "logs").await?;
+    // Unique by the transaction hash and the log index.
+    assert_eq!(logs, 658);
+    let transactions = count(&conn, "transactions").await?;
+
+    // Exported again, by inserts.
+    let sink = PostgresSink::create(
+        op.clone(),
+        PostgresSinkConfig {
+            method: PostgresWriteMethod::Insert,
+            batch_rows: 7,
+            ..conf.clone()
+        },
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    )?;
+    sink.commit(&range).await?;
+    assert_eq!(count(&conn, "blocks").await?, 2);
+    assert_eq!(count(&conn, "logs").await?, logs);
+    assert_eq!(count(&conn, "transactions").await?, transactions);
+
+    sink.rollback(&RolledBack {
+        output_dir: "".to_string(),
+        from_block: 16600002,
+        files: range
+            .files
+            .iter()
+            .map(|(k, v)| (k.clone(), vec![v.clone()]))
+            .collect(),
+    })
+    .await?;
+    assert_eq!(count(&conn, "blocks").await?, 1);
+    let row = conn
+        .query_one("SELECT max(block_number) FROM ethetl_test.transactions", &[
+        ])
+        .await?;
+    assert_eq!(row.get::<_, i64>(0), 16600001);
+
+    conn.batch_execute("DROP SCHEMA ethetl_test CASCADE")
+        .await?;
+    Ok(())
+}
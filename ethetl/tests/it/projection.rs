@@ -0,0 +1,107 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use arrow2::array::Array;
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_configs::TableColumns;
+use common_exceptions::Result;
+use ethetl::exporters::ColumnProjection;
+
+fn transactions() -> (Schema, Chunk<Box<dyn Array>>) {
+    let number = UInt64Array::from_slice([1, 2]).boxed();
+    let hash = Utf8Array::<i32>::from_slice(["0x1", "0x2"]).boxed();
+    let input = Utf8Array::<i32>::from_slice(["0xaa", "0xbb"]).boxed();
+    let schema = Schema::from(vec![
+        Field::new("block_number", number.data_type().clone(), true),
+        Field::new("hash", hash.data_type().clone(), true),
+        Field::new("input", input.data_type().clone(), true),
+    ]);
+    (schema, Chunk::try_new(vec![number, hash, input]).unwrap())
+}
+
+fn names(schema: &Schema) -> Vec<&str> {
+    schema.fields.iter().map(|f| f.name.as_str()).collect()
+}
+
+#[test]
+fn test_projection() -> Result<()> {
+    let mut tables = BTreeMap::new();
+    tables.insert("transactions".to_string(), TableColumns {
+        include: vec![],
+        exclude: vec!["input".to_string()],
+    });
+    tables.insert("logs".to_string(), TableColumns {
+        include: vec!["address".to_string()],
+        exclude: vec![],
+    });
+    let projection = ColumnProjection::create(tables);
+
+    // Exclude.
+    let (schema, chunk) = transactions();
+    let (schema, chunk) = projection.project("transactions", schema, chunk)?;
+    assert_eq!(names(&schema), vec!["block_number", "hash"]);
+    assert_eq!(chunk.arrays().len(), 2);
+    assert_eq!(chunk.len(), 2);
+
+    // The tables not configured keep all columns.
+    let (schema, chunk) = transactions();
+    let (schema, chunk) = projection.project("blocks", schema, chunk)?;
+    assert_eq!(names(&schema), vec!["block_number", "hash", "input"]);
+    assert_eq!(chunk.arrays().len(), 3);
+
+    assert!(projection.selects("logs", "address"));
+    assert!(!projection.selects("logs", "data"));
+    assert!(projection.selects("receipts", "status"));
+    Ok(())
+}
+
+#[test]
+fn test_projection_include() -> Result<()> {
+    let mut tables = BTreeMap::new();
+    tables.insert("transactions".to_string(), TableColumns {
+        include: vec!["input".to_string(), "block_number".to_string()],
+        exclude: vec![],
+    });
+    let projection = ColumnProjection::create(tables);
+
+    // The table order is kept.
+    let (schema, chunk) = transactions();
+    let (schema, chunk) = projection.project("transactions", schema, chunk)?;
+    assert_eq!(names(&schema), vec!["block_number", "input"]);
+    let input = chunk.arrays()[1]
+        .as_any()
+        .downcast_ref::<Utf8Array<i32>>()
+        .unwrap();
+    assert_eq!(input.value(1), "0xbb");
+    Ok(())
+}
+
+#[test]
+fn test_projection_unknown_column() {
+    let mut tables = BTreeMap::new();
+    tables.insert("transactions".to_string(), TableColumns {
+        include: vec![],
+        exclude: vec!["gas_prices".to_string()],
+    });
+    let projection = ColumnProjection::create(tables);
+
+    let (schema, chunk) = transactions();
+    assert!(projection.project("transactions", schema, chunk).is_err());
+}
@@ -0,0 +1,137 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow2::array::UInt64Array;
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::DataType;
+use arrow2::datatypes::Field;
+use arrow2::datatypes::Schema;
+use common_exceptions::Result;
+use common_storages::write_parquet;
+use datafusion::arrow::array::Int64Array;
+use ethetl::exporters::eth::TABLES;
+use ethetl::exporters::PathTemplate;
+use ethetl::query::format_batches;
+use ethetl::query::split_statements;
+use ethetl::query::write_batches;
+use ethetl::query::LocalQuery;
+use opendal::services::Fs;
+use opendal::Builder;
+use opendal::Operator;
+
+fn testdata_query() -> Result<LocalQuery> {
+    let mut builder = Fs::default();
+    builder.root(&format!("{}/tests/it/testdata", env!("CARGO_MANIFEST_DIR")));
+    let op = Arc::new(Operator::new(builder.build()?).finish());
+    Ok(LocalQuery::create(
+        op,
+        "",
+        &PathTemplate::default(),
+        1,
+        "eth",
+        TABLES.iter().map(|x| x.to_string()).collect(),
+    ))
+}
+
+#[test]
+fn test_split_statements() {
+    assert_eq!(
+        split_statements("USE eth;\n-- a; comment\nSELECT ';' FROM t;\n\n-- the end\n"),
+        ["USE eth", "-- a; comment\nSELECT ';' FROM t"]
+    );
+    assert_eq!(split_statements("SELECT 1"), ["SELECT 1"]);
+    assert!(split_statements(" ; -- nothing").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_local_query() -> Result<()> {
+    let query = testdata_query()?;
+    let tables = query.register_tables().await?;
+    assert_eq!(tables.len(), 6);
+    assert!(tables.contains(&("blocks".to_string(), 2)));
+
+    let results = query
+        .run("SELECT count(*) AS c FROM blocks; SELECT count(*) AS c FROM eth.transactions")
+        .await?;
+    assert_eq!(results.len(), 2);
+    let count = |i: usize| {
+        results[i][0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0)
+    };
+    assert_eq!(count(0), 2);
+    assert!(count(1) > 0);
+
+    // The top NFT and top ENS examples.
+    let sql = std::fs::read_to_string(format!(
+        "{}/../schemas/databend/3_example.sql",
+        env!("CARGO_MANIFEST_DIR")
+    ))?;
+    let results = query.run(&sql).await?;
+    assert_eq!(results.len(), 2);
+
+    let res = query.run("USE other").await;
+    assert!(res.is_err());
+
+    // The output file by the extension.
+    let dir = std::env::temp_dir().join(format!("ethetl-query-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let results = query
+        .run("SELECT number, hash FROM blocks ORDER BY number")
+        .await?;
+    let path = dir.join("blocks.csv").display().to_string();
+    write_batches(&results[0], &path)?;
+    let csv = std::fs::read_to_string(&path)?;
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.starts_with("number,hash\n16600001,"));
+    assert!(write_batches(&results[0], &dir.join("blocks.txt").display().to_string()).is_err());
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+// The outputs of the transforms are tables too.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_local_query_transform_outputs() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("ethetl-query-output-{}", std::process::id()));
+    let mut builder = Fs::default();
+    builder.root(&dir.display().to_string());
+    let op = Arc::new(Operator::new(builder.build()?).finish());
+    let schema = Schema::from(vec![Field::new("block_number", DataType::UInt64, false)]);
+    for (start, end) in [(1, 2), (3, 4)] {
+        let column = UInt64Array::from_vec(vec![start, end]);
+        write_parquet(
+            op.clone(),
+            &format!("block_gas/block_gas_{}_{}.parquet", start, end),
+            schema.clone(),
+            Chunk::try_new(vec![column.boxed()])?,
+        )
+        .await?;
+    }
+
+    let query = LocalQuery::create(op, "", &PathTemplate::default(), 1, "eth", vec![
+        "blocks".to_string(),
+        "block_gas".to_string(),
+    ]);
+    let tables = query.register_tables().await?;
+    assert_eq!(tables, [("block_gas".to_string(), 4)]);
+    let results = query.run("SELECT max(block_number) FROM block_gas").await?;
+    assert_eq!(format_batches(&results[0])?.matches("| 4 ").count(), 1);
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
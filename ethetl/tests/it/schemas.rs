@@ -0,0 +1,252 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow2::array::Array;
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use arrow2::datatypes::DataType;
+use common_configs::HashEncoding;
+use common_configs::SqlDialect;
+use common_configs::TableColumns;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use common_storages::read_parquet_metadata;
+use common_storages::write_parquet;
+use common_storages::write_parquet_chunks;
+use ethetl::exporters::eth::TABLES;
+use ethetl::exporters::ColumnProjection;
+use ethetl::exporters::PathTemplate;
+use ethetl::schemas::table_schema;
+use ethetl::schemas::DdlGenerator;
+use ethetl::schemas::FileMetadata;
+use ethetl::schemas::SchemaMigration;
+use ethetl::schemas::BLOCKS;
+use ethetl::schemas::ENS;
+use ethetl::schemas::REGISTRY;
+use ethetl::schemas::SCHEMA_VERSION;
+
+#[test]
+fn test_registry_tables() -> Result<()> {
+    let names = REGISTRY.iter().map(|t| t.name).collect::<Vec<_>>();
+    assert_eq!(names, TABLES.to_vec());
+
+    for table in REGISTRY {
+        for key in table.keys.iter().chain(table.unique_key) {
+            assert!(table.column(key).is_some(), "{}.{}", table.name, key);
+        }
+    }
+
+    let blocks = table_schema("blocks")?;
+    let schema = blocks.arrow_schema(HashEncoding::Binary);
+    let receipts_root = schema
+        .fields
+        .iter()
+        .find(|f| f.name == "receipts_root")
+        .unwrap();
+    assert_eq!(receipts_root.data_type, DataType::FixedSizeBinary(32));
+    assert!(table_schema("uncles").is_err());
+
+    // All the columns are of the first version.
+    assert!(blocks.added_since(SCHEMA_VERSION).is_empty());
+    assert_eq!(blocks.added_since(0).len(), blocks.columns.len());
+    assert!(blocks.changed_since(1).is_empty());
+
+    // The version 2 changed the log index.
+    let logs = table_schema("logs")?;
+    let changed = logs.changed_since(1);
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].name, "log_index");
+    assert!(logs.changed_since(SCHEMA_VERSION).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_registry_chunk() -> Result<()> {
+    let arrays: Vec<Box<dyn Array>> = vec![
+        Utf8Array::<i32>::from_slice(["foo"]).boxed(),
+        UInt64Array::from_slice([1]).boxed(),
+    ];
+    // Too few arrays.
+    assert!(ENS.chunk(HashEncoding::Hex, arrays).is_err());
+
+    let arrays = ENS
+        .arrow_schema(HashEncoding::Hex)
+        .fields
+        .iter()
+        .map(|f| arrow2::array::new_empty_array(f.data_type.clone()))
+        .collect::<Vec<_>>();
+    let (schema, chunk) = ENS.chunk(HashEncoding::Hex, arrays.clone())?;
+    assert_eq!(schema.fields.len(), 6);
+    assert_eq!(chunk.len(), 0);
+
+    // Hex arrays are not the binary schema.
+    assert!(ENS.chunk(HashEncoding::Binary, arrays).is_err());
+    Ok(())
+}
+
+// The generated Databend DDL has the columns and types of 1_schema.sql.
+#[test]
+fn test_databend_ddl_matches_schema_file() -> Result<()> {
+    for (file, encoding) in [
+        ("1_schema.sql", HashEncoding::Hex),
+        ("1_schema_binary.sql", HashEncoding::Binary),
+    ] {
+        let sql = std::fs::read_to_string(format!(
+            "{}/../schemas/databend/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            file
+        ))?;
+        let generator =
+            DdlGenerator::create(SqlDialect::Databend, encoding, ColumnProjection::default());
+
+        for table in REGISTRY {
+            let start = sql.find(&format!("CREATE TABLE {}\n", table.name)).unwrap();
+            let body = &sql[start..];
+            let body = &body[body.find('(').unwrap() + 1..body.find(");").unwrap()];
+            let columns = body
+                .lines()
+                .map(|x| x.trim().trim_end_matches(','))
+                .filter(|x| !x.is_empty())
+                .map(|x| {
+                    let (name, ty) = x.split_once(' ').unwrap();
+                    (name.to_string(), ty.trim().to_string())
+                })
+                .collect::<Vec<_>>();
+
+            let expected = table
+                .columns
+                .iter()
+                .map(|c| (c.name.to_string(), generator.column_type(c)))
+                .collect::<Vec<_>>();
+            assert_eq!(columns, expected, "{} of {}", table.name, file);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_ddl_dialects() -> Result<()> {
+    let mut tables = BTreeMap::new();
+    tables.insert("transactions".to_string(), TableColumns {
+        include: vec![],
+        exclude: vec!["input".to_string()],
+    });
+    let projection = ColumnProjection::create(tables);
+
+    let generator = DdlGenerator::create(
+        SqlDialect::ClickHouse,
+        HashEncoding::Binary,
+        projection.clone(),
+    );
+    let sql = generator.render("eth")?;
+    assert!(sql.contains("CREATE DATABASE IF NOT EXISTS eth;"));
+    assert!(sql.contains("CREATE TABLE IF NOT EXISTS eth.blocks"));
+    assert!(sql.contains("ENGINE = ReplacingMergeTree\nORDER BY (number);"));
+    assert!(sql.contains("ENGINE = ReplacingMergeTree\nORDER BY (block_number, hash);"));
+    assert!(sql.contains(
+        "ENGINE = ReplacingMergeTree\nORDER BY (block_number, transaction_hash, log_index, token_id);"
+    ));
+    assert!(sql.contains(
+        "ENGINE = ReplacingMergeTree\nORDER BY (block_number, transaction_hash, log_index);"
+    ));
+    assert!(sql.contains("hash              FixedString(32),"));
+    assert!(!sql.contains("    input "));
+    assert!(sql.contains("    method_id "));
+
+    let generator = DdlGenerator::create(SqlDialect::PostgreSQL, HashEncoding::Binary, projection);
+    let sql = generator.create_table("eth", &BLOCKS)?;
+    assert!(sql.contains("hash              BYTEA,"));
+    assert!(sql.contains("number            NUMERIC(20, 0),"));
+    assert!(sql.contains("base_fee_per_gas  NUMERIC(20, 0) -- "));
+    assert!(!sql.contains("ENGINE"));
+    let sql = generator.render("eth")?;
+    assert!(
+        sql.contains("CREATE UNIQUE INDEX IF NOT EXISTS blocks_unique_key ON eth.blocks (number);")
+    );
+    assert!(sql.contains(
+        "CREATE UNIQUE INDEX IF NOT EXISTS logs_unique_key ON eth.logs (transaction_hash, log_index);"
+    ));
+
+    let generator = DdlGenerator::create(
+        SqlDialect::BigQuery,
+        HashEncoding::Hex,
+        ColumnProjection::default(),
+    );
+    let sql = generator.render("eth")?;
+    assert!(sql.contains("CREATE SCHEMA IF NOT EXISTS eth;"));
+    assert!(sql.contains("value                    BIGNUMERIC,"));
+    assert!(sql.contains("hash              STRING,"));
+
+    // An unknown column of the projection.
+    let mut tables = BTreeMap::new();
+    tables.insert("ens".to_string(), TableColumns {
+        include: vec!["label".to_string()],
+        exclude: vec![],
+    });
+    let generator = DdlGenerator::create(
+        SqlDialect::DuckDB,
+        HashEncoding::Hex,
+        ColumnProjection::create(tables),
+    );
+    assert!(generator.render("eth").is_err());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_file_metadata() -> Result<()> {
+    let op = Arc::new(init_memory_operator()?);
+    let (schema, chunk) = ENS.chunk(
+        HashEncoding::Hex,
+        ENS.arrow_schema(HashEncoding::Hex)
+            .fields
+            .iter()
+            .map(|f| arrow2::array::new_empty_array(f.data_type.clone()))
+            .collect(),
+    )?;
+
+    let metadata = FileMetadata::create("ens", 5, 100, 199);
+    write_parquet_chunks(
+        op.clone(),
+        "pub/ens/ens_100_199.parquet",
+        schema.clone(),
+        vec![chunk.clone()],
+        metadata.to_map(),
+    )
+    .await?;
+    let read = read_parquet_metadata(op.clone(), "pub/ens/ens_100_199.parquet").await?;
+    assert_eq!(read["ethetl.schema_version"], SCHEMA_VERSION.to_string());
+    assert_eq!(FileMetadata::from_map(&read)?, Some(metadata));
+
+    // The files written before the metadata.
+    write_parquet(op.clone(), "pub/ens/ens_0_99.parquet", schema, chunk).await?;
+    let read = read_parquet_metadata(op.clone(), "pub/ens/ens_0_99.parquet").await?;
+    assert_eq!(FileMetadata::from_map(&read)?, None);
+
+    // Both have all the columns of the current version.
+    let migration = SchemaMigration::create(
+        op.clone(),
+        "pub",
+        &PathTemplate::default(),
+        5,
+        ColumnProjection::default(),
+    );
+    let report = migration.check().await?;
+    assert_eq!(report.files, 2);
+    assert!(report.outdated.is_empty());
+    assert!(report.backfill_ranges.is_empty());
+    Ok(())
+}
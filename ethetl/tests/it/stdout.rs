@@ -0,0 +1,122 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Cursor;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arrow2::io::ipc::read::read_stream_metadata;
+use arrow2::io::ipc::read::StreamReader;
+use arrow2::io::ipc::read::StreamState;
+use common_configs::StdoutFormat;
+use common_configs::StdoutSinkConfig;
+use common_exceptions::Result;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+use ethetl::sinks::StdoutSink;
+use ethetl::sinks::TABLE_METADATA_KEY;
+use serde_json::Value;
+
+use crate::common::testdata_operator;
+use crate::common::testdata_range;
+
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuf {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+fn testdata_sink(conf: StdoutSinkConfig, out: &SharedBuf) -> Result<StdoutSink> {
+    StdoutSink::create_with_writer(testdata_operator()?, conf, Box::new(out.clone()))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_stdout_sink_json() -> Result<()> {
+    let out = SharedBuf::default();
+    let sink = testdata_sink(
+        StdoutSinkConfig {
+            tables: vec!["blocks".to_string(), "logs".to_string()],
+            ..Default::default()
+        },
+        &out,
+    )?;
+
+    sink.commit(&testdata_range(&["blocks", "logs", "transactions"]))
+        .await?;
+    let data = out.take();
+    let lines = std::str::from_utf8(&data)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<std::result::Result<Vec<Value>, _>>()?;
+    assert_eq!(lines[0]["table"], "blocks");
+    assert_eq!(lines[0]["row"]["number"], 16600001);
+    assert_eq!(lines[1]["row"]["number"], 16600002);
+    assert!(lines.len() > 2);
+    assert!(lines[2..].iter().all(|x| x["table"] == "logs"));
+
+    sink.rollback(&RolledBack {
+        output_dir: "".to_string(),
+        from_block: 16600002,
+        files: Default::default(),
+    })
+    .await?;
+    assert_eq!(out.take(), b"{\"rolled_back_from\":16600002}\n");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_stdout_sink_arrow() -> Result<()> {
+    let out = SharedBuf::default();
+    let sink = testdata_sink(
+        StdoutSinkConfig {
+            format: StdoutFormat::Arrow,
+            tables: vec![],
+        },
+        &out,
+    )?;
+
+    // A stream per table, one after the other.
+    sink.commit(&testdata_range(&["blocks", "logs", "transactions"]))
+        .await?;
+    let mut reader = Cursor::new(out.take());
+    let mut tables = vec![];
+    for _ in 0..3 {
+        let metadata = read_stream_metadata(&mut reader)?;
+        tables.push(metadata.schema.metadata[TABLE_METADATA_KEY].clone());
+        let mut rows = 0;
+        for state in StreamReader::new(&mut reader, metadata, None) {
+            if let StreamState::Some(chunk) = state? {
+                rows += chunk.len();
+            }
+        }
+        assert!(rows > 0);
+    }
+    assert_eq!(tables, ["blocks", "logs", "transactions"]);
+    assert_eq!(reader.position() as usize, reader.get_ref().len());
+    Ok(())
+}
@@ -0,0 +1,39 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use ethetl::contexts::Throttle;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_throttle() {
+    // 10 blocks each 100ms, the first range starts right away.
+    let throttle = Throttle::create(100);
+    let started = Instant::now();
+    for _ in 0..3 {
+        throttle.acquire(10).await;
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+
+    // No limit.
+    let unlimited = Throttle::create(0);
+    let started = Instant::now();
+    for _ in 0..3 {
+        unlimited.acquire(1000).await;
+    }
+    assert!(started.elapsed() < Duration::from_millis(100));
+}
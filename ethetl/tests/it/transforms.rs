@@ -0,0 +1,126 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow2::array::UInt64Array;
+use common_configs::TransformConfig;
+use common_exceptions::Result;
+use common_storages::read_parquet;
+use ethetl::transforms::Transformer;
+
+use crate::common::testdata_file;
+use crate::common::testdata_operator;
+
+fn transform(name: &str, sql: &str) -> TransformConfig {
+    TransformConfig {
+        name: name.to_string(),
+        sql: sql.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_transformer_config() {
+    assert!(Transformer::create(vec![]).unwrap().is_empty());
+    let invalid = [
+        vec![transform("blocks", "SELECT 1")],
+        vec![transform("Block-Gas", "SELECT 1")],
+        vec![transform("block_gas", " ")],
+        vec![
+            transform("block_gas", "SELECT 1"),
+            transform("block_gas", "SELECT 2"),
+        ],
+    ];
+    for transforms in invalid {
+        assert!(Transformer::create(transforms).is_err());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_transformer() -> Result<()> {
+    let transformer = Transformer::create(vec![
+        transform(
+            "block_gas",
+            "SELECT block_number, count(*) AS txs, sum(gas) AS gas FROM transactions GROUP BY block_number ORDER BY block_number",
+        ),
+        TransformConfig {
+            block_column: "number".to_string(),
+            ..transform(
+                "busy_blocks",
+                "SELECT b.number FROM blocks b JOIN block_gas g ON b.number = g.block_number WHERE g.txs > 1000000",
+            )
+        },
+    ])?;
+    assert_eq!(transformer.tables(), ["block_gas", "busy_blocks"]);
+    assert_eq!(transformer.block_column("busy_blocks"), "number");
+
+    let op = testdata_operator()?;
+    for table in ["blocks", "transactions"] {
+        let (schema, chunks) = read_parquet(op.clone(), &testdata_file(table).path).await?;
+        transformer.stage("", 16600001, 16600002, table, &schema, chunks);
+    }
+    // A range exported again replaces its tables.
+    let (schema, chunks) = read_parquet(op.clone(), &testdata_file("blocks").path).await?;
+    transformer.stage("", 16600001, 16600002, "blocks", &schema, chunks);
+    assert!(transformer.staged_bytes("", 16600001, 16600002) > 0);
+    assert_eq!(transformer.staged_bytes("", 16600003, 16600004), 0);
+
+    let tables = transformer.take("", 16600001, 16600002);
+    assert_eq!(tables.len(), 2);
+    assert!(transformer.take("", 16600001, 16600002).is_empty());
+
+    let outputs = transformer.run(tables).await?;
+    assert_eq!(outputs.len(), 2);
+    let (name, schema, chunks) = &outputs[0];
+    assert_eq!(name, "block_gas");
+    let fields = schema
+        .fields
+        .iter()
+        .map(|f| f.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(fields, ["block_number", "txs", "gas"]);
+    let blocks = chunks[0].arrays()[0]
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .unwrap();
+    assert_eq!(blocks.values().as_slice(), [16600001, 16600002]);
+
+    // An output without rows.
+    let (name, _, chunks) = &outputs[1];
+    assert_eq!(name, "busy_blocks");
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].len(), 0);
+
+    // The block column must be in the output.
+    let transformer = Transformer::create(vec![transform(
+        "block_hashes",
+        "SELECT number, hash FROM blocks",
+    )])?;
+    let (schema, chunks) = read_parquet(op.clone(), &testdata_file("blocks").path).await?;
+    transformer.stage("", 16600001, 16600002, "blocks", &schema, chunks.clone());
+    let tables = transformer.take("", 16600001, 16600002);
+    assert!(transformer.run(tables).await.is_err());
+
+    // A failed range drops its tables.
+    transformer.stage("", 16600001, 16600002, "blocks", &schema, chunks);
+    transformer.discard("", 16600001, 16600002);
+    assert_eq!(transformer.staged_bytes("", 16600001, 16600002), 0);
+    assert!(transformer.take("", 16600001, 16600002).is_empty());
+
+    // Nothing is staged without transforms.
+    let transformer = Transformer::create(vec![])?;
+    let (schema, chunks) = read_parquet(op, &testdata_file("blocks").path).await?;
+    transformer.stage("", 16600001, 16600002, "blocks", &schema, chunks);
+    assert!(transformer.take("", 16600001, 16600002).is_empty());
+    Ok(())
+}
@@ -0,0 +1,233 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use arrow2::array::Array;
+use arrow2::array::UInt64Array;
+use arrow2::array::Utf8Array;
+use common_eth::bytes_to_hex;
+use common_exceptions::Result;
+use ethetl::chains::eth::RawBlock;
+use ethetl::exporters::PathTemplate;
+use ethetl::verify::logs_bloom;
+use ethetl::verify::ordered_trie_root;
+use ethetl::verify::receipts_root;
+use ethetl::verify::OutputVerifier;
+use ethetl::verify::RootVerifier;
+use serde_json::json;
+use serde_json::Value;
+use web3::types::Block;
+use web3::types::Bytes;
+use web3::types::Log;
+use web3::types::Transaction;
+use web3::types::TransactionReceipt;
+use web3::types::H160;
+use web3::types::H256;
+use web3::types::H64;
+
+use crate::common::testdata_operator;
+use crate::common::testdata_table;
+
+fn str_values(table: &BTreeMap<String, Box<dyn Array>>, name: &str) -> Vec<String> {
+    let array = table[name]
+        .as_any()
+        .downcast_ref::<Utf8Array<i32>>()
+        .unwrap();
+    array.values_iter().map(|x| x.to_string()).collect()
+}
+
+fn u64_values(table: &BTreeMap<String, Box<dyn Array>>, name: &str) -> Vec<u64> {
+    let array = table[name].as_any().downcast_ref::<UInt64Array>().unwrap();
+    array.values().to_vec()
+}
+
+// The mainnet genesis block.
+fn genesis() -> Value {
+    let zero = format!("{:#x}", H256::zero());
+    let empty_root = "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421";
+    json!({
+        "hash": "0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3",
+        "parentHash": zero,
+        "sha3Uncles": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+        "miner": format!("{:#x}", H160::zero()),
+        "stateRoot": "0xd7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544",
+        "transactionsRoot": empty_root,
+        "receiptsRoot": empty_root,
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "difficulty": "0x400000000",
+        "totalDifficulty": "0x400000000",
+        "number": "0x0",
+        "gasLimit": "0x1388",
+        "gasUsed": "0x0",
+        "timestamp": "0x0",
+        "extraData": "0x11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa",
+        "mixHash": zero,
+        "nonce": "0x0000000000000042",
+        "size": "0x21c",
+        "transactions": [],
+        "uncles": [],
+    })
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_verify_testdata() -> Result<()> {
+    let op = testdata_operator()?;
+    let verifier = OutputVerifier::create(op, "", &PathTemplate::default(), 1);
+    let report = verifier.verify().await?;
+    assert!(report.ok, "{:?}", report);
+    assert_eq!(report.first_block, Some(16600001));
+    assert_eq!(report.last_block, Some(16600002));
+    assert_eq!(report.blocks, 2);
+    assert_eq!(report.transactions, report.receipts);
+    assert_eq!(report.files["ens"], 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_ordered_trie_root() {
+    // The root of the empty trie, e.g. transactions_root of an empty block.
+    assert_eq!(
+        format!("{:#x}", ordered_trie_root(&[])),
+        "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+    );
+
+    // The root depends on the order of the items.
+    let items = (0..200u8).map(|i| vec![i; 40]).collect::<Vec<_>>();
+    let mut reversed = items.clone();
+    reversed.reverse();
+    assert_eq!(ordered_trie_root(&items), ordered_trie_root(&items));
+    assert_ne!(ordered_trie_root(&items), ordered_trie_root(&reversed));
+}
+
+#[test]
+fn test_logs_bloom() {
+    assert_eq!(logs_bloom(&[]), [0u8; 256]);
+
+    // Each address and topic sets at most 3 bits.
+    let log = Log {
+        address: H160::repeat_byte(1),
+        topics: vec![H256::repeat_byte(2)],
+        data: Bytes::default(),
+        block_hash: None,
+        block_number: None,
+        transaction_hash: None,
+        transaction_index: None,
+        log_index: None,
+        transaction_log_index: None,
+        log_type: None,
+        removed: None,
+    };
+    let bits = logs_bloom(&[log])
+        .iter()
+        .map(|x| x.count_ones())
+        .sum::<u32>();
+    assert!(bits > 0 && bits <= 6);
+}
+
+#[test]
+fn test_verify_genesis() -> Result<()> {
+    let block: Block<Transaction> = serde_json::from_value(genesis())?;
+    let raw: RawBlock = serde_json::from_value(genesis())?;
+    RootVerifier::create(&[block.clone()], &[raw.clone()], &[]).verify()?;
+
+    // The header hash covers the seal.
+    let mut forged = block;
+    forged.nonce = Some(H64::from_low_u64_be(0x43));
+    let err = RootVerifier::create(&[forged], &[raw], &[])
+        .verify()
+        .unwrap_err();
+    assert!(
+        err.to_string().starts_with("Block 0 hash mismatch"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+// The receipts of the testdata blocks, rebuilt from the receipts, logs and
+// transactions tables, against the receipts roots and blooms of the headers.
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_receipts_root_testdata() -> Result<()> {
+    let op = testdata_operator()?;
+    let blocks = testdata_table(&op, "blocks").await?;
+    let receipts = testdata_table(&op, "receipts").await?;
+    let logs = testdata_table(&op, "logs").await?;
+    let txs = testdata_table(&op, "transactions").await?;
+
+    // The logs of each transaction in the file order.
+    let mut tx_logs: HashMap<String, Vec<Value>> = HashMap::new();
+    let addresses = str_values(&logs, "event_address");
+    let data = str_values(&logs, "data");
+    let topics = str_values(&logs, "topics");
+    for (i, hash) in str_values(&logs, "transaction_hash")
+        .into_iter()
+        .enumerate()
+    {
+        let topics = topics[i]
+            .split('|')
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>();
+        tx_logs.entry(hash).or_default().push(json!({
+            "address": addresses[i],
+            "topics": topics,
+            "data": data[i],
+        }));
+    }
+    let tx_types = str_values(&txs, "hash")
+        .into_iter()
+        .zip(u64_values(&txs, "transaction_type"))
+        .collect::<HashMap<_, _>>();
+
+    let mut block_receipts: BTreeMap<u64, Vec<TransactionReceipt>> = BTreeMap::new();
+    let numbers = u64_values(&receipts, "block_number");
+    let indexes = u64_values(&receipts, "transaction_index");
+    let gas = u64_values(&receipts, "cumulative_gas_used");
+    let status = u64_values(&receipts, "status");
+    for (i, hash) in str_values(&receipts, "transaction_hash")
+        .into_iter()
+        .enumerate()
+    {
+        let receipt = serde_json::from_value(json!({
+            "transactionHash": hash,
+            "transactionIndex": format!("{:#x}", indexes[i]),
+            "blockHash": null,
+            "blockNumber": format!("{:#x}", numbers[i]),
+            "cumulativeGasUsed": format!("{:#x}", gas[i]),
+            "gasUsed": null,
+            "contractAddress": null,
+            "logs": tx_logs.remove(&hash).unwrap_or_default(),
+            "status": format!("{:#x}", status[i]),
+            "root": null,
+            "logsBloom": format!("0x{}", "0".repeat(512)),
+            "type": format!("{:#x}", tx_types[&hash]),
+        }))?;
+        block_receipts.entry(numbers[i]).or_default().push(receipt);
+    }
+    assert!(tx_logs.is_empty());
+
+    let roots = str_values(&blocks, "receipts_root");
+    let blooms = str_values(&blocks, "logs_bloom");
+    for (i, number) in u64_values(&blocks, "number").into_iter().enumerate() {
+        let mut receipts = block_receipts[&number].iter().collect::<Vec<_>>();
+        receipts.sort_by_key(|r| r.transaction_index);
+        let (root, bloom) = receipts_root(&receipts);
+        assert_eq!(format!("{:#x}", root), roots[i], "block {}", number);
+        assert_eq!(bytes_to_hex(&Bytes(bloom.to_vec())), blooms[i]);
+    }
+    assert_eq!(block_receipts.len(), 2);
+    Ok(())
+}
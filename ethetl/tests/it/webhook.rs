@@ -0,0 +1,236 @@
+// Copyright 2023 BohuTANG.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use common_configs::WebhookFilter;
+use common_configs::WebhookSinkConfig;
+use common_exceptions::Result;
+use common_storages::init_memory_operator;
+use ethetl::manifest::ManifestFile;
+use ethetl::manifest::TableManifest;
+use ethetl::sinks::compare_decimal;
+use ethetl::sinks::sign;
+use ethetl::sinks::CommittedRange;
+use ethetl::sinks::RolledBack;
+use ethetl::sinks::Sink;
+use ethetl::sinks::WebhookCursor;
+use ethetl::sinks::WebhookSink;
+use opendal::Operator;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::common::MockHttpServer;
+use crate::common::MockRequest;
+
+fn webhook_responder(_request: &MockRequest) -> Value {
+    json!({})
+}
+
+// The testdata files of the tables committed to the memory operator.
+async fn commit_testdata(op: Arc<Operator>, tables: &[&str]) -> Result<CommittedRange> {
+    let mut files = BTreeMap::new();
+    for table in tables {
+        let name = format!("{}_16600001_16600002.parquet", table);
+        let data = std::fs::read(format!(
+            "{}/tests/it/testdata/{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            table,
+            name
+        ))?;
+        let path = format!("pub/{}/{}", table, name);
+        op.object(&path).write(data).await?;
+        let file = ManifestFile {
+            path,
+            start: 16600001,
+            end: 16600002,
+            seq: 1,
+            ..Default::default()
+        };
+        let mut manifest = TableManifest::create(table);
+        manifest.append(file.clone());
+        manifest.write(op.clone(), "pub").await?;
+        files.insert(table.to_string(), file);
+    }
+    Ok(CommittedRange {
+        output_dir: "pub".to_string(),
+        start: 16600001,
+        end: 16600002,
+        files,
+    })
+}
+
+fn tables_of(request: &MockRequest) -> Vec<String> {
+    request.json()["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["table"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_webhook_sign() -> Result<()> {
+    // RFC 4231, test case 2.
+    assert_eq!(
+        sign("Jefe", b"what do ya want for nothing?")?,
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    assert_eq!(compare_decimal("1000", "999"), Some(Ordering::Greater));
+    assert_eq!(compare_decimal("0012.50", "12.5"), Some(Ordering::Equal));
+    assert_eq!(compare_decimal("0.001", "0.01"), Some(Ordering::Less));
+    assert_eq!(
+        compare_decimal(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+            "1"
+        ),
+        Some(Ordering::Greater)
+    );
+    assert_eq!(compare_decimal("-1", "0"), None);
+    assert_eq!(compare_decimal("0x10", "0"), None);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_webhook_sink() -> Result<()> {
+    let server = MockHttpServer::start(webhook_responder).await;
+    let op = Arc::new(init_memory_operator()?);
+    let conf = WebhookSinkConfig {
+        url: format!("{}/events", server.endpoint),
+        secret: "secret".to_string(),
+        tables: vec!["blocks".to_string(), "token_transfers".to_string()],
+        batch_rows: 1,
+        ..Default::default()
+    };
+
+    // The range before is not committed yet, nothing is delivered.
+    let sink = WebhookSink::create(op.clone(), WebhookSinkConfig {
+        from_block: 16600000,
+        ..conf.clone()
+    })?;
+    let range = commit_testdata(op.clone(), &["blocks", "token_transfers"]).await?;
+    sink.commit(&range).await?;
+    assert!(server.requests().is_empty());
+    assert_eq!(WebhookCursor::read(op.clone(), "pub").await?, None);
+
+    // A request per block, the blocks first.
+    let sink = WebhookSink::create(op.clone(), conf.clone())?;
+    sink.commit(&range).await?;
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    for (request, block) in requests.iter().zip([16600001u64, 16600002]) {
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/events");
+        assert_eq!(request.headers["x-ethetl-event"], "rows");
+        assert_eq!(
+            request.headers["x-ethetl-delivery"],
+            format!("{}-{}", block, block)
+        );
+        assert_eq!(
+            request.headers["x-ethetl-signature"],
+            sign("secret", &request.body)?
+        );
+        let body = request.json();
+        assert_eq!(body["from_block"], block);
+        assert_eq!(body["to_block"], block);
+        assert_eq!(body["rows"][0]["table"], "blocks");
+        assert_eq!(body["rows"][0]["row"]["number"], block);
+        for row in body["rows"].as_array().unwrap()[1..].iter() {
+            assert_eq!(row["table"], "token_transfers");
+            assert_eq!(row["row"]["block_number"], block);
+        }
+    }
+    assert_eq!(
+        WebhookCursor::read(op.clone(), "pub").await?,
+        Some(WebhookCursor {
+            next_block: 16600003
+        })
+    );
+
+    // Committed again after a restart, nothing is delivered again.
+    sink.commit(&range).await?;
+    assert_eq!(server.requests().len(), 2);
+
+    // The reorged block is delivered again after the rollback notice.
+    sink.rollback(&RolledBack {
+        output_dir: "pub".to_string(),
+        from_block: 16600002,
+        files: range
+            .files
+            .iter()
+            .map(|(k, v)| (k.clone(), vec![v.clone()]))
+            .collect(),
+    })
+    .await?;
+    sink.commit(&range).await?;
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[2].headers["x-ethetl-event"], "rollback");
+    assert_eq!(requests[2].json(), json!({"rolled_back_from": 16600002}));
+    assert_eq!(requests[3].body, requests[1].body);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_webhook_sink_filters() -> Result<()> {
+    let server = MockHttpServer::start(webhook_responder).await;
+    let op = Arc::new(init_memory_operator()?);
+    let range = commit_testdata(op.clone(), &["blocks", "token_transfers"]).await?;
+
+    let filters = vec![WebhookFilter {
+        table: "token_transfers".to_string(),
+        column: "value".to_string(),
+        min: "1".to_string(),
+    }];
+    let sink = WebhookSink::create(op.clone(), WebhookSinkConfig {
+        url: server.endpoint.clone(),
+        tables: vec!["token_transfers".to_string()],
+        filters,
+        batch_rows: 100000,
+        ..Default::default()
+    })?;
+    sink.commit(&range).await?;
+
+    // The blocks of the range in one request, no transfer of zero value.
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(!requests[0].headers.contains_key("x-ethetl-signature"));
+    assert_eq!(
+        requests[0].headers["x-ethetl-delivery"],
+        "16600001-16600002"
+    );
+    assert!(
+        tables_of(&requests[0])
+            .iter()
+            .all(|x| x == "token_transfers")
+    );
+    for row in requests[0].json()["rows"].as_array().unwrap() {
+        assert_ne!(row["row"]["value"], "0");
+    }
+
+    // An unknown column.
+    let res = WebhookSink::create(op, WebhookSinkConfig {
+        filters: vec![WebhookFilter {
+            table: "token_transfers".to_string(),
+            column: "amount".to_string(),
+            min: "1".to_string(),
+        }],
+        ..Default::default()
+    });
+    assert!(res.is_err());
+    Ok(())
+}
@@ -0,0 +1,99 @@
+-- The schema of `hash_encoding = "binary"`, the hashes, addresses and blooms are
+-- FixedSizeBinary in the parquet files, the topics are the concatenated 32 bytes topics.
+DROP DATABASE IF EXISTS eth;
+
+CREATE DATABASE eth;
+
+USE eth;
+
+CREATE TABLE blocks
+(
+    number            BIGINT UNSIGNED,
+    hash              BINARY,
+    parent_hash       BINARY,
+    nonce             VARCHAR,
+    sha3_uncles       BINARY,
+    logs_bloom        BINARY,
+    transactions_root BINARY,
+    state_root        BINARY,
+    receipts_root     BINARY,
+    difficulty        VARCHAR,
+    total_difficulty  VARCHAR,
+    size              BIGINT UNSIGNED,
+    extra_data        VARCHAR,
+    gas_limit         BIGINT UNSIGNED,
+    gas_used          BIGINT UNSIGNED,
+    timestamp         TIMESTAMP,
+    transaction_count BIGINT UNSIGNED,
+    base_fee_per_gas  BIGINT UNSIGNED
+);
+
+CREATE TABLE transactions
+(
+    hash                     BINARY,
+    nonce                    VARCHAR,
+    transaction_index        BIGINT UNSIGNED,
+    from_address             BINARY,
+    to_address               BINARY,
+    value                    DECIMAL(36, 18),
+    gas                      BIGINT UNSIGNED,
+    gas_price                BIGINT UNSIGNED,
+    method_id                VARCHAR,
+    input                    VARCHAR,
+    max_fee_per_gas          BIGINT UNSIGNED,
+    max_priority_fee_per_gas BIGINT UNSIGNED,
+    transaction_type         BIGINT UNSIGNED,
+    block_hash               BINARY,
+    block_number             BIGINT UNSIGNED,
+    block_timestamp          TIMESTAMP
+);
+
+CREATE TABLE logs
+(
+    log_index         BIGINT UNSIGNED,
+    transaction_hash  BINARY,
+    transaction_index BIGINT UNSIGNED,
+    block_hash        BINARY,
+    block_number      BIGINT UNSIGNED,
+    contract_address  BINARY,
+    event_address     BINARY,
+    data              VARCHAR,
+    topics            BINARY
+);
+
+CREATE TABLE receipts
+(
+    transaction_hash    BINARY,
+    transaction_index   BIGINT UNSIGNED,
+    block_hash          BINARY,
+    block_number        BIGINT UNSIGNED,
+    cumulative_gas_used BIGINT UNSIGNED,
+    gas_used            BIGINT UNSIGNED,
+    contract_address    BINARY,
+    root                BINARY,
+    status              BIGINT UNSIGNED,
+    effective_gas_price BIGINT UNSIGNED
+);
+
+CREATE TABLE token_transfers
+(
+    token_address    BINARY,
+    from_address     BINARY,
+    to_address       BINARY,
+    token_id         VARCHAR,
+    value            VARCHAR,
+    erc_standard     VARCHAR,
+    transaction_hash BINARY,
+    log_index        BIGINT UNSIGNED,
+    block_number     BIGINT UNSIGNED
+);
+
+CREATE TABLE ens
+(
+    name             VARCHAR,
+    cost             DECIMAL(36, 18),
+    expires          TIMESTAMP,
+    owner            BINARY,
+    transaction_hash BINARY,
+    block_number     BIGINT UNSIGNED
+);
\ No newline at end of file
@@ -6,5 +6,5 @@ SCRIPT_PATH="$(cd "$(dirname "$0")" >/dev/null 2>&1 && pwd)"
 cd "$SCRIPT_PATH/../.." || exit
 
 echo "Build(RELEASE) start..."
-cargo build --bin=ethetl --bin=ethetl-stream --release
+cargo build --bin=ethetl --bin=ethetl-stream --bin=ethetl-hybrid --release
 echo "All done..."